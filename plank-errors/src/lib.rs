@@ -7,4 +7,4 @@ pub mod reporter;
 pub mod printer;
 
 pub use reporter::Reporter;
-pub use printer::{print_diagnostic, print_diagnostics};
+pub use printer::{print_diagnostic, print_diagnostics, SourceFile};
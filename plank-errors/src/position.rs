@@ -14,16 +14,6 @@ impl Position {
         Position { line, column }
     }
 
-    /// Create a span that starts here, and goes to given position. If
-    /// `self == to`, then the span is considered empty.
-    ///
-    /// # Panics
-    ///
-    /// Panics if `to < self`.
-    pub fn span_to(self, to: Position) -> Span {
-        Span::new(self, to)
-    }
-
     /// Create a new position that is `amount` columns to the right.
     pub fn forward(mut self, amount: u32) -> Position {
         self.column += amount;
@@ -42,30 +32,42 @@ impl Position {
     }
 }
 
+/// Identifies a source file. Files are numbered in the order they were
+/// loaded, starting from zero.
+#[derive(PartialEq, PartialOrd, Eq, Ord, Hash, Debug, Copy, Clone)]
+pub struct FileId(pub u32);
+
 /// Represents a range inside source file. You can think of it as a selection
 /// inside the editor.
 #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub struct Span {
+    #[allow(missing_docs)] pub file: FileId,
     #[allow(missing_docs)] pub start: Position,
     #[allow(missing_docs)] pub end: Position,
 }
 
 impl Span {
-    /// Create a span that is between given positions.
+    /// Create a span that is between given positions in given file. If
+    /// `start == end`, then the span is considered empty.
     ///
     /// # Panics
     ///
     /// Panics if `start > end`.
-    pub fn new(start: Position, end: Position) -> Span {
+    pub fn new(file: FileId, start: Position, end: Position) -> Span {
         assert!(start <= end);
-        Span { start, end }
+        Span { file, start, end }
     }
 
     /// Return the smallest span that contains both `self` and `other` spans.
+    ///
+    /// # Panics
+    ///
+    /// Panics if spans are in different files.
     pub fn merge(self, other: Span) -> Span {
         use std::cmp;
+        assert_eq!(self.file, other.file, "merging spans from different files");
         let start = cmp::min(self.start, other.start);
         let end = cmp::max(self.end, other.end);
-        Span::new(start, end)
+        Span::new(self.file, start, end)
     }
 }
@@ -1,9 +1,21 @@
 //! Functions to pretty-print diagnostics.
 
 use std::collections::{BTreeMap, HashSet};
+use position::FileId;
 use reporter::{Diagnostic, Note, Severity};
 
 
+/// A source file that diagnostics can point into.
+#[derive(Debug, Copy, Clone)]
+pub struct SourceFile<'a> {
+    /// Name of the file, printed before annotated lines when diagnostics
+    /// refer to more than one file.
+    pub name: &'a str,
+    /// Contents of the file.
+    pub source: &'a str,
+}
+
+
 #[derive(PartialEq, PartialOrd, Eq, Ord, Hash, Debug, Copy, Clone)]
 enum MarkerStyle {
     // ~~~~~ or ____~
@@ -103,7 +115,8 @@ impl<'a> LineMarker<'a> {
 }
 
 struct Printer<'a> {
-    lines: Vec<&'a str>,
+    files: Vec<(&'a str, Vec<&'a str>)>,
+    current_file: usize,
     // use BTreeMap here so that we can iterate annotated lines in order
     line_markers: BTreeMap<u32, Vec<LineMarker<'a>>>,
     next_connect_col: u32,
@@ -112,9 +125,13 @@ struct Printer<'a> {
 }
 
 impl<'a> Printer<'a> {
-    fn new(source: &'a str) -> Self {
+    fn new(files: &[SourceFile<'a>]) -> Self {
         Printer {
-            lines: source.lines().map(str::trim_right).collect(),
+            files: files
+                .iter()
+                .map(|f| (f.name, f.source.lines().map(str::trim_right).collect()))
+                .collect(),
+            current_file: 0,
             line_markers: BTreeMap::new(),
             next_connect_col: 0,
             full_connection_cols: HashSet::new(),
@@ -137,11 +154,31 @@ impl<'a> Printer<'a> {
     }
 
     fn print_notes(&mut self, notes: &'a [Note]) {
+        // notes are grouped by file, files are ordered
+        // by the first note that refers to them
+        let mut files = Vec::<FileId>::new();
+        for note in notes {
+            if !files.contains(&note.span.file) {
+                files.push(note.span.file);
+            }
+        }
+        for file in files {
+            self.current_file = file.0 as usize;
+            if self.files.len() > 1 {
+                println!("  --> {}", self.files[self.current_file].0);
+            }
+            self.print_file_notes(notes, file);
+        }
+    }
+
+    fn print_file_notes(&mut self, notes: &'a [Note], file: FileId) {
         assert!(self.line_markers.is_empty());
         assert_eq!(self.next_connect_col, 0);
         assert!(self.full_connection_cols.is_empty());
         for (index, note) in notes.iter().enumerate() {
-            self.add_note_markers(note, MarkerStyle::from_note_index(index));
+            if note.span.file == file {
+                self.add_note_markers(note, MarkerStyle::from_note_index(index));
+            }
         }
         for markers in self.line_markers.values_mut() {
             markers.sort_by_key(LineMarker::end_col);
@@ -279,7 +316,8 @@ impl<'a> Printer<'a> {
 
     fn print_line(&mut self, line: u32) {
         self.print_line_header(Some(line), None);
-        for ch in self.lines.get((line - 1) as usize).unwrap_or(&"").chars() {
+        let lines = &self.files[self.current_file].1;
+        for ch in lines.get((line - 1) as usize).unwrap_or(&"").chars() {
             match ch {
                 // print these chars as one space, otherwise
                 // they will break note alignment with code
@@ -419,15 +457,18 @@ fn number_length(mut num: u32) -> u32 {
 }
 
 /// Print a single diagnostic to stdout.
-pub fn print_diagnostic(source: &str, diagnostic: &Diagnostic) {
-    Printer::new(source).pretty_print(diagnostic)
+///
+/// Spans in the diagnostic refer to `files` by index.
+pub fn print_diagnostic(files: &[SourceFile], diagnostic: &Diagnostic) {
+    Printer::new(files).pretty_print(diagnostic)
 }
 
 /// Print all diagnostics to stdout.
 ///
-/// Diagnostics will be printed in the given order.
-pub fn print_diagnostics(source: &str, diagnostics: &[Diagnostic]) {
-    let mut printer = Printer::new(source);
+/// Diagnostics will be printed in the given order. Spans in them refer to
+/// `files` by index.
+pub fn print_diagnostics(files: &[SourceFile], diagnostics: &[Diagnostic]) {
+    let mut printer = Printer::new(files);
     for diagnostic in diagnostics {
         printer.pretty_print(diagnostic);
         println!("");
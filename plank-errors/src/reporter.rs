@@ -74,10 +74,10 @@ impl Reporter {
     ///
     /// ```rust
     /// use plank_errors::reporter::Reporter;
-    /// use plank_errors::position::{Position, Span};
+    /// use plank_errors::position::{FileId, Position, Span};
     ///
     /// let mut reporter = Reporter::new();
-    /// # let error_span = Span::new(Position::new(1, 1), Position::new(1, 1));
+    /// # let error_span = Span::new(FileId(0), Position::new(1, 1), Position::new(1, 1));
     /// # let help_span = error_span;
     /// reporter
     ///     .error("error message", error_span)
@@ -98,10 +98,10 @@ impl Reporter {
     ///
     /// ```rust
     /// use plank_errors::reporter::Reporter;
-    /// use plank_errors::position::{Position, Span};
+    /// use plank_errors::position::{FileId, Position, Span};
     ///
     /// let mut reporter = Reporter::new();
-    /// # let warning_span = Span::new(Position::new(1, 1), Position::new(1, 1));
+    /// # let warning_span = Span::new(FileId(0), Position::new(1, 1), Position::new(1, 1));
     /// # let help_span = warning_span;
     /// reporter
     ///     .warning("warning message", warning_span)
@@ -122,9 +122,9 @@ impl Reporter {
     ///
     /// ```rust
     /// use plank_errors::reporter::{Reporter, Severity};
-    /// use plank_errors::position::{Position, Span};
+    /// use plank_errors::position::{FileId, Position, Span};
     /// let mut reporter = Reporter::new();
-    /// # let error_span = Span::new(Position::new(1, 1), Position::new(1, 1));
+    /// # let error_span = Span::new(FileId(0), Position::new(1, 1), Position::new(1, 1));
     /// reporter
     ///     .diagnostic(Severity::Error, "error message", error_span)
     ///     .span(error_span)
@@ -32,7 +32,12 @@ impl<'a> Context<'a> {
                 Instruction::Call(reg, _, _) |
                 Instruction::UnaryOp(reg, _, _) |
                 Instruction::TakeAddress(reg, _, _) |
-                Instruction::CastAssign(reg, _) => {
+                Instruction::PointerOffset(reg, _, _, _) |
+                Instruction::PointerDiff(reg, _, _, _) |
+                Instruction::CastAssign(reg, _) |
+                Instruction::ZeroExtend(reg, _) |
+                Instruction::SignExtend(reg, _) |
+                Instruction::Truncate(reg, _) => {
                     self.assign_position.entry((reg, id)).or_insert(index);
                 }
                 Instruction::Error |
@@ -50,10 +55,15 @@ impl<'a> Context<'a> {
                 Instruction::Assign(_, ref val) |
                 Instruction::UnaryOp(_, _, ref val) |
                 Instruction::FieldStore(_, _, ref val) |
-                Instruction::CastAssign(_, ref val) => {
+                Instruction::CastAssign(_, ref val) |
+                Instruction::ZeroExtend(_, ref val) |
+                Instruction::SignExtend(_, ref val) |
+                Instruction::Truncate(_, ref val) => {
                     self.check_value(val, id, index);
                 }
                 Instruction::DerefStore(ref a, _, _, ref b) |
+                Instruction::PointerOffset(_, ref a, _, ref b) |
+                Instruction::PointerDiff(_, ref a, _, ref b) |
                 Instruction::BinaryOp(_, _, ref a, ref b) => {
                     self.check_value(a, id, index);
                     self.check_value(b, id, index);
@@ -80,6 +90,7 @@ impl<'a> Context<'a> {
         match **value {
             Value::Int(_, _) |
             Value::Symbol(_, _) |
+            Value::Global(_) |
             Value::Bytes(_) |
             Value::Unit |
             Value::Error => {}
@@ -92,7 +103,11 @@ impl<'a> Context<'a> {
                 if !self.reachable_labels.contains(&(reg, block)) {
                     return;
                 }
-                let var_symbol = self.function.register_symbols[&reg];
+                let var_symbol = match self.function.register_symbols.get(&reg) {
+                    Some(&symbol) => symbol,
+                    // temporaries are left uninitialized only after an error
+                    None => return,
+                };
                 let name = self.ctx.symbols.get_name(var_symbol);
                 let msg = format!("var `{}` might be uninitialized here", name);
                 let span = Spanned::span(value);
@@ -120,6 +135,12 @@ impl<'a> Context<'a> {
                     self.visit_label(reg, a);
                     self.visit_label(reg, b);
                 }
+                BlockEnd::Switch(_, ref cases, default) => {
+                    for &(_, to) in cases {
+                        self.visit_label(reg, to);
+                    }
+                    self.visit_label(reg, default);
+                }
                 BlockEnd::Return(_) | BlockEnd::Error => {}
             }
         }
@@ -7,7 +7,17 @@ pub use ast::typed::{Signedness, Size, Symbol, Type};
 #[derive(Debug, Clone)]
 pub struct Program {
     pub structs: HashMap<Symbol, typed::Struct>,
+    pub enums: HashMap<Symbol, typed::Enum>,
+    pub impls: Vec<typed::Impl>,
     pub functions: HashMap<Symbol, Function>,
+    pub statics: HashMap<Symbol, Static>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Static {
+    pub typ: Type,
+    /// Initial value, laid out in memory
+    pub value: Vec<u8>,
 }
 
 #[derive(Debug, Clone)]
@@ -59,8 +69,17 @@ pub enum Instruction {
     FieldStore(Spanned<Reg>, Vec<usize>, Spanned<Value>),
     /// reg = &reg.field1.field2...
     TakeAddress(Reg, Spanned<Reg>, Vec<usize>),
+    /// reg = value1 + value2 * size_of::<type>()
+    PointerOffset(Reg, Spanned<Value>, Type, Spanned<Value>),
+    /// reg = (value1 - value2) / size_of::<type>()
+    PointerDiff(Reg, Spanned<Value>, Type, Spanned<Value>),
     Assign(Reg, Spanned<Value>),
     CastAssign(Reg, Spanned<Value>),
+    /// Integer conversions, register type is bigger than value type.
+    ZeroExtend(Reg, Spanned<Value>),
+    SignExtend(Reg, Spanned<Value>),
+    /// Integer conversion, register type is smaller than value type.
+    Truncate(Reg, Spanned<Value>),
     Error,
 }
 
@@ -69,6 +88,8 @@ pub enum Value {
     Int(u64, Size),
     Reg(Reg),
     Symbol(Symbol, Vec<Type>),
+    /// Address of a static
+    Global(Symbol),
     Bytes(Vec<u8>),
     Unit,
     Error,
@@ -79,6 +100,8 @@ pub enum BlockEnd {
     Return(Spanned<Value>),
     Jump(BlockId),
     Branch(Spanned<Value>, BlockId, BlockId),
+    /// Jumps to the block of the case equal to value, or to default block.
+    Switch(Spanned<Value>, Vec<(u64, BlockId)>, BlockId),
     Error,
 }
 
@@ -93,6 +116,12 @@ pub enum BinaryOp {
     LessEq(Signedness, Size),
     Greater(Signedness, Size),
     GreaterEq(Signedness, Size),
+    BitAnd(Size),
+    BitOr(Size),
+    BitXor(Size),
+    ShiftLeft(Size),
+    /// arithmetic shift for signed operands, logical otherwise
+    ShiftRight(Signedness, Size),
     Eq,
     Neq,
 }
@@ -101,6 +130,7 @@ pub enum BinaryOp {
 pub enum UnaryOp {
     Negate(Signedness, Size),
     Not,
+    BitNot(Size),
     DerefLoad,
     /// <arg>.field1.field2...
     FieldLoad(Type, Vec<usize>),
@@ -129,6 +159,16 @@ pub(crate) mod printer {
             Size::Bit8 => write!(f, "8"),
             Size::Bit16 => write!(f, "16"),
             Size::Bit32 => write!(f, "32"),
+            Size::Bit64 => write!(f, "64"),
+        }
+    }
+
+    fn write_size(f: &mut ::std::fmt::Formatter, size: Size) -> ::std::fmt::Result {
+        match size {
+            Size::Bit8 => write!(f, "8"),
+            Size::Bit16 => write!(f, "16"),
+            Size::Bit32 => write!(f, "32"),
+            Size::Bit64 => write!(f, "64"),
         }
     }
 
@@ -145,12 +185,31 @@ pub(crate) mod printer {
                 write_type_list(f, params, ctx)
             }
             Type::Error => write!(f, "?"),
-            Type::Function(ref _params, ref _out) => unimplemented!(),
+            Type::Function(ref _params, ref _out) | Type::Closure(ref _params, ref _out) => {
+                unimplemented!()
+            }
             Type::Int(sign, size) => write_int(f, sign, size),
             Type::Pointer(ref to) => {
                 write!(f, "*")?;
                 write_type(f, to, ctx)
             }
+            Type::Array(ref of, len) => {
+                write!(f, "[")?;
+                write_type(f, of, ctx)?;
+                write!(f, "; {}]", len)
+            }
+            Type::Tuple(ref types) => {
+                write!(f, "(")?;
+                let mut first = true;
+                for ty in types.iter() {
+                    if !first {
+                        write!(f, ",")?;
+                    }
+                    first = false;
+                    write_type(f, ty, ctx)?;
+                }
+                write!(f, ")")
+            }
             Type::Var(_) => panic!("found type var"),
         }
     }
@@ -192,11 +251,23 @@ pub(crate) mod printer {
                     write!(f, "{}", self.ctx.symbols.get_name(sym))?;
                     write_type_list(f, params, self.ctx)
                 }
+                Value::Global(sym) => write!(f, "&{}", self.ctx.symbols.get_name(sym)),
                 Value::Bytes(_) => write!(f, "<bytes>"),
             }
         }
     }
 
+    struct TypeDisplay<'a> {
+        typ: &'a Type,
+        ctx: &'a CompileCtx,
+    }
+
+    impl<'a> ::std::fmt::Display for TypeDisplay<'a> {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+            write_type(f, self.typ, self.ctx)
+        }
+    }
+
     fn d<'a>(value: &'a Value, ctx: &'a CompileCtx) -> ValueDisplay<'a> {
         ValueDisplay { value, ctx }
     }
@@ -212,6 +283,18 @@ pub(crate) mod printer {
                     write!(f, "add_")?;
                     write_int(f, sign, size)
                 }
+                BinaryOp::BitAnd(size) => {
+                    write!(f, "and_")?;
+                    write_size(f, size)
+                }
+                BinaryOp::BitOr(size) => {
+                    write!(f, "or_")?;
+                    write_size(f, size)
+                }
+                BinaryOp::BitXor(size) => {
+                    write!(f, "xor_")?;
+                    write_size(f, size)
+                }
                 BinaryOp::Div(sign, size) => {
                     write!(f, "div_")?;
                     write_int(f, sign, size)
@@ -242,6 +325,14 @@ pub(crate) mod printer {
                     write_int(f, sign, size)
                 }
                 BinaryOp::Neq => write!(f, "neq"),
+                BinaryOp::ShiftLeft(size) => {
+                    write!(f, "shl_")?;
+                    write_size(f, size)
+                }
+                BinaryOp::ShiftRight(sign, size) => {
+                    write!(f, "shr_")?;
+                    write_int(f, sign, size)
+                }
                 BinaryOp::Sub(sign, size) => {
                     write!(f, "sub_")?;
                     write_int(f, sign, size)
@@ -261,6 +352,10 @@ pub(crate) mod printer {
     impl<'a> ::std::fmt::Display for UnaryOpDisplay<'a> {
         fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
             match *self.op {
+                UnaryOp::BitNot(size) => {
+                    write!(f, "bit_not_")?;
+                    write_size(f, size)
+                }
                 UnaryOp::DerefLoad => write!(f, "deref"),
                 UnaryOp::FieldLoad(_, ref fields) => {
                     write!(f, "load_field ")?;
@@ -290,6 +385,14 @@ pub(crate) mod printer {
     }
 
     pub(crate) fn print_program(program: &Program, ctx: &CompileCtx) {
+        for (&id, s) in &program.statics {
+            println!(
+                "static {}: {} = {:?}",
+                ctx.symbols.get_name(id),
+                TypeDisplay { typ: &s.typ, ctx },
+                s.value
+            );
+        }
         for (&id, f) in &program.functions {
             println!("function {}", ctx.symbols.get_name(id));
             print_function(f, ctx);
@@ -342,6 +445,15 @@ pub(crate) mod printer {
             Instruction::CastAssign(reg, ref value) => {
                 println!("    r{} = cast {}", reg.0, d(value, ctx));
             }
+            Instruction::ZeroExtend(reg, ref value) => {
+                println!("    r{} = zext {}", reg.0, d(value, ctx));
+            }
+            Instruction::SignExtend(reg, ref value) => {
+                println!("    r{} = sext {}", reg.0, d(value, ctx));
+            }
+            Instruction::Truncate(reg, ref value) => {
+                println!("    r{} = trunc {}", reg.0, d(value, ctx));
+            }
             Instruction::DerefStore(ref dest, _, ref fields, ref value) => {
                 print!("    deref_store {} ", d(dest, ctx));
                 for field in fields {
@@ -375,6 +487,14 @@ pub(crate) mod printer {
                 }
                 println!();
             }
+            Instruction::PointerOffset(dest, ref ptr, ref typ, ref offset) => {
+                print!("    r{} = ptr_offset {} {} ", dest.0, d(ptr, ctx), d(offset, ctx));
+                println!("{}", TypeDisplay { typ, ctx });
+            }
+            Instruction::PointerDiff(dest, ref a, ref typ, ref b) => {
+                print!("    r{} = ptr_diff {} {} ", dest.0, d(a, ctx), d(b, ctx));
+                println!("{}", TypeDisplay { typ, ctx });
+            }
             Instruction::UnaryOp(dest, ref op, ref value) => {
                 println!("    r{} = {} {}", dest.0, du(op), d(value, ctx));
             }
@@ -389,6 +509,13 @@ pub(crate) mod printer {
             BlockEnd::Branch(ref val, a, b) => {
                 println!("    branch {} label_{} label_{}", d(val, ctx), a.0, b.0);
             }
+            BlockEnd::Switch(ref val, ref cases, default) => {
+                print!("    switch {}", d(val, ctx));
+                for &(value, id) in cases {
+                    print!(" {} => label_{},", value, id.0);
+                }
+                println!(" _ => label_{}", default.0);
+            }
             BlockEnd::Error => {
                 println!("    error");
             }
@@ -12,9 +12,23 @@ pub enum Expr {
     Unary(Spanned<UnaryOp>, Box<Spanned<Expr>>),
     Call(Box<Spanned<Expr>>, Vec<Spanned<Expr>>),
     Field(Box<Spanned<Expr>>, Spanned<String>),
+    /// Call of form `value.name(params)`, which is either a method
+    /// call or a call of function stored in a field.
+    MethodCall(Box<Spanned<Expr>>, Spanned<String>, Vec<Spanned<Expr>>),
+    Index(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Array(Vec<Spanned<Expr>>),
+    Tuple(Vec<Spanned<Expr>>),
     Name(Spanned<Symbol>, Vec<Spanned<Type>>),
     Literal(Literal),
     Cast(Box<Spanned<Expr>>, Spanned<Type>),
+    /// Lambda with its parameters, body and local
+    /// variables of enclosing functions it captures.
+    Lambda(Vec<Var>, Box<Spanned<Expr>>, Vec<Symbol>),
+    If(Box<Spanned<Expr>>, Box<Spanned<Expr>>, Option<Box<Spanned<Expr>>>),
+    /// Block with its statements and optional trailing
+    /// expression, which gives the value of the block.
+    Block(Vec<Spanned<Statement>>, Option<Box<Spanned<Expr>>>),
+    Loop(Symbol, Box<Spanned<Statement>>),
     Error,
 }
 
@@ -25,10 +39,24 @@ pub enum Statement {
         Box<Spanned<Statement>>,
         Option<Box<Spanned<Statement>>>,
     ),
-    Loop(Box<Spanned<Statement>>),
-    While(Spanned<Expr>, Box<Spanned<Statement>>),
-    Break,
-    Continue,
+    /// Every loop gets a symbol, which `break`
+    /// and `continue` use to refer to it.
+    Loop(Symbol, Box<Spanned<Statement>>),
+    While(Symbol, Spanned<Expr>, Box<Spanned<Statement>>),
+    For(
+        Symbol,
+        Spanned<Symbol>,
+        Spanned<Expr>,
+        Spanned<Expr>,
+        bool,
+        Box<Spanned<Statement>>,
+    ),
+    Match(Spanned<Expr>, Vec<MatchArm>),
+    /// Break with the value of `loop`, which is
+    /// `unit` if not given, or without value for
+    /// other loops.
+    Break(Symbol, Option<Spanned<Expr>>),
+    Continue(Symbol),
     Return(Spanned<Expr>),
     Let(Spanned<Symbol>, Spanned<Type>, Option<Spanned<Expr>>),
     Block(Vec<Spanned<Statement>>),
@@ -36,6 +64,22 @@ pub enum Statement {
     Error,
 }
 
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Spanned<Pattern>,
+    pub body: Spanned<Statement>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(Spanned<Symbol>),
+    Variant(Spanned<Symbol>, Vec<Spanned<Pattern>>),
+    Literal(bool, Literal),
+    Range(Box<Spanned<Pattern>>, Box<Spanned<Pattern>>),
+    Error,
+}
+
 #[derive(Debug, Clone)]
 pub enum Type {
     Wildcard,
@@ -45,11 +89,16 @@ pub enum Type {
     U16,
     I32,
     U32,
+    I64,
+    U64,
     Bool,
     Unit,
     Concrete(Spanned<Symbol>, Vec<Spanned<Type>>),
     Pointer(Box<Spanned<Type>>),
+    Array(Box<Spanned<Type>>, u32),
+    Tuple(Vec<Spanned<Type>>),
     Function(Vec<Spanned<Type>>, Box<Spanned<Type>>),
+    Closure(Vec<Spanned<Type>>, Box<Spanned<Type>>),
     Error,
 }
 
@@ -82,8 +131,68 @@ pub struct Struct {
     pub fields: Vec<Var>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub complete_span: Span,
+    pub name: ItemName,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: Spanned<Symbol>,
+    pub fields: Vec<Spanned<Type>>,
+}
+
+/// Type alias, uses of which are already replaced with
+/// `typ`. Kept only to check its type parameters.
+#[derive(Debug, Clone)]
+pub struct TypeAlias {
+    pub complete_span: Span,
+    pub name: ItemName,
+    pub typ: Spanned<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Static {
+    pub mutable: bool,
+    pub name: Spanned<Symbol>,
+    pub typ: Spanned<Type>,
+    pub value: Spanned<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub name: Spanned<Symbol>,
+    pub typ: Spanned<Type>,
+    pub value: Spanned<Expr>,
+}
+
+/// Implementation of a trait. Type is either a primitive, or
+/// struct or enum applied to `type_params` in the same order.
+#[derive(Debug, Clone)]
+pub struct Impl {
+    pub trait_: Spanned<Symbol>,
+    pub type_params: Vec<Spanned<Symbol>>,
+    pub typ: Spanned<Type>,
+    /// Function implementing each method of the trait.
+    pub functions: HashMap<Symbol, Spanned<Symbol>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Program {
     pub structs: HashMap<Symbol, Struct>,
+    pub enums: HashMap<Symbol, Enum>,
+    /// Methods of each type, functions in `impl` blocks
+    /// that take `self` as their first parameter.
+    pub methods: HashMap<Symbol, HashMap<String, Symbol>>,
+    /// Methods of each trait that take `self` as their first parameter.
+    pub traits: HashMap<Symbol, HashMap<String, Symbol>>,
+    /// Traits that type parameters are bounded by.
+    pub bounds: HashMap<Symbol, Vec<Symbol>>,
+    pub impls: Vec<Impl>,
+    pub type_aliases: HashMap<Symbol, TypeAlias>,
     pub functions: Vec<Function>,
+    pub statics: Vec<Static>,
+    pub consts: Vec<Const>,
 }
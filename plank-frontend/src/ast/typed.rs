@@ -11,9 +11,21 @@ pub enum Expr {
     Unary(Spanned<UnaryOp>, TypedExpr),
     Call(TypedExpr, Vec<TypedExpr>),
     Field(TypedExpr, Spanned<usize>),
+    Index(TypedExpr, TypedExpr),
+    Array(Vec<TypedExpr>),
+    Tuple(Vec<TypedExpr>),
     Name(Spanned<Symbol>, Vec<Spanned<Type>>),
     Literal(Literal),
     Cast(TypedExpr, Spanned<Type>),
+    /// Lambda with its parameters, body and captured variables.
+    /// Lambdas are replaced with closures before building cfg.
+    Lambda(Vec<Var>, TypedExpr, Vec<Var>),
+    /// Closure value made from its environment, which is stored
+    /// in the enclosing function, and the function implementing it.
+    Closure(TypedExpr, TypedExpr),
+    If(TypedExpr, TypedExpr, Option<TypedExpr>),
+    Block(Vec<Spanned<Statement>>, Option<TypedExpr>),
+    Loop(Symbol, Box<Spanned<Statement>>),
     Error,
 }
 
@@ -31,10 +43,20 @@ pub enum Statement {
         Box<Spanned<Statement>>,
         Option<Box<Spanned<Statement>>>,
     ),
-    Loop(Box<Spanned<Statement>>),
-    While(TypedExpr, Box<Spanned<Statement>>),
-    Break,
-    Continue,
+    Loop(Symbol, Box<Spanned<Statement>>),
+    While(Symbol, TypedExpr, Box<Spanned<Statement>>),
+    /// Loop over integers, flag tells if the end is included.
+    For(
+        Symbol,
+        Spanned<Symbol>,
+        TypedExpr,
+        TypedExpr,
+        bool,
+        Box<Spanned<Statement>>,
+    ),
+    Match(TypedExpr, Vec<MatchArm>),
+    Break(Symbol, Option<TypedExpr>),
+    Continue(Symbol),
     Return(TypedExpr),
     Let(Spanned<Symbol>, Spanned<Type>, Option<TypedExpr>),
     Block(Vec<Spanned<Statement>>),
@@ -42,10 +64,59 @@ pub enum Statement {
     Error,
 }
 
+impl Expr {
+    /// Whether this is a block that always jumps
+    /// away before producing its value.
+    pub fn diverges(&self) -> bool {
+        match *self {
+            Expr::Block(ref stmts, None) => stmts.last().is_some_and(|s| s.diverges()),
+            _ => false,
+        }
+    }
+}
+
+impl Statement {
+    /// Whether the statement always jumps away, so
+    /// that code right after it is never reached.
+    pub fn diverges(&self) -> bool {
+        match *self {
+            Statement::Break(_, _) | Statement::Continue(_) | Statement::Return(_) => true,
+            Statement::Block(ref stmts) => stmts.last().is_some_and(|s| s.diverges()),
+            Statement::Expr(ref expr) => expr.expr.diverges(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: TypedPattern,
+    pub body: Spanned<Statement>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(Symbol),
+    /// Index of the variant in its enum and patterns for its fields.
+    Variant(usize, Vec<TypedPattern>),
+    Int(i128),
+    /// Inclusive range of integers.
+    Range(Spanned<i128>, Spanned<i128>),
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypedPattern {
+    pub pattern: Pattern,
+    pub span: Span,
+    pub typ: Type,
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Copy, Clone)]
 pub struct TypeVar(pub u32);
 
-#[derive(Debug, Clone)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Type {
     Var(TypeVar),
     Bool,
@@ -53,11 +124,27 @@ pub enum Type {
     Int(Signedness, Size),
     Concrete(Symbol, Rc<[Type]>),
     Pointer(Rc<Type>),
+    Array(Rc<Type>, u32),
+    Tuple(Rc<[Type]>),
     Function(Rc<[Type]>, Rc<Type>),
+    /// Closure is a pair of pointer to its environment and
+    /// function that takes that pointer as first parameter.
+    Closure(Rc<[Type]>, Rc<Type>),
     Error,
 }
 
 impl Type {
+    /// Closures are laid out as if they were a struct that has
+    /// pointer to environment and function as its fields.
+    pub fn closure_fields(params: &[Type], out: &Rc<Type>) -> [Type; 2] {
+        let env = Type::Pointer(Rc::new(Type::Unit));
+        let params = Some(env.clone())
+            .into_iter()
+            .chain(params.iter().cloned())
+            .collect::<Vec<_>>();
+        [env, Type::Function(params.into(), out.clone())]
+    }
+
     pub fn replace(&self, mapping: &HashMap<Symbol, Type>) -> Type {
         match *self {
             Type::Bool | Type::Error | Type::Int(_, _) | Type::Var(_) | Type::Unit => self.clone(),
@@ -80,10 +167,31 @@ impl Type {
                 let out = out.replace(mapping);
                 Type::Function(params, Rc::new(out))
             }
+            Type::Closure(ref params, ref out) => {
+                let params = params
+                    .iter()
+                    .map(|ty| ty.replace(mapping))
+                    .collect::<Vec<_>>()
+                    .into();
+                let out = out.replace(mapping);
+                Type::Closure(params, Rc::new(out))
+            }
             Type::Pointer(ref to) => {
                 let to = to.replace(mapping);
                 Type::Pointer(Rc::new(to))
             }
+            Type::Array(ref of, len) => {
+                let of = of.replace(mapping);
+                Type::Array(Rc::new(of), len)
+            }
+            Type::Tuple(ref types) => {
+                let types = types
+                    .iter()
+                    .map(|ty| ty.replace(mapping))
+                    .collect::<Vec<_>>()
+                    .into();
+                Type::Tuple(types)
+            }
         }
     }
 }
@@ -113,8 +221,84 @@ pub struct Struct {
     pub fields: Vec<Var>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub complete_span: Span,
+    pub name: Symbol,
+    pub type_params: Vec<Symbol>,
+    pub variants: Vec<Variant>,
+}
+
+impl Enum {
+    /// Enums are laid out as if they were a struct that has the tag
+    /// as its first field, followed by fields of every variant.
+    /// Returns index of given variant field in such struct.
+    pub fn field_index(&self, variant: usize, field: usize) -> usize {
+        let previous = self.variants[..variant]
+            .iter()
+            .map(|v| v.fields.len())
+            .sum::<usize>();
+        1 + previous + field
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: Symbol,
+    pub fields: Vec<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Static {
+    pub mutable: bool,
+    pub name: Symbol,
+    pub typ: Type,
+    pub value: TypedExpr,
+}
+
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub name: Symbol,
+    pub typ: Type,
+    pub value: TypedExpr,
+}
+
+/// Implementation of a trait. Type is either a primitive, or
+/// struct or enum applied to `type_params` in the same order.
+#[derive(Debug, Clone)]
+pub struct Impl {
+    pub trait_: Symbol,
+    pub type_params: Vec<Symbol>,
+    pub typ: Type,
+    /// Function implementing each method of the trait.
+    pub functions: HashMap<Symbol, Symbol>,
+}
+
+impl Impl {
+    /// Checks if this implementation is for given type, and
+    /// returns values of its type parameters if it is.
+    pub fn matches(&self, typ: &Type) -> Option<Vec<Type>> {
+        match (&self.typ, typ) {
+            (&Type::Concrete(a, _), &Type::Concrete(b, ref params)) if a == b => {
+                Some(params.to_vec())
+            }
+            (&Type::Int(a_sign, a_size), &Type::Int(b_sign, b_size))
+                if a_sign == b_sign && a_size == b_size =>
+            {
+                Some(Vec::new())
+            }
+            (&Type::Bool, &Type::Bool) => Some(Vec::new()),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Program {
     pub structs: HashMap<Symbol, Struct>,
+    pub enums: HashMap<Symbol, Enum>,
+    pub impls: Vec<Impl>,
     pub functions: Vec<Function>,
+    pub statics: Vec<Static>,
+    pub consts: Vec<Const>,
 }
@@ -1,13 +1,23 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::rc::Rc;
 use plank_syntax::position::{Span, Spanned};
 use ast::typed as t;
 use ast::cfg;
+use const_eval::{self, Constant};
 use CompileCtx;
 
 
+/// Integer ranges with more values than this are matched with
+/// comparisons instead of being expanded into switch cases.
+const MAX_SWITCH_RANGE: i128 = 256;
+
 struct LoopDescr {
     start: cfg::BlockId,
     after: cfg::BlockId,
+    /// Register for values given to `break`, only
+    /// `loop` used as expression has one.
+    result: Option<cfg::Reg>,
 }
 
 enum LValue {
@@ -46,8 +56,11 @@ struct Builder<'a> {
     parameters: Vec<cfg::Reg>,
     registers: HashMap<cfg::Reg, cfg::Type>,
     blocks: HashMap<cfg::BlockId, cfg::Block>,
-    current_loop: Option<LoopDescr>,
+    loops: HashMap<t::Symbol, LoopDescr>,
     ctx: &'a mut CompileCtx,
+    statics: &'a HashMap<t::Symbol, bool>,
+    consts: &'a HashMap<t::Symbol, Option<Constant>>,
+    enums: &'a HashMap<t::Symbol, t::Enum>,
     next_block_id: u32,
     next_reg: u32,
     var_registers: HashMap<t::Symbol, cfg::Reg>,
@@ -56,13 +69,21 @@ struct Builder<'a> {
 }
 
 impl<'a> Builder<'a> {
-    fn new(ctx: &'a mut CompileCtx) -> Self {
+    fn new(
+        ctx: &'a mut CompileCtx,
+        statics: &'a HashMap<t::Symbol, bool>,
+        consts: &'a HashMap<t::Symbol, Option<Constant>>,
+        enums: &'a HashMap<t::Symbol, t::Enum>,
+    ) -> Self {
         Builder {
             parameters: Vec::new(),
             registers: HashMap::new(),
             blocks: HashMap::new(),
-            current_loop: None,
+            loops: HashMap::new(),
             ctx,
+            statics,
+            consts,
+            enums,
             next_block_id: 0,
             next_reg: 0,
             var_registers: HashMap::new(),
@@ -144,6 +165,61 @@ impl<'a> Builder<'a> {
         }
     }
 
+    fn emit_load(&mut self, source: &LValue, typ: &t::Type, span: Span) -> RValue {
+        match *source {
+            LValue::Invalid | LValue::Error => RValue::Temp(cfg::Value::Error),
+            LValue::Reg(reg, ref fields) if fields.is_empty() => RValue::Var(reg),
+            LValue::Reg(reg, ref fields) => {
+                let reg_type = self.registers[&reg].clone();
+                let target = self.new_register(typ.clone());
+                self.emit_instruction(
+                    cfg::Instruction::UnaryOp(
+                        target,
+                        cfg::UnaryOp::FieldLoad(reg_type, fields.clone()),
+                        Spanned::new(cfg::Value::Reg(reg), span),
+                    ),
+                    span,
+                );
+                RValue::Temp(cfg::Value::Reg(target))
+            }
+            LValue::Deref(ref val, _, ref fields) if fields.is_empty() => {
+                let target = self.new_register(typ.clone());
+                self.emit_instruction(
+                    cfg::Instruction::UnaryOp(
+                        target,
+                        cfg::UnaryOp::DerefLoad,
+                        Spanned::new(val.as_value(), span),
+                    ),
+                    span,
+                );
+                RValue::Temp(cfg::Value::Reg(target))
+            }
+            LValue::Deref(ref val, ref ptr_type, ref fields) => {
+                let address_type = t::Type::Pointer(Rc::new(typ.clone()));
+                let address = self.new_register(address_type);
+                self.emit_instruction(
+                    cfg::Instruction::UnaryOp(
+                        address,
+                        cfg::UnaryOp::OffsetAddress(ptr_type.clone(), fields.clone()),
+                        Spanned::new(val.as_value(), span),
+                    ),
+                    span,
+                );
+                let target = self.new_register(typ.clone());
+                self.emit_instruction(
+                    cfg::Instruction::UnaryOp(
+                        target,
+                        cfg::UnaryOp::DerefLoad,
+                        Spanned::new(cfg::Value::Reg(address), span),
+                    ),
+                    span,
+                );
+                self.emit_instruction(cfg::Instruction::Drop(address), span);
+                RValue::Temp(cfg::Value::Reg(target))
+            }
+        }
+    }
+
     fn emit_take_address(&mut self, target: cfg::Reg, value: Spanned<LValue>, op_span: Span) {
         let value_span = Spanned::span(&value);
         match Spanned::into_value(value) {
@@ -220,6 +296,16 @@ impl<'a> Builder<'a> {
         }
     }
 
+    fn drop_block_vars(&mut self, stmts: &[Spanned<t::Statement>]) {
+        for stmt in stmts {
+            if let t::Statement::Let(sym, _, _) = **stmt {
+                let span = Spanned::span(stmt);
+                let reg = self.var_registers[&sym];
+                self.emit_instruction(cfg::Instruction::Drop(reg), span);
+            }
+        }
+    }
+
     fn build_statement(&mut self, s: &Spanned<t::Statement>) {
         let span = Spanned::span(s);
         match **s {
@@ -229,46 +315,35 @@ impl<'a> Builder<'a> {
                     self.emit_instruction(cfg::Instruction::StartStatement, span);
                     self.build_statement(stmt);
                 }
-                for stmt in stmts {
-                    if let t::Statement::Let(sym, _, _) = **stmt {
-                        let span = Spanned::span(stmt);
-                        let reg = self.var_registers[&sym];
-                        self.emit_instruction(cfg::Instruction::Drop(reg), span);
+                self.drop_block_vars(stmts);
+            }
+            t::Statement::Break(sym, ref value) => {
+                let LoopDescr { after, result, .. } = self.loops[&sym];
+                if let Some(ref value) = *value {
+                    let built = self.build_expr(value);
+                    if let Some(result) = result {
+                        self.emit_instruction(
+                            cfg::Instruction::Assign(
+                                result,
+                                Spanned::new(built.as_value(), value.span),
+                            ),
+                            span,
+                        );
                     }
+                    self.drop_value(&built, value.span);
                 }
+                let new = self.new_block();
+                let link = cfg::BlockLink::Strong(new);
+                self.end_block(cfg::BlockEnd::Jump(after), link);
+                self.start_block(new);
+            }
+            t::Statement::Continue(sym) => {
+                let start = self.loops[&sym].start;
+                let new = self.new_block();
+                let link = cfg::BlockLink::Strong(new);
+                self.end_block(cfg::BlockEnd::Jump(start), link);
+                self.start_block(new);
             }
-            t::Statement::Break => match self.current_loop {
-                Some(LoopDescr { after, .. }) => {
-                    let new = self.new_block();
-                    let link = cfg::BlockLink::Strong(new);
-                    self.end_block(cfg::BlockEnd::Jump(after), link);
-                    self.start_block(new);
-                }
-                None => {
-                    self.emit_instruction(cfg::Instruction::Error, span);
-                    self.ctx
-                        .reporter
-                        .error("cannot use `break` outside loop", span)
-                        .span(span)
-                        .build();
-                }
-            },
-            t::Statement::Continue => match self.current_loop {
-                Some(LoopDescr { start, .. }) => {
-                    let new = self.new_block();
-                    let link = cfg::BlockLink::Strong(new);
-                    self.end_block(cfg::BlockEnd::Jump(start), link);
-                    self.start_block(new);
-                }
-                None => {
-                    self.emit_instruction(cfg::Instruction::Error, span);
-                    self.ctx
-                        .reporter
-                        .error("cannot use `continue` outside loop", span)
-                        .span(span)
-                        .build();
-                }
-            },
             t::Statement::Expr(ref e) => {
                 let expr = self.build_expr(e);
                 self.drop_value(&expr, e.span);
@@ -337,20 +412,7 @@ impl<'a> Builder<'a> {
                 // give it a register, but don't initialize it
                 self.new_var_register(Spanned::into_value(name), (**typ).clone());
             }
-            t::Statement::Loop(ref body) => {
-                let start = self.new_block();
-                let after = self.new_block();
-                let outer_loop = self.current_loop.take();
-                self.current_loop = Some(LoopDescr { start, after });
-                let link = cfg::BlockLink::Weak(start);
-                self.end_block(cfg::BlockEnd::Jump(start), link);
-                self.start_block(start);
-                self.build_statement(body);
-                let link = cfg::BlockLink::Weak(after);
-                self.end_block(cfg::BlockEnd::Jump(start), link);
-                self.current_loop = outer_loop;
-                self.start_block(after);
-            }
+            t::Statement::Loop(sym, ref body) => self.build_loop(sym, body, None),
             t::Statement::Return(ref e) => {
                 let value = self.build_expr(e);
                 let new = self.new_block();
@@ -363,12 +425,16 @@ impl<'a> Builder<'a> {
                 );
                 self.start_block(new);
             }
-            t::Statement::While(ref cond, ref body) => {
+            t::Statement::While(sym, ref cond, ref body) => {
                 let start = self.new_block();
                 let body_start = self.new_block();
                 let after = self.new_block();
-                let outer_loop = self.current_loop.take();
-                self.current_loop = Some(LoopDescr { start, after });
+                let descr = LoopDescr {
+                    start,
+                    after,
+                    result: None,
+                };
+                self.loops.insert(sym, descr);
                 let link = cfg::BlockLink::Weak(start);
                 self.end_block(cfg::BlockEnd::Jump(start), link);
                 self.start_block(start);
@@ -387,20 +453,483 @@ impl<'a> Builder<'a> {
                 self.build_statement(body);
                 let link = cfg::BlockLink::Weak(after);
                 self.end_block(cfg::BlockEnd::Jump(start), link);
-                self.current_loop = outer_loop;
                 self.start_block(after);
                 self.drop_value(&c, cond.span);
             }
+            t::Statement::For(sym, name, ref start, ref end, inclusive, ref body) => {
+                let name = Spanned::into_value(name);
+                self.build_for(sym, name, (start, end), inclusive, body, span);
+            }
+            t::Statement::Match(ref value, ref arms) => {
+                let built_value = self.build_expr(value);
+                let reg = match built_value {
+                    RValue::Var(reg) | RValue::Temp(cfg::Value::Reg(reg)) => reg,
+                    RValue::Temp(ref value_) => {
+                        let reg = self.new_register(value.typ.clone());
+                        let value_ = Spanned::new(value_.clone(), value.span);
+                        self.emit_instruction(cfg::Instruction::Assign(reg, value_), value.span);
+                        reg
+                    }
+                };
+                let after = self.new_block();
+                if let t::Type::Int(sign, size) = value.typ {
+                        self.build_int_match(reg, sign, size, arms, after, value.span);
+                } else {
+                    for (index, arm) in arms.iter().enumerate() {
+                        let is_last = index + 1 == arms.len();
+                        let next = if is_last { after } else { self.new_block() };
+                        // last arm is not tested, because if the match is
+                        // exhaustive, it must match all remaining values
+                        if !is_last {
+                            self.build_pattern_test(reg, &mut Vec::new(), &arm.pattern, next);
+                        }
+                        self.build_arm(reg, arm, after, next);
+                        if !is_last {
+                            self.start_block(next);
+                        }
+                    }
+                    if arms.is_empty() {
+                        let link = cfg::BlockLink::Weak(after);
+                        self.end_block(cfg::BlockEnd::Jump(after), link);
+                    }
+                }
+                self.start_block(after);
+                if let RValue::Temp(_) = built_value {
+                    self.emit_instruction(cfg::Instruction::Drop(reg), value.span);
+                }
+            }
             t::Statement::Error => {
                 self.emit_instruction(cfg::Instruction::Error, span);
             }
         }
     }
 
+    /// Builds bindings and body of a match arm, ending
+    /// the current block with a jump to `after`.
+    /// Builds `for` loop with hidden counter, which is copied to
+    /// the loop variable at the start of each iteration, so that
+    /// changing the variable does not affect the loop. `continue`
+    /// jumps to the block that increments the counter.
+    fn build_loop(&mut self, sym: t::Symbol, body: &Spanned<t::Statement>, result: Option<cfg::Reg>) {
+        let start = self.new_block();
+        let after = self.new_block();
+        self.loops.insert(sym, LoopDescr { start, after, result });
+        let link = cfg::BlockLink::Weak(start);
+        self.end_block(cfg::BlockEnd::Jump(start), link);
+        self.start_block(start);
+        self.build_statement(body);
+        let link = cfg::BlockLink::Weak(after);
+        self.end_block(cfg::BlockEnd::Jump(start), link);
+        self.start_block(after);
+    }
+
+    fn build_for(
+        &mut self,
+        sym: t::Symbol,
+        name: t::Symbol,
+        (start, end): (&t::TypedExpr, &t::TypedExpr),
+        inclusive: bool,
+        body: &Spanned<t::Statement>,
+        span: Span,
+    ) {
+        let typ = start.typ.clone();
+        let new_value_register = |builder: &mut Self, value: &t::TypedExpr| {
+            let built = builder.build_expr(value);
+            let reg = builder.new_register(typ.clone());
+            builder.emit_instruction(
+                cfg::Instruction::Assign(reg, Spanned::new(built.as_value(), value.span)),
+                value.span,
+            );
+            builder.drop_value(&built, value.span);
+            reg
+        };
+        let counter = new_value_register(self, start);
+        let end_reg = new_value_register(self, end);
+        let counter_value = Spanned::new(cfg::Value::Reg(counter), start.span);
+        let end_value = Spanned::new(cfg::Value::Reg(end_reg), end.span);
+
+        let cond_block = self.new_block();
+        let body_block = self.new_block();
+        let step_block = self.new_block();
+        let after = self.new_block();
+        let link = cfg::BlockLink::Weak(cond_block);
+        self.end_block(cfg::BlockEnd::Jump(cond_block), link);
+        self.start_block(cond_block);
+        let op = if inclusive {
+            t::BinaryOp::LessEqual
+        } else {
+            t::BinaryOp::Less
+        };
+        let cond = self.emit_range_op(op, counter_value.clone(), end_value.clone(), span);
+        let link = cfg::BlockLink::Weak(body_block);
+        self.end_block(
+            cfg::BlockEnd::Branch(Spanned::new(cond.as_value(), span), body_block, after),
+            link,
+        );
+
+        self.start_block(body_block);
+        self.drop_value(&cond, span);
+        let var = self.new_var_register(name, typ.clone());
+        self.emit_instruction(cfg::Instruction::Assign(var, counter_value.clone()), span);
+        let descr = LoopDescr {
+            start: step_block,
+            after,
+            result: None,
+        };
+        self.loops.insert(sym, descr);
+        self.build_statement(body);
+        self.emit_instruction(cfg::Instruction::Drop(var), span);
+        let link = cfg::BlockLink::Weak(step_block);
+        self.end_block(cfg::BlockEnd::Jump(step_block), link);
+
+        self.start_block(step_block);
+        if inclusive {
+            // stop before the counter overflows
+            let is_last = self.emit_range_op(
+                t::BinaryOp::Equal,
+                counter_value.clone(),
+                end_value.clone(),
+                span,
+            );
+            let increment_block = self.new_block();
+            let link = cfg::BlockLink::Weak(increment_block);
+            self.end_block(
+                cfg::BlockEnd::Branch(Spanned::new(is_last.as_value(), span), after, increment_block),
+                link,
+            );
+            self.start_block(increment_block);
+            self.drop_value(&is_last, span);
+        }
+        let one = match typ {
+            t::Type::Int(_, size) => cfg::Value::Int(1, size),
+            _ => cfg::Value::Error,
+        };
+        let next = self.emit_range_op(t::BinaryOp::Add, counter_value, Spanned::new(one, span), span);
+        self.emit_instruction(
+            cfg::Instruction::Assign(counter, Spanned::new(next.as_value(), span)),
+            span,
+        );
+        self.drop_value(&next, span);
+        let link = cfg::BlockLink::Weak(after);
+        self.end_block(cfg::BlockEnd::Jump(cond_block), link);
+
+        self.start_block(after);
+        self.drop_value(&cond, span);
+        self.emit_instruction(cfg::Instruction::Drop(counter), span);
+        self.emit_instruction(cfg::Instruction::Drop(end_reg), span);
+    }
+
+    /// Emits an operation on counter of `for` loop.
+    fn emit_range_op(
+        &mut self,
+        op: t::BinaryOp,
+        lhs: Spanned<cfg::Value>,
+        rhs: Spanned<cfg::Value>,
+        span: Span,
+    ) -> RValue {
+        let arg_type = match *lhs {
+            cfg::Value::Reg(reg) => self.registers[&reg].clone(),
+            _ => t::Type::Error,
+        };
+        let result_type = match op {
+            t::BinaryOp::Add => arg_type.clone(),
+            _ => t::Type::Bool,
+        };
+        if let Some(op) = binop_to_instruction(op, &arg_type) {
+            let result = self.new_register(result_type);
+            self.emit_instruction(cfg::Instruction::BinaryOp(result, op, lhs, rhs), span);
+            RValue::Temp(cfg::Value::Reg(result))
+        } else {
+            RValue::Temp(cfg::Value::Error)
+        }
+    }
+
+    fn build_arm(
+        &mut self,
+        reg: cfg::Reg,
+        arm: &t::MatchArm,
+        after: cfg::BlockId,
+        next: cfg::BlockId,
+    ) {
+        let mut bindings = Vec::new();
+        self.build_pattern_bindings(reg, &mut Vec::new(), &arm.pattern, &mut bindings);
+        self.build_statement(&arm.body);
+        for binding in bindings {
+            self.emit_instruction(cfg::Instruction::Drop(binding), arm.pattern.span);
+        }
+        let link = cfg::BlockLink::Weak(next);
+        self.end_block(cfg::BlockEnd::Jump(after), link);
+    }
+
+    /// Builds a match on an integer with a switch. Every value is
+    /// assigned to the first arm matching it, small ranges are expanded
+    /// into switch cases and large ones are tested before the switch.
+    fn build_int_match(
+        &mut self,
+        reg: cfg::Reg,
+        sign: t::Signedness,
+        size: t::Size,
+        arms: &[t::MatchArm],
+        after: cfg::BlockId,
+        span: Span,
+    ) {
+        let arm_blocks = arms.iter().map(|_| self.new_block()).collect::<Vec<_>>();
+        let mut covered = Vec::new();
+        let mut assigned = Vec::new();
+        // like in other matches, values not matched by any arm go to the last
+        // arm, because exhaustiveness is checked before
+        let mut default = arm_blocks.last().cloned().unwrap_or(after);
+        for (arm, &block) in arms.iter().zip(arm_blocks.iter()) {
+            let (lo, hi) = match arm.pattern.pattern {
+                t::Pattern::Int(value) => (value, value),
+                t::Pattern::Range(lo, hi) => (*lo, *hi),
+                t::Pattern::Wildcard |
+                t::Pattern::Binding(_) |
+                t::Pattern::Variant(_, _) |
+                t::Pattern::Error => {
+                    default = block;
+                    break;
+                }
+            };
+            for (lo, hi) in subtract_ranges(lo, hi, &covered) {
+                assigned.push((lo, hi, block, arm.pattern.span));
+            }
+            covered.push((lo, hi));
+        }
+
+        let mut cases = Vec::new();
+        for (lo, hi, block, pattern_span) in assigned {
+            if hi - lo < MAX_SWITCH_RANGE {
+                for value in lo..hi + 1 {
+                    cases.push((const_eval::to_bits(value, size), block));
+                }
+            } else {
+                let value = Spanned::new(cfg::Value::Reg(reg), span);
+                let cond = self.build_int_test(value, sign, size, lo, hi, pattern_span);
+                let next = self.new_block();
+                let link = cfg::BlockLink::Weak(next);
+                let value = Spanned::new(cfg::Value::Reg(cond), pattern_span);
+                self.end_block(cfg::BlockEnd::Branch(value, block, next), link);
+                self.start_block(next);
+                self.emit_instruction(cfg::Instruction::Drop(cond), pattern_span);
+            }
+        }
+        let value = Spanned::new(cfg::Value::Reg(reg), span);
+        let link = cfg::BlockLink::Weak(arm_blocks.first().cloned().unwrap_or(after));
+        self.end_block(cfg::BlockEnd::Switch(value, cases, default), link);
+
+        for (index, arm) in arms.iter().enumerate() {
+            let next = arm_blocks.get(index + 1).cloned().unwrap_or(after);
+            self.start_block(arm_blocks[index]);
+            self.build_arm(reg, arm, after, next);
+        }
+    }
+
+    /// Emits a check whether `value` is in range `lo..=hi`,
+    /// and returns register containing the result.
+    fn build_int_test(
+        &mut self,
+        value: Spanned<cfg::Value>,
+        sign: t::Signedness,
+        size: t::Size,
+        lo: i128,
+        hi: i128,
+        span: Span,
+    ) -> cfg::Reg {
+        let cond = self.new_register(t::Type::Bool);
+        let int = |value| Spanned::new(cfg::Value::Int(const_eval::to_bits(value, size), size), span);
+        if lo == hi {
+            let op = cfg::Instruction::BinaryOp(cond, cfg::BinaryOp::Eq, value, int(lo));
+            self.emit_instruction(op, span);
+            return cond;
+        }
+        let above = self.new_register(t::Type::Bool);
+        let below = self.new_register(t::Type::Bool);
+        self.emit_instruction(
+            cfg::Instruction::BinaryOp(
+                above,
+                cfg::BinaryOp::GreaterEq(sign, size),
+                value.clone(),
+                int(lo),
+            ),
+            span,
+        );
+        self.emit_instruction(
+            cfg::Instruction::BinaryOp(below, cfg::BinaryOp::LessEq(sign, size), value, int(hi)),
+            span,
+        );
+        self.emit_instruction(
+            cfg::Instruction::BinaryOp(
+                cond,
+                cfg::BinaryOp::BitAnd(t::Size::Bit8),
+                Spanned::new(cfg::Value::Reg(above), span),
+                Spanned::new(cfg::Value::Reg(below), span),
+            ),
+            span,
+        );
+        self.emit_instruction(cfg::Instruction::Drop(above), span);
+        self.emit_instruction(cfg::Instruction::Drop(below), span);
+        cond
+    }
+
+    /// Emits checks that value in `reg` at `fields` matches the
+    /// pattern, jumping to `fail` if it doesn't.
+    fn build_pattern_test(
+        &mut self,
+        reg: cfg::Reg,
+        fields: &mut Vec<usize>,
+        pattern: &t::TypedPattern,
+        fail: cfg::BlockId,
+    ) {
+        let span = pattern.span;
+        let reg_type = self.registers[&reg].clone();
+        let cond = match (&pattern.pattern, &pattern.typ) {
+            (&t::Pattern::Variant(variant, _), _) => {
+                let tag_type = t::Type::Int(t::Signedness::Unsigned, t::Size::Bit8);
+                let tag = self.new_register(tag_type);
+                fields.push(0);
+                self.emit_instruction(
+                    cfg::Instruction::UnaryOp(
+                        tag,
+                        cfg::UnaryOp::FieldLoad(reg_type, fields.clone()),
+                        Spanned::new(cfg::Value::Reg(reg), span),
+                    ),
+                    span,
+                );
+                fields.pop();
+                let cond = self.new_register(t::Type::Bool);
+                self.emit_instruction(
+                    cfg::Instruction::BinaryOp(
+                        cond,
+                        cfg::BinaryOp::Eq,
+                        Spanned::new(cfg::Value::Reg(tag), span),
+                        Spanned::new(cfg::Value::Int(variant as u64, t::Size::Bit8), span),
+                    ),
+                    span,
+                );
+                self.emit_instruction(cfg::Instruction::Drop(tag), span);
+                cond
+            }
+            (&t::Pattern::Int(value), &t::Type::Int(_, _)) => {
+                self.build_field_int_test(reg, fields, pattern, value, value)
+            }
+            (&t::Pattern::Range(lo, hi), &t::Type::Int(_, _)) => {
+                self.build_field_int_test(reg, fields, pattern, *lo, *hi)
+            }
+            _ => return,
+        };
+        let matched = self.new_block();
+        let link = cfg::BlockLink::Weak(matched);
+        self.end_block(
+            cfg::BlockEnd::Branch(Spanned::new(cfg::Value::Reg(cond), span), matched, fail),
+            link,
+        );
+        self.start_block(matched);
+        self.emit_instruction(cfg::Instruction::Drop(cond), span);
+        if let t::Pattern::Variant(variant, ref patterns) = pattern.pattern {
+            for (index, field_pattern) in patterns.iter().enumerate() {
+                if let Some(field) = self.variant_field_index(&pattern.typ, variant, index) {
+                    fields.push(field);
+                    self.build_pattern_test(reg, fields, field_pattern, fail);
+                    fields.pop();
+                }
+            }
+        }
+    }
+
+    /// Emits a check whether integer in `reg` at `fields` is
+    /// in range `lo..=hi`, and returns register with the result.
+    fn build_field_int_test(
+        &mut self,
+        reg: cfg::Reg,
+        fields: &[usize],
+        pattern: &t::TypedPattern,
+        lo: i128,
+        hi: i128,
+    ) -> cfg::Reg {
+        let (sign, size) = match pattern.typ {
+            t::Type::Int(sign, size) => (sign, size),
+            _ => panic!("int pattern of non-int type"),
+        };
+        let span = pattern.span;
+        if fields.is_empty() {
+            let value = Spanned::new(cfg::Value::Reg(reg), span);
+            return self.build_int_test(value, sign, size, lo, hi, span);
+        }
+        let reg_type = self.registers[&reg].clone();
+        let field = self.new_register(t::Type::Int(sign, size));
+        self.emit_instruction(
+            cfg::Instruction::UnaryOp(
+                field,
+                cfg::UnaryOp::FieldLoad(reg_type, fields.to_vec()),
+                Spanned::new(cfg::Value::Reg(reg), span),
+            ),
+            span,
+        );
+        let value = Spanned::new(cfg::Value::Reg(field), span);
+        let cond = self.build_int_test(value, sign, size, lo, hi, span);
+        self.emit_instruction(cfg::Instruction::Drop(field), span);
+        cond
+    }
+
+    /// Assigns parts of value in `reg` to variables bound by the pattern.
+    fn build_pattern_bindings(
+        &mut self,
+        reg: cfg::Reg,
+        fields: &mut Vec<usize>,
+        pattern: &t::TypedPattern,
+        bindings: &mut Vec<cfg::Reg>,
+    ) {
+        let span = pattern.span;
+        match pattern.pattern {
+            t::Pattern::Wildcard |
+            t::Pattern::Int(_) |
+            t::Pattern::Range(_, _) |
+            t::Pattern::Error => {}
+            t::Pattern::Binding(symbol) => {
+                let var_register = self.new_var_register(symbol, pattern.typ.clone());
+                if fields.is_empty() {
+                    let value = Spanned::new(cfg::Value::Reg(reg), span);
+                    self.emit_instruction(cfg::Instruction::Assign(var_register, value), span);
+                } else {
+                    let reg_type = self.registers[&reg].clone();
+                    self.emit_instruction(
+                        cfg::Instruction::UnaryOp(
+                            var_register,
+                            cfg::UnaryOp::FieldLoad(reg_type, fields.clone()),
+                            Spanned::new(cfg::Value::Reg(reg), span),
+                        ),
+                        span,
+                    );
+                }
+                bindings.push(var_register);
+            }
+            t::Pattern::Variant(variant, ref patterns) => {
+                for (index, field_pattern) in patterns.iter().enumerate() {
+                    if let Some(field) = self.variant_field_index(&pattern.typ, variant, index) {
+                        fields.push(field);
+                        self.build_pattern_bindings(reg, fields, field_pattern, bindings);
+                        fields.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    fn variant_field_index(&self, typ: &t::Type, variant: usize, field: usize) -> Option<usize> {
+        match *typ {
+            t::Type::Concrete(sym, _) => self.enums
+                .get(&sym)
+                .map(|e| e.field_index(variant, field)),
+            _ => None,
+        }
+    }
+
     fn build_expr(&mut self, e: &t::TypedExpr) -> RValue {
         match *e.expr {
             t::Expr::Binary(ref lhs, op, ref rhs) => match Spanned::into_value(op) {
                 t::BinaryOp::Assign => {
+                    self.check_static_assign(lhs);
                     let target = self.build_expr_lvalue(lhs);
                     let value = self.build_expr(rhs);
                     self.emit_store(
@@ -412,32 +941,37 @@ impl<'a> Builder<'a> {
                 }
                 t::BinaryOp::And => self.build_and(lhs, rhs),
                 t::BinaryOp::Or => self.build_or(lhs, rhs),
-                op => if let Some(op) = binop_to_instruction(op, &lhs.typ) {
+                op => if let Some(op) = op.compound_assign_op() {
+                    self.build_compound_assign(lhs, op, rhs, e.span)
+                } else {
                     let built_lhs = self.build_expr(lhs);
                     let built_rhs = self.build_expr(rhs);
-                    let result = self.new_register(e.typ.clone());
-                    self.emit_instruction(
-                        cfg::Instruction::BinaryOp(
-                            result,
-                            op,
-                            Spanned::new(built_lhs.as_value(), lhs.span),
-                            Spanned::new(built_rhs.as_value(), rhs.span),
-                        ),
+                    let result = self.emit_binary_op(
+                        op,
+                        (lhs, built_lhs.as_value()),
+                        (rhs, built_rhs.as_value()),
+                        &e.typ,
                         e.span,
                     );
                     self.drop_value(&built_lhs, lhs.span);
                     self.drop_value(&built_rhs, rhs.span);
-                    RValue::Temp(cfg::Value::Reg(result))
-                } else {
-                    RValue::Temp(cfg::Value::Error)
+                    result
                 },
             },
             t::Expr::Call(ref name, ref params) => {
-                let callee = self.build_expr(name);
-                let params = params
+                let mut callee = self.build_expr(name);
+                let mut params = params
                     .iter()
                     .map(|p| Spanned::new(self.build_expr(p), p.span))
                     .collect::<Vec<_>>();
+                if let t::Type::Closure(_, _) = name.typ {
+                    // closure passes its environment to the function
+                    let closure = callee;
+                    let env = self.emit_closure_field_load(&closure, &name.typ, 0, name.span);
+                    callee = self.emit_closure_field_load(&closure, &name.typ, 1, name.span);
+                    self.drop_value(&closure, name.span);
+                    params.insert(0, Spanned::new(env, name.span));
+                }
                 let param_values = params
                     .iter()
                     .map(|p| Spanned::new(p.as_value(), Spanned::span(p)))
@@ -470,6 +1004,54 @@ impl<'a> Builder<'a> {
                 self.drop_value(&built_expr, expr.span);
                 RValue::Temp(cfg::Value::Reg(target))
             }
+            t::Expr::Index(ref array, ref index) => {
+                let mut temp = None;
+                let target = match self.build_expr_lvalue(e) {
+                    LValue::Invalid => {
+                        // indexing temporary, put it in register
+                        // so that we can take its address
+                        let value = self.build_expr(array);
+                        let reg = self.new_register(array.typ.clone());
+                        self.emit_instruction(
+                            cfg::Instruction::Assign(
+                                reg,
+                                Spanned::new(value.as_value(), array.span),
+                            ),
+                            array.span,
+                        );
+                        self.drop_value(&value, array.span);
+                        temp = Some(reg);
+                        let target = LValue::Reg(reg, Vec::new());
+                        self.build_index_lvalue(target, array, index, &e.typ)
+                    }
+                    target => target,
+                };
+                let value = self.emit_load(&target, &e.typ, e.span);
+                if let LValue::Deref(ref ptr, _, _) = target {
+                    self.drop_value(ptr, e.span);
+                }
+                if let Some(reg) = temp {
+                    self.emit_instruction(cfg::Instruction::Drop(reg), e.span);
+                }
+                value
+            }
+            t::Expr::Array(ref values) | t::Expr::Tuple(ref values) => {
+                let result = self.new_register(e.typ.clone());
+                self.emit_instruction(cfg::Instruction::Init(result), e.span);
+                for (index, value) in values.iter().enumerate() {
+                    let built_value = self.build_expr(value);
+                    self.emit_instruction(
+                        cfg::Instruction::FieldStore(
+                            Spanned::new(result, e.span),
+                            vec![index],
+                            Spanned::new(built_value.as_value(), value.span),
+                        ),
+                        value.span,
+                    );
+                    self.drop_value(&built_value, value.span);
+                }
+                RValue::Temp(cfg::Value::Reg(result))
+            }
             t::Expr::Literal(ref literal) => RValue::Temp(match *literal {
                 t::Literal::Unit => cfg::Value::Unit,
                 t::Literal::Bool(b) => if b {
@@ -479,12 +1061,7 @@ impl<'a> Builder<'a> {
                 },
                 t::Literal::Char(c) => cfg::Value::Int(u64::from(c), cfg::Size::Bit8),
                 t::Literal::Number(n) => {
-                    let size = match e.typ {
-                        t::Type::Int(_, size) => size,
-                        t::Type::Error => return RValue::Temp(cfg::Value::Error),
-                        _ => panic!("bad int type"),
-                    };
-                    cfg::Value::Int(n.value, size)
+                    self.build_int_literal(i128::from(n.value), &e.typ, e.span)
                 }
                 t::Literal::Str(ref bytes) => {
                     let mut bytes = bytes.clone();
@@ -497,6 +1074,11 @@ impl<'a> Builder<'a> {
                 let name = Spanned::into_value(name);
                 if let Some(reg) = self.var_registers.get(&name).cloned() {
                     RValue::Var(reg)
+                } else if self.statics.contains_key(&name) {
+                    let target = self.build_expr_lvalue(e);
+                    self.emit_load(&target, &e.typ, e.span)
+                } else if let Some(&value) = self.consts.get(&name) {
+                    RValue::Temp(constant_to_value(value, &e.typ))
                 } else {
                     let type_params = type_params
                         .iter()
@@ -509,6 +1091,16 @@ impl<'a> Builder<'a> {
             t::Expr::Unary(op, ref expr) => {
                 let op = Spanned::into_value(op);
                 if op == t::UnaryOp::AddressOf {
+                    if let Some(name) = self.const_name(expr) {
+                        let name = self.ctx.symbols.get_name(name);
+                        let msg = format!("cannot take the address of constant `{}`", name);
+                        self.ctx
+                            .reporter
+                            .error(msg, expr.span)
+                            .span(expr.span)
+                            .build();
+                        return RValue::Temp(cfg::Value::Error);
+                    }
                     let value = self.build_expr_lvalue(expr);
                     let result = self.new_register(e.typ.clone());
                     self.emit_take_address(
@@ -517,6 +1109,11 @@ impl<'a> Builder<'a> {
                         expr.span,
                     );
                     RValue::Temp(cfg::Value::Reg(result))
+                } else if let (t::UnaryOp::Minus, &t::Expr::Literal(t::Literal::Number(n))) =
+                    (op, &*expr.expr)
+                {
+                    // `128i8` alone is out of range, so check `-128i8` as a whole
+                    RValue::Temp(self.build_int_literal(-i128::from(n.value), &e.typ, e.span))
                 } else if let Some(op) = unop_to_instruction(op, &expr.typ) {
                     let built_expr = self.build_expr(expr);
                     let result = self.new_register(e.typ.clone());
@@ -537,33 +1134,226 @@ impl<'a> Builder<'a> {
             t::Expr::Cast(ref expr, ref typ) => {
                 let value = self.build_expr(expr);
                 let result = self.new_register((**typ).clone());
+                let value = Spanned::new(value.as_value(), expr.span);
+                let instruction = match (&expr.typ, &**typ) {
+                    (&t::Type::Int(sign, from), &t::Type::Int(_, to)) => {
+                        let from = const_eval::size_in_bits(from);
+                        let to = const_eval::size_in_bits(to);
+                        match (from.cmp(&to), sign) {
+                            (Ordering::Less, t::Signedness::Unsigned) => {
+                                cfg::Instruction::ZeroExtend(result, value)
+                            }
+                            (Ordering::Less, t::Signedness::Signed) => {
+                                cfg::Instruction::SignExtend(result, value)
+                            }
+                            (Ordering::Greater, _) => cfg::Instruction::Truncate(result, value),
+                            (Ordering::Equal, _) => cfg::Instruction::CastAssign(result, value),
+                        }
+                    }
+                    _ => cfg::Instruction::CastAssign(result, value),
+                };
+                self.emit_instruction(instruction, expr.span);
+                RValue::Temp(cfg::Value::Reg(result))
+            }
+            t::Expr::Closure(ref env, ref function) => self.build_closure(env, function, e),
+            t::Expr::Lambda(_, _, _) => panic!("lambda was not lowered"),
+            t::Expr::If(ref cond, ref then, ref else_) => {
+                self.build_if(cond, then, else_.as_ref(), e)
+            }
+            t::Expr::Block(ref stmts, ref value) => {
+                for stmt in stmts {
+                    let span = Spanned::span(stmt);
+                    self.emit_instruction(cfg::Instruction::StartStatement, span);
+                    self.build_statement(stmt);
+                }
+                let result = match *value {
+                    Some(ref value) => {
+                        self.emit_instruction(cfg::Instruction::StartStatement, value.span);
+                        let built = self.build_expr(value);
+                        // variables of the block are dropped
+                        // below, so value must not refer to them
+                        if let RValue::Var(_) = built {
+                            let result = self.new_register(e.typ.clone());
+                            self.emit_instruction(
+                                cfg::Instruction::Assign(
+                                    result,
+                                    Spanned::new(built.as_value(), value.span),
+                                ),
+                                value.span,
+                            );
+                            RValue::Temp(cfg::Value::Reg(result))
+                        } else {
+                            built
+                        }
+                    }
+                    None => RValue::Temp(cfg::Value::Unit),
+                };
+                self.drop_block_vars(stmts);
+                result
+            }
+            t::Expr::Loop(sym, ref body) => {
+                let result = self.new_register(e.typ.clone());
+                self.build_loop(sym, body, Some(result));
+                RValue::Temp(cfg::Value::Reg(result))
+            }
+        }
+    }
+
+    /// Builds `if` expression, each branch stores its
+    /// value in the same register, unless it diverges.
+    fn build_if(
+        &mut self,
+        cond: &t::TypedExpr,
+        then: &t::TypedExpr,
+        else_: Option<&t::TypedExpr>,
+        e: &t::TypedExpr,
+    ) -> RValue {
+        let c = self.build_expr(cond);
+        let body = self.new_block();
+        let else_body = self.new_block();
+        let after = self.new_block();
+        let result = self.new_register(e.typ.clone());
+        let link = cfg::BlockLink::Weak(body);
+        self.end_block(
+            cfg::BlockEnd::Branch(
+                Spanned::new(c.as_value(), cond.span),
+                body,
+                else_body,
+            ),
+            link,
+        );
+        let branches = [(body, Some(then)), (else_body, else_)];
+        for (i, &(block, branch)) in branches.iter().enumerate() {
+            self.start_block(block);
+            self.drop_value(&c, cond.span);
+            let value = match branch {
+                Some(branch) if branch.expr.diverges() => {
+                    self.build_expr(branch);
+                    None
+                }
+                Some(branch) => Some((self.build_expr(branch), branch.span)),
+                None => Some((RValue::Temp(cfg::Value::Unit), cond.span)),
+            };
+            if let Some((value, span)) = value {
                 self.emit_instruction(
-                    cfg::Instruction::CastAssign(
-                        result,
-                        Spanned::new(value.as_value(), expr.span),
-                    ),
-                    expr.span,
+                    cfg::Instruction::Assign(result, Spanned::new(value.as_value(), span)),
+                    span,
                 );
-                RValue::Temp(cfg::Value::Reg(result))
+                self.drop_value(&value, span);
             }
+            let next = if i == 0 { else_body } else { after };
+            self.end_block(cfg::BlockEnd::Jump(after), cfg::BlockLink::Weak(next));
+        }
+        self.start_block(after);
+        RValue::Temp(cfg::Value::Reg(result))
+    }
+
+    fn build_closure(
+        &mut self,
+        env: &t::TypedExpr,
+        function: &t::TypedExpr,
+        e: &t::TypedExpr,
+    ) -> RValue {
+        // environment lives in a register of enclosing
+        // function, it is never dropped
+        let value = self.build_expr(env);
+        let env_reg = self.new_register(env.typ.clone());
+        self.emit_instruction(
+            cfg::Instruction::Assign(env_reg, Spanned::new(value.as_value(), env.span)),
+            env.span,
+        );
+        self.drop_value(&value, env.span);
+        let address = self.new_register(t::Type::Pointer(Rc::new(env.typ.clone())));
+        self.emit_instruction(
+            cfg::Instruction::TakeAddress(address, Spanned::new(env_reg, env.span), Vec::new()),
+            env.span,
+        );
+        let erased = self.new_register(t::Type::Pointer(Rc::new(t::Type::Unit)));
+        self.emit_instruction(
+            cfg::Instruction::CastAssign(erased, Spanned::new(cfg::Value::Reg(address), env.span)),
+            env.span,
+        );
+        self.emit_instruction(cfg::Instruction::Drop(address), env.span);
+        let function_value = self.build_expr(function);
+        let result = self.new_register(e.typ.clone());
+        self.emit_instruction(cfg::Instruction::Init(result), e.span);
+        let fields = vec![
+            (cfg::Value::Reg(erased), env.span),
+            (function_value.as_value(), function.span),
+        ];
+        for (index, (value, span)) in fields.into_iter().enumerate() {
+            self.emit_instruction(
+                cfg::Instruction::FieldStore(
+                    Spanned::new(result, e.span),
+                    vec![index],
+                    Spanned::new(value, span),
+                ),
+                span,
+            );
         }
+        self.emit_instruction(cfg::Instruction::Drop(erased), env.span);
+        self.drop_value(&function_value, function.span);
+        RValue::Temp(cfg::Value::Reg(result))
+    }
+
+    fn emit_closure_field_load(
+        &mut self,
+        closure: &RValue,
+        typ: &t::Type,
+        field: usize,
+        span: Span,
+    ) -> RValue {
+        let field_type = match *typ {
+            t::Type::Closure(ref params, ref out) => {
+                t::Type::closure_fields(params, out)[field].clone()
+            }
+            _ => panic!("expected a closure"),
+        };
+        let target = self.new_register(field_type);
+        self.emit_instruction(
+            cfg::Instruction::UnaryOp(
+                target,
+                cfg::UnaryOp::FieldLoad(typ.clone(), vec![field]),
+                Spanned::new(closure.as_value(), span),
+            ),
+            span,
+        );
+        RValue::Temp(cfg::Value::Reg(target))
     }
 
     fn build_expr_lvalue(&mut self, e: &t::TypedExpr) -> LValue {
         match *e.expr {
             t::Expr::Binary(_, _, _) |
             t::Expr::Call(_, _) |
+            t::Expr::Array(_) |
+            t::Expr::Tuple(_) |
             t::Expr::Literal(_) |
-            t::Expr::Cast(_, _) => LValue::Invalid,
+            t::Expr::Cast(_, _) |
+            t::Expr::If(_, _, _) |
+            t::Expr::Block(_, _) |
+            t::Expr::Loop(_, _) |
+            t::Expr::Closure(_, _) => LValue::Invalid,
+            t::Expr::Lambda(_, _, _) => panic!("lambda was not lowered"),
             t::Expr::Error => LValue::Error,
             t::Expr::Field(ref expr, index) => {
                 let mut lvalue = self.build_expr_lvalue(expr);
                 lvalue.add_field(Spanned::into_value(index));
                 lvalue
             }
+            t::Expr::Index(ref array, ref index) => if is_pointer(&array.typ) {
+                let ptr = self.build_expr(array);
+                self.build_pointer_index(ptr, array.span, index, &e.typ)
+            } else {
+                let lvalue = self.build_expr_lvalue(array);
+                self.build_index_lvalue(lvalue, array, index, &e.typ)
+            },
             t::Expr::Name(ref name, _) => {
                 if let Some(reg) = self.var_registers.get(&**name).cloned() {
                     LValue::Reg(reg, Vec::new())
+                } else if self.statics.contains_key(&**name) {
+                    let address = cfg::Value::Global(**name);
+                    let ptr_type = t::Type::Pointer(Rc::new(e.typ.clone()));
+                    LValue::Deref(RValue::Temp(address), ptr_type, Vec::new())
                 } else {
                     LValue::Invalid
                 }
@@ -578,6 +1368,235 @@ impl<'a> Builder<'a> {
         }
     }
 
+    /// Builds int literal `value`, reporting it if it does not fit in `typ`.
+    fn build_int_literal(&mut self, value: i128, typ: &t::Type, span: Span) -> cfg::Value {
+        match *typ {
+            t::Type::Int(sign, size) if !const_eval::fits_in(value, sign, size) => {
+                let msg = format!(
+                    "literal out of range for `{}`",
+                    const_eval::int_type_name(sign, size)
+                );
+                self.ctx.reporter.error(msg, span).span(span).build();
+                cfg::Value::Error
+            }
+            t::Type::Int(_, size) => cfg::Value::Int(const_eval::to_bits(value, size), size),
+            t::Type::Error => cfg::Value::Error,
+            _ => panic!("bad int type"),
+        }
+    }
+
+    /// Returns the constant that expression names, if any.
+    fn const_name(&self, e: &t::TypedExpr) -> Option<t::Symbol> {
+        match *e.expr {
+            t::Expr::Name(ref name, _) if !self.var_registers.contains_key(&**name) &&
+                self.consts.contains_key(&**name) => Some(**name),
+            _ => None,
+        }
+    }
+
+    fn check_static_assign(&mut self, target: &t::TypedExpr) {
+        match *target.expr {
+            t::Expr::Field(ref expr, _) => self.check_static_assign(expr),
+            t::Expr::Index(ref array, _) if !is_pointer(&array.typ) => {
+                self.check_static_assign(array)
+            }
+            t::Expr::Name(ref name, _) if self.statics.get(&**name) == Some(&false) => {
+                let name = self.ctx.symbols.get_name(**name);
+                let msg = format!("cannot assign to immutable static `{}`", name);
+                self.ctx
+                    .reporter
+                    .error(msg, target.span)
+                    .span(target.span)
+                    .build();
+            }
+            _ => {}
+        }
+    }
+
+    fn build_index_lvalue(
+        &mut self,
+        array: LValue,
+        array_expr: &t::TypedExpr,
+        index: &t::TypedExpr,
+        elem_type: &t::Type,
+    ) -> LValue {
+        let mut array = match array {
+            LValue::Invalid => return LValue::Invalid,
+            LValue::Error => return LValue::Error,
+            array => array,
+        };
+        if let t::Expr::Literal(t::Literal::Number(n)) = *index.expr {
+            // constant index is just like a field access
+            array.add_field(n.value as usize);
+            return array;
+        }
+        let array_ptr_type = t::Type::Pointer(Rc::new(array_expr.typ.clone()));
+        let array_ptr = self.new_register(array_ptr_type);
+        self.emit_take_address(
+            array_ptr,
+            Spanned::new(array, array_expr.span),
+            array_expr.span,
+        );
+        let array_ptr = RValue::Temp(cfg::Value::Reg(array_ptr));
+        self.build_pointer_index(array_ptr, array_expr.span, index, elem_type)
+    }
+
+    fn build_pointer_index(
+        &mut self,
+        ptr: RValue,
+        ptr_span: Span,
+        index: &t::TypedExpr,
+        elem_type: &t::Type,
+    ) -> LValue {
+        let built_index = self.build_expr(index);
+        let elem_ptr_type = t::Type::Pointer(Rc::new(elem_type.clone()));
+        let elem_ptr = self.new_register(elem_ptr_type.clone());
+        self.emit_instruction(
+            cfg::Instruction::PointerOffset(
+                elem_ptr,
+                Spanned::new(ptr.as_value(), ptr_span),
+                elem_type.clone(),
+                Spanned::new(built_index.as_value(), index.span),
+            ),
+            index.span,
+        );
+        self.drop_value(&ptr, ptr_span);
+        self.drop_value(&built_index, index.span);
+        LValue::Deref(
+            RValue::Temp(cfg::Value::Reg(elem_ptr)),
+            elem_ptr_type,
+            Vec::new(),
+        )
+    }
+
+    fn emit_binary_op(
+        &mut self,
+        op: t::BinaryOp,
+        (lhs, lhs_value): (&t::TypedExpr, cfg::Value),
+        (rhs, rhs_value): (&t::TypedExpr, cfg::Value),
+        result_type: &t::Type,
+        span: Span,
+    ) -> RValue {
+        let lhs_value = Spanned::new(lhs_value, lhs.span);
+        let rhs_value = Spanned::new(rhs_value, rhs.span);
+        match (op, &lhs.typ) {
+            (t::BinaryOp::Add, &t::Type::Pointer(ref elem_type)) |
+            (t::BinaryOp::Subtract, &t::Type::Pointer(ref elem_type)) => {
+                let rhs = (rhs, rhs_value);
+                return self.emit_pointer_op(op, lhs_value, elem_type, rhs, result_type, span);
+            }
+            _ => {}
+        }
+        if let Some(op) = binop_to_instruction(op, &lhs.typ) {
+            let result = self.new_register(result_type.clone());
+            self.emit_instruction(
+                cfg::Instruction::BinaryOp(result, op, lhs_value, rhs_value),
+                span,
+            );
+            RValue::Temp(cfg::Value::Reg(result))
+        } else {
+            RValue::Temp(cfg::Value::Error)
+        }
+    }
+
+    fn emit_pointer_op(
+        &mut self,
+        op: t::BinaryOp,
+        ptr: Spanned<cfg::Value>,
+        elem_type: &t::Type,
+        (rhs, rhs_value): (&t::TypedExpr, Spanned<cfg::Value>),
+        result_type: &t::Type,
+        span: Span,
+    ) -> RValue {
+        let elem_type = elem_type.clone();
+        let result = self.new_register(result_type.clone());
+        match (op, is_pointer(&rhs.typ)) {
+            (t::BinaryOp::Subtract, true) => {
+                self.emit_instruction(
+                    cfg::Instruction::PointerDiff(result, ptr, elem_type, rhs_value),
+                    span,
+                );
+            }
+            (t::BinaryOp::Add, _) => {
+                self.emit_instruction(
+                    cfg::Instruction::PointerOffset(result, ptr, elem_type, rhs_value),
+                    span,
+                );
+            }
+            _ => {
+                // offsets are unsigned, so subtract by adding negated offset
+                let offset_type = t::Type::Int(t::Signedness::Unsigned, t::Size::Bit32);
+                let negated = self.new_register(offset_type);
+                self.emit_instruction(
+                    cfg::Instruction::BinaryOp(
+                        negated,
+                        cfg::BinaryOp::Sub(t::Signedness::Unsigned, t::Size::Bit32),
+                        Spanned::new(cfg::Value::Int(0, t::Size::Bit32), rhs.span),
+                        rhs_value,
+                    ),
+                    span,
+                );
+                self.emit_instruction(
+                    cfg::Instruction::PointerOffset(
+                        result,
+                        ptr,
+                        elem_type,
+                        Spanned::new(cfg::Value::Reg(negated), rhs.span),
+                    ),
+                    span,
+                );
+                self.emit_instruction(cfg::Instruction::Drop(negated), span);
+            }
+        }
+        RValue::Temp(cfg::Value::Reg(result))
+    }
+
+    fn build_compound_assign(
+        &mut self,
+        lhs: &t::TypedExpr,
+        op: t::BinaryOp,
+        rhs: &t::TypedExpr,
+        span: Span,
+    ) -> RValue {
+        self.check_static_assign(lhs);
+        // build lvalue only once, so that side effects
+        // in `*f() += 1` happen only once too
+        let target = match self.build_expr_lvalue(lhs) {
+            // `*p += { p = q; 1 }` still targets the old `p`
+            LValue::Deref(RValue::Var(ptr), ptr_type, fields) => {
+                let copy = self.new_register(ptr_type.clone());
+                self.emit_instruction(
+                    cfg::Instruction::Assign(
+                        copy,
+                        Spanned::new(cfg::Value::Reg(ptr), lhs.span),
+                    ),
+                    lhs.span,
+                );
+                LValue::Deref(RValue::Temp(cfg::Value::Reg(copy)), ptr_type, fields)
+            }
+            target => target,
+        };
+        // current value is read after rhs for every kind of lvalue,
+        // like it is for plain variables
+        let built_rhs = self.build_expr(rhs);
+        let current = self.emit_load(&target, &lhs.typ, lhs.span);
+        let value = self.emit_binary_op(
+            op,
+            (lhs, current.as_value()),
+            (rhs, built_rhs.as_value()),
+            &lhs.typ,
+            span,
+        );
+        self.drop_value(&current, lhs.span);
+        self.drop_value(&built_rhs, rhs.span);
+        self.emit_store(
+            Spanned::new(target, lhs.span),
+            Spanned::new(value.as_value(), span),
+            span,
+        );
+        value
+    }
+
     fn build_and(&mut self, lhs: &t::TypedExpr, rhs: &t::TypedExpr) -> RValue {
         let built_lhs = self.build_expr(lhs);
         let rhs_block = self.new_block();
@@ -673,6 +1692,10 @@ impl<'a> Builder<'a> {
     }
 }
 
+fn is_pointer(typ: &t::Type) -> bool {
+    matches!(*typ, t::Type::Pointer(_))
+}
+
 fn binop_to_instruction(op: t::BinaryOp, arg_type: &t::Type) -> Option<cfg::BinaryOp> {
     let int = match *arg_type {
         t::Type::Int(sign, size) => Some((sign, size)),
@@ -688,9 +1711,26 @@ fn binop_to_instruction(op: t::BinaryOp, arg_type: &t::Type) -> Option<cfg::Bina
         t::BinaryOp::Modulo => int.map(|(sign, size)| cfg::BinaryOp::Mod(sign, size)),
         t::BinaryOp::Multiply => int.map(|(sign, size)| cfg::BinaryOp::Mul(sign, size)),
         t::BinaryOp::Subtract => int.map(|(sign, size)| cfg::BinaryOp::Sub(sign, size)),
+        t::BinaryOp::BitAnd => int.map(|(_, size)| cfg::BinaryOp::BitAnd(size)),
+        t::BinaryOp::BitOr => int.map(|(_, size)| cfg::BinaryOp::BitOr(size)),
+        t::BinaryOp::BitXor => int.map(|(_, size)| cfg::BinaryOp::BitXor(size)),
+        t::BinaryOp::ShiftLeft => int.map(|(_, size)| cfg::BinaryOp::ShiftLeft(size)),
+        t::BinaryOp::ShiftRight => int.map(|(sign, size)| cfg::BinaryOp::ShiftRight(sign, size)),
         t::BinaryOp::Equal => Some(cfg::BinaryOp::Eq),
         t::BinaryOp::NotEqual => Some(cfg::BinaryOp::Neq),
-        t::BinaryOp::And | t::BinaryOp::Or | t::BinaryOp::Assign => {
+        t::BinaryOp::And |
+        t::BinaryOp::Or |
+        t::BinaryOp::Assign |
+        t::BinaryOp::AddAssign |
+        t::BinaryOp::SubtractAssign |
+        t::BinaryOp::MultiplyAssign |
+        t::BinaryOp::DivideAssign |
+        t::BinaryOp::ModuloAssign |
+        t::BinaryOp::BitAndAssign |
+        t::BinaryOp::BitOrAssign |
+        t::BinaryOp::BitXorAssign |
+        t::BinaryOp::ShiftLeftAssign |
+        t::BinaryOp::ShiftRightAssign => {
             panic!("invalid binop");
         }
     }
@@ -705,12 +1745,30 @@ fn unop_to_instruction(op: t::UnaryOp, arg_type: &t::Type) -> Option<cfg::UnaryO
         t::UnaryOp::Deref => Some(cfg::UnaryOp::DerefLoad),
         t::UnaryOp::Minus => int.map(|(sign, size)| cfg::UnaryOp::Negate(sign, size)),
         t::UnaryOp::Not => Some(cfg::UnaryOp::Not),
+        t::UnaryOp::BitNot => int.map(|(_, size)| cfg::UnaryOp::BitNot(size)),
         t::UnaryOp::Plus | t::UnaryOp::AddressOf => panic!("invalid unary op"),
     }
 }
 
-fn compile_fn(f: &t::Function, ctx: &mut CompileCtx) -> cfg::Function {
-    let mut builder = Builder::new(ctx);
+fn constant_to_value(constant: Option<Constant>, typ: &t::Type) -> cfg::Value {
+    match (constant, typ) {
+        (Some(Constant::Int(i)), &t::Type::Int(_, size)) => {
+            cfg::Value::Int(const_eval::to_bits(i, size), size)
+        }
+        (Some(Constant::Bool(b)), _) => cfg::Value::Int(b as u64, cfg::Size::Bit8),
+        (Some(Constant::Unit), _) => cfg::Value::Unit,
+        _ => cfg::Value::Error,
+    }
+}
+
+fn compile_fn(
+    f: &t::Function,
+    statics: &HashMap<t::Symbol, bool>,
+    consts: &HashMap<t::Symbol, Option<Constant>>,
+    enums: &HashMap<t::Symbol, t::Enum>,
+    ctx: &mut CompileCtx,
+) -> cfg::Function {
+    let mut builder = Builder::new(ctx, statics, consts, enums);
     let start_block = builder.build_function(f);
     debug_assert!(builder.current_block.is_none());
     cfg::Function {
@@ -725,14 +1783,58 @@ fn compile_fn(f: &t::Function, ctx: &mut CompileCtx) -> cfg::Function {
     }
 }
 
-pub(crate) fn build_cfg(program: &t::Program, ctx: &mut CompileCtx) -> cfg::Program {
+pub(crate) fn build_cfg(
+    program: &t::Program,
+    consts: &HashMap<t::Symbol, Option<Constant>>,
+    ctx: &mut CompileCtx,
+) -> cfg::Program {
+    let static_mutability = program
+        .statics
+        .iter()
+        .map(|s| (s.name, s.mutable))
+        .collect();
+
     let functions = program
         .functions
         .iter()
-        .map(|f| (f.name, compile_fn(f, ctx)))
+        .map(|f| {
+            let function = compile_fn(f, &static_mutability, consts, &program.enums, ctx);
+            (f.name, function)
+        })
         .collect();
 
     let structs = program.structs.clone();
+    let enums = program.enums.clone();
+    let impls = program.impls.clone();
+    let statics = ::build_statics::build_statics(program, consts, ctx);
 
-    cfg::Program { structs, functions }
+    cfg::Program {
+        structs,
+        enums,
+        impls,
+        functions,
+        statics,
+    }
+}
+
+/// Returns parts of range `lo..=hi` not covered by any of the `ranges`.
+fn subtract_ranges(lo: i128, hi: i128, ranges: &[(i128, i128)]) -> Vec<(i128, i128)> {
+    let mut parts = vec![(lo, hi)];
+    for &(a, b) in ranges.iter().filter(|&&(a, b)| a <= b) {
+        parts = parts
+            .into_iter()
+            .flat_map(|(lo, hi)| {
+                let mut rest = Vec::new();
+                if lo < a {
+                    rest.push((lo, hi.min(a - 1)));
+                }
+                if hi > b {
+                    rest.push((lo.max(b + 1), hi));
+                }
+                rest
+            })
+            .collect();
+    }
+    parts.retain(|&(lo, hi)| lo <= hi);
+    parts
 }
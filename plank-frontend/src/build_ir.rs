@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use plank_ir::ir;
-use ast::cfg;
+use ast::{cfg, typed};
 use struct_layout::LayoutEngine;
 use CompileCtx;
 
@@ -10,18 +10,23 @@ struct Builder<'a> {
     ctx: &'a CompileCtx,
     function_name: cfg::Symbol,
     function: &'a cfg::Function,
+    impls: &'a [typed::Impl],
     type_params: HashMap<cfg::Symbol, cfg::Type>,
     dependencies: HashMap<ir::Symbol, (cfg::Symbol, Vec<cfg::Type>)>,
+    strings: &'a mut HashMap<Vec<u8>, ir::Symbol>,
     registers: HashMap<ir::Reg, ir::Layout>,
+    next_reg: u32,
 }
 
 impl<'a> Builder<'a> {
     fn new(
         function_name: cfg::Symbol,
         function: &'a cfg::Function,
+        impls: &'a [typed::Impl],
         type_params: HashMap<cfg::Symbol, cfg::Type>,
         ctx: &'a CompileCtx,
         layouts: &'a LayoutEngine<'a>,
+        strings: &'a mut HashMap<Vec<u8>, ir::Symbol>,
     ) -> Self {
         let registers = function
             .registers
@@ -34,14 +39,23 @@ impl<'a> Builder<'a> {
                 (reg, layout)
             })
             .collect();
+        let next_reg = function
+            .registers
+            .keys()
+            .map(|reg| reg.0 + 1)
+            .max()
+            .unwrap_or(0);
         Builder {
             ctx,
             type_params,
             layouts,
             function_name,
             function,
+            impls,
             dependencies: HashMap::new(),
+            strings,
             registers,
+            next_reg,
         }
     }
 
@@ -88,7 +102,7 @@ impl<'a> Builder<'a> {
         } else if self.function_name == ::builtins::ALIGN_OF {
             debug_assert_eq!(self.type_params.len(), 1);
             let param = self.type_params.values().next().unwrap();
-            let align = u64::from(self.layouts.size_of(param).unwrap());
+            let align = u64::from(self.layouts.align_of(param).unwrap());
             blocks.insert(
                 ir::BlockId(0),
                 ir::Block {
@@ -112,14 +126,29 @@ impl<'a> Builder<'a> {
     fn build_block(&mut self, block: &cfg::Block) -> ir::Block {
         let mut ops = Vec::new();
         for op in &block.ops {
-            if let Some(op) = self.build_instruction(op) {
-                ops.push(op);
+            match **op {
+                cfg::Instruction::PointerOffset(dest, ref ptr, ref typ, ref offset) => {
+                    self.build_pointer_offset(&mut ops, dest, ptr, typ, offset);
+                }
+                cfg::Instruction::PointerDiff(dest, ref a, ref typ, ref b) => {
+                    self.build_pointer_diff(&mut ops, dest, a, typ, b);
+                }
+                ref op => if let Some(op) = self.build_instruction(op) {
+                    ops.push(op);
+                },
             }
         }
         let end = match block.end {
             cfg::BlockEnd::Branch(ref val, a, b) => {
                 ir::BlockEnd::Branch(self.convert_value(val), ir::BlockId(a.0), ir::BlockId(b.0))
             }
+            cfg::BlockEnd::Switch(ref val, ref cases, default) => {
+                let cases = cases
+                    .iter()
+                    .map(|&(value, block)| (value, ir::BlockId(block.0)))
+                    .collect();
+                ir::BlockEnd::Switch(self.convert_value(val), cases, ir::BlockId(default.0))
+            }
             cfg::BlockEnd::Error => panic!("cannot build ir with errors"),
             cfg::BlockEnd::Jump(id) => ir::BlockEnd::Jump(ir::BlockId(id.0)),
             cfg::BlockEnd::Return(ref val) => if self.is_zero_sized_value(val) {
@@ -131,6 +160,79 @@ impl<'a> Builder<'a> {
         ir::Block { ops, end }
     }
 
+    fn build_pointer_offset(
+        &mut self,
+        ops: &mut Vec<ir::Instruction>,
+        dest: cfg::Reg,
+        ptr: &cfg::Value,
+        typ: &cfg::Type,
+        offset: &cfg::Value,
+    ) {
+        let typ = typ.replace(&self.type_params);
+        let size = self.layouts.size_of(&typ).unwrap();
+        let dest = ir::Reg(dest.0);
+        let ptr = self.convert_value(ptr);
+        let add = ir::BinaryOp::IntOp(ir::IntOp::Add, ir::Signedness::Unsigned, pointer_size());
+        match (size, self.convert_value(offset)) {
+            (0, _) => ops.push(ir::Instruction::Assign(dest, ptr)),
+            (_, ir::Value::Int(offset, _)) => {
+                let offset = offset.wrapping_mul(u64::from(size)) & pointer_mask();
+                let offset = ir::Value::Int(offset, pointer_size());
+                ops.push(ir::Instruction::BinaryOp(dest, add, ptr, offset));
+            }
+            (1, offset) => ops.push(ir::Instruction::BinaryOp(dest, add, ptr, offset)),
+            (_, offset) => {
+                let mul =
+                    ir::BinaryOp::IntOp(ir::IntOp::Mul, ir::Signedness::Unsigned, pointer_size());
+                let temp = self.new_temp_register();
+                let size = ir::Value::Int(u64::from(size), pointer_size());
+                ops.push(ir::Instruction::BinaryOp(temp, mul, offset, size));
+                ops.push(ir::Instruction::BinaryOp(dest, add, ptr, ir::Value::Reg(temp)));
+                ops.push(ir::Instruction::Drop(temp));
+            }
+        }
+    }
+
+    fn build_pointer_diff(
+        &mut self,
+        ops: &mut Vec<ir::Instruction>,
+        dest: cfg::Reg,
+        a: &cfg::Value,
+        typ: &cfg::Type,
+        b: &cfg::Value,
+    ) {
+        let typ = typ.replace(&self.type_params);
+        let size = self.layouts.size_of(&typ).unwrap();
+        let dest = ir::Reg(dest.0);
+        let a = self.convert_value(a);
+        let b = self.convert_value(b);
+        let sub = ir::BinaryOp::IntOp(ir::IntOp::Sub, ir::Signedness::Unsigned, pointer_size());
+        match size {
+            0 => {
+                let value = ir::Value::Int(0, pointer_size());
+                ops.push(ir::Instruction::Assign(dest, value));
+            }
+            1 => ops.push(ir::Instruction::BinaryOp(dest, sub, a, b)),
+            _ => {
+                let div =
+                    ir::BinaryOp::IntOp(ir::IntOp::Div, ir::Signedness::Signed, pointer_size());
+                let temp = self.new_temp_register();
+                let size = ir::Value::Int(u64::from(size), pointer_size());
+                ops.push(ir::Instruction::BinaryOp(temp, sub, a, b));
+                ops.push(ir::Instruction::BinaryOp(dest, div, ir::Value::Reg(temp), size));
+                ops.push(ir::Instruction::Drop(temp));
+            }
+        }
+    }
+
+    fn new_temp_register(&mut self) -> ir::Reg {
+        let reg = ir::Reg(self.next_reg);
+        self.next_reg += 1;
+        let layout = pointer_layout();
+        self.registers.insert(reg, layout);
+        reg
+    }
+
     fn build_instruction(&mut self, i: &cfg::Instruction) -> Option<ir::Instruction> {
         match *i {
             cfg::Instruction::Assign(to, ref val) => if self.is_zero_sized(to) {
@@ -235,7 +337,7 @@ impl<'a> Builder<'a> {
             cfg::Instruction::TakeAddress(dest, reg, ref fields) => {
                 let dest = ir::Reg(dest.0);
                 if self.is_zero_sized(*reg) {
-                    let value = ir::Value::Int(0, ir::Size::Bit32);
+                    let value = ir::Value::Int(0, pointer_size());
                     Some(ir::Instruction::Assign(dest, value))
                 } else {
                     let offset = {
@@ -259,6 +361,7 @@ impl<'a> Builder<'a> {
                     cfg::Size::Bit8 => ir::Size::Bit8,
                     cfg::Size::Bit16 => ir::Size::Bit16,
                     cfg::Size::Bit32 => ir::Size::Bit32,
+                    cfg::Size::Bit64 => ir::Size::Bit64,
                 };
                 Some(ir::Instruction::UnaryOp(
                     dest,
@@ -298,6 +401,20 @@ impl<'a> Builder<'a> {
                 let arg = ir::Value::Int(1, ir::Size::Bit8);
                 Some(ir::Instruction::BinaryOp(dest, op, val, arg))
             }
+            cfg::Instruction::UnaryOp(dest, cfg::UnaryOp::BitNot(size), ref val) => {
+                debug_assert!(!self.is_zero_sized(dest));
+                let dest = ir::Reg(dest.0);
+                let val = self.convert_value(val);
+                let (size, mask) = match size {
+                    cfg::Size::Bit8 => (ir::Size::Bit8, 0xff),
+                    cfg::Size::Bit16 => (ir::Size::Bit16, 0xffff),
+                    cfg::Size::Bit32 => (ir::Size::Bit32, 0xffff_ffff),
+                    cfg::Size::Bit64 => (ir::Size::Bit64, 0xffff_ffff_ffff_ffff),
+                };
+                let op = ir::BinaryOp::BitOp(ir::BitOp::Xor, size);
+                let arg = ir::Value::Int(mask, size);
+                Some(ir::Instruction::BinaryOp(dest, op, val, arg))
+            }
             cfg::Instruction::UnaryOp(
                 dest,
                 cfg::UnaryOp::OffsetAddress(ref typ, ref fields),
@@ -311,10 +428,16 @@ impl<'a> Builder<'a> {
                 let offset = self.find_offset(typ, fields);
                 let val = self.convert_value(val);
                 let op =
-                    ir::BinaryOp::IntOp(ir::IntOp::Add, ir::Signedness::Unsigned, ir::Size::Bit32);
-                let arg = ir::Value::Int(u64::from(offset), ir::Size::Bit32);
+                    ir::BinaryOp::IntOp(ir::IntOp::Add, ir::Signedness::Unsigned, pointer_size());
+                let arg = ir::Value::Int(u64::from(offset), pointer_size());
                 Some(ir::Instruction::BinaryOp(dest, op, val, arg))
             }
+            cfg::Instruction::PointerOffset(_, _, _, _) => {
+                panic!("pointer offset must be built by `build_pointer_offset`")
+            }
+            cfg::Instruction::PointerDiff(_, _, _, _) => {
+                panic!("pointer difference must be built by `build_pointer_diff`")
+            }
             cfg::Instruction::Error => panic!("cannot build ir with errors"),
             cfg::Instruction::CastAssign(to, ref val) => if self.is_zero_sized(to) {
                 None
@@ -322,6 +445,18 @@ impl<'a> Builder<'a> {
                 let val = self.convert_value(val);
                 Some(ir::Instruction::CastAssign(ir::Reg(to.0), val))
             },
+            cfg::Instruction::ZeroExtend(to, ref val) => {
+                let val = self.convert_value(val);
+                Some(ir::Instruction::ZeroExtend(ir::Reg(to.0), val))
+            }
+            cfg::Instruction::SignExtend(to, ref val) => {
+                let val = self.convert_value(val);
+                Some(ir::Instruction::SignExtend(ir::Reg(to.0), val))
+            }
+            cfg::Instruction::Truncate(to, ref val) => {
+                let val = self.convert_value(val);
+                Some(ir::Instruction::Truncate(ir::Reg(to.0), val))
+            }
         }
     }
 
@@ -334,6 +469,7 @@ impl<'a> Builder<'a> {
         match *value {
             cfg::Value::Unit => true,
             cfg::Value::Bytes(_) |
+            cfg::Value::Global(_) |
             cfg::Value::Int(_, _) |
             cfg::Value::Symbol(_, _) => false,
             cfg::Value::Reg(reg) => self.is_zero_sized(reg),
@@ -346,6 +482,7 @@ impl<'a> Builder<'a> {
             .iter()
             .map(|ty| ty.replace(&self.type_params))
             .collect::<Vec<_>>();
+        let (id, type_params) = dispatch(self.impls, id, type_params);
         let mut symbol: String = self.ctx.symbols.get_name(id).into();
         if !type_params.is_empty() {
             symbol.push_str("::<");
@@ -374,6 +511,23 @@ impl<'a> Builder<'a> {
                 to.push('*');
                 self.write_type(to, ty);
             }
+            cfg::Type::Array(ref ty, len) => {
+                to.push('[');
+                self.write_type(to, ty);
+                to.push_str(&format!(";{}]", len));
+            }
+            cfg::Type::Tuple(ref types) => {
+                to.push('(');
+                let mut first = true;
+                for typ in types.iter() {
+                    if !first {
+                        to.push(',');
+                    }
+                    first = false;
+                    self.write_type(to, typ);
+                }
+                to.push(')');
+            }
             cfg::Type::Concrete(name, ref params) => {
                 to.push_str(self.ctx.symbols.get_name(name));
                 if !params.is_empty() {
@@ -389,8 +543,12 @@ impl<'a> Builder<'a> {
                     to.push('>');
                 }
             }
-            cfg::Type::Function(ref params, ref out) => {
-                to.push_str("fn(");
+            cfg::Type::Function(ref params, ref out) | cfg::Type::Closure(ref params, ref out) => {
+                let (open, close) = match *typ {
+                    cfg::Type::Closure(_, _) => ("|", "|->"),
+                    _ => ("fn(", ")->"),
+                };
+                to.push_str(open);
                 let mut first = true;
                 for param in params.iter() {
                     if !first {
@@ -399,27 +557,40 @@ impl<'a> Builder<'a> {
                     first = false;
                     self.write_type(to, param);
                 }
-                to.push_str(")->");
+                to.push_str(close);
                 self.write_type(to, out);
             }
             cfg::Type::Int(cfg::Signedness::Unsigned, cfg::Size::Bit8) => to.push_str("u8"),
             cfg::Type::Int(cfg::Signedness::Unsigned, cfg::Size::Bit16) => to.push_str("u16"),
             cfg::Type::Int(cfg::Signedness::Unsigned, cfg::Size::Bit32) => to.push_str("u32"),
+            cfg::Type::Int(cfg::Signedness::Unsigned, cfg::Size::Bit64) => to.push_str("u64"),
             cfg::Type::Int(cfg::Signedness::Signed, cfg::Size::Bit8) => to.push_str("i8"),
             cfg::Type::Int(cfg::Signedness::Signed, cfg::Size::Bit16) => to.push_str("i16"),
             cfg::Type::Int(cfg::Signedness::Signed, cfg::Size::Bit32) => to.push_str("i32"),
+            cfg::Type::Int(cfg::Signedness::Signed, cfg::Size::Bit64) => to.push_str("i64"),
         }
     }
 
     fn convert_value(&mut self, value: &cfg::Value) -> ir::Value {
         match *value {
             cfg::Value::Unit => panic!("cannot convert zero sized value"),
-            cfg::Value::Bytes(ref bytes) => ir::Value::Bytes(bytes.clone()),
+            cfg::Value::Bytes(ref bytes) => {
+                // string literals are stored as unnamed globals
+                let next_id = self.strings.len();
+                let symbol = self.strings
+                    .entry(bytes.clone())
+                    .or_insert_with(|| ir::Symbol(format!("@string_{}", next_id).into()));
+                ir::Value::Global(symbol.clone())
+            }
+            cfg::Value::Global(sym) => {
+                ir::Value::Global(ir::Symbol(self.ctx.symbols.get_name(sym).into()))
+            }
             cfg::Value::Int(value, size) => {
                 let size = match size {
                     cfg::Size::Bit8 => ir::Size::Bit8,
                     cfg::Size::Bit16 => ir::Size::Bit16,
                     cfg::Size::Bit32 => ir::Size::Bit32,
+                    cfg::Size::Bit64 => ir::Size::Bit64,
                 };
                 ir::Value::Int(value, size)
             }
@@ -441,6 +612,51 @@ impl<'a> Builder<'a> {
     }
 }
 
+/// Replaces trait method with function that implements it for
+/// `Self`, which is the first type parameter. Other functions
+/// are returned unchanged.
+fn dispatch(
+    impls: &[typed::Impl],
+    function: cfg::Symbol,
+    type_params: Vec<cfg::Type>,
+) -> (cfg::Symbol, Vec<cfg::Type>) {
+    for impl_ in impls {
+        if let Some(&implementation) = impl_.functions.get(&function) {
+            if let Some(mut params) = impl_.matches(&type_params[0]) {
+                params.extend(type_params[1..].iter().cloned());
+                return (implementation, params);
+            }
+        }
+    }
+    (function, type_params)
+}
+
+/// Integer size used for pointer arithmetic.
+fn pointer_size() -> ir::Size {
+    match ir::POINTER_SIZE {
+        1 => ir::Size::Bit8,
+        2 => ir::Size::Bit16,
+        4 => ir::Size::Bit32,
+        8 => ir::Size::Bit64,
+        _ => panic!("unsupported pointer size"),
+    }
+}
+
+/// Mask that keeps the bits that fit in a pointer.
+fn pointer_mask() -> u64 {
+    match ir::POINTER_SIZE {
+        8 => !0,
+        size => (1 << (size * 8)) - 1,
+    }
+}
+
+fn pointer_layout() -> ir::Layout {
+    ir::Layout {
+        size: ir::POINTER_SIZE,
+        align: ir::POINTER_SIZE,
+    }
+}
+
 fn convert_binop(op: cfg::BinaryOp) -> ir::BinaryOp {
     fn conv_sign(sign: cfg::Signedness) -> ir::Signedness {
         match sign {
@@ -453,6 +669,7 @@ fn convert_binop(op: cfg::BinaryOp) -> ir::BinaryOp {
             cfg::Size::Bit8 => ir::Size::Bit8,
             cfg::Size::Bit16 => ir::Size::Bit16,
             cfg::Size::Bit32 => ir::Size::Bit32,
+            cfg::Size::Bit64 => ir::Size::Bit64,
         }
     }
     match op {
@@ -483,14 +700,27 @@ fn convert_binop(op: cfg::BinaryOp) -> ir::BinaryOp {
         cfg::BinaryOp::Sub(sign, size) => {
             ir::BinaryOp::IntOp(ir::IntOp::Sub, conv_sign(sign), conv_size(size))
         }
+        cfg::BinaryOp::BitAnd(size) => ir::BinaryOp::BitOp(ir::BitOp::And, conv_size(size)),
+        cfg::BinaryOp::BitOr(size) => ir::BinaryOp::BitOp(ir::BitOp::Or, conv_size(size)),
+        cfg::BinaryOp::BitXor(size) => ir::BinaryOp::BitOp(ir::BitOp::Xor, conv_size(size)),
+        cfg::BinaryOp::ShiftLeft(size) => {
+            // left shift does not depend on signedness
+            let sign = ir::Signedness::Unsigned;
+            ir::BinaryOp::ShiftOp(ir::ShiftOp::Left, sign, conv_size(size))
+        }
+        cfg::BinaryOp::ShiftRight(sign, size) => {
+            ir::BinaryOp::ShiftOp(ir::ShiftOp::Right, conv_sign(sign), conv_size(size))
+        }
         cfg::BinaryOp::Eq => ir::BinaryOp::Eq,
         cfg::BinaryOp::Neq => ir::BinaryOp::Neq,
     }
 }
 
 pub(crate) fn build_ir(program: &cfg::Program, ctx: &CompileCtx) -> ir::Program {
-    let layout = LayoutEngine::new(&program.structs);
+    let layout = LayoutEngine::new(&program.structs, &program.enums);
     let mut functions = HashMap::new();
+    let mut globals = HashMap::new();
+    let mut strings = HashMap::new();
     let mut queue = HashMap::new();
     for (&id, f) in &program.functions {
         if f.type_params.is_empty() {
@@ -511,11 +741,39 @@ pub(crate) fn build_ir(program: &cfg::Program, ctx: &CompileCtx) -> ir::Program
             .cloned()
             .zip(types.into_iter())
             .collect();
-        let mut builder = Builder::new(sym, function, type_params, ctx, &layout);
+        let mut builder = Builder::new(
+            sym,
+            function,
+            &program.impls,
+            type_params,
+            ctx,
+            &layout,
+            &mut strings,
+        );
         let function = builder.build();
         queue.extend(builder.dependencies);
         functions.insert(symbol, function);
     }
 
-    ir::Program { functions }
+    for (&id, s) in &program.statics {
+        let symbol = ir::Symbol(ctx.symbols.get_name(id).into());
+        let (size, align) = layout.size_align(&s.typ).unwrap();
+        let global = ir::Global {
+            layout: ir::Layout { size, align },
+            value: s.value.clone(),
+        };
+        globals.insert(symbol, global);
+    }
+    for (value, symbol) in strings {
+        let global = ir::Global {
+            layout: ir::Layout {
+                size: value.len() as u32,
+                align: 1,
+            },
+            value,
+        };
+        globals.insert(symbol, global);
+    }
+
+    ir::Program { functions, globals }
 }
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use ast::typed as t;
+use ast::cfg;
+use const_eval::{Constant, Evaluator};
+use struct_layout::{LayoutEngine, LayoutResult};
+use CompileCtx;
+
+
+pub(crate) fn build_statics(
+    program: &t::Program,
+    consts: &HashMap<t::Symbol, Option<Constant>>,
+    ctx: &mut CompileCtx,
+) -> HashMap<t::Symbol, cfg::Static> {
+    let evaluator = Evaluator::new(
+        ctx,
+        LayoutEngine::new(&program.structs, &program.enums),
+        HashMap::new(),
+        consts.clone(),
+    );
+    let layouts = LayoutEngine::new(&program.structs, &program.enums);
+    let mut ctx = Context::new(evaluator, layouts, &program.structs, &program.enums);
+    let mut statics = HashMap::new();
+    for s in &program.statics {
+        if let Some(value) = ctx.build_static(s) {
+            let static_ = cfg::Static {
+                typ: s.typ.clone(),
+                value,
+            };
+            statics.insert(s.name, static_);
+        }
+    }
+    statics
+}
+
+struct Context<'a> {
+    evaluator: Evaluator<'a>,
+    layouts: LayoutEngine<'a>,
+    structs: &'a HashMap<t::Symbol, t::Struct>,
+    enums: &'a HashMap<t::Symbol, t::Enum>,
+    variants: HashMap<t::Symbol, usize>,
+}
+
+impl<'a> Context<'a> {
+    fn new(
+        evaluator: Evaluator<'a>,
+        layouts: LayoutEngine<'a>,
+        structs: &'a HashMap<t::Symbol, t::Struct>,
+        enums: &'a HashMap<t::Symbol, t::Enum>,
+    ) -> Self {
+        let variants = enums
+            .values()
+            .flat_map(|e| {
+                e.variants
+                    .iter()
+                    .enumerate()
+                    .map(|(index, v)| (v.name, index))
+            })
+            .collect();
+        Context {
+            evaluator,
+            layouts,
+            structs,
+            enums,
+            variants,
+        }
+    }
+
+    fn build_static(&mut self, s: &t::Static) -> Option<Vec<u8>> {
+        // mismatch is reported by type check, and value
+        // would not fit into the layout of declared type
+        if s.value.typ != s.typ {
+            return None;
+        }
+        let size = match self.layouts.size_of(&s.typ) {
+            LayoutResult::Ok(size) => size,
+            // errors are already reported
+            LayoutResult::Error | LayoutResult::HasTypeParam => return None,
+        };
+        let mut value = vec![0; size as usize];
+        self.encode(&s.value, &mut value).ok().map(|()| value)
+    }
+
+    fn encode(&mut self, expr: &t::TypedExpr, to: &mut [u8]) -> Result<(), ()> {
+        match *expr.expr {
+            t::Expr::Array(ref values) | t::Expr::Tuple(ref values) => {
+                let mut result = Ok(());
+                for (index, value) in values.iter().enumerate() {
+                    let (offset, _) = self.layouts.field_info(&expr.typ, index);
+                    result = result.and(self.encode_at(value, to, offset));
+                }
+                result
+            }
+            t::Expr::Call(ref callee, ref params) if self.is_constructor(callee) => {
+                let mut result = Ok(());
+                for (index, param) in params.iter().enumerate() {
+                    let (offset, _) = self.layouts.field_info(&expr.typ, index);
+                    result = result.and(self.encode_at(param, to, offset));
+                }
+                result
+            }
+            t::Expr::Call(ref callee, ref params) if self.variant_index(callee).is_some() => {
+                let variant = self.variant_index(callee).unwrap();
+                let e = match expr.typ {
+                    t::Type::Concrete(sym, _) => &self.enums[&sym],
+                    _ => return Err(()),
+                };
+                to[0] = variant as u8;
+                let mut result = Ok(());
+                for (index, param) in params.iter().enumerate() {
+                    let field = e.field_index(variant, index);
+                    let (offset, _) = self.layouts.field_info(&expr.typ, field);
+                    result = result.and(self.encode_at(param, to, offset));
+                }
+                result
+            }
+            _ => {
+                match self.evaluator.eval(expr)? {
+                    Constant::Int(value) => write_int(to, value as u64),
+                    Constant::Bool(b) => to[0] = b as u8,
+                    Constant::Unit => {}
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn is_constructor(&self, callee: &t::TypedExpr) -> bool {
+        match *callee.expr {
+            t::Expr::Name(name, _) => self.structs.contains_key(&*name),
+            _ => false,
+        }
+    }
+
+    fn variant_index(&self, callee: &t::TypedExpr) -> Option<usize> {
+        match *callee.expr {
+            t::Expr::Name(name, _) => self.variants.get(&*name).cloned(),
+            _ => None,
+        }
+    }
+
+    fn encode_at(&mut self, expr: &t::TypedExpr, to: &mut [u8], offset: u32) -> Result<(), ()> {
+        let size = match self.layouts.size_of(&expr.typ) {
+            LayoutResult::Ok(size) => size,
+            LayoutResult::Error | LayoutResult::HasTypeParam => return Err(()),
+        };
+        let start = offset as usize;
+        let end = start + size as usize;
+        if end > to.len() {
+            return Err(());
+        }
+        self.encode(expr, &mut to[start..end])
+    }
+}
+
+fn write_int(to: &mut [u8], value: u64) {
+    for (index, byte) in to.iter_mut().enumerate() {
+        *byte = (value >> (index * 8)) as u8;
+    }
+}
@@ -5,11 +5,17 @@ use CompileCtx;
 
 
 pub(crate) fn check_casts(program: &mut Program, ctx: &mut CompileCtx) {
-    let layouts = LayoutEngine::new(&program.structs);
+    let layouts = LayoutEngine::new(&program.structs, &program.enums);
     let mut ctx = Context::new(ctx, layouts);
     for f in &mut program.functions {
         ctx.check_function(f);
     }
+    for s in &mut program.statics {
+        ctx.check_expr(&mut s.value);
+    }
+    for c in &mut program.consts {
+        ctx.check_expr(&mut c.value);
+    }
 }
 
 struct Context<'a> {
@@ -33,7 +39,8 @@ impl<'a> Context<'a> {
             Statement::Block(ref mut stmts) => for stmt in stmts {
                 self.check_statement(stmt);
             },
-            Statement::Break | Statement::Continue | Statement::Error => {}
+            Statement::Break(_, None) | Statement::Continue(_) | Statement::Error => {}
+            Statement::Break(_, Some(ref mut value)) => self.check_expr(value),
             Statement::Expr(ref mut expr) | Statement::Return(ref mut expr) => {
                 self.check_expr(expr)
             }
@@ -48,17 +55,28 @@ impl<'a> Context<'a> {
                 self.check_expr(value);
             }
             Statement::Let(_, _, None) => {}
-            Statement::Loop(ref mut body) => self.check_statement(body),
-            Statement::While(ref mut cond, ref mut body) => {
+            Statement::Loop(_, ref mut body) => self.check_statement(body),
+            Statement::While(_, ref mut cond, ref mut body) => {
                 self.check_expr(cond);
                 self.check_statement(body);
             }
+            Statement::For(_, _, ref mut start, ref mut end, _, ref mut body) => {
+                self.check_expr(start);
+                self.check_expr(end);
+                self.check_statement(body);
+            }
+            Statement::Match(ref mut value, ref mut arms) => {
+                self.check_expr(value);
+                for arm in arms {
+                    self.check_statement(&mut arm.body);
+                }
+            }
         }
     }
 
     fn check_expr(&mut self, expr: &mut TypedExpr) {
         match *expr.expr.as_mut() {
-            Expr::Binary(ref mut lhs, _, ref mut rhs) => {
+            Expr::Binary(ref mut lhs, _, ref mut rhs) | Expr::Index(ref mut lhs, ref mut rhs) => {
                 self.check_expr(lhs);
                 self.check_expr(rhs);
                 return;
@@ -70,13 +88,52 @@ impl<'a> Context<'a> {
                 }
                 return;
             }
-            Expr::Field(ref mut expr, _) | Expr::Unary(_, ref mut expr) => {
+            Expr::Array(ref mut values) | Expr::Tuple(ref mut values) => {
+                for value in values {
+                    self.check_expr(value);
+                }
+                return;
+            }
+            Expr::Field(ref mut expr, _) |
+            Expr::Unary(_, ref mut expr) |
+            Expr::Lambda(_, ref mut expr, _) => {
                 self.check_expr(expr);
                 return;
             }
+            Expr::Closure(ref mut env, ref mut function) => {
+                self.check_expr(env);
+                self.check_expr(function);
+                return;
+            }
+            Expr::If(ref mut cond, ref mut then, ref mut else_) => {
+                self.check_expr(cond);
+                self.check_expr(then);
+                if let Some(ref mut else_) = *else_ {
+                    self.check_expr(else_);
+                }
+                return;
+            }
+            Expr::Loop(_, ref mut body) => {
+                self.check_statement(body);
+                return;
+            }
+            Expr::Block(ref mut stmts, ref mut value) => {
+                for stmt in stmts {
+                    self.check_statement(stmt);
+                }
+                if let Some(ref mut value) = *value {
+                    self.check_expr(value);
+                }
+                return;
+            }
             Expr::Error | Expr::Literal(_) | Expr::Name(_, _) => return,
             Expr::Cast(ref mut value, ref typ) => {
                 self.check_expr(value);
+                // integers are converted by value, other
+                // types are reinterpreted as they are
+                if let (&Type::Int(_, _), &Type::Int(_, _)) = (&value.typ, &**typ) {
+                    return;
+                }
                 let value_layout = self.layouts.size_of(&value.typ);
                 let typ_layout = self.layouts.size_of(typ);
                 match (value_layout, typ_layout) {
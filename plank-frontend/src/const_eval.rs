@@ -0,0 +1,394 @@
+use std::collections::{HashMap, HashSet};
+use plank_syntax::position::{Span, Spanned};
+use ast::typed::{self as t, BinaryOp, Signedness, Size, Symbol, Type, UnaryOp};
+use struct_layout::{LayoutEngine, LayoutResult};
+use CompileCtx;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Constant {
+    Int(i128),
+    Bool(bool),
+    Unit,
+}
+
+/// Evaluates all `const` items in program. Constants that could not
+/// be evaluated map to `None`, errors for them are already reported.
+pub(crate) fn eval_consts(
+    program: &t::Program,
+    ctx: &mut CompileCtx,
+) -> HashMap<Symbol, Option<Constant>> {
+    let layouts = LayoutEngine::new(&program.structs, &program.enums);
+    let consts = program.consts.iter().map(|c| (c.name, c)).collect();
+    let mut evaluator = Evaluator::new(ctx, layouts, consts, HashMap::new());
+    for c in &program.consts {
+        let _ = evaluator.eval_const(c.name, c.value.span);
+    }
+    evaluator.values
+}
+
+pub(crate) struct Evaluator<'a> {
+    ctx: &'a mut CompileCtx,
+    layouts: LayoutEngine<'a>,
+    consts: HashMap<Symbol, &'a t::Const>,
+    values: HashMap<Symbol, Option<Constant>>,
+    in_progress: HashSet<Symbol>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(
+        ctx: &'a mut CompileCtx,
+        layouts: LayoutEngine<'a>,
+        consts: HashMap<Symbol, &'a t::Const>,
+        values: HashMap<Symbol, Option<Constant>>,
+    ) -> Self {
+        Evaluator {
+            ctx,
+            layouts,
+            consts,
+            values,
+            in_progress: HashSet::new(),
+        }
+    }
+
+    fn is_const(&self, name: Symbol) -> bool {
+        self.values.contains_key(&name) || self.consts.contains_key(&name)
+    }
+
+    fn eval_const(&mut self, name: Symbol, span: Span) -> Result<Constant, ()> {
+        if let Some(&value) = self.values.get(&name) {
+            return value.ok_or(());
+        }
+        if self.in_progress.contains(&name) {
+            let msg = format!(
+                "constant `{}` depends on itself",
+                self.ctx.symbols.get_name(name)
+            );
+            self.ctx.reporter.error(msg, span).span(span).build();
+            return Err(());
+        }
+        let const_ = self.consts[&name];
+        self.in_progress.insert(name);
+        let value = match const_.typ {
+            Type::Int(_, _) | Type::Bool | Type::Unit => self.eval(&const_.value),
+            Type::Error => Err(()),
+            _ => {
+                let span = const_.value.span;
+                self.ctx
+                    .reporter
+                    .error("constants must have integer, `bool` or `unit` type", span)
+                    .span(span)
+                    .build();
+                Err(())
+            }
+        };
+        self.in_progress.remove(&name);
+        self.values.insert(name, value.ok());
+        value
+    }
+
+    pub fn eval(&mut self, expr: &t::TypedExpr) -> Result<Constant, ()> {
+        match *expr.expr {
+            t::Expr::Literal(t::Literal::Unit) => Ok(Constant::Unit),
+            t::Expr::Literal(t::Literal::Bool(b)) => Ok(Constant::Bool(b)),
+            t::Expr::Literal(t::Literal::Char(c)) => Ok(Constant::Int(i128::from(c))),
+            t::Expr::Literal(t::Literal::Number(n)) => {
+                self.check_literal(i128::from(n.value), &expr.typ, expr.span)
+            }
+            t::Expr::Name(name, _) if self.is_const(*name) => {
+                self.eval_const(*name, expr.span)
+            }
+            t::Expr::Call(ref callee, ref params) if params.is_empty() => {
+                self.eval_builtin_call(callee, expr.span)
+            }
+            t::Expr::Unary(op, ref arg) => self.eval_unary(Spanned::into_value(op), arg, expr),
+            t::Expr::Binary(ref lhs, op, ref rhs) => {
+                self.eval_binary(lhs, Spanned::into_value(op), rhs, expr)
+            }
+            t::Expr::Cast(ref value, ref typ) => {
+                let value = self.eval(value)?;
+                let bits = match value {
+                    Constant::Int(i) => i as u64,
+                    Constant::Bool(b) => b as u64,
+                    Constant::Unit => 0,
+                };
+                match **typ {
+                    Type::Int(sign, size) => Ok(Constant::Int(from_bits(bits, sign, size))),
+                    Type::Bool => Ok(Constant::Bool(bits & 0xFF != 0)),
+                    Type::Unit => Ok(Constant::Unit),
+                    Type::Error => Err(()),
+                    _ => {
+                        self.report_not_constant(expr.span);
+                        Err(())
+                    }
+                }
+            }
+            t::Expr::If(ref cond, ref then, ref else_) => match self.eval(cond)? {
+                Constant::Bool(true) => self.eval(then),
+                Constant::Bool(false) => match *else_ {
+                    Some(ref else_) => self.eval(else_),
+                    None => Ok(Constant::Unit),
+                },
+                _ => Err(()),
+            },
+            t::Expr::Block(ref stmts, ref value) if stmts.is_empty() => match *value {
+                Some(ref value) => self.eval(value),
+                None => Ok(Constant::Unit),
+            },
+            t::Expr::Literal(t::Literal::Str(_)) => {
+                // string is a pointer to its bytes, and constant
+                // data cannot contain addresses
+                let span = expr.span;
+                self.ctx
+                    .reporter
+                    .error("string literals cannot be used in constant expressions", span)
+                    .span_note("consider using a byte array, like `['h', 'i', 0]`", span)
+                    .build();
+                Err(())
+            }
+            t::Expr::Error => Err(()),
+            t::Expr::Block(_, _) |
+            t::Expr::Loop(_, _) |
+            t::Expr::Name(_, _) |
+            t::Expr::Call(_, _) |
+            t::Expr::Field(_, _) |
+            t::Expr::Index(_, _) |
+            t::Expr::Array(_) |
+            t::Expr::Tuple(_) |
+            t::Expr::Lambda(_, _, _) |
+            t::Expr::Closure(_, _) => {
+                self.report_not_constant(expr.span);
+                Err(())
+            }
+        }
+    }
+
+    fn eval_builtin_call(&mut self, callee: &t::TypedExpr, span: Span) -> Result<Constant, ()> {
+        let (name, typ) = match *callee.expr {
+            t::Expr::Name(name, ref params) if params.len() == 1 => (*name, &*params[0]),
+            _ => {
+                self.report_not_constant(span);
+                return Err(());
+            }
+        };
+        let result = if name == ::builtins::SIZE_OF {
+            self.layouts.size_of(typ)
+        } else if name == ::builtins::ALIGN_OF {
+            self.layouts.align_of(typ)
+        } else {
+            self.report_not_constant(span);
+            return Err(());
+        };
+        match result {
+            LayoutResult::Ok(value) => Ok(Constant::Int(i128::from(value))),
+            LayoutResult::Error => Err(()),
+            LayoutResult::HasTypeParam => {
+                self.report_not_constant(span);
+                Err(())
+            }
+        }
+    }
+
+    fn eval_unary(
+        &mut self,
+        op: UnaryOp,
+        arg: &t::TypedExpr,
+        expr: &t::TypedExpr,
+    ) -> Result<Constant, ()> {
+        if let (UnaryOp::Minus, &t::Expr::Literal(t::Literal::Number(n))) = (op, &*arg.expr) {
+            // check `-128i8` as a whole, because `128i8` alone is out of range
+            return self.check_literal(-i128::from(n.value), &expr.typ, expr.span);
+        }
+        if op == UnaryOp::Deref || op == UnaryOp::AddressOf {
+            self.report_not_constant(expr.span);
+            return Err(());
+        }
+        match (op, self.eval(arg)?) {
+            (UnaryOp::Plus, value) => Ok(value),
+            (UnaryOp::Minus, Constant::Int(i)) => {
+                self.check_overflow(-i, &expr.typ, expr.span, "negate")
+            }
+            (UnaryOp::Not, Constant::Bool(b)) => Ok(Constant::Bool(!b)),
+            (UnaryOp::BitNot, Constant::Int(i)) => match expr.typ {
+                Type::Int(sign, size) => Ok(Constant::Int(from_bits(!(i as u64), sign, size))),
+                _ => Err(()),
+            },
+            _ => Err(()),
+        }
+    }
+
+    fn eval_binary(
+        &mut self,
+        lhs: &t::TypedExpr,
+        op: BinaryOp,
+        rhs: &t::TypedExpr,
+        expr: &t::TypedExpr,
+    ) -> Result<Constant, ()> {
+        if op == BinaryOp::Assign || op.compound_assign_op().is_some() {
+            self.report_not_constant(expr.span);
+            return Err(());
+        }
+        let a = self.eval(lhs);
+        let b = self.eval(rhs);
+        let (a, b) = (a?, b?);
+        let (a, b, sign, size) = match (a, b, &lhs.typ) {
+            (Constant::Int(a), Constant::Int(b), &Type::Int(sign, size)) => (a, b, sign, size),
+            (a, b, _) => {
+                return match (op, a, b) {
+                    (BinaryOp::Equal, a, b) => Ok(Constant::Bool(a == b)),
+                    (BinaryOp::NotEqual, a, b) => Ok(Constant::Bool(a != b)),
+                    (BinaryOp::And, Constant::Bool(a), Constant::Bool(b)) => {
+                        Ok(Constant::Bool(a && b))
+                    }
+                    (BinaryOp::Or, Constant::Bool(a), Constant::Bool(b)) => {
+                        Ok(Constant::Bool(a || b))
+                    }
+                    _ => Err(()),
+                };
+            }
+        };
+        let span = expr.span;
+        let bit_count = size_in_bits(size);
+        match op {
+            BinaryOp::Add => self.check_overflow(a + b, &lhs.typ, span, "add"),
+            BinaryOp::Subtract => self.check_overflow(a - b, &lhs.typ, span, "subtract"),
+            // product of two 64 bit values might not fit even in `i128`
+            BinaryOp::Multiply => match a.checked_mul(b) {
+                Some(value) => self.check_overflow(value, &lhs.typ, span, "multiply"),
+                None => {
+                    let msg = "attempt to multiply with overflow";
+                    self.ctx.reporter.error(msg, span).span(span).build();
+                    Err(())
+                }
+            },
+            BinaryOp::Divide | BinaryOp::Modulo if b == 0 => {
+                let msg = if op == BinaryOp::Divide {
+                    "attempt to divide by zero"
+                } else {
+                    "attempt to calculate the remainder with a divisor of zero"
+                };
+                self.ctx.reporter.error(msg, span).span(span).build();
+                Err(())
+            }
+            BinaryOp::Divide => self.check_overflow(a / b, &lhs.typ, span, "divide"),
+            BinaryOp::Modulo => {
+                self.check_overflow(a % b, &lhs.typ, span, "calculate the remainder")
+            }
+            BinaryOp::BitAnd => Ok(Constant::Int(from_bits(a as u64 & b as u64, sign, size))),
+            BinaryOp::BitOr => Ok(Constant::Int(from_bits(a as u64 | b as u64, sign, size))),
+            BinaryOp::BitXor => Ok(Constant::Int(from_bits(a as u64 ^ b as u64, sign, size))),
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                // same semantics as at runtime: shift amount is treated as unsigned,
+                // shifting by bit width or more gives 0 (or all sign bits)
+                let amount = to_bits(b, size);
+                let value = match (op, sign) {
+                    (BinaryOp::ShiftLeft, _) if amount >= bit_count => 0,
+                    (BinaryOp::ShiftLeft, _) => from_bits((a as u64) << amount, sign, size),
+                    (_, Signedness::Unsigned) if amount >= bit_count => 0,
+                    (_, Signedness::Unsigned) => a >> amount,
+                    (_, Signedness::Signed) => a >> amount.min(bit_count - 1),
+                };
+                Ok(Constant::Int(value))
+            }
+            BinaryOp::Less => Ok(Constant::Bool(a < b)),
+            BinaryOp::LessEqual => Ok(Constant::Bool(a <= b)),
+            BinaryOp::Greater => Ok(Constant::Bool(a > b)),
+            BinaryOp::GreaterEqual => Ok(Constant::Bool(a >= b)),
+            BinaryOp::Equal => Ok(Constant::Bool(a == b)),
+            BinaryOp::NotEqual => Ok(Constant::Bool(a != b)),
+            _ => Err(()),
+        }
+    }
+
+    fn check_literal(&mut self, value: i128, typ: &Type, span: Span) -> Result<Constant, ()> {
+        match *typ {
+            Type::Int(sign, size) if !fits_in(value, sign, size) => {
+                let msg = format!("literal out of range for `{}`", int_type_name(sign, size));
+                self.ctx.reporter.error(msg, span).span(span).build();
+                Err(())
+            }
+            Type::Int(_, _) => Ok(Constant::Int(value)),
+            _ => Err(()),
+        }
+    }
+
+    fn check_overflow(
+        &mut self,
+        value: i128,
+        typ: &Type,
+        span: Span,
+        operation: &str,
+    ) -> Result<Constant, ()> {
+        match *typ {
+            Type::Int(sign, size) if !fits_in(value, sign, size) => {
+                let msg = format!("attempt to {} with overflow", operation);
+                self.ctx.reporter.error(msg, span).span(span).build();
+                Err(())
+            }
+            Type::Int(_, _) => Ok(Constant::Int(value)),
+            _ => Err(()),
+        }
+    }
+
+    fn report_not_constant(&mut self, span: Span) {
+        self.ctx
+            .reporter
+            .error("expression is not constant", span)
+            .span(span)
+            .build();
+    }
+}
+
+pub(crate) fn size_in_bits(size: Size) -> u64 {
+    match size {
+        Size::Bit8 => 8,
+        Size::Bit16 => 16,
+        Size::Bit32 => 32,
+        Size::Bit64 => 64,
+    }
+}
+
+/// Returns the smallest and the largest value of an int type.
+pub(crate) fn int_bounds(sign: Signedness, size: Size) -> (i128, i128) {
+    let bits = size_in_bits(size);
+    match sign {
+        Signedness::Unsigned => (0, (1 << bits) - 1),
+        Signedness::Signed => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+    }
+}
+
+pub(crate) fn fits_in(value: i128, sign: Signedness, size: Size) -> bool {
+    let (min, max) = int_bounds(sign, size);
+    value >= min && value <= max
+}
+
+/// Returns lowest `size` bits of value.
+pub(crate) fn to_bits(value: i128, size: Size) -> u64 {
+    let bits = size_in_bits(size);
+    (value as u64) & (!0 >> (64 - bits))
+}
+
+fn from_bits(bits: u64, sign: Signedness, size: Size) -> i128 {
+    let bit_count = size_in_bits(size);
+    let bits = bits & (!0 >> (64 - bit_count));
+    match sign {
+        Signedness::Unsigned => i128::from(bits),
+        Signedness::Signed if bits >> (bit_count - 1) == 1 => {
+            i128::from(bits) - (1 << bit_count)
+        }
+        Signedness::Signed => i128::from(bits),
+    }
+}
+
+pub(crate) fn int_type_name(sign: Signedness, size: Size) -> &'static str {
+    match (sign, size) {
+        (Signedness::Unsigned, Size::Bit8) => "u8",
+        (Signedness::Unsigned, Size::Bit16) => "u16",
+        (Signedness::Unsigned, Size::Bit32) => "u32",
+        (Signedness::Unsigned, Size::Bit64) => "u64",
+        (Signedness::Signed, Size::Bit8) => "i8",
+        (Signedness::Signed, Size::Bit16) => "i16",
+        (Signedness::Signed, Size::Bit32) => "i32",
+        (Signedness::Signed, Size::Bit64) => "i64",
+    }
+}
@@ -70,6 +70,10 @@ fn analyze_function(f: &mut Function, ctx: &mut CompileCtx) {
                         queue.push_back(a);
                         queue.push_back(b);
                     }
+                    BlockEnd::Switch(_, ref cases, default) => {
+                        queue.extend(cases.iter().map(|&(_, block)| block));
+                        queue.push_back(default);
+                    }
                     BlockEnd::Jump(next) => {
                         queue.push_back(next);
                     }
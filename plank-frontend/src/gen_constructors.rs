@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use plank_syntax::position::Spanned;
 use ast::cfg::{Block, BlockEnd, BlockId, BlockLink, Function, Instruction, Program, Reg, Value};
-use ast::typed::{self as t, Struct};
+use ast::typed::{self as t, Enum, Size, Struct};
 
 
 fn generate_constructor(s: &Struct) -> Function {
@@ -53,9 +53,69 @@ fn generate_constructor(s: &Struct) -> Function {
     }
 }
 
+fn generate_variant_constructor(e: &Enum, variant: usize) -> Function {
+    let span = e.complete_span;
+    let fields = &e.variants[variant].fields;
+    let mut ops = Vec::new();
+    let output = Reg(0);
+    ops.push(Spanned::new(Instruction::Init(Reg(0)), span));
+    let tag = Instruction::FieldStore(
+        Spanned::new(Reg(0), span),
+        vec![0],
+        Spanned::new(Value::Int(variant as u64, Size::Bit8), span),
+    );
+    ops.push(Spanned::new(tag, span));
+    for i in 0..fields.len() {
+        let i = Instruction::FieldStore(
+            Spanned::new(Reg(0), span),
+            vec![e.field_index(variant, i)],
+            Spanned::new(Value::Reg(Reg(i as u32 + 1)), span),
+        );
+        ops.push(Spanned::new(i, span));
+    }
+    let parameters = (1..(fields.len() as u32 + 1))
+        .map(Reg)
+        .collect();
+    let mut registers = (1..(fields.len() + 1))
+        .map(|i| (Reg(i as u32), fields[i - 1].clone()))
+        .collect::<HashMap<_, _>>();
+    let complete_type = t::Type::Concrete(
+        e.name,
+        e.type_params
+            .iter()
+            .map(|&s| t::Type::Concrete(s, Vec::new().into()))
+            .collect::<Vec<_>>()
+            .into(),
+    );
+    registers.insert(Reg(0), complete_type.clone());
+    let block = Block {
+        ops,
+        link: BlockLink::None,
+        end: BlockEnd::Return(Spanned::new(Value::Reg(output), span)),
+    };
+    let mut blocks = HashMap::new();
+    blocks.insert(BlockId(0), block);
+    Function {
+        complete_span: span,
+        parameters,
+        registers,
+        register_symbols: HashMap::new(),
+        type_params: e.type_params.clone(),
+        out_type: complete_type,
+        start_block: Some(BlockId(0)),
+        blocks,
+    }
+}
+
 pub(crate) fn add_constructors(program: &mut Program) {
     for (id, s) in &program.structs {
         let ctor = generate_constructor(s);
         program.functions.insert(*id, ctor);
     }
+    for e in program.enums.values() {
+        for (index, variant) in e.variants.iter().enumerate() {
+            let ctor = generate_variant_constructor(e, index);
+            program.functions.insert(variant.name, ctor);
+        }
+    }
 }
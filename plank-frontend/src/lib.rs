@@ -13,7 +13,11 @@ mod type_param_check;
 mod wildcard_check;
 mod type_check;
 mod cast_check;
+mod match_check;
+mod const_eval;
+mod lower_closures;
 mod build_cfg;
+mod build_statics;
 mod dead_code;
 mod return_check;
 mod gen_constructors;
@@ -32,6 +36,11 @@ mod builtins {
     pub const SIZE_OF_TYPE_PARAM: Symbol = Symbol(4);
     pub const ALIGN_OF_TYPE_PARAM: Symbol = Symbol(5);
     pub const PUTC_PARAM: Symbol = Symbol(6);
+    pub const ALLOC: Symbol = Symbol(7);
+    pub const FREE: Symbol = Symbol(8);
+    pub const ALLOC_SIZE_PARAM: Symbol = Symbol(9);
+    pub const ALLOC_ALIGN_PARAM: Symbol = Symbol(10);
+    pub const FREE_PARAM: Symbol = Symbol(11);
 }
 
 use plank_errors::Reporter;
@@ -56,7 +65,10 @@ pub fn compile(program: &Program, reporter: Reporter) -> Result<plank_ir::Progra
     struct_check::check_program(&mut resolved, &mut ctx);
     let mut typed = type_check::type_check(&resolved, &mut ctx);
     cast_check::check_casts(&mut typed, &mut ctx);
-    let mut cfg = build_cfg::build_cfg(&typed, &mut ctx);
+    match_check::check_matches(&typed, &mut ctx);
+    let consts = const_eval::eval_consts(&typed, &mut ctx);
+    lower_closures::lower_closures(&mut typed, &mut ctx);
+    let mut cfg = build_cfg::build_cfg(&typed, &consts, &mut ctx);
     dead_code::remove_dead_code(&mut cfg, &mut ctx);
     assign_check::check_program(&cfg, &mut ctx);
     return_check::check_returns(&mut cfg, &mut ctx);
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use plank_syntax::position::{Span, Spanned};
+use ast::typed::{Expr, Function, FunctionType, Literal, Program, Statement, Struct, Symbol, Type,
+                 TypedExpr, UnaryOp, Var};
+use CompileCtx;
+
+
+/// Replaces every lambda with a closure. Body of the lambda becomes
+/// a new function, which gets environment as its first parameter.
+/// Environment is a struct with pointers to captured variables.
+pub(crate) fn lower_closures(program: &mut Program, ctx: &mut CompileCtx) {
+    let mut functions = Vec::new();
+    let mut structs = Vec::new();
+    for f in &mut program.functions {
+        let mut lowerer = Lowerer {
+            ctx,
+            function_name: f.name,
+            type_params: &f.type_params,
+            next_lambda: 0,
+            functions: &mut functions,
+            structs: &mut structs,
+        };
+        if let Some(ref mut body) = f.body {
+            lowerer.lower_statement(body, &HashMap::new());
+        }
+    }
+    program.functions.extend(functions);
+    program.structs.extend(structs.into_iter().map(|s| (s.name, s)));
+}
+
+/// Captured variable, stored at given field of the
+/// environment that first parameter points to.
+#[derive(Debug, Clone)]
+struct Capture {
+    env: Symbol,
+    env_type: Type,
+    field: usize,
+}
+
+struct Lowerer<'a> {
+    ctx: &'a mut CompileCtx,
+    function_name: Symbol,
+    type_params: &'a [Symbol],
+    next_lambda: u32,
+    functions: &'a mut Vec<Function>,
+    structs: &'a mut Vec<Struct>,
+}
+
+impl<'a> Lowerer<'a> {
+    fn lower_statement(
+        &mut self,
+        stmt: &mut Spanned<Statement>,
+        captures: &HashMap<Symbol, Capture>,
+    ) {
+        match **stmt {
+            Statement::Block(ref mut stmts) => for stmt in stmts {
+                self.lower_statement(stmt, captures);
+            },
+            Statement::Break(_, None) | Statement::Continue(_) | Statement::Error => {}
+            Statement::Break(_, Some(ref mut value)) => self.lower_expr(value, captures),
+            Statement::Expr(ref mut expr) | Statement::Return(ref mut expr) => {
+                self.lower_expr(expr, captures)
+            }
+            Statement::If(ref mut cond, ref mut then, ref mut else_) => {
+                self.lower_expr(cond, captures);
+                self.lower_statement(then, captures);
+                if let Some(ref mut else_) = *else_ {
+                    self.lower_statement(else_, captures);
+                }
+            }
+            Statement::Let(_, _, Some(ref mut value)) => {
+                self.lower_expr(value, captures);
+            }
+            Statement::Let(_, _, None) => {}
+            Statement::Loop(_, ref mut body) => self.lower_statement(body, captures),
+            Statement::While(_, ref mut cond, ref mut body) => {
+                self.lower_expr(cond, captures);
+                self.lower_statement(body, captures);
+            }
+            Statement::For(_, _, ref mut start, ref mut end, _, ref mut body) => {
+                self.lower_expr(start, captures);
+                self.lower_expr(end, captures);
+                self.lower_statement(body, captures);
+            }
+            Statement::Match(ref mut value, ref mut arms) => {
+                self.lower_expr(value, captures);
+                for arm in arms {
+                    self.lower_statement(&mut arm.body, captures);
+                }
+            }
+        }
+    }
+
+    fn lower_expr(&mut self, expr: &mut TypedExpr, captures: &HashMap<Symbol, Capture>) {
+        let lowered = match *expr.expr.as_mut() {
+            Expr::Binary(ref mut lhs, _, ref mut rhs) | Expr::Index(ref mut lhs, ref mut rhs) => {
+                self.lower_expr(lhs, captures);
+                self.lower_expr(rhs, captures);
+                return;
+            }
+            Expr::Call(ref mut expr, ref mut params) => {
+                self.lower_expr(expr, captures);
+                for param in params {
+                    self.lower_expr(param, captures);
+                }
+                return;
+            }
+            Expr::Array(ref mut values) | Expr::Tuple(ref mut values) => {
+                for value in values {
+                    self.lower_expr(value, captures);
+                }
+                return;
+            }
+            Expr::Field(ref mut expr, _) |
+            Expr::Unary(_, ref mut expr) |
+            Expr::Cast(ref mut expr, _) => {
+                self.lower_expr(expr, captures);
+                return;
+            }
+            Expr::Closure(ref mut env, ref mut function) => {
+                self.lower_expr(env, captures);
+                self.lower_expr(function, captures);
+                return;
+            }
+            Expr::If(ref mut cond, ref mut then, ref mut else_) => {
+                self.lower_expr(cond, captures);
+                self.lower_expr(then, captures);
+                if let Some(ref mut else_) = *else_ {
+                    self.lower_expr(else_, captures);
+                }
+                return;
+            }
+            Expr::Loop(_, ref mut body) => {
+                self.lower_statement(body, captures);
+                return;
+            }
+            Expr::Block(ref mut stmts, ref mut value) => {
+                for stmt in stmts {
+                    self.lower_statement(stmt, captures);
+                }
+                if let Some(ref mut value) = *value {
+                    self.lower_expr(value, captures);
+                }
+                return;
+            }
+            Expr::Error | Expr::Literal(_) => return,
+            Expr::Name(name, _) => match captures.get(&*name) {
+                Some(capture) => load_capture(capture, &expr.typ, expr.span),
+                None => return,
+            },
+            Expr::Lambda(ref params, ref body, ref captured) => {
+                self.lower_lambda(params, body, captured, &expr.typ, expr.span, captures)
+            }
+        };
+        *expr = lowered;
+    }
+
+    fn lower_lambda(
+        &mut self,
+        params: &[Var],
+        body: &TypedExpr,
+        captured: &[Var],
+        typ: &Type,
+        span: Span,
+        outer_captures: &HashMap<Symbol, Capture>,
+    ) -> TypedExpr {
+        let (param_types, out_type) = match *typ {
+            Type::Function(ref params, ref out) | Type::Closure(ref params, ref out) => {
+                (params.clone(), out.clone())
+            }
+            _ => return make_expr(Expr::Error, Type::Error, span),
+        };
+        let index = self.next_lambda;
+        self.next_lambda += 1;
+        let type_params = self.type_params
+            .iter()
+            .map(|&param| Type::Concrete(param, Vec::new().into()))
+            .collect::<Vec<_>>();
+        let mut body = body.clone();
+        let mut function_params = params.to_vec();
+        let env = if let Type::Closure(_, _) = *typ {
+            let env_param = self.ctx.symbols.new_symbol("env");
+            let (env, env_type) =
+                self.make_env(index, captured, &type_params, span, outer_captures);
+            let captures = (0..captured.len())
+                .map(|field| {
+                    let capture = Capture {
+                        env: env_param,
+                        env_type: env_type.clone(),
+                        field,
+                    };
+                    (captured[field].name, capture)
+                })
+                .collect();
+            self.lower_expr(&mut body, &captures);
+            let erased = Type::closure_fields(&param_types, &out_type)[0].clone();
+            function_params.insert(
+                0,
+                Var {
+                    name: env_param,
+                    typ: erased,
+                },
+            );
+            Some(env)
+        } else {
+            self.lower_expr(&mut body, &HashMap::new());
+            None
+        };
+
+        let name = format!(
+            "{}::{{closure#{}}}",
+            self.ctx.symbols.get_name(self.function_name),
+            index
+        );
+        let name = self.ctx.symbols.new_symbol(name);
+        let function_type = Type::Function(
+            function_params.iter().map(|p| p.typ.clone()).collect::<Vec<_>>().into(),
+            out_type.clone(),
+        );
+        self.functions.push(Function {
+            complete_span: span,
+            fn_type: FunctionType::Normal,
+            name,
+            type_params: self.type_params.to_vec(),
+            params: function_params,
+            return_type: (*out_type).clone(),
+            body: Some(Spanned::new(Statement::Return(body), span)),
+        });
+        let type_params = type_params
+            .into_iter()
+            .map(|typ| Spanned::new(typ, span))
+            .collect();
+        let function = make_expr(
+            Expr::Name(Spanned::new(name, span), type_params),
+            function_type,
+            span,
+        );
+        match env {
+            Some(env) => make_expr(Expr::Closure(env, function), typ.clone(), span),
+            None => function,
+        }
+    }
+
+    /// Creates struct for environment of a lambda and returns
+    /// expression that constructs it, and type of the struct.
+    fn make_env(
+        &mut self,
+        index: u32,
+        captured: &[Var],
+        type_params: &[Type],
+        span: Span,
+        outer_captures: &HashMap<Symbol, Capture>,
+    ) -> (TypedExpr, Type) {
+        if captured.is_empty() {
+            let env = make_expr(Expr::Literal(Literal::Unit), Type::Unit, span);
+            return (env, Type::Unit);
+        }
+        let name = format!(
+            "{}::{{closure_env#{}}}",
+            self.ctx.symbols.get_name(self.function_name),
+            index
+        );
+        let name = self.ctx.symbols.new_symbol(name);
+        let fields = captured
+            .iter()
+            .map(|var| {
+                let field_name = self.ctx.symbols.get_name(var.name).to_string();
+                Var {
+                    name: self.ctx.symbols.new_symbol(field_name),
+                    typ: Type::Pointer(Rc::new(var.typ.clone())),
+                }
+            })
+            .collect::<Vec<_>>();
+        let env_type = Type::Concrete(name, type_params.to_vec().into());
+        let constructor_type = Type::Function(
+            fields.iter().map(|f| f.typ.clone()).collect::<Vec<_>>().into(),
+            Rc::new(env_type.clone()),
+        );
+        self.structs.push(Struct {
+            complete_span: span,
+            name,
+            type_params: self.type_params.to_vec(),
+            fields,
+        });
+        let values = captured
+            .iter()
+            .map(|var| {
+                let value = make_expr(
+                    Expr::Name(Spanned::new(var.name, span), Vec::new()),
+                    var.typ.clone(),
+                    span,
+                );
+                let mut address = make_expr(
+                    Expr::Unary(Spanned::new(UnaryOp::AddressOf, span), value),
+                    Type::Pointer(Rc::new(var.typ.clone())),
+                    span,
+                );
+                self.lower_expr(&mut address, outer_captures);
+                address
+            })
+            .collect();
+        let type_params = type_params
+            .iter()
+            .map(|typ| Spanned::new(typ.clone(), span))
+            .collect();
+        let constructor = make_expr(
+            Expr::Name(Spanned::new(name, span), type_params),
+            constructor_type,
+            span,
+        );
+        let env = make_expr(Expr::Call(constructor, values), env_type.clone(), span);
+        (env, env_type)
+    }
+}
+
+/// Builds `*(*(env as *Env)).field` for a captured variable.
+fn load_capture(capture: &Capture, typ: &Type, span: Span) -> TypedExpr {
+    let ptr_type = Type::Pointer(Rc::new(typ.clone()));
+    let env_ptr_type = Type::Pointer(Rc::new(capture.env_type.clone()));
+    let env = make_expr(
+        Expr::Name(Spanned::new(capture.env, span), Vec::new()),
+        Type::Pointer(Rc::new(Type::Unit)),
+        span,
+    );
+    let env = make_expr(
+        Expr::Cast(env, Spanned::new(env_ptr_type, span)),
+        Type::Pointer(Rc::new(capture.env_type.clone())),
+        span,
+    );
+    let env = make_expr(
+        Expr::Unary(Spanned::new(UnaryOp::Deref, span), env),
+        capture.env_type.clone(),
+        span,
+    );
+    let field = make_expr(
+        Expr::Field(env, Spanned::new(capture.field, span)),
+        ptr_type,
+        span,
+    );
+    make_expr(
+        Expr::Unary(Spanned::new(UnaryOp::Deref, span), field),
+        typ.clone(),
+        span,
+    )
+}
+
+fn make_expr(expr: Expr, typ: Type, span: Span) -> TypedExpr {
+    TypedExpr {
+        expr: Box::new(expr),
+        span,
+        typ,
+    }
+}
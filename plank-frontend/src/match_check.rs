@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::iter;
+use plank_syntax::position::Spanned;
+use ast::typed::{Enum, Expr, MatchArm, Pattern, Program, Statement, Symbol, Type, TypedExpr,
+                 TypedPattern};
+use const_eval;
+use CompileCtx;
+
+
+pub(crate) fn check_matches(program: &Program, ctx: &mut CompileCtx) {
+    let mut ctx = Context {
+        ctx,
+        enums: &program.enums,
+    };
+    for f in &program.functions {
+        if let Some(ref body) = f.body {
+            ctx.check_statement(body);
+        }
+    }
+}
+
+/// Pattern with bindings replaced by wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Pat {
+    Wildcard,
+    Variant(usize, Vec<Pat>),
+    /// Inclusive range of integers, single values are ranges too.
+    Range(i128, i128),
+}
+
+/// Constructor of a value, ranges of integers are split so
+/// that each pattern either covers them fully or not at all.
+#[derive(Debug, Copy, Clone)]
+enum Ctor {
+    Variant(usize),
+    Range(i128, i128),
+}
+
+struct Context<'a> {
+    ctx: &'a mut CompileCtx,
+    enums: &'a HashMap<Symbol, Enum>,
+}
+
+impl<'a> Context<'a> {
+    fn check_statement(&mut self, stmt: &Spanned<Statement>) {
+        match **stmt {
+            Statement::Block(ref stmts) => for stmt in stmts {
+                self.check_statement(stmt);
+            },
+            Statement::If(ref cond, ref then, ref else_) => {
+                self.check_expr(cond);
+                self.check_statement(then);
+                if let Some(ref else_) = *else_ {
+                    self.check_statement(else_);
+                }
+            }
+            Statement::Loop(_, ref body) => self.check_statement(body),
+            Statement::While(_, ref cond, ref body) => {
+                self.check_expr(cond);
+                self.check_statement(body);
+            }
+            Statement::For(_, _, ref start, ref end, _, ref body) => {
+                self.check_expr(start);
+                self.check_expr(end);
+                self.check_statement(body);
+            }
+            Statement::Match(ref value, ref arms) => {
+                self.check_expr(value);
+                self.check_match(value, arms);
+                for arm in arms {
+                    self.check_statement(&arm.body);
+                }
+            }
+            Statement::Expr(ref expr) |
+            Statement::Let(_, _, Some(ref expr)) |
+            Statement::Break(_, Some(ref expr)) |
+            Statement::Return(ref expr) => self.check_expr(expr),
+            Statement::Break(_, None) |
+            Statement::Continue(_) |
+            Statement::Let(_, _, None) |
+            Statement::Error => {}
+        }
+    }
+
+    /// Looks for matches inside block expressions.
+    fn check_expr(&mut self, expr: &TypedExpr) {
+        match *expr.expr {
+            Expr::Binary(ref lhs, _, ref rhs) | Expr::Index(ref lhs, ref rhs) => {
+                self.check_expr(lhs);
+                self.check_expr(rhs);
+            }
+            Expr::Call(ref expr, ref params) => {
+                self.check_expr(expr);
+                for param in params {
+                    self.check_expr(param);
+                }
+            }
+            Expr::Array(ref values) | Expr::Tuple(ref values) => for value in values {
+                self.check_expr(value);
+            },
+            Expr::Field(ref expr, _) |
+            Expr::Unary(_, ref expr) |
+            Expr::Cast(ref expr, _) |
+            Expr::Lambda(_, ref expr, _) => self.check_expr(expr),
+            Expr::Closure(ref env, ref function) => {
+                self.check_expr(env);
+                self.check_expr(function);
+            }
+            Expr::If(ref cond, ref then, ref else_) => {
+                self.check_expr(cond);
+                self.check_expr(then);
+                if let Some(ref else_) = *else_ {
+                    self.check_expr(else_);
+                }
+            }
+            Expr::Loop(_, ref body) => self.check_statement(body),
+            Expr::Block(ref stmts, ref value) => {
+                for stmt in stmts {
+                    self.check_statement(stmt);
+                }
+                if let Some(ref value) = *value {
+                    self.check_expr(value);
+                }
+            }
+            Expr::Error | Expr::Literal(_) | Expr::Name(_, _) => {}
+        }
+    }
+
+    fn check_match(&mut self, value: &TypedExpr, arms: &[MatchArm]) {
+        // all patterns are simplified first to report all invalid ones
+        let patterns = arms.iter()
+            .map(|arm| self.simplify(&arm.pattern))
+            .collect::<Vec<_>>();
+        let patterns = match patterns.into_iter().collect::<Option<Vec<_>>>() {
+            Some(patterns) => patterns,
+            None => return,
+        };
+        if let Type::Error = value.typ {
+            return;
+        }
+        let types = vec![value.typ.clone()];
+        let mut rows: Vec<Vec<Pat>> = Vec::new();
+        for (arm, pattern) in arms.iter().zip(patterns) {
+            let row = vec![pattern];
+            if !self.is_useful(&rows, &row, &types) {
+                let span = arm.pattern.span;
+                let duplicate = rows.iter().position(|prev| *prev == row);
+                if let Some(index) = duplicate {
+                    let prev_span = arms[index].pattern.span;
+                    self.ctx
+                        .reporter
+                        .warning("duplicate pattern", span)
+                        .span_note("and again matched here", span)
+                        .span_note("pattern is first matched here", prev_span)
+                        .build();
+                } else {
+                    self.ctx
+                        .reporter
+                        .warning("unreachable pattern", span)
+                        .span(span)
+                        .build();
+                }
+            }
+            rows.push(row);
+        }
+        if let Some(missing) = self.find_missing(&rows, &types) {
+            let msg = format!("non-exhaustive patterns: `{}` not covered", missing[0]);
+            self.ctx
+                .reporter
+                .error(msg, value.span)
+                .span(value.span)
+                .build();
+        }
+    }
+
+    /// Converts pattern to `Pat`, reporting invalid integer patterns.
+    /// Returns `None` if pattern is invalid or contains errors.
+    fn simplify(&mut self, pattern: &TypedPattern) -> Option<Pat> {
+        match pattern.pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => Some(Pat::Wildcard),
+            Pattern::Error => None,
+            Pattern::Variant(variant, ref patterns) => {
+                let patterns = patterns
+                    .iter()
+                    .map(|p| self.simplify(p))
+                    .collect::<Vec<_>>();
+                let patterns = patterns.into_iter().collect::<Option<_>>()?;
+                Some(Pat::Variant(variant, patterns))
+            }
+            Pattern::Int(value) => {
+                let value = Spanned::new(value, pattern.span);
+                if self.check_bound(value, &pattern.typ) {
+                    Some(Pat::Range(*value, *value))
+                } else {
+                    None
+                }
+            }
+            Pattern::Range(lo, hi) => {
+                let lo_valid = self.check_bound(lo, &pattern.typ);
+                let hi_valid = self.check_bound(hi, &pattern.typ);
+                if !lo_valid || !hi_valid {
+                    None
+                } else if *lo > *hi {
+                    self.ctx
+                        .reporter
+                        .error("lower range bound must not be greater than upper", pattern.span)
+                        .span(pattern.span)
+                        .build();
+                    None
+                } else {
+                    Some(Pat::Range(*lo, *hi))
+                }
+            }
+        }
+    }
+
+    fn check_bound(&mut self, value: Spanned<i128>, typ: &Type) -> bool {
+        match *typ {
+            Type::Int(sign, size) if !const_eval::fits_in(*value, sign, size) => {
+                let span = Spanned::span(&value);
+                let msg = format!(
+                    "literal out of range for `{}`",
+                    const_eval::int_type_name(sign, size)
+                );
+                self.ctx.reporter.error(msg, span).span(span).build();
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Checks whether there is a value that is matched by `row`,
+    /// but not by any of the `rows`.
+    fn is_useful(&self, rows: &[Vec<Pat>], row: &[Pat], types: &[Type]) -> bool {
+        if row.is_empty() {
+            return rows.is_empty();
+        }
+        let ctors = match row[0] {
+            Pat::Variant(variant, _) => vec![Ctor::Variant(variant)],
+            Pat::Range(lo, hi) => split_range(lo, hi, rows)
+                .into_iter()
+                .map(|(lo, hi)| Ctor::Range(lo, hi))
+                .collect(),
+            Pat::Wildcard => match self.all_ctors(&types[0], rows) {
+                Some(ctors) if ctors.iter().all(|&c| is_covered(c, rows)) => ctors,
+                _ => return self.is_useful(&default_rows(rows), &row[1..], &types[1..]),
+            },
+        };
+        ctors.into_iter().any(|ctor| {
+            let types = self.specialize_types(types, ctor);
+            let arity = types.len() + 1 - row.len();
+            let rows = specialize(rows, ctor, arity);
+            match specialize_row(row, ctor, arity) {
+                Some(row) => self.is_useful(&rows, &row, &types),
+                None => false,
+            }
+        })
+    }
+
+    /// Finds values that are not matched by any of the `rows`,
+    /// and returns them formatted as patterns.
+    fn find_missing(&self, rows: &[Vec<Pat>], types: &[Type]) -> Option<Vec<String>> {
+        if types.is_empty() {
+            return if rows.is_empty() {
+                Some(Vec::new())
+            } else {
+                None
+            };
+        }
+        let ctors = match self.all_ctors(&types[0], rows) {
+            Some(ctors) => ctors,
+            None => {
+                let missing = self.find_missing(&default_rows(rows), &types[1..])?;
+                return Some(iter::once("_".into()).chain(missing).collect());
+            }
+        };
+        if let Some(&ctor) = ctors.iter().find(|&&c| !is_covered(c, rows)) {
+            let missing = self.find_missing(&default_rows(rows), &types[1..])?;
+            let arity = self.specialize_types(types, ctor).len() + 1 - types.len();
+            let fields = vec!["_".to_string(); arity];
+            let pattern = self.format_ctor(&types[0], ctor, &fields);
+            return Some(iter::once(pattern).chain(missing).collect());
+        }
+        for ctor in ctors {
+            let specialized_types = self.specialize_types(types, ctor);
+            let arity = specialized_types.len() + 1 - types.len();
+            let rows = specialize(rows, ctor, arity);
+            if let Some(mut missing) = self.find_missing(&rows, &specialized_types) {
+                let rest = missing.split_off(arity);
+                let pattern = self.format_ctor(&types[0], ctor, &missing);
+                return Some(iter::once(pattern).chain(rest).collect());
+            }
+        }
+        None
+    }
+
+    /// Returns constructors for all values of a type, or `None`
+    /// if values of the type can only be matched with wildcards.
+    fn all_ctors(&self, typ: &Type, rows: &[Vec<Pat>]) -> Option<Vec<Ctor>> {
+        match *typ {
+            Type::Int(sign, size) => {
+                let (min, max) = const_eval::int_bounds(sign, size);
+                let ranges = split_range(min, max, rows)
+                    .into_iter()
+                    .map(|(lo, hi)| Ctor::Range(lo, hi))
+                    .collect();
+                Some(ranges)
+            }
+            _ => self.enum_of(typ)
+                .map(|(e, _)| (0..e.variants.len()).map(Ctor::Variant).collect()),
+        }
+    }
+
+    fn enum_of(&self, typ: &Type) -> Option<(&'a Enum, HashMap<Symbol, Type>)> {
+        match *typ {
+            Type::Concrete(sym, ref params) => self.enums.get(&sym).map(|e| {
+                let mapping = e.type_params
+                    .iter()
+                    .cloned()
+                    .zip(params.iter().cloned())
+                    .collect();
+                (e, mapping)
+            }),
+            _ => None,
+        }
+    }
+
+    /// Replaces type of the first column with types of the constructor fields.
+    fn specialize_types(&self, types: &[Type], ctor: Ctor) -> Vec<Type> {
+        match ctor {
+            Ctor::Variant(variant) => {
+                let (e, mapping) = self.enum_of(&types[0]).expect("matching variant of non-enum");
+                e.variants[variant]
+                    .fields
+                    .iter()
+                    .map(|typ| typ.replace(&mapping))
+                    .chain(types[1..].iter().cloned())
+                    .collect()
+            }
+            Ctor::Range(_, _) => types[1..].to_vec(),
+        }
+    }
+
+    fn format_ctor(&self, typ: &Type, ctor: Ctor, fields: &[String]) -> String {
+        match ctor {
+            Ctor::Variant(variant) => {
+                let (e, _) = self.enum_of(typ).expect("formatting variant of non-enum");
+                let name = self.ctx.symbols.get_name(e.variants[variant].name);
+                if fields.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}({})", name, fields.join(", "))
+                }
+            }
+            Ctor::Range(lo, hi) => match *typ {
+                Type::Int(sign, size) if const_eval::int_bounds(sign, size) == (lo, hi) => {
+                    "_".into()
+                }
+                _ if lo == hi => lo.to_string(),
+                _ => format!("{}..={}", lo, hi),
+            },
+        }
+    }
+}
+
+/// Splits range `lo..=hi` at the boundaries of ranges in the first column.
+fn split_range(lo: i128, hi: i128, rows: &[Vec<Pat>]) -> Vec<(i128, i128)> {
+    let mut points = vec![lo, hi + 1];
+    for row in rows {
+        if let Pat::Range(a, b) = row[0] {
+            points.extend(
+                [a, b + 1]
+                    .iter()
+                    .cloned()
+                    .filter(|&p| p > lo && p <= hi),
+            );
+        }
+    }
+    points.sort();
+    points.dedup();
+    points.windows(2).map(|w| (w[0], w[1] - 1)).collect()
+}
+
+fn is_covered(ctor: Ctor, rows: &[Vec<Pat>]) -> bool {
+    rows.iter().any(|row| match (&row[0], ctor) {
+        (&Pat::Variant(v, _), Ctor::Variant(variant)) => v == variant,
+        (&Pat::Range(a, b), Ctor::Range(lo, hi)) => a <= lo && hi <= b,
+        _ => false,
+    })
+}
+
+fn specialize_row(row: &[Pat], ctor: Ctor, arity: usize) -> Option<Vec<Pat>> {
+    match (&row[0], ctor) {
+        (&Pat::Wildcard, _) => Some(
+            vec![Pat::Wildcard; arity]
+                .into_iter()
+                .chain(row[1..].iter().cloned())
+                .collect(),
+        ),
+        (&Pat::Variant(v, ref fields), Ctor::Variant(variant)) if v == variant => {
+            Some(fields.iter().chain(row[1..].iter()).cloned().collect())
+        }
+        (&Pat::Range(a, b), Ctor::Range(lo, hi)) if a <= lo && hi <= b => Some(row[1..].to_vec()),
+        _ => None,
+    }
+}
+
+fn specialize(rows: &[Vec<Pat>], ctor: Ctor, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| specialize_row(row, ctor, arity))
+        .collect()
+}
+
+fn default_rows(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| match row[0] {
+            Pat::Wildcard => Some(row[1..].to_vec()),
+            Pat::Variant(_, _) | Pat::Range(_, _) => None,
+        })
+        .collect()
+}
@@ -1,7 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::collections::hash_map::Entry;
+use std::mem;
+use std::slice;
 use plank_syntax::ast as p;
-use plank_syntax::position::{Position, Span, Spanned};
+use plank_syntax::position::{FileId, Position, Span, Spanned};
 use ast::resolved::{self as r, Symbol};
 use CompileCtx;
 
@@ -15,8 +17,27 @@ struct Resolver<'a> {
     ctx: &'a mut CompileCtx,
     global_structs: HashMap<String, (Symbol, Span)>,
     global_functions: HashMap<String, Function>,
+    global_values: HashMap<String, (Symbol, Span)>,
+    global_variants: HashMap<Symbol, usize>,
+    global_traits: HashMap<String, (Symbol, Span)>,
+    methods: HashMap<Symbol, HashMap<String, Function>>,
+    bounds: HashMap<Symbol, Vec<Symbol>>,
+    impl_targets: HashMap<Span, Symbol>,
+    modules: HashMap<Vec<String>, Namespace>,
+    /// Namespaces of modules with their imports added.
+    imports: HashMap<Vec<String>, Namespace>,
+    /// Type aliases, with other aliases in their bodies expanded.
+    aliases: HashMap<Symbol, r::TypeAlias>,
+    public_items: HashSet<Symbol>,
+    current_module: Vec<String>,
     type_vars: HashMap<String, Symbol>,
     scopes: Vec<HashMap<String, Symbol>>,
+    /// Lambdas being resolved, with index of the first scope
+    /// inside them and locals they capture from outside.
+    lambdas: Vec<(usize, Vec<Symbol>)>,
+    /// Loops being resolved, with their labels and
+    /// whether `break` can give them a value.
+    loops: Vec<(Option<Spanned<String>>, Symbol, bool)>,
 }
 
 impl<'a> Resolver<'a> {
@@ -25,36 +46,343 @@ impl<'a> Resolver<'a> {
             ctx,
             global_structs: HashMap::new(),
             global_functions: HashMap::new(),
+            global_values: HashMap::new(),
+            global_variants: HashMap::new(),
+            global_traits: HashMap::new(),
+            methods: HashMap::new(),
+            bounds: HashMap::new(),
+            impl_targets: HashMap::new(),
+            modules: HashMap::new(),
+            imports: HashMap::new(),
+            aliases: HashMap::new(),
+            public_items: HashSet::new(),
+            current_module: Vec::new(),
             type_vars: HashMap::new(),
             scopes: Vec::new(),
+            lambdas: Vec::new(),
+            loops: Vec::new(),
         }
     }
 
     fn resolve_program(&mut self, program: &p::Program) -> r::Program {
+        let mut resolved = r::Program {
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            methods: HashMap::new(),
+            traits: HashMap::new(),
+            bounds: HashMap::new(),
+            impls: Vec::new(),
+            type_aliases: HashMap::new(),
+            functions: Vec::new(),
+            statics: Vec::new(),
+            consts: Vec::new(),
+        };
+
+        self.collect_module(program, Vec::new());
+        let mut aliases = HashMap::new();
+        self.import_module(program, Vec::new(), &mut aliases);
+        self.expand_aliases(aliases);
+        self.resolve_module(program, Vec::new(), &mut resolved);
+        resolved.bounds = ::std::mem::take(&mut self.bounds);
+        resolved.type_aliases = ::std::mem::take(&mut self.aliases);
+
+        resolved.functions.push(make_builtin_size_of());
+        resolved.functions.push(make_builtin_align_of());
+        resolved.functions.push(make_builtin_getc());
+        resolved.functions.push(make_builtin_putc());
+        resolved.functions.push(make_builtin_alloc());
+        resolved.functions.push(make_builtin_free());
+        resolved
+    }
+
+    fn collect_module(&mut self, program: &p::Program, path: Vec<String>) {
+        self.current_module = path.clone();
         self.collect_globals(program);
+        self.collect_impls(program);
+        self.collect_values(program);
+        let namespace = Namespace {
+            structs: ::std::mem::take(&mut self.global_structs),
+            functions: ::std::mem::take(&mut self.global_functions),
+            values: ::std::mem::take(&mut self.global_values),
+            traits: ::std::mem::take(&mut self.global_traits),
+        };
+        self.modules.insert(path.clone(), namespace);
+
+        let mut module_spans = HashMap::new();
+        for module in &program.modules {
+            let name = &module.name.0;
+            let span = Spanned::span(&module.name);
+            if let Some(&prev_span) = module_spans.get(name) {
+                let msg = format!("module `{}` is defined multiple times", name);
+                let short_msg = format!("module `{}` is defined here", name);
+                self.ctx
+                    .reporter
+                    .error(msg, span)
+                    .span_note("and again defined here", span)
+                    .span_note(short_msg, prev_span)
+                    .build();
+                continue;
+            }
+            module_spans.insert(name, span);
+            if let Some(ref program) = module.program {
+                let mut path = path.clone();
+                path.push(name.clone());
+                self.collect_module(program, path);
+            }
+        }
+    }
+
+    /// Adds imports of each module and resolves type aliases,
+    /// which have to be known before other items are resolved.
+    fn import_module(
+        &mut self,
+        program: &p::Program,
+        path: Vec<String>,
+        aliases: &mut HashMap<Symbol, r::TypeAlias>,
+    ) {
+        {
+            let namespace = &self.modules[&path];
+            self.global_structs = namespace.structs.clone();
+            self.global_functions = namespace.functions.clone();
+            self.global_values = namespace.values.clone();
+            self.global_traits = namespace.traits.clone();
+        }
+        self.current_module = path.clone();
         self.add_builtins();
+        for use_ in &program.uses {
+            self.add_import(use_);
+        }
 
-        let structs = program
-            .structs
-            .iter()
-            .map(|s| {
-                let s = self.resolve_struct(s);
-                (*s.name.name, s)
-            })
-            .collect();
+        for t in &program.type_aliases {
+            // aliases that are defined multiple times are already reported
+            let (symbol, span) = self.global_structs[&t.name.name.0];
+            if span != Spanned::span(&t.name.name) {
+                continue;
+            }
+            self.check_no_bounds(&t.name);
+            let name = self.resolve_item_name_as(&t.name, symbol);
+            let typ = self.resolve_type(&t.typ);
+            let alias = r::TypeAlias {
+                complete_span: t.complete_span,
+                name,
+                typ,
+            };
+            aliases.insert(symbol, alias);
+        }
+
+        let namespace = Namespace {
+            structs: ::std::mem::take(&mut self.global_structs),
+            functions: ::std::mem::take(&mut self.global_functions),
+            values: ::std::mem::take(&mut self.global_values),
+            traits: ::std::mem::take(&mut self.global_traits),
+        };
+        self.imports.insert(path.clone(), namespace);
 
-        let mut functions = program
-            .functions
+        let mut seen_modules = HashSet::new();
+        for module in &program.modules {
+            // modules that are defined multiple times are already reported
+            if !seen_modules.insert(&module.name.0) {
+                continue;
+            }
+            if let Some(ref program) = module.program {
+                let mut path = path.clone();
+                path.push(module.name.0.clone());
+                self.import_module(program, path, aliases);
+            }
+        }
+    }
+
+    fn expand_aliases(&mut self, mut aliases: HashMap<Symbol, r::TypeAlias>) {
+        let mut mentions = HashMap::new();
+        for (&symbol, alias) in &aliases {
+            let mut mentioned = HashSet::new();
+            walk_concrete(&alias.typ, &mut |sym, _| {
+                if aliases.contains_key(&sym) {
+                    mentioned.insert(sym);
+                }
+                None
+            });
+            mentions.insert(symbol, mentioned);
+        }
+        let recursive = ::struct_check::find_cycles(&mentions);
+        for &symbol in &recursive {
+            let alias = aliases.get_mut(&symbol).expect("alias disappeared");
+            let msg = format!(
+                "type alias `{}` is recursive",
+                self.ctx.symbols.get_name(symbol),
+            );
+            self.ctx
+                .reporter
+                .error(msg, alias.complete_span)
+                .span(alias.complete_span)
+                .build();
+            *alias.typ = r::Type::Error;
+        }
+        let expanded = aliases
             .iter()
-            .map(|f| self.resolve_function(f))
+            .map(|(&symbol, alias)| (symbol, expand_type(&alias.typ, &aliases)))
             .collect::<Vec<_>>();
+        for (symbol, typ) in expanded {
+            aliases.get_mut(&symbol).expect("alias disappeared").typ = typ;
+        }
+        self.aliases = aliases;
+    }
+
+    fn resolve_module(&mut self, program: &p::Program, path: Vec<String>, resolved: &mut r::Program) {
+        {
+            let namespace = self.imports.remove(&path).expect("module disappeared");
+            self.global_structs = namespace.structs;
+            self.global_functions = namespace.functions;
+            self.global_values = namespace.values;
+            self.global_traits = namespace.traits;
+        }
+        self.current_module = path.clone();
+
+        for s in &program.structs {
+            let s = self.resolve_struct(s);
+            resolved.structs.insert(*s.name.name, s);
+        }
+
+        for e in &program.enums {
+            // enums that are defined multiple times are already reported
+            let name = &e.name.name;
+            if self.global_structs[&name.0].1 != Spanned::span(name) {
+                continue;
+            }
+            let e = self.resolve_enum(e);
+            resolved.enums.insert(*e.name.name, e);
+        }
+
+        for t in &program.traits {
+            // traits that are defined multiple times are already reported
+            if self.global_traits[&t.name.0].1 != Spanned::span(&t.name) {
+                continue;
+            }
+            self.resolve_trait(t, resolved);
+        }
+
+        for i in &program.impls {
+            match i.trait_ {
+                Some(ref trait_) => self.resolve_trait_impl(i, trait_, resolved),
+                None => self.resolve_impl(i, resolved),
+            }
+        }
 
-        functions.push(make_builtin_size_of());
-        functions.push(make_builtin_align_of());
-        functions.push(make_builtin_getc());
-        functions.push(make_builtin_putc());
+        for f in &program.functions {
+            let f = self.resolve_function(f);
+            resolved.functions.push(f);
+        }
+
+        for s in &program.statics {
+            let s = self.resolve_static(s);
+            resolved.statics.push(s);
+        }
 
-        r::Program { structs, functions }
+        for c in &program.consts {
+            let c = self.resolve_const(c);
+            resolved.consts.push(c);
+        }
+
+        let mut seen_modules = HashSet::new();
+        for module in &program.modules {
+            // modules that are defined multiple times are already reported
+            if !seen_modules.insert(&module.name.0) {
+                continue;
+            }
+            if let Some(ref program) = module.program {
+                let mut path = path.clone();
+                path.push(module.name.0.clone());
+                self.resolve_module(program, path, resolved);
+            }
+        }
+    }
+
+    fn add_import(&mut self, use_: &p::Use) {
+        let (item, module_path) = use_.path.split_last().expect("empty use path");
+        let path = module_path.iter().map(|i| i.0.clone()).collect::<Vec<_>>();
+        let (struct_, function, value, trait_) = match self.modules.get(&path) {
+            Some(namespace) => (
+                namespace.structs.get(&item.0).cloned(),
+                namespace.functions.get(&item.0).cloned(),
+                namespace.values.get(&item.0).cloned(),
+                namespace.traits.get(&item.0).cloned(),
+            ),
+            None => {
+                let span = module_path
+                    .iter()
+                    .map(Spanned::span)
+                    .fold(Spanned::span(&module_path[0]), Span::merge);
+                let msg = format!("unknown module `{}`", path.join("::"));
+                self.ctx.reporter.error(msg, span).span(span).build();
+                return;
+            }
+        };
+
+        let name = &item.0;
+        let item_span = Spanned::span(item);
+        let defined = struct_
+            .or_else(|| function.as_ref().map(|f| (f.name, f.name_span)))
+            .or(value)
+            .or(trait_);
+        let (symbol, def_span) = match defined {
+            Some(defined) => defined,
+            None => {
+                let msg = if path.is_empty() {
+                    format!("`{}` is not defined in the root module", name)
+                } else {
+                    format!("`{}` is not defined in module `{}`", name, path.join("::"))
+                };
+                self.ctx.reporter.error(msg, item_span).span(item_span).build();
+                return;
+            }
+        };
+
+        // private items are visible in their module and its submodules
+        if !self.public_items.contains(&symbol) && !self.current_module.starts_with(&path) {
+            let msg = format!("`{}` is private", name);
+            let short_msg = format!("`{}` is defined here", name);
+            self.ctx
+                .reporter
+                .error(msg, item_span)
+                .span_note("imported here", item_span)
+                .span_note(short_msg, def_span)
+                .build();
+        }
+
+        let prev_span = self.global_structs
+            .get(name)
+            .cloned()
+            .or_else(|| self.global_functions.get(name).map(|f| (f.name, f.name_span)))
+            .or_else(|| self.global_values.get(name).cloned())
+            .or_else(|| self.global_traits.get(name).cloned());
+        match prev_span {
+            // importing same item again is harmless
+            Some((prev_symbol, _)) if prev_symbol == symbol => {}
+            Some((_, prev_span)) => {
+                let msg = format!("`{}` is defined multiple times", name);
+                let short_msg = format!("`{}` is defined here", name);
+                self.ctx
+                    .reporter
+                    .error(msg, item_span)
+                    .span_note("imported here", item_span)
+                    .span_note(short_msg, prev_span)
+                    .build();
+            }
+            None => {
+                if let Some(struct_) = struct_ {
+                    self.global_structs.insert(name.clone(), struct_);
+                }
+                if let Some(function) = function {
+                    self.global_functions.insert(name.clone(), function);
+                }
+                if let Some(value) = value {
+                    self.global_values.insert(name.clone(), value);
+                }
+                if let Some(trait_) = trait_ {
+                    self.global_traits.insert(name.clone(), trait_);
+                }
+            }
+        }
     }
 
     fn collect_globals(&mut self, program: &p::Program) {
@@ -64,9 +392,45 @@ impl<'a> Resolver<'a> {
             // add function only if struct is seen for the first time yet,
             // because otherwise we will get "struct defined multiple times"
             // AND "function defined multiple times" on same positions
-            if self.add_struct(name, span).is_ok() {
+            if self.add_struct(name, span, "struct", struct_.public).is_ok() {
                 let params = struct_.fields.iter().map(|f| f.name.0.clone());
-                self.add_function(name, span, params);
+                let symbol_name = self.qualify(name);
+                self.add_function(name, symbol_name, span, params, struct_.public);
+            }
+        }
+
+        for enum_ in &program.enums {
+            let name = &enum_.name.name.0;
+            let span = Spanned::span(&enum_.name.name);
+            if self.add_struct(name, span, "enum", enum_.public).is_err() {
+                continue;
+            }
+            for variant in &enum_.variants {
+                let name = &variant.name.0;
+                let span = Spanned::span(&variant.name);
+                let params = (0..variant.fields.len()).map(|i| i.to_string());
+                let symbol_name = self.qualify(name);
+                self.add_function(name, symbol_name, span, params, enum_.public);
+                if let Some(f) = self.global_functions.get(name) {
+                    if f.name_span == span {
+                        self.global_variants.insert(f.name, variant.fields.len());
+                    }
+                }
+            }
+        }
+
+        for trait_ in &program.traits {
+            if self.add_trait(&trait_.name, trait_.public).is_err() {
+                continue;
+            }
+            let symbol = self.global_traits[&trait_.name.0].0;
+            let trait_name = self.ctx.symbols.get_name(symbol).to_string();
+            for fn_ in &trait_.functions {
+                let name = &fn_.name.name.0;
+                let span = Spanned::span(&fn_.name.name);
+                let params = fn_.params.iter().map(|f| f.name.0.clone());
+                let symbol_name = format!("{}::{}", trait_name, name);
+                self.add_method(symbol, name, symbol_name, span, params);
             }
         }
 
@@ -74,15 +438,181 @@ impl<'a> Resolver<'a> {
             let name = &fn_.name.name.0;
             let span = Spanned::span(&fn_.name.name);
             let params = fn_.params.iter().map(|f| f.name.0.clone());
-            self.add_function(name, span, params);
+            // external functions are linked by their plain name
+            let symbol_name = match fn_.fn_type {
+                p::FunctionType::Normal => self.qualify(name),
+                p::FunctionType::Extern => name.clone(),
+            };
+            self.add_function(name, symbol_name, span, params, fn_.public);
+        }
+
+        // aliases are added last, so that functions
+        // with same name don't get the alias symbol
+        for alias in &program.type_aliases {
+            let name = &alias.name.name.0;
+            let span = Spanned::span(&alias.name.name);
+            let _ = self.add_struct(name, span, "type alias", alias.public);
         }
     }
 
-    fn add_struct(&mut self, name: &str, mut span: Span) -> Result<(), ()> {
+    fn collect_impls(&mut self, program: &p::Program) {
+        // functions of trait implementations are only called
+        // through the trait, so they are not collected
+        for impl_ in program.impls.iter().filter(|i| i.trait_.is_none()) {
+            let target = match self.check_impl_target(impl_, program) {
+                Some(target) => target,
+                None => continue,
+            };
+            self.impl_targets.insert(impl_.complete_span, target);
+            let type_name = self.ctx.symbols.get_name(target).to_string();
+            for fn_ in &impl_.functions {
+                let name = &fn_.name.name.0;
+                let span = Spanned::span(&fn_.name.name);
+                let params = fn_.params.iter().map(|f| f.name.0.clone());
+                let symbol_name = format!("{}::{}", type_name, name);
+                self.add_method(target, name, symbol_name, span, params);
+            }
+        }
+    }
+
+    fn check_impl_target(&mut self, impl_: &p::Impl, program: &p::Program) -> Option<Symbol> {
+        let span = Spanned::span(&impl_.typ);
+        let (name, params) = match *impl_.typ {
+            p::Type::Concrete(ref name, ref params) => (name, params),
+            _ => {
+                let msg = "`impl` blocks can only be defined for structs and enums";
+                self.ctx.reporter.error(msg, span).span(span).build();
+                return None;
+            }
+        };
+        // types are looked up before imports are added,
+        // so only types from current module are found
+        let target = match self.global_structs.get(&name.0) {
+            Some(&(symbol, _)) => symbol,
+            None => {
+                let msg = format!("cannot find struct or enum `{}` in this module", name.0);
+                self.ctx
+                    .reporter
+                    .error(msg, span)
+                    .span_note("`impl` blocks must be in the same module as their type", span)
+                    .build();
+                return None;
+            }
+        };
+        let type_params = program
+            .structs
+            .iter()
+            .map(|s| &s.name)
+            .chain(program.enums.iter().map(|e| &e.name))
+            .find(|item| item.name.0 == name.0)
+            .map(|item| &item.type_params[..]);
+        let type_params = match type_params {
+            Some(type_params) => type_params,
+            None => {
+                let msg = "`impl` blocks can only be defined for structs and enums";
+                let short_msg = format!("`{}` is a type alias", name.0);
+                self.ctx
+                    .reporter
+                    .error(msg, span)
+                    .span_note(short_msg, span)
+                    .build();
+                return None;
+            }
+        };
+        let params_match = impl_.type_params.len() == type_params.len()
+            && params.len() == type_params.len()
+            && params.iter().zip(&impl_.type_params).all(|(param, var)| match **param {
+                p::Type::Concrete(ref name, ref params) => {
+                    name.0 == var.name.0 && params.is_empty()
+                }
+                _ => false,
+            });
+        if params_match {
+            Some(target)
+        } else {
+            let msg = format!("`impl` must have the same type parameters as `{}`", name.0);
+            let params = format_type_params(type_params);
+            let short_msg = format!("try `impl{} {}{}`", params, name.0, params);
+            self.ctx
+                .reporter
+                .error(msg, span)
+                .span_note(short_msg, span)
+                .build();
+            None
+        }
+    }
+
+    fn collect_values(&mut self, program: &p::Program) {
+        for static_ in &program.statics {
+            self.add_value(&static_.name, static_.public);
+        }
+        for const_ in &program.consts {
+            self.add_value(&const_.name, const_.public);
+        }
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        let mut qualified = String::new();
+        for module in &self.current_module {
+            qualified.push_str(module);
+            qualified.push_str("::");
+        }
+        qualified.push_str(name);
+        qualified
+    }
+
+    fn check_not_builtin(&mut self, name: &str, span: Span) -> Result<(), ()> {
+        match name {
+            "size_of" | "align_of" | "putc" | "getc" | "alloc" | "free" => {
+                let msg = format!("`{}` is a built-in function", name);
+                self.ctx.reporter.error(msg, span).span(span).build();
+                Err(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn add_value(&mut self, name: &Spanned<p::Ident>, public: bool) {
+        let mut span = Spanned::span(name);
+        let name = &name.0;
+        if self.check_not_builtin(name, span).is_err() {
+            return;
+        }
+        let prev_span = if let Some(f) = self.global_functions.get(name) {
+            Some(f.name_span)
+        } else {
+            self.global_values.get(name).map(|&(_, span)| span)
+        };
+        if let Some(mut prev_span) = prev_span {
+            if prev_span.start > span.start {
+                ::std::mem::swap(&mut prev_span, &mut span);
+            }
+            let msg = format!("`{}` is defined multiple times", name);
+            let short_msg = format!("`{}` is defined here", name);
+            self.ctx
+                .reporter
+                .error(msg, span)
+                .span_note("and again defined here", span)
+                .span_note(short_msg, prev_span)
+                .build();
+        } else {
+            let symbol = self.ctx.symbols.new_symbol(self.qualify(name));
+            self.global_values.insert(name.clone(), (symbol, span));
+            if public {
+                self.public_items.insert(symbol);
+            }
+        }
+    }
+
+    fn add_struct(&mut self, name: &str, mut span: Span, kind: &str, public: bool) -> Result<(), ()> {
+        let qualified = self.qualify(name);
         match self.global_structs.entry(name.into()) {
             Entry::Vacant(entry) => {
-                let symbol = self.ctx.symbols.new_symbol(name);
+                let symbol = self.ctx.symbols.new_symbol(qualified);
                 entry.insert((symbol, span));
+                if public {
+                    self.public_items.insert(symbol);
+                }
                 Ok(())
             }
             Entry::Occupied(entry) => {
@@ -91,8 +621,8 @@ impl<'a> Resolver<'a> {
                 if prev_span.start > span.start {
                     ::std::mem::swap(&mut prev_span, &mut span);
                 }
-                let msg = format!("struct `{}` is defined multiple times", name);
-                let short_msg = format!("struct `{}` is defined here", name);
+                let msg = format!("{} `{}` is defined multiple times", kind, name);
+                let short_msg = format!("{} `{}` is defined here", kind, name);
                 self.ctx
                     .reporter
                     .error(msg, span)
@@ -104,37 +634,47 @@ impl<'a> Resolver<'a> {
         }
     }
 
-    fn add_function<I>(&mut self, name: &str, mut span: Span, param_names: I)
-    where
-        I: Iterator<Item = String>,
-    {
-        if name == "size_of" {
-            self.ctx
-                .reporter
-                .error("`size_of` is a built-in function", span)
-                .span(span)
-                .build();
-            return;
-        } else if name == "align_of" {
-            self.ctx
-                .reporter
-                .error("`align_of` is a built-in function", span)
-                .span(span)
-                .build();
-            return;
-        } else if name == "putc" {
-            self.ctx
-                .reporter
-                .error("`putc` is a built-in function", span)
-                .span(span)
-                .build();
-            return;
-        } else if name == "getc" {
+    fn add_trait(&mut self, name: &Spanned<p::Ident>, public: bool) -> Result<(), ()> {
+        let mut span = Spanned::span(name);
+        let name = &name.0;
+        let prev_span = self.global_structs
+            .get(name)
+            .or_else(|| self.global_traits.get(name))
+            .map(|&(_, span)| span);
+        if let Some(mut prev_span) = prev_span {
+            if prev_span.start > span.start {
+                ::std::mem::swap(&mut prev_span, &mut span);
+            }
+            let msg = format!("`{}` is defined multiple times", name);
+            let short_msg = format!("`{}` is defined here", name);
             self.ctx
                 .reporter
-                .error("`getc` is a built-in function", span)
-                .span(span)
+                .error(msg, span)
+                .span_note("and again defined here", span)
+                .span_note(short_msg, prev_span)
                 .build();
+            Err(())
+        } else {
+            let symbol = self.ctx.symbols.new_symbol(self.qualify(name));
+            self.global_traits.insert(name.clone(), (symbol, span));
+            if public {
+                self.public_items.insert(symbol);
+            }
+            Ok(())
+        }
+    }
+
+    fn add_function<I>(
+        &mut self,
+        name: &str,
+        symbol_name: String,
+        mut span: Span,
+        param_names: I,
+        public: bool,
+    ) where
+        I: Iterator<Item = String>,
+    {
+        if self.check_not_builtin(name, span).is_err() {
             return;
         }
         match self.global_functions.entry(name.into()) {
@@ -144,12 +684,52 @@ impl<'a> Resolver<'a> {
                 let symbol = self.global_structs
                     .get(name)
                     .map(|&(s, _)| s)
-                    .unwrap_or(self.ctx.symbols.new_symbol(name));
+                    .unwrap_or(self.ctx.symbols.new_symbol(symbol_name));
                 entry.insert(Function {
                     name: symbol,
                     name_span: span,
                     param_names: param_names.collect(),
                 });
+                if public {
+                    self.public_items.insert(symbol);
+                }
+            }
+            Entry::Occupied(entry) => {
+                let mut prev_span = entry.get().name_span;
+                // always have previous item going before current
+                if prev_span.start > span.start {
+                    ::std::mem::swap(&mut prev_span, &mut span);
+                }
+                let msg = format!("function `{}` is defined multiple times", name);
+                let short_msg = format!("function `{}` is defined here", name);
+                self.ctx
+                    .reporter
+                    .error(msg, span)
+                    .span_note("and again defined here", span)
+                    .span_note(short_msg, prev_span)
+                    .build();
+            }
+        }
+    }
+
+    fn add_method<I>(
+        &mut self,
+        target: Symbol,
+        name: &str,
+        symbol_name: String,
+        mut span: Span,
+        param_names: I,
+    ) where
+        I: Iterator<Item = String>,
+    {
+        let methods = self.methods.entry(target).or_default();
+        match methods.entry(name.into()) {
+            Entry::Vacant(entry) => {
+                entry.insert(Function {
+                    name: self.ctx.symbols.new_symbol(symbol_name),
+                    name_span: span,
+                    param_names: param_names.collect(),
+                });
             }
             Entry::Occupied(entry) => {
                 let mut prev_span = entry.get().name_span;
@@ -171,6 +751,7 @@ impl<'a> Resolver<'a> {
 
     fn add_builtins(&mut self) {
         let dummy_span = Span {
+            file: FileId(0),
             start: Position { line: 0, column: 0 },
             end: Position { line: 0, column: 0 },
         };
@@ -206,9 +787,26 @@ impl<'a> Resolver<'a> {
                 param_names: Vec::new(),
             },
         );
+        self.global_functions.insert(
+            "alloc".into(),
+            Function {
+                name: ::builtins::ALLOC,
+                name_span: dummy_span,
+                param_names: vec!["size".into(), "align".into()],
+            },
+        );
+        self.global_functions.insert(
+            "free".into(),
+            Function {
+                name: ::builtins::FREE,
+                name_span: dummy_span,
+                param_names: vec!["ptr".into()],
+            },
+        );
     }
 
     fn resolve_struct(&mut self, struct_: &p::Struct) -> r::Struct {
+        self.check_no_bounds(&struct_.name);
         let name = self.resolve_item_name(&struct_.name);
         let fields = self.resolve_var_list(&struct_.fields, "field");
         r::Struct {
@@ -218,11 +816,54 @@ impl<'a> Resolver<'a> {
         }
     }
 
+    fn resolve_enum(&mut self, enum_: &p::Enum) -> r::Enum {
+        let symbol = self.global_structs[&enum_.name.name.0].0;
+        self.check_no_bounds(&enum_.name);
+        let name = self.resolve_item_name_as(&enum_.name, symbol);
+        let variants = enum_
+            .variants
+            .iter()
+            .map(|v| {
+                let span = Spanned::span(&v.name);
+                let symbol = match self.global_functions.get(&v.name.0) {
+                    Some(f) if f.name_span == span => f.name,
+                    // variant name clashes with another item,
+                    // so give it a fresh symbol
+                    _ => self.ctx.symbols.new_symbol(v.name.0.clone()),
+                };
+                let fields = v.fields.iter().map(|t| self.resolve_type(t)).collect();
+                r::Variant {
+                    name: Spanned::new(symbol, span),
+                    fields,
+                }
+            })
+            .collect();
+        r::Enum {
+            name,
+            variants,
+            complete_span: enum_.complete_span,
+        }
+    }
+
+    fn check_no_bounds(&mut self, name: &p::ItemName) {
+        for bound in name.type_params.iter().flat_map(|p| &p.bounds) {
+            let span = Spanned::span(bound);
+            let msg = "trait bounds can only be used on functions and `impl` blocks";
+            self.ctx.reporter.error(msg, span).span(span).build();
+        }
+    }
+
     fn resolve_item_name(&mut self, name: &p::ItemName) -> r::ItemName {
         let symbol = self.global_functions[&name.name.0].name;
+        self.resolve_item_name_as(name, symbol)
+    }
+
+    fn resolve_item_name_as(&mut self, name: &p::ItemName, symbol: Symbol) -> r::ItemName {
         let mut type_var_spans = HashMap::new();
         let mut type_params = Vec::new();
-        for var in &name.type_params {
+        self.type_vars.clear();
+        for param in &name.type_params {
+            let var = &param.name;
             let symbol = if let Some(&span) = type_var_spans.get(&var.0) {
                 let msg = format!("type parameter `{}` is listed multiple times", &var.0);
                 let short_msg = format!("`{}` is defined here", &var.0);
@@ -239,6 +880,14 @@ impl<'a> Resolver<'a> {
                 self.type_vars.insert(var.0.clone(), symbol);
                 symbol
             };
+            let bounds = param
+                .bounds
+                .iter()
+                .filter_map(|b| self.resolve_trait_name(b))
+                .collect::<Vec<_>>();
+            if !bounds.is_empty() {
+                self.bounds.insert(symbol, bounds);
+            }
             type_params.push(Spanned::new(symbol, Spanned::span(var)));
         }
         r::ItemName {
@@ -287,24 +936,44 @@ impl<'a> Resolver<'a> {
             p::Type::U16 => r::Type::U16,
             p::Type::I32 => r::Type::I32,
             p::Type::U32 => r::Type::U32,
+            p::Type::I64 => r::Type::I64,
+            p::Type::U64 => r::Type::U64,
             p::Type::Wildcard => r::Type::Wildcard,
             p::Type::Pointer(ref typ) => {
                 let typ = self.resolve_type(typ);
                 r::Type::Pointer(Box::new(typ))
             }
+            p::Type::Array(ref typ, len) => {
+                let typ = self.resolve_type(typ);
+                r::Type::Array(Box::new(typ), len)
+            }
+            p::Type::Tuple(ref types) => {
+                let types = types.iter().map(|typ| self.resolve_type(typ)).collect();
+                r::Type::Tuple(types)
+            }
             p::Type::Function(ref params, ref out) => {
                 let params = params.iter().map(|typ| self.resolve_type(typ)).collect();
                 let out = self.resolve_type(out);
                 r::Type::Function(params, Box::new(out))
             }
+            p::Type::Closure(ref params, ref out) => {
+                let params = params.iter().map(|typ| self.resolve_type(typ)).collect();
+                let out = self.resolve_type(out);
+                r::Type::Closure(params, Box::new(out))
+            }
             p::Type::Concrete(ref name, ref params) => {
                 let params = params.iter().map(|typ| self.resolve_type(typ)).collect();
                 if let Some(&sym) = self.type_vars.get(&name.0) {
                     let name = Spanned::new(sym, Spanned::span(name));
                     r::Type::Concrete(name, params)
-                } else if let Some(sym) = self.global_structs.get(&name.0) {
-                    let name = Spanned::new(sym.0, Spanned::span(name));
-                    r::Type::Concrete(name, params)
+                } else if let Some(&(sym, _)) = self.global_structs.get(&name.0) {
+                    match expand_alias(sym, &params, &self.aliases) {
+                        Some(typ) => typ,
+                        None => {
+                            let name = Spanned::new(sym, Spanned::span(name));
+                            r::Type::Concrete(name, params)
+                        }
+                    }
                 } else {
                     let msg = format!("unknown type `{}`", &name.0);
                     self.ctx
@@ -316,23 +985,274 @@ impl<'a> Resolver<'a> {
                 }
             }
         };
-        Spanned::new(typ, span)
+        Spanned::new(typ, span)
+    }
+
+    fn resolve_static(&mut self, s: &p::Static) -> r::Static {
+        let name = self.resolve_value_name(&s.name);
+        self.type_vars.clear();
+        let typ = self.resolve_type(&s.typ);
+        let value = self.resolve_expr(&s.value);
+        r::Static {
+            mutable: s.mutable,
+            name,
+            typ,
+            value,
+        }
+    }
+
+    fn resolve_const(&mut self, c: &p::Const) -> r::Const {
+        let name = self.resolve_value_name(&c.name);
+        self.type_vars.clear();
+        let typ = self.resolve_type(&c.typ);
+        let value = self.resolve_expr(&c.value);
+        r::Const { name, typ, value }
+    }
+
+    fn resolve_value_name(&mut self, name: &Spanned<p::Ident>) -> Spanned<Symbol> {
+        let span = Spanned::span(name);
+        let symbol = match self.global_values.get(&name.0) {
+            Some(&(symbol, prev_span)) if prev_span == span => symbol,
+            // value was defined multiple times, and this one is not
+            // the first one, so give it a fresh symbol
+            _ => self.ctx.symbols.new_symbol(name.0.clone()),
+        };
+        Spanned::new(symbol, span)
+    }
+
+    fn resolve_impl(&mut self, impl_: &p::Impl, resolved: &mut r::Program) {
+        // impls with invalid types are already reported
+        let target = match self.impl_targets.get(&impl_.complete_span) {
+            Some(&target) => target,
+            None => return,
+        };
+        for f in &impl_.functions {
+            let name = &f.name.name.0;
+            let span = Spanned::span(&f.name.name);
+            let symbol = match self.methods.get(&target).and_then(|m| m.get(name)) {
+                Some(m) if m.name_span == span => Some(m.name),
+                _ => None,
+            };
+            // function was defined multiple times, and this one is
+            // not the first one, so give it a fresh symbol
+            let f = match symbol {
+                Some(symbol) => self.resolve_function_as(f, symbol, &impl_.type_params),
+                None => {
+                    let symbol = self.ctx.symbols.new_symbol(name.clone());
+                    self.resolve_function_as(f, symbol, &impl_.type_params);
+                    continue;
+                }
+            };
+            if let Some(receiver) = f.params.first() {
+                if self.ctx.symbols.get_name(*receiver.name) == "self" {
+                    if is_valid_receiver(&receiver.typ, target, &f.name.type_params) {
+                        resolved
+                            .methods
+                            .entry(target)
+                            .or_default()
+                            .insert(name.clone(), *f.name.name);
+                    } else {
+                        let span = Spanned::span(&receiver.typ);
+                        let typ = match *impl_.typ {
+                            p::Type::Concrete(ref name, _) => &name.0,
+                            _ => unreachable!(),
+                        };
+                        let typ = format!("{}{}", typ, format_type_params(&impl_.type_params));
+                        let msg = format!("`self` must have type `{}` or `*{}`", typ, typ);
+                        self.ctx.reporter.error(msg, span).span(span).build();
+                    }
+                }
+            }
+            resolved.functions.push(f);
+        }
+    }
+
+    fn resolve_trait_name(&mut self, name: &Spanned<p::Ident>) -> Option<Symbol> {
+        match self.global_traits.get(&name.0) {
+            Some(&(symbol, _)) => Some(symbol),
+            None => {
+                let msg = format!("unknown trait `{}`", &name.0);
+                self.ctx
+                    .reporter
+                    .error(msg, Spanned::span(name))
+                    .span_note("unknown trait", Spanned::span(name))
+                    .build();
+                None
+            }
+        }
+    }
+
+    fn resolve_trait(&mut self, trait_: &p::Trait, resolved: &mut r::Program) {
+        let symbol = self.global_traits[&trait_.name.0].0;
+        // every method has implicit type parameter `Self`,
+        // which is bounded by the trait itself
+        let self_param = p::TypeParam {
+            name: Spanned::new(p::Ident("Self".into()), Spanned::span(&trait_.name)),
+            bounds: vec![trait_.name.clone()],
+        };
+        let mut methods = HashMap::new();
+        for f in &trait_.functions {
+            let name = &f.name.name.0;
+            let span = Spanned::span(&f.name.name);
+            if let Some(ref body) = f.body {
+                let span = Spanned::span(body);
+                let msg = "trait methods cannot have a body";
+                self.ctx.reporter.error(msg, span).span(span).build();
+            }
+            let method = match self.methods.get(&symbol).and_then(|m| m.get(name)) {
+                Some(m) if m.name_span == span => Some(m.name),
+                _ => None,
+            };
+            let f = match method {
+                Some(method) => self.resolve_function_as(f, method, slice::from_ref(&self_param)),
+                None => {
+                    let method = self.ctx.symbols.new_symbol(name.clone());
+                    self.resolve_function_as(f, method, slice::from_ref(&self_param));
+                    continue;
+                }
+            };
+            if let Some(receiver) = f.params.first() {
+                if self.ctx.symbols.get_name(*receiver.name) == "self" {
+                    let self_type = *f.name.type_params[0];
+                    if is_valid_receiver(&receiver.typ, self_type, &[]) {
+                        methods.insert(name.clone(), *f.name.name);
+                    } else {
+                        let span = Spanned::span(&receiver.typ);
+                        let msg = "`self` must have type `Self` or `*Self`";
+                        self.ctx.reporter.error(msg, span).span(span).build();
+                    }
+                }
+            }
+            resolved.functions.push(f);
+        }
+        resolved.traits.insert(symbol, methods);
+    }
+
+    fn resolve_trait_impl(
+        &mut self,
+        impl_: &p::Impl,
+        trait_name: &Spanned<p::Ident>,
+        resolved: &mut r::Program,
+    ) {
+        let trait_ = match self.resolve_trait_name(trait_name) {
+            Some(trait_) => trait_,
+            None => return,
+        };
+        let name = p::ItemName {
+            name: trait_name.clone(),
+            type_params: impl_.type_params.clone(),
+        };
+        let type_params = self.resolve_item_name_as(&name, trait_).type_params;
+        let typ = self.resolve_type(&impl_.typ);
+        let type_name = match self.check_trait_impl_target(&typ, &type_params) {
+            Some(type_name) => type_name,
+            None => return,
+        };
+        let trait_str = self.ctx.symbols.get_name(trait_).to_string();
+        let methods = self.methods.get(&trait_).cloned().unwrap_or_default();
+        let mut functions = HashMap::new();
+        let mut function_spans = HashMap::new();
+        for f in &impl_.functions {
+            let name = &f.name.name.0;
+            let span = Spanned::span(&f.name.name);
+            let symbol_name = format!("<{} as {}>::{}", type_name, trait_str, name);
+            let symbol = self.ctx.symbols.new_symbol(symbol_name);
+            let resolved_fn = self.resolve_function_as(f, symbol, &impl_.type_params);
+            let method = match methods.get(name) {
+                Some(method) => method.name,
+                None => {
+                    let msg = format!("`{}` is not a method of trait `{}`", name, &trait_name.0);
+                    self.ctx.reporter.error(msg, span).span(span).build();
+                    continue;
+                }
+            };
+            if let Some(&prev_span) = function_spans.get(name) {
+                let msg = format!("function `{}` is defined multiple times", name);
+                let short_msg = format!("function `{}` is defined here", name);
+                self.ctx
+                    .reporter
+                    .error(msg, span)
+                    .span_note("and again defined here", span)
+                    .span_note(short_msg, prev_span)
+                    .build();
+                continue;
+            }
+            function_spans.insert(name, span);
+            functions.insert(method, Spanned::new(symbol, span));
+            resolved.functions.push(resolved_fn);
+        }
+        let mut missing = methods
+            .keys()
+            .filter(|m| !function_spans.contains_key(m))
+            .collect::<Vec<_>>();
+        missing.sort();
+        for name in missing {
+            let span = Spanned::span(&impl_.typ);
+            let msg = format!(
+                "missing method `{}` in implementation of trait `{}`",
+                name,
+                &trait_name.0,
+            );
+            self.ctx.reporter.error(msg, span).span(span).build();
+        }
+        resolved.impls.push(r::Impl {
+            trait_: Spanned::new(trait_, Spanned::span(trait_name)),
+            type_params,
+            typ,
+            functions,
+        });
+    }
+
+    /// Checks type that trait is implemented for, and returns
+    /// its name to be used in names of implementing functions.
+    fn check_trait_impl_target(
+        &mut self,
+        typ: &Spanned<r::Type>,
+        type_params: &[Spanned<Symbol>],
+    ) -> Option<String> {
+        let span = Spanned::span(typ);
+        let name = match **typ {
+            r::Type::Concrete(name, ref params)
+                if !type_params.iter().any(|p| **p == *name) =>
+            {
+                if is_valid_receiver(typ, *name, type_params) && params.len() == type_params.len() {
+                    return Some(self.ctx.symbols.get_name(*name).to_string());
+                }
+                let msg = "type parameters of `impl` must be the parameters of its type";
+                self.ctx.reporter.error(msg, span).span(span).build();
+                return None;
+            }
+            r::Type::Error => return None,
+            r::Type::Bool => "bool",
+            r::Type::I8 => "i8",
+            r::Type::U8 => "u8",
+            r::Type::I16 => "i16",
+            r::Type::U16 => "u16",
+            r::Type::I32 => "i32",
+            r::Type::U32 => "u32",
+            r::Type::I64 => "i64",
+            r::Type::U64 => "u64",
+            _ => {
+                let msg = "traits can only be implemented for structs, enums, integers and `bool`";
+                self.ctx.reporter.error(msg, span).span(span).build();
+                return None;
+            }
+        };
+        if type_params.is_empty() {
+            Some(name.into())
+        } else {
+            let span = Spanned::span(&type_params[0]);
+            let msg = format!("type parameters of `impl` are not used by `{}`", name);
+            self.ctx.reporter.error(msg, span).span(span).build();
+            None
+        }
     }
 
     fn resolve_function(&mut self, f: &p::Function) -> r::Function {
-        let name = self.resolve_item_name(&f.name);
-        let params = self.resolve_var_list(&f.params, "parameter");
-        let return_type = self.resolve_type(&f.return_type);
-
-        debug_assert!(self.scopes.is_empty());
-        self.scopes.push(HashMap::new());
-        for (name, sym) in f.params.iter().zip(params.iter()) {
-            self.add_local(&name.name.0, Spanned::into_value(sym.name));
-        }
-        let body = f.body.as_ref().map(|s| self.resolve_statement(s));
-        self.scopes.clear();
+        let symbol = self.global_functions[&f.name.name.0].name;
+        let resolved = self.resolve_function_as(f, symbol, &[]);
 
-        if f.name.name.0 == "main" {
+        if self.current_module.is_empty() && f.name.name.0 == "main" {
             let span = Spanned::span(&f.name.name);
             if !f.name.type_params.is_empty() {
                 self.ctx
@@ -341,10 +1261,10 @@ impl<'a> Resolver<'a> {
                     .span(span)
                     .build();
             }
-            if !f.params.is_empty() {
+            if !f.params.is_empty() && !is_main_args(&f.params) {
                 self.ctx
                     .reporter
-                    .error("`main` cannot take parameters", span)
+                    .error("`main` must take no parameters or `(argc: u32, argv: **u8)`", span)
                     .span(span)
                     .build();
             }
@@ -357,6 +1277,33 @@ impl<'a> Resolver<'a> {
             }
         }
 
+        resolved
+    }
+
+    /// Resolves function with given symbol, type parameters
+    /// of `impl` block go before function's own ones.
+    fn resolve_function_as(
+        &mut self,
+        f: &p::Function,
+        symbol: Symbol,
+        impl_params: &[p::TypeParam],
+    ) -> r::Function {
+        let name = p::ItemName {
+            name: f.name.name.clone(),
+            type_params: impl_params.iter().chain(&f.name.type_params).cloned().collect(),
+        };
+        let name = self.resolve_item_name_as(&name, symbol);
+        let params = self.resolve_var_list(&f.params, "parameter");
+        let return_type = self.resolve_type(&f.return_type);
+
+        debug_assert!(self.scopes.is_empty());
+        self.scopes.push(HashMap::new());
+        for (name, sym) in f.params.iter().zip(params.iter()) {
+            self.add_local(&name.name.0, Spanned::into_value(sym.name));
+        }
+        let body = f.body.as_ref().map(|s| self.resolve_statement(s));
+        self.scopes.clear();
+
         r::Function {
             complete_span: f.complete_span,
             name,
@@ -372,15 +1319,35 @@ impl<'a> Resolver<'a> {
         let statement: r::Statement = match **s {
             p::Statement::Block(ref statements) => {
                 self.scopes.push(HashMap::new());
-                let statements = statements
-                    .iter()
-                    .map(|s| self.resolve_statement(s))
-                    .collect();
+                let resolved = self.resolve_block(statements);
                 self.scopes.pop().expect("missing scope");
-                r::Statement::Block(statements)
+                r::Statement::Block(resolved)
             }
-            p::Statement::Break => r::Statement::Break,
-            p::Statement::Continue => r::Statement::Continue,
+            p::Statement::Break(ref label, ref value) => match self.find_loop(label, "break", span) {
+                Some((symbol, true)) => {
+                    let value = match *value {
+                        Some(ref value) => self.resolve_expr(value),
+                        None => Spanned::new(r::Expr::Literal(r::Literal::Unit), span),
+                    };
+                    r::Statement::Break(symbol, Some(value))
+                }
+                Some((symbol, false)) => {
+                    if let Some(ref value) = *value {
+                        let span = Spanned::span(value);
+                        self.ctx
+                            .reporter
+                            .error("`break` with value can only be used in `loop`", span)
+                            .span(span)
+                            .build();
+                    }
+                    r::Statement::Break(symbol, None)
+                }
+                None => r::Statement::Error,
+            },
+            p::Statement::Continue(ref label) => match self.find_loop(label, "continue", span) {
+                Some((symbol, _)) => r::Statement::Continue(symbol),
+                None => r::Statement::Error,
+            },
             p::Statement::Expr(ref expr) => {
                 let expr = self.resolve_expr(expr);
                 r::Statement::Expr(expr)
@@ -402,24 +1369,260 @@ impl<'a> Resolver<'a> {
                 let symbol = Spanned::new(symbol, name_span);
                 r::Statement::Let(symbol, typ, value)
             }
-            p::Statement::Loop(ref statement) => {
+            p::Statement::LetTuple(ref names, ref typ, ref value) => {
+                r::Statement::Block(self.resolve_let_tuple(names, typ, value, span))
+            }
+            p::Statement::Loop(ref label, ref statement) => {
+                let symbol = self.start_loop(label, true);
                 let statement = self.resolve_statement(statement);
-                r::Statement::Loop(Box::new(statement))
+                self.loops.pop().expect("missing loop");
+                r::Statement::Loop(symbol, Box::new(statement))
             }
             p::Statement::Return(ref expr) => {
                 let expr = self.resolve_expr(expr);
                 r::Statement::Return(expr)
             }
-            p::Statement::While(ref cond, ref body) => {
+            p::Statement::While(ref label, ref cond, ref body) => {
+                let symbol = self.start_loop(label, false);
                 let cond = self.resolve_expr(cond);
                 let body = self.resolve_statement(body);
-                r::Statement::While(cond, Box::new(body))
+                self.loops.pop().expect("missing loop");
+                r::Statement::While(symbol, cond, Box::new(body))
+            }
+            p::Statement::For(ref label, ref name, ref start, ref end, inclusive, ref body) => {
+                // bounds are evaluated before the loop starts
+                let start = self.resolve_expr(start);
+                let end = self.resolve_expr(end);
+                let loop_symbol = self.start_loop(label, false);
+                self.scopes.push(HashMap::new());
+                let symbol = self.ctx.symbols.new_symbol(name.0.clone());
+                self.add_local(&name.0, symbol);
+                let body = self.resolve_statement(body);
+                self.scopes.pop().expect("missing scope");
+                self.loops.pop().expect("missing loop");
+                let name = Spanned::new(symbol, Spanned::span(name));
+                r::Statement::For(loop_symbol, name, start, end, inclusive, Box::new(body))
+            }
+            p::Statement::Match(ref value, ref arms) => {
+                let value = self.resolve_expr(value);
+                let arms = arms.iter()
+                    .map(|arm| {
+                        self.scopes.push(HashMap::new());
+                        let pattern = self.resolve_pattern(&arm.pattern, &mut HashMap::new());
+                        let body = self.resolve_statement(&arm.body);
+                        self.scopes.pop().expect("missing scope");
+                        r::MatchArm { pattern, body }
+                    })
+                    .collect();
+                r::Statement::Match(value, arms)
             }
             p::Statement::Error => r::Statement::Error,
         };
         Spanned::new(statement, span)
     }
 
+    /// Adds a loop that `break` and `continue` can refer to.
+    fn start_loop(&mut self, label: &Option<Spanned<p::Ident>>, has_value: bool) -> Symbol {
+        let label = label
+            .as_ref()
+            .map(|label| Spanned::new(label.0.clone(), Spanned::span(label)));
+        if let Some(ref label) = label {
+            let enclosing = self.loops
+                .iter()
+                .filter_map(|(label, _, _)| label.as_ref())
+                .find(|enclosing| ***enclosing == **label)
+                .map(Spanned::span);
+            if let Some(enclosing_span) = enclosing {
+                let msg = format!("label `'{}` is already used by an enclosing loop", &**label);
+                let span = Spanned::span(label);
+                self.ctx
+                    .reporter
+                    .error(msg, span)
+                    .span(span)
+                    .span_note("enclosing loop has the same label", enclosing_span)
+                    .build();
+            }
+        }
+        let name = match label {
+            Some(ref label) => format!("'{}", &**label),
+            None => "loop".to_string(),
+        };
+        let symbol = self.ctx.symbols.new_symbol(name);
+        self.loops.push((label, symbol, has_value));
+        symbol
+    }
+
+    /// Finds the loop that `break` or `continue` refers to, and
+    /// whether it can give a value.
+    fn find_loop(
+        &mut self,
+        label: &Option<Spanned<p::Ident>>,
+        keyword: &str,
+        span: Span,
+    ) -> Option<(Symbol, bool)> {
+        match *label {
+            None => match self.loops.last() {
+                Some(&(_, symbol, has_value)) => Some((symbol, has_value)),
+                None => {
+                    let msg = format!("cannot use `{}` outside loop", keyword);
+                    self.ctx.reporter.error(msg, span).span(span).build();
+                    None
+                }
+            },
+            Some(ref label) => {
+                let found = self.loops
+                    .iter()
+                    .rev()
+                    .find(|(name, _, _)| name.as_ref().map(|name| &**name) == Some(&label.0))
+                    .map(|&(_, symbol, has_value)| (symbol, has_value));
+                if found.is_none() {
+                    let msg = format!("use of undeclared label `'{}`", label.0);
+                    let span = Spanned::span(label);
+                    self.ctx.reporter.error(msg, span).span(span).build();
+                }
+                found
+            }
+        }
+    }
+
+    fn resolve_block(&mut self, statements: &[Spanned<p::Statement>]) -> Vec<Spanned<r::Statement>> {
+        let mut resolved = Vec::new();
+        for s in statements {
+            // fields of tuple must be visible to the rest of the block
+            if let p::Statement::LetTuple(ref names, ref typ, ref value) = **s {
+                let lets = self.resolve_let_tuple(names, typ, value, Spanned::span(s));
+                resolved.extend(lets);
+            } else {
+                resolved.push(self.resolve_statement(s));
+            }
+        }
+        resolved
+    }
+
+    /// Desugars `let (a, b) = value;` into a let of hidden tuple
+    /// variable, followed by a let of each named field.
+    fn resolve_let_tuple(
+        &mut self,
+        names: &[Spanned<Option<p::Ident>>],
+        typ: &Option<Spanned<p::Type>>,
+        value: &Spanned<p::Expr>,
+        span: Span,
+    ) -> Vec<Spanned<r::Statement>> {
+        let value = self.resolve_expr(value);
+        let typ = match *typ {
+            Some(ref typ) => {
+                if let p::Type::Tuple(ref types) = **typ {
+                    if types.len() != names.len() {
+                        let msg = format!(
+                            "cannot destructure tuple of {} elements into {} names",
+                            types.len(),
+                            names.len(),
+                        );
+                        let span = Spanned::span(typ);
+                        self.ctx.reporter.error(msg, span).span(span).build();
+                    }
+                }
+                self.resolve_type(typ)
+            }
+            None => {
+                let types = names
+                    .iter()
+                    .map(|name| Spanned::new(r::Type::Wildcard, Spanned::span(name)))
+                    .collect();
+                Spanned::new(r::Type::Tuple(types), span)
+            }
+        };
+        let tuple = self.ctx.symbols.new_symbol("tuple");
+        let tuple_let = r::Statement::Let(Spanned::new(tuple, span), typ, Some(value));
+        let mut statements = vec![Spanned::new(tuple_let, span)];
+        for (index, name) in names.iter().enumerate() {
+            let name_span = Spanned::span(name);
+            let name = match **name {
+                Some(ref name) => name,
+                None => continue,
+            };
+            let tuple = r::Expr::Name(Spanned::new(tuple, name_span), Vec::new());
+            let field = Spanned::new(index.to_string(), name_span);
+            let value = r::Expr::Field(Box::new(Spanned::new(tuple, name_span)), field);
+            let symbol = self.ctx.symbols.new_symbol(name.0.clone());
+            self.add_local(&name.0, symbol);
+            let stmt = r::Statement::Let(
+                Spanned::new(symbol, name_span),
+                Spanned::new(r::Type::Wildcard, name_span),
+                Some(Spanned::new(value, name_span)),
+            );
+            statements.push(Spanned::new(stmt, name_span));
+        }
+        statements
+    }
+
+    fn resolve_pattern(
+        &mut self,
+        pattern: &Spanned<p::Pattern>,
+        bindings: &mut HashMap<String, Span>,
+    ) -> Spanned<r::Pattern> {
+        let span = Spanned::span(pattern);
+        let pattern = match **pattern {
+            p::Pattern::Wildcard => r::Pattern::Wildcard,
+            p::Pattern::Name(ref name) => if let Some(symbol) = self.resolve_variant(&name.0) {
+                r::Pattern::Variant(Spanned::new(symbol, span), Vec::new())
+            } else {
+                if let Some(&prev_span) = bindings.get(&name.0) {
+                    let msg = format!(
+                        "identifier `{}` is bound more than once in the same pattern",
+                        &name.0
+                    );
+                    let short_msg = format!("`{}` is bound here", &name.0);
+                    self.ctx
+                        .reporter
+                        .error(msg, span)
+                        .span_note("and again here", span)
+                        .span_note(short_msg, prev_span)
+                        .build();
+                } else {
+                    bindings.insert(name.0.clone(), span);
+                }
+                let symbol = self.ctx.symbols.new_symbol(name.0.clone());
+                self.add_local(&name.0, symbol);
+                r::Pattern::Binding(Spanned::new(symbol, span))
+            },
+            p::Pattern::Variant(ref name, ref patterns) => {
+                let name_span = Spanned::span(name);
+                if let Some(symbol) = self.resolve_variant(&name.0) {
+                    let patterns = patterns
+                        .iter()
+                        .map(|p| self.resolve_pattern(p, bindings))
+                        .collect();
+                    r::Pattern::Variant(Spanned::new(symbol, name_span), patterns)
+                } else {
+                    let msg = format!("unknown enum variant `{}`", &name.0);
+                    self.ctx
+                        .reporter
+                        .error(msg, name_span)
+                        .span(name_span)
+                        .build();
+                    r::Pattern::Error
+                }
+            }
+            p::Pattern::Literal(negated, ref literal) => {
+                r::Pattern::Literal(negated, literal.clone())
+            }
+            p::Pattern::Range(ref start, ref end) => {
+                let start = self.resolve_pattern(start, bindings);
+                let end = self.resolve_pattern(end, bindings);
+                r::Pattern::Range(Box::new(start), Box::new(end))
+            }
+        };
+        Spanned::new(pattern, span)
+    }
+
+    fn resolve_variant(&self, name: &str) -> Option<Symbol> {
+        self.global_functions
+            .get(name)
+            .map(|f| f.name)
+            .filter(|symbol| self.global_variants.contains_key(symbol))
+    }
+
     fn resolve_expr(&mut self, e: &Spanned<p::Expr>) -> Spanned<r::Expr> {
         let span = Spanned::span(e);
         let expr = match **e {
@@ -428,31 +1631,60 @@ impl<'a> Resolver<'a> {
                 let rhs = self.resolve_expr(rhs);
                 r::Expr::Binary(Box::new(lhs), op, Box::new(rhs))
             }
-            p::Expr::Call(ref expr, ref params) => {
-                let expr = self.resolve_expr(expr);
+            p::Expr::Call(ref expr, ref params) => if let p::Expr::Field(ref value, ref name) = ***expr {
+                let value = self.resolve_expr(value);
+                let name = Spanned::new(name.0.clone(), Spanned::span(name));
+                // methods are looked up by the type of value,
+                // so only positional parameters can be used
+                if let Some(params) = self.resolve_call_params(&r::Expr::Error, params) {
+                    r::Expr::MethodCall(Box::new(value), name, params)
+                } else {
+                    r::Expr::Error
+                }
+            } else {
+                let expr = self.resolve_callee(expr);
                 if let Some(params) = self.resolve_call_params(&expr, params) {
                     r::Expr::Call(Box::new(expr), params)
                 } else {
                     r::Expr::Error
                 }
-            }
+            },
             p::Expr::Field(ref expr, ref field) => {
                 let expr = self.resolve_expr(expr);
                 let field = Spanned::new(field.0.clone(), Spanned::span(field));
                 r::Expr::Field(Box::new(expr), field)
             }
+            p::Expr::Index(ref expr, ref index) => {
+                let expr = self.resolve_expr(expr);
+                let index = self.resolve_expr(index);
+                r::Expr::Index(Box::new(expr), Box::new(index))
+            }
+            p::Expr::Array(ref values) => {
+                let values = values.iter().map(|v| self.resolve_expr(v)).collect();
+                r::Expr::Array(values)
+            }
+            p::Expr::Tuple(ref values) => {
+                let values = values.iter().map(|v| self.resolve_expr(v)).collect();
+                r::Expr::Tuple(values)
+            }
             p::Expr::Literal(ref lit) => r::Expr::Literal(lit.clone()),
             p::Expr::Name(ref name, ref params) => {
-                let params = params.iter().map(|t| self.resolve_type(t)).collect();
-                let name_span = Spanned::span(name);
-                match self.resolve_var(&name.0, name_span) {
-                    Some(symbol) => {
-                        let name = Spanned::new(symbol, name_span);
-                        r::Expr::Name(name, params)
-                    }
-                    None => r::Expr::Error,
+                let expr = self.resolve_name(name, params);
+                let is_unit_variant = match expr {
+                    r::Expr::Name(ref name, _) => self.global_variants.get(&**name) == Some(&0),
+                    _ => false,
+                };
+                // variants without fields are used without
+                // parentheses, so desugar them into calls
+                if is_unit_variant {
+                    r::Expr::Call(Box::new(Spanned::new(expr, span)), Vec::new())
+                } else {
+                    expr
                 }
             }
+            p::Expr::Associated(ref typ, ref name, ref params) => {
+                self.resolve_associated(typ, name, params)
+            }
             p::Expr::Unary(op, ref expr) => {
                 let expr = self.resolve_expr(expr);
                 r::Expr::Unary(op, Box::new(expr))
@@ -462,16 +1694,105 @@ impl<'a> Resolver<'a> {
                 let typ = self.resolve_type(typ);
                 r::Expr::Cast(Box::new(expr), typ)
             }
+            p::Expr::Lambda(ref params, ref body) => {
+                let resolved_params = self.resolve_var_list(params, "parameter");
+                self.scopes.push(HashMap::new());
+                self.lambdas.push((self.scopes.len() - 1, Vec::new()));
+                // loops outside can't be broken from inside the lambda
+                let outer_loops = mem::take(&mut self.loops);
+                for (name, sym) in params.iter().zip(resolved_params.iter()) {
+                    self.add_local(&name.name.0, Spanned::into_value(sym.name));
+                }
+                let body = self.resolve_expr(body);
+                self.loops = outer_loops;
+                let (_, captures) = self.lambdas.pop().expect("missing lambda");
+                self.scopes.pop().expect("missing scope");
+                r::Expr::Lambda(resolved_params, Box::new(body), captures)
+            }
+            p::Expr::If(ref cond, ref then, ref else_) => {
+                let cond = self.resolve_expr(cond);
+                let then = self.resolve_expr(then);
+                let else_ = else_.as_ref().map(|e| Box::new(self.resolve_expr(e)));
+                r::Expr::If(Box::new(cond), Box::new(then), else_)
+            }
+            p::Expr::Loop(ref label, ref body) => {
+                let symbol = self.start_loop(label, true);
+                let body = self.resolve_statement(body);
+                self.loops.pop().expect("missing loop");
+                r::Expr::Loop(symbol, Box::new(body))
+            }
+            p::Expr::Block(ref statements, ref value) => {
+                self.scopes.push(HashMap::new());
+                let statements = self.resolve_block(statements);
+                let value = value.as_ref().map(|e| Box::new(self.resolve_expr(e)));
+                self.scopes.pop().expect("missing scope");
+                r::Expr::Block(statements, value)
+            }
         };
         Spanned::new(expr, span)
     }
 
+    fn resolve_callee(&mut self, e: &Spanned<p::Expr>) -> Spanned<r::Expr> {
+        match **e {
+            p::Expr::Name(ref name, ref params) => {
+                let expr = self.resolve_name(name, params);
+                Spanned::new(expr, Spanned::span(e))
+            }
+            _ => self.resolve_expr(e),
+        }
+    }
+
+    fn resolve_name(&mut self, name: &Spanned<p::Ident>, params: &[Spanned<p::Type>]) -> r::Expr {
+        let params = params.iter().map(|t| self.resolve_type(t)).collect();
+        let name_span = Spanned::span(name);
+        match self.resolve_var(&name.0, name_span) {
+            Some(symbol) => {
+                let name = Spanned::new(symbol, name_span);
+                r::Expr::Name(name, params)
+            }
+            None => r::Expr::Error,
+        }
+    }
+
+    fn resolve_associated(
+        &mut self,
+        typ: &Spanned<p::Ident>,
+        name: &Spanned<p::Ident>,
+        params: &[Spanned<p::Type>],
+    ) -> r::Expr {
+        let params = params.iter().map(|t| self.resolve_type(t)).collect();
+        let target = self.global_structs
+            .get(&typ.0)
+            .or_else(|| self.global_traits.get(&typ.0));
+        let target = match target {
+            Some(&(symbol, _)) => symbol,
+            None => {
+                let msg = format!("unknown type `{}`", &typ.0);
+                self.ctx
+                    .reporter
+                    .error(msg, Spanned::span(typ))
+                    .span_note("unknown type", Spanned::span(typ))
+                    .build();
+                return r::Expr::Error;
+            }
+        };
+        let name_span = Spanned::span(name);
+        match self.methods.get(&target).and_then(|m| m.get(&name.0)) {
+            Some(f) => r::Expr::Name(Spanned::new(f.name, name_span), params),
+            None => {
+                let msg = format!("`{}` does not have a function named `{}`", &typ.0, &name.0);
+                self.ctx.reporter.error(msg, name_span).span(name_span).build();
+                r::Expr::Error
+            }
+        }
+    }
+
     fn resolve_call_params(
         &mut self,
         callee: &r::Expr,
         params: &[p::CallParam],
     ) -> Option<Vec<Spanned<r::Expr>>> {
-        match params.get(0) {
+        match params.first() {
             None => Some(Vec::new()),
             Some(&p::CallParam::Named(_, _)) => self.resolve_named_params(callee, params),
             Some(&p::CallParam::Unnamed(_)) => self.resolve_unnamed_params(params),
@@ -486,23 +1807,28 @@ impl<'a> Resolver<'a> {
         let name_and_pos = match *callee {
             r::Expr::Name(name, _) => {
                 let name = Spanned::into_value(name);
-                // TODO: fix this, this is horrible hack
-                let name_str = match self.ctx.symbols.get_name(name) {
-                    "@plank_putc" => "putc",
-                    "@plank_getc" => "getc",
-                    s => s,
-                };
-                if let Some(f) = self.global_functions.get(name_str) {
-                    let positions = f.param_names
-                        .iter()
-                        .cloned()
-                        .enumerate()
-                        .map(|(index, name)| (name, index))
-                        .collect::<HashMap<_, _>>();
-                    Some((name_str.to_string(), positions))
-                } else {
-                    None
-                }
+                // look up by symbol, because imported functions
+                // have qualified names
+                self.global_functions
+                    .iter()
+                    .find(|&(_, f)| f.name == name)
+                    .map(|(name_str, f)| (name_str.clone(), f))
+                    .or_else(|| {
+                        self.methods
+                            .values()
+                            .flat_map(HashMap::values)
+                            .find(|f| f.name == name)
+                            .map(|f| (self.ctx.symbols.get_name(name).to_string(), f))
+                    })
+                    .map(|(name_str, f)| {
+                        let positions = f.param_names
+                            .iter()
+                            .cloned()
+                            .enumerate()
+                            .map(|(index, name)| (name, index))
+                            .collect::<HashMap<_, _>>();
+                        (name_str, positions)
+                    })
             }
             _ => None,
         };
@@ -543,7 +1869,7 @@ impl<'a> Resolver<'a> {
                             self.resolve_remaining_params(iterator.as_slice());
                             return None;
                         }
-                        Some(&mut index) if index == ::std::usize::MAX => {
+                        Some(&mut index) if index == usize::MAX => {
                             // good name, but already used
                             let msg = format!("parameter `{}` is supplied twice", ident.0,);
                             let span = Spanned::span(ident);
@@ -557,7 +1883,7 @@ impl<'a> Resolver<'a> {
                                 resolved.push(None);
                             }
                             resolved[*index] = Some(expr);
-                            *index = ::std::usize::MAX;
+                            *index = usize::MAX;
                         }
                     }
                 }
@@ -572,7 +1898,7 @@ impl<'a> Resolver<'a> {
             }
         }
         for (name, pos) in positions {
-            if pos != ::std::usize::MAX {
+            if pos != usize::MAX {
                 let msg = format!("missing parameter `{}`", name);
                 let span = param_list_span(params);
                 self.ctx.reporter.error(msg, span).span(span).build();
@@ -614,14 +1940,24 @@ impl<'a> Resolver<'a> {
     }
 
     fn resolve_var(&mut self, var: &str, span: Span) -> Option<Symbol> {
-        for scope in self.scopes.iter().rev() {
+        for (index, scope) in self.scopes.iter().enumerate().rev() {
             if let Some(&symbol) = scope.get(var) {
+                // every lambda entered after the scope of
+                // the variable has to capture it
+                for &mut (start, ref mut captures) in &mut self.lambdas {
+                    if start > index && !captures.contains(&symbol) {
+                        captures.push(symbol);
+                    }
+                }
                 return Some(symbol);
             }
         }
         if let Some(f) = self.global_functions.get(var) {
             return Some(f.name);
         }
+        if let Some(&(symbol, _)) = self.global_values.get(var) {
+            return Some(symbol);
+        }
         let msg = format!("unknown value `{}`", var);
         self.ctx.reporter.error(msg, span).span(span).build();
         None
@@ -635,6 +1971,7 @@ impl<'a> Resolver<'a> {
 
 fn make_builtin_size_of() -> r::Function {
     let dummy_span = Span {
+        file: FileId(0),
         start: Position { line: 0, column: 0 },
         end: Position { line: 0, column: 0 },
     };
@@ -653,6 +1990,7 @@ fn make_builtin_size_of() -> r::Function {
 
 fn make_builtin_align_of() -> r::Function {
     let dummy_span = Span {
+        file: FileId(0),
         start: Position { line: 0, column: 0 },
         end: Position { line: 0, column: 0 },
     };
@@ -669,8 +2007,26 @@ fn make_builtin_align_of() -> r::Function {
     }
 }
 
+/// Checks if parameters have types `u32` and `**u8`, which
+/// are used to pass command line arguments to `main`.
+fn is_main_args(params: &[p::Var]) -> bool {
+    if params.len() != 2 {
+        return false;
+    }
+    let argc_ok = matches!(*params[0].typ, p::Type::U32);
+    let argv_ok = match *params[1].typ {
+        p::Type::Pointer(ref inner) => match ***inner {
+            p::Type::Pointer(ref inner) => matches!(***inner, p::Type::U8),
+            _ => false,
+        },
+        _ => false,
+    };
+    argc_ok && argv_ok
+}
+
 fn make_builtin_getc() -> r::Function {
     let dummy_span = Span {
+        file: FileId(0),
         start: Position { line: 0, column: 0 },
         end: Position { line: 0, column: 0 },
     };
@@ -689,6 +2045,7 @@ fn make_builtin_getc() -> r::Function {
 
 fn make_builtin_putc() -> r::Function {
     let dummy_span = Span {
+        file: FileId(0),
         start: Position { line: 0, column: 0 },
         end: Position { line: 0, column: 0 },
     };
@@ -710,12 +2067,164 @@ fn make_builtin_putc() -> r::Function {
     }
 }
 
+fn make_builtin_alloc() -> r::Function {
+    let dummy_span = Span {
+        file: FileId(0),
+        start: Position { line: 0, column: 0 },
+        end: Position { line: 0, column: 0 },
+    };
+    let u8_type = Spanned::new(r::Type::U8, dummy_span);
+    r::Function {
+        complete_span: dummy_span,
+        name: r::ItemName {
+            name: Spanned::new(::builtins::ALLOC, dummy_span),
+            type_params: Vec::new(),
+        },
+        params: vec![
+            r::Var {
+                name: Spanned::new(::builtins::ALLOC_SIZE_PARAM, dummy_span),
+                typ: Spanned::new(r::Type::U32, dummy_span),
+            },
+            r::Var {
+                name: Spanned::new(::builtins::ALLOC_ALIGN_PARAM, dummy_span),
+                typ: Spanned::new(r::Type::U32, dummy_span),
+            },
+        ],
+        return_type: Spanned::new(r::Type::Pointer(Box::new(u8_type)), dummy_span),
+        body: None,
+        fn_type: r::FunctionType::Normal,
+    }
+}
+
+fn make_builtin_free() -> r::Function {
+    let dummy_span = Span {
+        file: FileId(0),
+        start: Position { line: 0, column: 0 },
+        end: Position { line: 0, column: 0 },
+    };
+    let u8_type = Spanned::new(r::Type::U8, dummy_span);
+    r::Function {
+        complete_span: dummy_span,
+        name: r::ItemName {
+            name: Spanned::new(::builtins::FREE, dummy_span),
+            type_params: Vec::new(),
+        },
+        params: vec![
+            r::Var {
+                name: Spanned::new(::builtins::FREE_PARAM, dummy_span),
+                typ: Spanned::new(r::Type::Pointer(Box::new(u8_type)), dummy_span),
+            },
+        ],
+        return_type: Spanned::new(r::Type::Unit, dummy_span),
+        body: None,
+        fn_type: r::FunctionType::Normal,
+    }
+}
+
+/// Items defined in a module, keyed by their unqualified names.
+struct Namespace {
+    structs: HashMap<String, (Symbol, Span)>,
+    functions: HashMap<String, Function>,
+    values: HashMap<String, (Symbol, Span)>,
+    traits: HashMap<String, (Symbol, Span)>,
+}
+
+#[derive(Clone)]
 struct Function {
     name: Symbol,
     name_span: Span,
     param_names: Vec<String>,
 }
 
+/// Checks that type of `self` is the `impl` type or a pointer to it.
+/// Rebuilds a type, replacing each concrete type for which `f`
+/// returns a new type. Type parameters are visited first.
+fn walk_concrete<F>(typ: &Spanned<r::Type>, f: &mut F) -> Spanned<r::Type>
+where
+    F: FnMut(Symbol, &[Spanned<r::Type>]) -> Option<r::Type>,
+{
+    let span = Spanned::span(typ);
+    let typ = match **typ {
+        r::Type::Pointer(ref typ) => r::Type::Pointer(Box::new(walk_concrete(typ, f))),
+        r::Type::Array(ref typ, len) => r::Type::Array(Box::new(walk_concrete(typ, f)), len),
+        r::Type::Tuple(ref types) => {
+            r::Type::Tuple(types.iter().map(|typ| walk_concrete(typ, f)).collect())
+        }
+        r::Type::Function(ref params, ref out) => {
+            let params = params.iter().map(|typ| walk_concrete(typ, f)).collect();
+            r::Type::Function(params, Box::new(walk_concrete(out, f)))
+        }
+        r::Type::Closure(ref params, ref out) => {
+            let params = params.iter().map(|typ| walk_concrete(typ, f)).collect();
+            r::Type::Closure(params, Box::new(walk_concrete(out, f)))
+        }
+        r::Type::Concrete(name, ref params) => {
+            let params = params.iter().map(|typ| walk_concrete(typ, f)).collect::<Vec<_>>();
+            match f(*name, &params) {
+                Some(typ) => typ,
+                None => r::Type::Concrete(name, params),
+            }
+        }
+        ref typ => typ.clone(),
+    };
+    Spanned::new(typ, span)
+}
+
+/// Expands all type aliases in a type.
+fn expand_type(typ: &Spanned<r::Type>, aliases: &HashMap<Symbol, r::TypeAlias>) -> Spanned<r::Type> {
+    walk_concrete(typ, &mut |sym, params| expand_alias(sym, params, aliases))
+}
+
+/// Gives the type that alias `sym` stands for. Aliases with wrong
+/// number of type parameters are left for `type_param_check`.
+fn expand_alias(
+    sym: Symbol,
+    params: &[Spanned<r::Type>],
+    aliases: &HashMap<Symbol, r::TypeAlias>,
+) -> Option<r::Type> {
+    let alias = aliases.get(&sym)?;
+    if alias.name.type_params.len() != params.len() {
+        return None;
+    }
+    let vars = alias
+        .name
+        .type_params
+        .iter()
+        .map(|var| **var)
+        .zip(params)
+        .collect::<HashMap<_, _>>();
+    let typ = expand_type(&alias.typ, aliases);
+    let typ = walk_concrete(&typ, &mut |sym, params| match vars.get(&sym) {
+        Some(&typ) if params.is_empty() => Some((**typ).clone()),
+        _ => None,
+    });
+    Some(Spanned::into_value(typ))
+}
+
+fn is_valid_receiver(typ: &r::Type, target: Symbol, type_params: &[Spanned<Symbol>]) -> bool {
+    let typ = match *typ {
+        r::Type::Pointer(ref typ) => &**typ,
+        ref typ => typ,
+    };
+    match *typ {
+        r::Type::Concrete(name, ref params) => {
+            *name == target && params.iter().zip(type_params).all(|(param, var)| match **param {
+                r::Type::Concrete(name, ref params) => *name == **var && params.is_empty(),
+                _ => false,
+            })
+        }
+        _ => false,
+    }
+}
+
+fn format_type_params(params: &[p::TypeParam]) -> String {
+    if params.is_empty() {
+        return String::new();
+    }
+    let params = params.iter().map(|p| &p.name.0[..]).collect::<Vec<_>>();
+    format!("<{}>", params.join(", "))
+}
+
 fn param_list_span(params: &[p::CallParam]) -> Span {
     assert!(!params.is_empty());
     let span = match params[0] {
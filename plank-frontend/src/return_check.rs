@@ -51,6 +51,10 @@ fn check_function(f: &mut Function, ctx: &mut CompileCtx) {
                 queue.push_back(a);
                 queue.push_back(b);
             }
+            BlockEnd::Switch(_, ref cases, default) => {
+                queue.extend(cases.iter().map(|&(_, block)| block));
+                queue.push_back(default);
+            }
             BlockEnd::Jump(a) => {
                 queue.push_back(a);
             }
@@ -67,8 +67,12 @@ impl Solver {
 
     fn add_fact(&mut self, fact: Pair) {
         let node = self.concrete_node(fact.0, fact.1);
-        self.visit_queue.push(node);
-        self.needed_visits.insert(node, 0);
+        // same fact can be added multiple times, but
+        // each node must be visited only once
+        if self.needed_visits[&node] != 0 {
+            self.visit_queue.push(node);
+            self.needed_visits.insert(node, 0);
+        }
     }
 
     fn add_rule<I: ExactSizeIterator<Item=Pair>>(&mut self, bounds: I, result: Pair) {
@@ -119,16 +123,26 @@ impl Solver {
             Type::U16 |
             Type::I32 |
             Type::U32 |
+            Type::I64 |
+            Type::U64 |
             Type::Bool |
             Type::Unit |
             Type::Pointer(_) |
             Type::Function(_, _) |
+            Type::Closure(_, _) |
             Type::Error => {}
+            Type::Array(ref typ, _) => self.add_struct(program, root, typ, acc),
+            Type::Tuple(ref types) => for typ in types {
+                self.add_struct(program, root, typ, acc);
+            },
             Type::Concrete(sym, ref params) => {
                 let sym = *sym;
                 self.add_rule(acc.iter().cloned(), (root, sym));
                 if !params.is_empty() {
-                    let symbols = &program.structs[&sym].name.type_params;
+                    let symbols = match program.structs.get(&sym) {
+                        Some(s) => &s.name.type_params,
+                        None => &program.enums[&sym].name.type_params,
+                    };
                     debug_assert_eq!(params.len(), symbols.len());
                     for (typ, &p) in params.iter().zip(symbols.iter()) {
                         let new_pair = (sym, *p);
@@ -151,6 +165,11 @@ pub(crate) fn check_program(program: &mut Program, ctx: &mut CompileCtx) {
         .values()
         .flat_map(|s| s.name.type_params.iter().map(|x| **x))
         .chain(program.structs.keys().cloned())
+        .chain(program
+            .enums
+            .values()
+            .flat_map(|e| e.name.type_params.iter().map(|x| **x)))
+        .chain(program.enums.keys().cloned())
         .collect::<Vec<_>>();
     let symbols_hashset = symbols.iter().cloned().collect::<HashSet<_>>();
     debug_assert_eq!(symbols.len(), symbols_hashset.len());
@@ -161,6 +180,11 @@ pub(crate) fn check_program(program: &mut Program, ctx: &mut CompileCtx) {
             solver.add_struct(program, name, &var.typ, &mut acc);
         }
     }
+    for (&name, e) in &program.enums {
+        for typ in e.variants.iter().flat_map(|v| v.fields.iter()) {
+            solver.add_struct(program, name, typ, &mut acc);
+        }
+    }
     solver.solve();
     for (&name, s) in &mut program.structs {
         if solver.is_recursive(name) {
@@ -177,4 +201,33 @@ pub(crate) fn check_program(program: &mut Program, ctx: &mut CompileCtx) {
             }
         }
     }
+    for (&name, e) in &mut program.enums {
+        if solver.is_recursive(name) {
+            let msg = format!(
+                "enum `{}` is recursive",
+                ctx.symbols.get_name(name),
+            );
+            ctx.reporter
+                .error(msg, e.complete_span)
+                .span(e.complete_span)
+                .build();
+            for typ in e.variants.iter_mut().flat_map(|v| v.fields.iter_mut()) {
+                **typ = Type::Error;
+            }
+        }
+    }
+}
+
+/// Finds symbols that refer to themselves, directly or through
+/// other symbols. Used to detect recursive type aliases.
+pub(crate) fn find_cycles(mentions: &HashMap<Symbol, HashSet<Symbol>>) -> HashSet<Symbol> {
+    let symbols = mentions.keys().cloned().collect();
+    let mut solver = Solver::new(&symbols);
+    for (&a, mentioned) in mentions {
+        for &b in mentioned {
+            solver.add_fact((a, b));
+        }
+    }
+    solver.solve();
+    symbols.into_iter().filter(|&sym| solver.is_recursive(sym)).collect()
 }
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
-use ast::cfg::{Size, Symbol, Type};
-use ast::typed::Struct;
+use ast::cfg::{Signedness, Size, Symbol, Type};
+use ast::typed::{Enum, Struct};
 
 
 const POINTER_SIZE: u32 = ::plank_ir::ir::POINTER_SIZE;
@@ -22,6 +22,19 @@ impl<T> LayoutResult<T> {
         }
     }
 
+    fn combine<U, V, F>(self, other: LayoutResult<U>, f: F) -> LayoutResult<V>
+    where
+        F: FnOnce(T, U) -> V,
+    {
+        match (self, other) {
+            (LayoutResult::Ok(a), LayoutResult::Ok(b)) => LayoutResult::Ok(f(a, b)),
+            (LayoutResult::Error, _) | (_, LayoutResult::Error) => LayoutResult::Error,
+            (LayoutResult::HasTypeParam, _) | (_, LayoutResult::HasTypeParam) => {
+                LayoutResult::HasTypeParam
+            }
+        }
+    }
+
     pub fn unwrap(self) -> T {
         match self {
             LayoutResult::Ok(t) => t,
@@ -32,11 +45,12 @@ impl<T> LayoutResult<T> {
 
 pub struct LayoutEngine<'a> {
     structs: &'a HashMap<Symbol, Struct>,
+    enums: &'a HashMap<Symbol, Enum>,
 }
 
 impl<'a> LayoutEngine<'a> {
-    pub fn new(structs: &'a HashMap<Symbol, Struct>) -> Self {
-        LayoutEngine { structs }
+    pub fn new(structs: &'a HashMap<Symbol, Struct>, enums: &'a HashMap<Symbol, Enum>) -> Self {
+        LayoutEngine { structs, enums }
     }
 
     #[allow(dead_code)]
@@ -58,9 +72,21 @@ impl<'a> LayoutEngine<'a> {
             Type::Int(_, Size::Bit8) => LayoutResult::Ok((1, 1)),
             Type::Int(_, Size::Bit16) => LayoutResult::Ok((2, 2)),
             Type::Int(_, Size::Bit32) => LayoutResult::Ok((4, 4)),
+            Type::Int(_, Size::Bit64) => LayoutResult::Ok((8, 8)),
             Type::Var(_) => LayoutResult::Error,
             Type::Unit => LayoutResult::Ok((0, 1)),
+            Type::Array(ref typ, len) => self.size_align(typ).map(|(s, a)| (s * len, a)),
+            Type::Tuple(ref types) => self.fields_size_align(types.iter().cloned())
+                .map(|(s, a)| (align_up(s, a), a)),
+            Type::Closure(ref params, ref out) => {
+                let fields = Type::closure_fields(params, out);
+                self.fields_size_align(fields.iter().cloned())
+                    .map(|(s, a)| (align_up(s, a), a))
+            }
             Type::Concrete(sym, ref params) => {
+                if let Some(e) = self.enums.get(&sym) {
+                    return self.enum_size_align(e, params);
+                }
                 let s = match self.structs.get(&sym) {
                     Some(s) => s,
                     None => return LayoutResult::HasTypeParam,
@@ -71,32 +97,57 @@ impl<'a> LayoutEngine<'a> {
                     .cloned()
                     .zip(params.iter().cloned())
                     .collect();
-                s.fields
-                    .iter()
-                    .map(|field| field.typ.replace(&mapping))
-                    .fold(LayoutResult::Ok((0, 1)), |a, b| {
-                        match (a, self.size_align(&b)) {
-                            (LayoutResult::Ok((s, a)), LayoutResult::Ok((s2, a2))) => {
-                                let s = (s + a2 - 1) / a2 * a2 + s2;
-                                let a = lcm(a, a2);
-                                LayoutResult::Ok((s, a))
-                            }
-                            (LayoutResult::Error, _) | (_, LayoutResult::Error) => {
-                                LayoutResult::Error
-                            }
-                            (LayoutResult::HasTypeParam, _) | (_, LayoutResult::HasTypeParam) => {
-                                LayoutResult::HasTypeParam
-                            }
-                        }
-                    })
-                    .map(|(s, a)| {
-                        let s = (s + a - 1) / a * a;
-                        (s, a)
-                    })
+                let fields = s.fields.iter().map(|field| field.typ.replace(&mapping));
+                self.fields_size_align(fields)
+                    .map(|(s, a)| (align_up(s, a), a))
             }
         }
     }
 
+    /// Returns size and alignment of fields laid out one after
+    /// another, without padding after the last field.
+    fn fields_size_align<I>(&self, fields: I) -> LayoutResult<(u32, u32)>
+    where
+        I: Iterator<Item = Type>,
+    {
+        fields.fold(LayoutResult::Ok((0, 1)), |acc, field| {
+            acc.combine(self.size_align(&field), |(s, a), (s2, a2)| {
+                (align_up(s, a2) + s2, lcm(a, a2))
+            })
+        })
+    }
+
+    /// Returns offset and type of a field laid out by `fields_size_align`.
+    fn unpadded_field_info(&self, fields: &[Type], field: usize) -> (u32, Type) {
+        debug_assert!(field < fields.len());
+        let (before, _) = self.fields_size_align(fields.iter().take(field).cloned())
+            .unwrap();
+        let typ = fields[field].clone();
+        let (_, field_align) = self.size_align(&typ).unwrap();
+        (align_up(before, field_align), typ)
+    }
+
+    /// Enum is laid out as a one byte tag, followed by
+    /// space for the biggest variant.
+    fn enum_size_align(&self, e: &Enum, params: &[Type]) -> LayoutResult<(u32, u32)> {
+        debug_assert_eq!(params.len(), e.type_params.len());
+        let mapping = e.type_params
+            .iter()
+            .cloned()
+            .zip(params.iter().cloned())
+            .collect();
+        e.variants
+            .iter()
+            .map(|v| {
+                let fields = v.fields.iter().map(|field| field.replace(&mapping));
+                self.fields_size_align(fields)
+            })
+            .fold(LayoutResult::Ok((0, 1)), |acc, variant| {
+                acc.combine(variant, |(s, a), (s2, a2)| (s.max(s2), lcm(a, a2)))
+            })
+            .map(|(s, a)| (align_up(align_up(1, a) + s, a), a))
+    }
+
     pub fn field_info(&self, ty: &Type, field: usize) -> (u32, Type) {
         match *ty {
             Type::Unit |
@@ -106,6 +157,46 @@ impl<'a> LayoutEngine<'a> {
             Type::Function(_, _) |
             Type::Int(_, _) |
             Type::Var(_) => panic!("no fields on type"),
+            Type::Array(ref typ, len) => {
+                debug_assert!((field as u32) < len);
+                let size = self.size_of(typ).unwrap();
+                (size * field as u32, (**typ).clone())
+            }
+            Type::Tuple(ref types) => self.unpadded_field_info(types, field),
+            Type::Closure(ref params, ref out) => {
+                let fields = Type::closure_fields(params, out);
+                self.unpadded_field_info(&fields, field)
+            }
+            Type::Concrete(sym, ref params) if self.enums.contains_key(&sym) => {
+                let e = &self.enums[&sym];
+                if field == 0 {
+                    let tag_type = Type::Int(Signedness::Unsigned, Size::Bit8);
+                    return (0, tag_type);
+                }
+                let mapping = e.type_params
+                    .iter()
+                    .cloned()
+                    .zip(params.iter().cloned())
+                    .collect();
+                let mut index = field - 1;
+                let variant = e.variants
+                    .iter()
+                    .find(|v| if index < v.fields.len() {
+                        true
+                    } else {
+                        index -= v.fields.len();
+                        false
+                    })
+                    .expect("enum field out of bounds");
+                let fields = variant.fields.iter().map(|field| field.replace(&mapping));
+                let (before, _) = self.fields_size_align(fields.clone().take(index))
+                    .unwrap();
+                let typ = fields.clone().nth(index).unwrap();
+                let (_, field_align) = self.size_align(&typ).unwrap();
+                let (_, enum_align) = self.size_align(ty).unwrap();
+                let offset = align_up(1, enum_align) + align_up(before, field_align);
+                (offset, typ)
+            }
             Type::Concrete(sym, ref params) => {
                 let s = &self.structs[&sym];
                 debug_assert_eq!(params.len(), s.type_params.len());
@@ -132,6 +223,10 @@ impl<'a> LayoutEngine<'a> {
     }
 }
 
+fn align_up(offset: u32, align: u32) -> u32 {
+    (offset + align - 1) / align * align
+}
+
 fn lcm(a: u32, b: u32) -> u32 {
     fn gcd(mut a: u32, mut b: u32) -> u32 {
         while b > 0 {
@@ -18,6 +18,11 @@ impl Symbols {
         names.insert(::builtins::SIZE_OF_TYPE_PARAM, "T".into());
         names.insert(::builtins::ALIGN_OF_TYPE_PARAM, "T".into());
         names.insert(::builtins::PUTC_PARAM, "ch".into());
+        names.insert(::builtins::ALLOC, "@plank_alloc".into());
+        names.insert(::builtins::FREE, "@plank_free".into());
+        names.insert(::builtins::ALLOC_SIZE_PARAM, "size".into());
+        names.insert(::builtins::ALLOC_ALIGN_PARAM, "align".into());
+        names.insert(::builtins::FREE_PARAM, "ptr".into());
         Symbols {
             next_symbol: names.len() as u32,
             symbol_names: names,
@@ -2,7 +2,7 @@ mod rollback_map;
 mod unify;
 
 use std::borrow::{Borrow, Cow};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::rc::Rc;
 use plank_syntax::position::{Span, Spanned};
@@ -20,8 +20,16 @@ enum Reason {
     RightOperand(Span),
     UnaryOperand(Span),
     FunctionParam(usize, Span),
+    Receiver(Span),
+    ArrayElement(Span),
+    Index(Span),
     Return(Span),
     Assign(Span),
+    Pattern(Span),
+    ElseBranch(Span),
+    RangeBound(Span),
+    MissingElse(Span),
+    BreakValue(Span),
 }
 
 #[derive(Debug, Clone)]
@@ -46,11 +54,22 @@ impl Scheme {
                     sym
                 }
                 Type::Bool | Type::Int(_, _) | Type::Error | Type::Var(_) | Type::Unit => return,
-                Type::Pointer(ref mut t) => {
+                Type::Pointer(ref mut t) | Type::Array(ref mut t, _) => {
                     walk(Rc::make_mut(t), vars, params);
                     return;
                 }
-                Type::Function(ref mut p, ref mut o) => {
+                Type::Tuple(ref mut p) => {
+                    let params = p.iter()
+                        .map(|p| {
+                            let mut o = p.clone();
+                            walk(&mut o, vars, params);
+                            o
+                        })
+                        .collect::<Vec<_>>();
+                    *p = params.into();
+                    return;
+                }
+                Type::Function(ref mut p, ref mut o) | Type::Closure(ref mut p, ref mut o) => {
                     walk(Rc::make_mut(o), vars, params);
                     let params = p.iter()
                         .map(|p| {
@@ -93,7 +112,7 @@ impl<'a> fmt::Display for TypeFormatter<'a> {
             Type::Bool => write!(f, "bool"),
             Type::Concrete(sym, ref params) => {
                 write!(f, "{}", self.inferer.ctx.symbols.get_name(sym))?;
-                if params.len() > 0 {
+                if !params.is_empty() {
                     write!(f, "<")?;
                     for (index, param) in params.iter().enumerate() {
                         if index > 0 {
@@ -113,30 +132,8 @@ impl<'a> fmt::Display for TypeFormatter<'a> {
                 Ok(())
             }
             Type::Error => write!(f, "_"),
-            Type::Function(ref params, ref out) => {
-                write!(f, "fn(")?;
-                for (index, param) in params.iter().enumerate() {
-                    if index > 0 {
-                        write!(f, ", ")?;
-                    }
-                    write!(
-                        f,
-                        "{}",
-                        TypeFormatter {
-                            typ: param,
-                            inferer: self.inferer,
-                        }
-                    )?;
-                }
-                write!(
-                    f,
-                    ") -> {}",
-                    TypeFormatter {
-                        typ: out,
-                        inferer: self.inferer,
-                    }
-                )
-            }
+            Type::Function(ref params, ref out) => self.write_signature(f, ("fn(", ")"), params, out),
+            Type::Closure(ref params, ref out) => self.write_signature(f, ("|", "|"), params, out),
             Type::Int(sign, size) => {
                 match sign {
                     r::Signedness::Unsigned => write!(f, "u")?,
@@ -146,6 +143,7 @@ impl<'a> fmt::Display for TypeFormatter<'a> {
                     r::Size::Bit8 => write!(f, "8"),
                     r::Size::Bit16 => write!(f, "16"),
                     r::Size::Bit32 => write!(f, "32"),
+                    r::Size::Bit64 => write!(f, "64"),
                 }
             }
             Type::Pointer(ref typ) => write!(
@@ -156,18 +154,92 @@ impl<'a> fmt::Display for TypeFormatter<'a> {
                     inferer: self.inferer,
                 }
             ),
-            Type::Var(var) => write!(f, "{}", self.inferer.unifier.describe_var(var)),
+            Type::Array(ref typ, len) => write!(
+                f,
+                "[{}; {}]",
+                TypeFormatter {
+                    typ,
+                    inferer: self.inferer,
+                },
+                len
+            ),
+            Type::Tuple(ref types) => {
+                write!(f, "(")?;
+                for (index, typ) in types.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(
+                        f,
+                        "{}",
+                        TypeFormatter {
+                            typ,
+                            inferer: self.inferer,
+                        }
+                    )?;
+                }
+                if types.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Type::Var(var) => match self.inferer.unifier.lambda_signature(var) {
+                Some((ref params, ref out)) => self.write_signature(f, ("fn(", ")"), params, out),
+                None => write!(f, "{}", self.inferer.unifier.describe_var(var)),
+            },
         }
     }
 }
 
+impl<'a> TypeFormatter<'a> {
+    fn write_signature(
+        &self,
+        f: &mut fmt::Formatter,
+        (open, close): (&str, &str),
+        params: &[Type],
+        out: &Type,
+    ) -> fmt::Result {
+        write!(f, "{}", open)?;
+        for (index, param) in params.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(
+                f,
+                "{}",
+                TypeFormatter {
+                    typ: param,
+                    inferer: self.inferer,
+                }
+            )?;
+        }
+        write!(
+            f,
+            "{} -> {}",
+            close,
+            TypeFormatter {
+                typ: out,
+                inferer: self.inferer,
+            }
+        )
+    }
+}
+
 struct Inferer<'a> {
     ctx: &'a mut CompileCtx,
     unifier: UnifyTable,
     string_type: Type,
     return_type: Option<Type>,
+    loops: HashMap<Symbol, Type>,
     env: HashMap<Symbol, Scheme>,
     fields: HashMap<Symbol, HashMap<String, (usize, Scheme)>>,
+    methods: HashMap<Symbol, HashMap<String, Symbol>>,
+    traits: HashMap<Symbol, HashMap<String, Symbol>>,
+    bounds: HashMap<Symbol, Vec<Symbol>>,
+    impls: Vec<t::Impl>,
+    structs: HashSet<Symbol>,
+    enums: HashSet<Symbol>,
+    variants: HashMap<Symbol, usize>,
 }
 
 impl<'a> Inferer<'a> {
@@ -179,8 +251,16 @@ impl<'a> Inferer<'a> {
             unifier: UnifyTable::new(),
             string_type,
             return_type: None,
+            loops: HashMap::new(),
             env: HashMap::new(),
             fields: HashMap::new(),
+            methods: HashMap::new(),
+            traits: HashMap::new(),
+            bounds: HashMap::new(),
+            impls: Vec::new(),
+            structs: HashSet::new(),
+            enums: HashSet::new(),
+            variants: HashMap::new(),
         }
     }
 
@@ -229,6 +309,39 @@ impl<'a> Inferer<'a> {
                             format!("right operand should be `{}`, but is `{}`", expected, got,);
                         (msg, span)
                     }
+                    Reason::ArrayElement(span) => {
+                        let msg =
+                            format!("array element should be `{}`, but is `{}`", expected, got,);
+                        (msg, span)
+                    }
+                    Reason::Pattern(span) => {
+                        let msg = format!("pattern should be `{}`, but is `{}`", expected, got,);
+                        (msg, span)
+                    }
+                    Reason::ElseBranch(span) => {
+                        let msg = format!("`else` branch should be `{}`, but is `{}`", expected, got);
+                        (msg, span)
+                    }
+                    Reason::RangeBound(span) => {
+                        let msg = format!("range bound should be `{}`, but is `{}`", expected, got);
+                        (msg, span)
+                    }
+                    Reason::MissingElse(span) => {
+                        let msg = format!("`if` without `else` should be `unit`, but is `{}`", got);
+                        (msg, span)
+                    }
+                    Reason::BreakValue(span) => {
+                        let msg = format!("`break` value should be `{}`, but is `{}`", expected, got);
+                        (msg, span)
+                    }
+                    Reason::Receiver(span) => {
+                        let msg = format!("receiver should be `{}`, but is `{}`", expected, got,);
+                        (msg, span)
+                    }
+                    Reason::Index(span) => {
+                        let msg = format!("index should be `{}`, but is `{}`", expected, got,);
+                        (msg, span)
+                    }
                     Reason::FunctionParam(mut index, span) => {
                         index += 1;
                         let suff = match (index % 100, index % 10) {
@@ -272,10 +385,23 @@ impl<'a> Inferer<'a> {
             r::Type::U16 => Type::Int(t::Signedness::Unsigned, t::Size::Bit16),
             r::Type::I32 => Type::Int(t::Signedness::Signed, t::Size::Bit32),
             r::Type::U32 => Type::Int(t::Signedness::Unsigned, t::Size::Bit32),
+            r::Type::I64 => Type::Int(t::Signedness::Signed, t::Size::Bit64),
+            r::Type::U64 => Type::Int(t::Signedness::Unsigned, t::Size::Bit64),
             r::Type::Pointer(ref typ) => {
                 let typ = self.convert_resolved_type(typ);
                 t::Type::Pointer(Rc::new(typ))
             }
+            r::Type::Array(ref typ, len) => {
+                let typ = self.convert_resolved_type(typ);
+                t::Type::Array(Rc::new(typ), len)
+            }
+            r::Type::Tuple(ref types) => {
+                let types = types
+                    .iter()
+                    .map(|t| self.convert_resolved_type(t))
+                    .collect::<Vec<_>>();
+                t::Type::Tuple(types.into())
+            }
             r::Type::Concrete(sym, ref params) => {
                 let params = params
                     .iter()
@@ -291,6 +417,14 @@ impl<'a> Inferer<'a> {
                 let out = self.convert_resolved_type(out);
                 t::Type::Function(params.into(), Rc::new(out))
             }
+            r::Type::Closure(ref params, ref out) => {
+                let params = params
+                    .iter()
+                    .map(|t| self.convert_resolved_type(t))
+                    .collect::<Vec<_>>();
+                let out = self.convert_resolved_type(out);
+                t::Type::Closure(params.into(), Rc::new(out))
+            }
             r::Type::Error => t::Type::Error,
         }
     }
@@ -299,11 +433,23 @@ impl<'a> Inferer<'a> {
         match *typ {
             Type::Unit => "unit".into(),
             Type::Bool => "a bool".into(),
-            Type::Concrete(sym, _) => format!("struct `{}`", self.ctx.symbols.get_name(sym)).into(),
+            Type::Concrete(sym, _) => {
+                let kind = if self.enums.contains(&sym) {
+                    "enum"
+                } else if self.structs.contains(&sym) {
+                    "struct"
+                } else {
+                    "type parameter"
+                };
+                format!("{} `{}`", kind, self.ctx.symbols.get_name(sym)).into()
+            }
             Type::Error => "error".into(),
             Type::Function(_, _) => "a function".into(),
+            Type::Closure(_, _) => "a closure".into(),
             Type::Int(_, _) => "an int".into(),
             Type::Pointer(_) => "a pointer".into(),
+            Type::Array(_, _) => "an array".into(),
+            Type::Tuple(_) => "a tuple".into(),
             Type::Var(_) => "type variable".into(),
         }
     }
@@ -335,12 +481,39 @@ impl<'a> Inferer<'a> {
                 let lhs = self.infer_expr(lhs);
                 let rhs = self.infer_expr(rhs);
                 let (param_type, out_type) = match *op {
+                    BinaryOp::Add |
+                    BinaryOp::Subtract |
+                    BinaryOp::AddAssign |
+                    BinaryOp::SubtractAssign if self.is_pointer(&lhs.typ) => {
+                        let typ = self.infer_pointer_arithmetic(&lhs, *op, &rhs);
+                        let typed = t::Expr::Binary(lhs, op, rhs);
+                        return t::TypedExpr {
+                            expr: Box::new(typed),
+                            typ,
+                            span: Spanned::span(expr),
+                        };
+                    }
                     BinaryOp::Equal | BinaryOp::NotEqual => (self.fresh_var(), Type::Bool),
                     BinaryOp::Add |
                     BinaryOp::Divide |
                     BinaryOp::Modulo |
                     BinaryOp::Multiply |
-                    BinaryOp::Subtract => {
+                    BinaryOp::Subtract |
+                    BinaryOp::BitAnd |
+                    BinaryOp::BitOr |
+                    BinaryOp::BitXor |
+                    BinaryOp::ShiftLeft |
+                    BinaryOp::ShiftRight |
+                    BinaryOp::AddAssign |
+                    BinaryOp::SubtractAssign |
+                    BinaryOp::MultiplyAssign |
+                    BinaryOp::DivideAssign |
+                    BinaryOp::ModuloAssign |
+                    BinaryOp::BitAndAssign |
+                    BinaryOp::BitOrAssign |
+                    BinaryOp::BitXorAssign |
+                    BinaryOp::ShiftLeftAssign |
+                    BinaryOp::ShiftRightAssign => {
                         let var = self.fresh_int_var();
                         (var.clone(), var)
                     }
@@ -366,64 +539,44 @@ impl<'a> Inferer<'a> {
             }
             r::Expr::Call(ref expr, ref params) => {
                 let expr = self.infer_expr(expr);
-                let expr_type = self.unifier.shallow_normalize(&expr.typ);
-                match expr_type {
-                    Type::Function(param_types, out_type) => if params.len() != param_types.len() {
-                        let end = if param_types.len() % 100 == 11 || param_types.len() % 10 != 1 {
-                            "s"
-                        } else {
-                            ""
-                        };
-                        let msg = format!(
-                            "function expects {} parameter{}, got {}",
-                            param_types.len(),
-                            end,
-                            params.len()
-                        );
-                        let short_msg = format!("expected {} parameter{}", param_types.len(), end);
-                        self.ctx
-                            .reporter
-                            .error(msg, expr.span)
-                            .span_note(short_msg, expr.span)
-                            .build();
-                        (t::Expr::Error, Type::Error)
-                    } else {
-                        let params = params
-                            .iter()
-                            .map(|p| self.infer_expr(p))
-                            .collect::<Vec<_>>();
-                        for i in 0..params.len() {
-                            let reason = Reason::FunctionParam(i, params[i].span);
-                            self.unify(&params[i].typ, &param_types[i], reason);
-                        }
-                        let expr = t::Expr::Call(expr, params);
-                        (expr, (*out_type).clone())
-                    },
-                    Type::Error => (t::Expr::Error, Type::Error),
-                    Type::Var(_) => {
-                        self.ctx
-                            .reporter
-                            .error("cannot infer the type before call", expr.span)
-                            .span(expr.span)
-                            .build();
-                        (t::Expr::Error, Type::Error)
-                    }
-                    typ => {
-                        let msg = format!("cannot call {}", self.type_name(&typ));
-                        self.ctx
-                            .reporter
-                            .error(msg, expr.span)
-                            .span(expr.span)
-                            .build();
-                        (t::Expr::Error, Type::Error)
-                    }
-                }
+                self.infer_call(expr, None, params)
+            }
+            r::Expr::MethodCall(ref expr, ref name, ref params) => {
+                let expr = self.infer_expr(expr);
+                self.infer_method_call(expr, name, params)
             }
             r::Expr::Error => (t::Expr::Error, t::Type::Error),
             r::Expr::Field(ref expr, ref field) => {
                 let expr = self.infer_expr(expr);
                 self.check_field(expr, field)
             }
+            r::Expr::Index(ref expr, ref index) => {
+                let expr = self.infer_expr(expr);
+                let index = self.infer_expr(index);
+                let index_type = Type::Int(t::Signedness::Unsigned, t::Size::Bit32);
+                self.unify(&index.typ, &index_type, Reason::Index(index.span));
+                self.check_index(expr, index)
+            }
+            r::Expr::Array(ref values) => {
+                let values = values
+                    .iter()
+                    .map(|v| self.infer_expr(v))
+                    .collect::<Vec<_>>();
+                let elem_type = self.fresh_var();
+                for value in &values {
+                    self.unify(&value.typ, &elem_type, Reason::ArrayElement(value.span));
+                }
+                let typ = Type::Array(Rc::new(elem_type), values.len() as u32);
+                (t::Expr::Array(values), typ)
+            }
+            r::Expr::Tuple(ref values) => {
+                let values = values
+                    .iter()
+                    .map(|v| self.infer_expr(v))
+                    .collect::<Vec<_>>();
+                let types = values.iter().map(|v| v.typ.clone()).collect::<Vec<_>>();
+                (t::Expr::Tuple(values), Type::Tuple(types.into()))
+            }
             r::Expr::Literal(ref literal) => {
                 let typ = self.infer_literal(literal);
                 let expr = t::Expr::Literal(literal.clone());
@@ -448,7 +601,7 @@ impl<'a> Inferer<'a> {
                         let var = self.fresh_var();
                         (Type::Pointer(Rc::new(var.clone())), var)
                     }
-                    UnaryOp::Minus | UnaryOp::Plus => {
+                    UnaryOp::Minus | UnaryOp::Plus | UnaryOp::BitNot => {
                         let var = self.fresh_int_var();
                         (var.clone(), var)
                     }
@@ -464,6 +617,84 @@ impl<'a> Inferer<'a> {
                 let cast_typ = Spanned::new(typ.clone(), typ_span);
                 (t::Expr::Cast(expr, cast_typ), typ)
             }
+            r::Expr::Lambda(ref params, ref body, ref captures) => {
+                let params = params
+                    .iter()
+                    .map(|param| {
+                        let typ = self.convert_resolved_type(&param.typ);
+                        let scheme = Scheme {
+                            vars: Vec::new(),
+                            typ: typ.clone(),
+                        };
+                        self.env.insert(Spanned::into_value(param.name), scheme);
+                        t::Var {
+                            name: Spanned::into_value(param.name),
+                            typ,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                // `return` inside the body returns from the lambda
+                let out_type = self.fresh_var();
+                let outer_return = self.return_type.replace(out_type.clone());
+                let body = self.infer_expr(body);
+                self.return_type = outer_return;
+                self.unify(&body.typ, &out_type, Reason::Return(body.span));
+                let captures = captures
+                    .iter()
+                    .map(|&name| t::Var {
+                        name,
+                        typ: self.env[&name].typ.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                let param_types = params
+                    .iter()
+                    .map(|p| p.typ.clone())
+                    .collect::<Vec<_>>()
+                    .into();
+                let out_type = Rc::new(body.typ.clone());
+                // only lambdas without captures can be used as functions
+                let typ = if captures.is_empty() {
+                    Type::Var(self.unifier.fresh_lambda_var(param_types, out_type))
+                } else {
+                    Type::Closure(param_types, out_type)
+                };
+                (t::Expr::Lambda(params, body, captures), typ)
+            }
+            r::Expr::If(ref cond, ref then, ref else_) => {
+                let cond = self.infer_expr(cond);
+                self.unify(&cond.typ, &Type::Bool, Reason::IfCondition(cond.span));
+                let then = self.infer_expr(then);
+                let else_ = else_.as_ref().map(|e| self.infer_expr(e));
+                let typ = match else_ {
+                    Some(ref else_) => {
+                        self.unify(&else_.typ, &then.typ, Reason::ElseBranch(else_.span))
+                    }
+                    None => self.unify(&then.typ, &Type::Unit, Reason::MissingElse(then.span)),
+                };
+                (t::Expr::If(cond, then, else_), typ)
+            }
+            r::Expr::Block(ref stmts, ref value) => {
+                let stmts = stmts
+                    .iter()
+                    .map(|s| Spanned::map_ref(s, |s| self.infer_statement(s)))
+                    .collect();
+                let value = value.as_ref().map(|e| self.infer_expr(e));
+                let block = t::Expr::Block(stmts, value);
+                let typ = match block {
+                    t::Expr::Block(_, Some(ref value)) => value.typ.clone(),
+                    // block that never finishes can be used as any
+                    // type, it becomes `unit` if nothing else is needed
+                    _ if block.diverges() => self.fresh_var(),
+                    _ => Type::Unit,
+                };
+                (block, typ)
+            }
+            r::Expr::Loop(sym, ref body) => {
+                let typ = self.fresh_var();
+                self.loops.insert(sym, typ.clone());
+                let body = Spanned::map_ref(body, |s| self.infer_statement(s));
+                (t::Expr::Loop(sym, Box::new(body)), typ)
+            }
         };
         t::TypedExpr {
             expr: Box::new(typed),
@@ -472,6 +703,301 @@ impl<'a> Inferer<'a> {
         }
     }
 
+    fn infer_call(
+        &mut self,
+        expr: t::TypedExpr,
+        receiver: Option<t::TypedExpr>,
+        params: &[Spanned<r::Expr>],
+    ) -> (t::Expr, Type) {
+        let expr_type = match self.unifier.shallow_normalize(&expr.typ) {
+            // calling a lambda does not decide if it is a function
+            Type::Var(var) => match self.unifier.lambda_signature(var) {
+                Some((params, out)) => Type::Function(params, out),
+                None => Type::Var(var),
+            },
+            typ => typ,
+        };
+        match expr_type {
+            Type::Function(param_types, out_type) | Type::Closure(param_types, out_type) => {
+                // receiver is not counted in error messages
+                let skipped = receiver.is_some() as usize;
+                if params.len() + skipped != param_types.len() {
+                    let expected = param_types.len() - skipped;
+                    let end = if expected % 100 == 11 || expected % 10 != 1 {
+                        "s"
+                    } else {
+                        ""
+                    };
+                    let msg = format!(
+                        "function expects {} parameter{}, got {}",
+                        expected,
+                        end,
+                        params.len()
+                    );
+                    let short_msg = format!("expected {} parameter{}", expected, end);
+                    self.ctx
+                        .reporter
+                        .error(msg, expr.span)
+                        .span_note(short_msg, expr.span)
+                        .build();
+                    (t::Expr::Error, Type::Error)
+                } else {
+                    let params = receiver
+                        .into_iter()
+                        .chain(params.iter().map(|p| self.infer_expr(p)))
+                        .collect::<Vec<_>>();
+                    for i in 0..params.len() {
+                        let reason = if i < skipped {
+                            Reason::Receiver(params[i].span)
+                        } else {
+                            Reason::FunctionParam(i - skipped, params[i].span)
+                        };
+                        self.unify(&params[i].typ, &param_types[i], reason);
+                    }
+                    let expr = t::Expr::Call(expr, params);
+                    (expr, (*out_type).clone())
+                }
+            }
+            Type::Error => (t::Expr::Error, Type::Error),
+            Type::Var(_) => {
+                self.ctx
+                    .reporter
+                    .error("cannot infer the type before call", expr.span)
+                    .span(expr.span)
+                    .build();
+                (t::Expr::Error, Type::Error)
+            }
+            typ => {
+                let msg = format!("cannot call {}", self.type_name(&typ));
+                self.ctx
+                    .reporter
+                    .error(msg, expr.span)
+                    .span(expr.span)
+                    .build();
+                (t::Expr::Error, Type::Error)
+            }
+        }
+    }
+
+    fn infer_method_call(
+        &mut self,
+        expr: t::TypedExpr,
+        name: &Spanned<String>,
+        params: &[Spanned<r::Expr>],
+    ) -> (t::Expr, Type) {
+        let expr_type = self.unifier.shallow_normalize(&expr.typ);
+        match expr_type {
+            Type::Concrete(sym, _) if self.fields.get(&sym).map(|f| f.contains_key(&**name))
+                == Some(true) =>
+            {
+                // function stored in a field
+                let span = expr.span.merge(Spanned::span(name));
+                let (field, typ) = self.check_field(expr, name);
+                let field = t::TypedExpr {
+                    expr: Box::new(field),
+                    typ,
+                    span,
+                };
+                self.infer_call(field, None, params)
+            }
+            Type::Error => (t::Expr::Error, Type::Error),
+            Type::Var(_) => {
+                self.ctx
+                    .reporter
+                    .error("cannot infer the type before method call", expr.span)
+                    .span(expr.span)
+                    .build();
+                (t::Expr::Error, Type::Error)
+            }
+            Type::Pointer(ref typ) => {
+                let span = expr.span;
+                let op = Spanned::new(t::UnaryOp::Deref, span);
+                let deref = t::TypedExpr {
+                    expr: Box::new(t::Expr::Unary(op, expr)),
+                    span,
+                    typ: (**typ).clone(),
+                };
+                self.infer_method_call(deref, name, params)
+            }
+            typ => match self.find_method(&typ, name) {
+                Ok(Some((method, type_params))) => {
+                    // type parameters of `impl` (or `Self` of trait) are
+                    // taken from the value, method's own ones are inferred
+                    let vars = self.env[&method].vars.len();
+                    let name_span = Spanned::span(name);
+                    let type_params = type_params
+                        .iter()
+                        .cloned()
+                        .chain((type_params.len()..vars).map(|_| self.fresh_var()))
+                        .map(|t| Spanned::new(t, name_span))
+                        .collect::<Vec<_>>();
+                    let typ = self.infer_symbol(method, &type_params);
+                    let takes_pointer = match typ {
+                        Type::Function(ref params, _) => matches!(params[0], Type::Pointer(_)),
+                        _ => unreachable!(),
+                    };
+                    let receiver = if takes_pointer {
+                        if !self.is_lvalue(&expr) {
+                            let msg = "cannot take the address of a temporary";
+                            self.ctx.reporter.error(msg, expr.span).span(expr.span).build();
+                            return (t::Expr::Error, Type::Error);
+                        }
+                        let span = expr.span;
+                        let op = Spanned::new(t::UnaryOp::AddressOf, span);
+                        t::TypedExpr {
+                            typ: Type::Pointer(Rc::new(expr.typ.clone())),
+                            expr: Box::new(t::Expr::Unary(op, expr)),
+                            span,
+                        }
+                    } else {
+                        expr
+                    };
+                    let callee = t::TypedExpr {
+                        expr: Box::new(t::Expr::Name(Spanned::new(method, name_span), type_params)),
+                        typ,
+                        span: name_span,
+                    };
+                    self.infer_call(callee, Some(receiver), params)
+                }
+                Ok(None) => {
+                    if let Type::Concrete(sym, _) = typ {
+                        if self.is_type_param(sym) {
+                            self.report_missing_bound(sym, name);
+                            return (t::Expr::Error, Type::Error);
+                        }
+                    }
+                    let msg = match typ {
+                        Type::Concrete(_, _) => format!(
+                            "{} does not have method `{}`",
+                            self.type_name(&typ),
+                            **name,
+                        ),
+                        _ => format!("no method `{}` on {}", **name, self.type_name(&typ)),
+                    };
+                    let span = match typ {
+                        Type::Concrete(_, _) => Spanned::span(name),
+                        _ => expr.span,
+                    };
+                    self.ctx.reporter.error(msg, span).span(span).build();
+                    (t::Expr::Error, Type::Error)
+                }
+                Err(()) => (t::Expr::Error, Type::Error),
+            },
+        }
+    }
+
+    fn is_type_param(&self, sym: Symbol) -> bool {
+        !self.structs.contains(&sym) && !self.enums.contains(&sym)
+    }
+
+    fn report_missing_bound(&mut self, param: Symbol, method: &Spanned<String>) {
+        let param = self.ctx.symbols.get_name(param).to_string();
+        let msg = format!("type parameter `{}` has no method `{}`", param, **method);
+        let mut traits = self.traits
+            .iter()
+            .filter(|&(_, methods)| methods.contains_key(&**method))
+            .map(|(&trait_, _)| self.ctx.symbols.get_name(trait_))
+            .collect::<Vec<_>>();
+        traits.sort();
+        let note = match traits.first() {
+            Some(trait_) => format!("consider adding a bound `{}: {}`", param, trait_),
+            None => format!(
+                "consider adding a bound with a trait that has method `{}`",
+                **method,
+            ),
+        };
+        let span = Spanned::span(method);
+        self.ctx.reporter.error(msg, span).span_note(note, span).build();
+    }
+
+    /// Finds method with given name for a type, and type parameters
+    /// that are determined by the type. Methods from `impl` block of
+    /// the type are preferred to methods of traits.
+    fn find_method(
+        &mut self,
+        typ: &Type,
+        name: &Spanned<String>,
+    ) -> Result<Option<(Symbol, Vec<Type>)>, ()> {
+        if let Type::Concrete(sym, ref params) = *typ {
+            if let Some(&method) = self.methods.get(&sym).and_then(|m| m.get(&**name)) {
+                return Ok(Some((method, params.to_vec())));
+            }
+        }
+        let traits = match *typ {
+            Type::Concrete(sym, _) if self.bounds.contains_key(&sym) => self.bounds[&sym].clone(),
+            _ => self.impls
+                .iter()
+                .filter(|i| i.matches(typ).is_some())
+                .map(|i| i.trait_)
+                .collect(),
+        };
+        let methods = traits
+            .iter()
+            .filter_map(|t| self.traits.get(t).and_then(|m| m.get(&**name)))
+            .cloned()
+            .collect::<Vec<_>>();
+        match methods.len() {
+            0 => Ok(None),
+            1 => Ok(Some((methods[0], vec![typ.clone()]))),
+            _ => {
+                let msg = format!(
+                    "method `{}` of {} is ambiguous, it is defined by multiple traits",
+                    **name,
+                    self.type_name(typ),
+                );
+                let span = Spanned::span(name);
+                self.ctx.reporter.error(msg, span).span(span).build();
+                Err(())
+            }
+        }
+    }
+
+    /// Checks that type parameters of a symbol satisfy their bounds.
+    fn check_bounds(&mut self, symbol: Symbol, params: &[Spanned<Type>]) {
+        let vars = match self.env.get(&symbol) {
+            Some(scheme) => scheme.vars.clone(),
+            None => return,
+        };
+        for (var, param) in vars.iter().zip(params) {
+            let traits = match self.bounds.get(var) {
+                Some(traits) => traits.clone(),
+                None => continue,
+            };
+            for trait_ in traits {
+                if !self.implements(param, trait_) {
+                    let msg = format!(
+                        "`{}` does not implement trait `{}`",
+                        self.format_type(param),
+                        self.ctx.symbols.get_name(trait_),
+                    );
+                    let span = Spanned::span(param);
+                    self.ctx.reporter.error(msg, span).span(span).build();
+                }
+            }
+        }
+    }
+
+    fn implements(&self, typ: &Type, trait_: Symbol) -> bool {
+        match *typ {
+            Type::Error => true,
+            Type::Concrete(sym, _) if self.bounds.contains_key(&sym) => {
+                self.bounds[&sym].contains(&trait_)
+            }
+            _ => self.impls.iter().filter(|i| i.trait_ == trait_).any(|i| {
+                // type parameters of implementation must satisfy their bounds too
+                match i.matches(typ) {
+                    Some(params) => i.type_params.iter().zip(&params).all(|(var, param)| {
+                        self.bounds
+                            .get(var)
+                            .map(|traits| traits.iter().all(|&t| self.implements(param, t)))
+                            .unwrap_or(true)
+                    }),
+                    None => false,
+                }
+            }),
+        }
+    }
+
     fn check_field(&mut self, expr: t::TypedExpr, field: &Spanned<String>) -> (t::Expr, Type) {
         let expr_type = self.unifier.shallow_normalize(&expr.typ);
         match expr_type {
@@ -499,6 +1025,27 @@ impl<'a> Inferer<'a> {
                     }
                 }
             }
+            Type::Tuple(ref types) => match field.parse::<usize>() {
+                Ok(index) if index < types.len() => {
+                    let typ = types[index].clone();
+                    let expr = t::Expr::Field(expr, Spanned::map_ref(field, |_| index));
+                    (expr, typ)
+                }
+                _ => {
+                    let msg = format!(
+                        "{} of {} elements does not have field `{}`",
+                        self.type_name(&expr_type),
+                        types.len(),
+                        **field,
+                    );
+                    self.ctx
+                        .reporter
+                        .error(msg, Spanned::span(field))
+                        .span(Spanned::span(field))
+                        .build();
+                    (t::Expr::Error, Type::Error)
+                }
+            },
             Type::Error => (t::Expr::Error, Type::Error),
             Type::Var(_) => {
                 self.ctx
@@ -530,6 +1077,87 @@ impl<'a> Inferer<'a> {
         }
     }
 
+    fn is_pointer(&self, typ: &Type) -> bool {
+        matches!(self.unifier.shallow_normalize(typ), Type::Pointer(_))
+    }
+
+    /// Whether expression refers to a place in memory, so
+    /// that its address can be taken.
+    fn is_lvalue(&self, expr: &t::TypedExpr) -> bool {
+        match *expr.expr {
+            t::Expr::Name(_, _) | t::Expr::Error => true,
+            t::Expr::Unary(ref op, _) => **op == t::UnaryOp::Deref,
+            t::Expr::Field(ref expr, _) => self.is_lvalue(expr),
+            t::Expr::Index(ref array, _) => self.is_pointer(&array.typ) || self.is_lvalue(array),
+            _ => false,
+        }
+    }
+
+    fn infer_pointer_arithmetic(
+        &mut self,
+        lhs: &t::TypedExpr,
+        op: BinaryOp,
+        rhs: &t::TypedExpr,
+    ) -> Type {
+        let lhs_type = self.unifier.shallow_normalize(&lhs.typ);
+        let rhs_type = self.unifier.shallow_normalize(&rhs.typ);
+        match (op, rhs_type) {
+            (BinaryOp::Subtract, Type::Pointer(_)) => {
+                // difference between two pointers, in elements
+                self.unify(&rhs.typ, &lhs_type, Reason::RightOperand(rhs.span));
+                Type::Int(t::Signedness::Signed, t::Size::Bit32)
+            }
+            _ => {
+                let offset_type = Type::Int(t::Signedness::Unsigned, t::Size::Bit32);
+                self.unify(&rhs.typ, &offset_type, Reason::RightOperand(rhs.span));
+                lhs_type
+            }
+        }
+    }
+
+    fn check_index(&mut self, expr: t::TypedExpr, index: t::TypedExpr) -> (t::Expr, Type) {
+        let expr_type = self.unifier.shallow_normalize(&expr.typ);
+        match expr_type {
+            Type::Array(ref typ, len) => {
+                if let t::Expr::Literal(r::Literal::Number(num)) = *index.expr {
+                    if num.value >= u64::from(len) {
+                        let msg = format!(
+                            "index out of bounds: the length is {} but the index is {}",
+                            len,
+                            num.value,
+                        );
+                        self.ctx
+                            .reporter
+                            .error(msg, index.span)
+                            .span(index.span)
+                            .build();
+                        return (t::Expr::Error, Type::Error);
+                    }
+                }
+                (t::Expr::Index(expr, index), (**typ).clone())
+            }
+            Type::Pointer(ref typ) => (t::Expr::Index(expr, index), (**typ).clone()),
+            Type::Error => (t::Expr::Error, Type::Error),
+            Type::Var(_) => {
+                self.ctx
+                    .reporter
+                    .error("cannot infer the type before indexing", expr.span)
+                    .span(expr.span)
+                    .build();
+                (t::Expr::Error, Type::Error)
+            }
+            typ => {
+                let msg = format!("cannot index {}", self.type_name(&typ));
+                self.ctx
+                    .reporter
+                    .error(msg, expr.span)
+                    .span(expr.span)
+                    .build();
+                (t::Expr::Error, Type::Error)
+            }
+        }
+    }
+
     fn infer_statement(&mut self, stmt: &r::Statement) -> t::Statement {
         match *stmt {
             r::Statement::Block(ref stmts) => t::Statement::Block(
@@ -538,8 +1166,15 @@ impl<'a> Inferer<'a> {
                     .map(|s| Spanned::map_ref(s, |s| self.infer_statement(s)))
                     .collect(),
             ),
-            r::Statement::Break => t::Statement::Break,
-            r::Statement::Continue => t::Statement::Continue,
+            r::Statement::Break(sym, ref value) => {
+                let value = value.as_ref().map(|value| self.infer_expr(value));
+                if let Some(ref value) = value {
+                    let expected = self.loops[&sym].clone();
+                    self.unify(&value.typ, &expected, Reason::BreakValue(value.span));
+                }
+                t::Statement::Break(sym, value)
+            }
+            r::Statement::Continue(sym) => t::Statement::Continue(sym),
             r::Statement::Expr(ref expr) => t::Statement::Expr(self.infer_expr(expr)),
             r::Statement::If(ref cond, ref then, ref else_) => {
                 let cond = self.infer_expr(cond);
@@ -552,13 +1187,17 @@ impl<'a> Inferer<'a> {
             }
             r::Statement::Let(sym, ref typ, ref value) => {
                 let value = value.as_ref().map(|value| self.infer_expr(value));
-                let ty = self.convert_resolved_type(typ);
+                let mut ty = self.convert_resolved_type(typ);
                 if let Some(ref value) = value {
                     let reason = Reason::Assign(value.span);
                     let scheme = Scheme {
                         vars: Vec::new(),
                         typ: self.unify(&value.typ, &ty, reason),
                     };
+                    // don't report wildcards in the type again
+                    if let Type::Error = scheme.typ {
+                        ty = Type::Error;
+                    }
                     self.env.insert(Spanned::into_value(sym), scheme);
                 } else {
                     let scheme = Scheme {
@@ -570,9 +1209,11 @@ impl<'a> Inferer<'a> {
                 let typ = Spanned::new(ty, Spanned::span(typ));
                 t::Statement::Let(sym, typ, value)
             }
-            r::Statement::Loop(ref stmt) => {
+            r::Statement::Loop(sym, ref stmt) => {
+                let typ = self.fresh_var();
+                self.loops.insert(sym, typ);
                 let s = Spanned::map_ref(stmt, |s| self.infer_statement(s));
-                t::Statement::Loop(Box::new(s))
+                t::Statement::Loop(sym, Box::new(s))
             }
             r::Statement::Return(ref expr) => {
                 let expr = self.infer_expr(expr);
@@ -580,16 +1221,141 @@ impl<'a> Inferer<'a> {
                 self.unify(&expr.typ, &expected, Reason::Return(expr.span));
                 t::Statement::Return(expr)
             }
-            r::Statement::While(ref cond, ref body) => {
+            r::Statement::While(label, ref cond, ref body) => {
                 let cond = self.infer_expr(cond);
                 let body = Spanned::map_ref(body, |s| self.infer_statement(s));
                 self.unify(&cond.typ, &Type::Bool, Reason::WhileCondition(cond.span));
-                t::Statement::While(cond, Box::new(body))
+                t::Statement::While(label, cond, Box::new(body))
+            }
+            r::Statement::For(label, sym, ref start, ref end, inclusive, ref body) => {
+                let start = self.infer_expr(start);
+                let end = self.infer_expr(end);
+                let typ = self.fresh_int_var();
+                let typ = self.unify(&start.typ, &typ, Reason::RangeBound(start.span));
+                let typ = self.unify(&end.typ, &typ, Reason::RangeBound(end.span));
+                let scheme = Scheme {
+                    vars: Vec::new(),
+                    typ,
+                };
+                self.env.insert(Spanned::into_value(sym), scheme);
+                let body = Spanned::map_ref(body, |s| self.infer_statement(s));
+                t::Statement::For(label, sym, start, end, inclusive, Box::new(body))
+            }
+            r::Statement::Match(ref value, ref arms) => {
+                let value = self.infer_expr(value);
+                let arms = arms.iter()
+                    .map(|arm| {
+                        let pattern = self.infer_pattern(&arm.pattern, &value.typ);
+                        let body = Spanned::map_ref(&arm.body, |s| self.infer_statement(s));
+                        t::MatchArm { pattern, body }
+                    })
+                    .collect();
+                t::Statement::Match(value, arms)
             }
             r::Statement::Error => t::Statement::Error,
         }
     }
 
+    fn infer_pattern(&mut self, pattern: &Spanned<r::Pattern>, expected: &Type) -> t::TypedPattern {
+        let span = Spanned::span(pattern);
+        let (typed, typ) = match **pattern {
+            r::Pattern::Wildcard => (t::Pattern::Wildcard, expected.clone()),
+            r::Pattern::Binding(sym) => {
+                let scheme = Scheme {
+                    vars: Vec::new(),
+                    typ: expected.clone(),
+                };
+                self.env.insert(Spanned::into_value(sym), scheme);
+                (t::Pattern::Binding(Spanned::into_value(sym)), expected.clone())
+            }
+            r::Pattern::Variant(sym, ref patterns) => {
+                let sym = Spanned::into_value(sym);
+                let params = (0..self.env[&sym].vars.len())
+                    .map(|_| self.fresh_var())
+                    .collect::<Vec<_>>();
+                let typ = self.env[&sym].instantiate(&params);
+                let index = self.variants.get(&sym).cloned();
+                match (typ, index) {
+                    (Type::Function(ref fields, ref out), Some(index)) => {
+                        let typ = self.unify(out, expected, Reason::Pattern(span));
+                        if fields.len() == patterns.len() {
+                            let patterns = patterns
+                                .iter()
+                                .zip(fields.iter())
+                                .map(|(p, typ)| self.infer_pattern(p, typ))
+                                .collect();
+                            if let Type::Error = typ {
+                                (t::Pattern::Error, Type::Error)
+                            } else {
+                                (t::Pattern::Variant(index, patterns), typ)
+                            }
+                        } else {
+                            let end = if fields.len() % 100 == 11 || fields.len() % 10 != 1 {
+                                "s"
+                            } else {
+                                ""
+                            };
+                            let msg = format!(
+                                "variant `{}` has {} field{}, but pattern has {}",
+                                self.ctx.symbols.get_name(sym),
+                                fields.len(),
+                                end,
+                                patterns.len(),
+                            );
+                            self.ctx.reporter.error(msg, span).span(span).build();
+                            self.bind_error_patterns(patterns);
+                            (t::Pattern::Error, Type::Error)
+                        }
+                    }
+                    _ => {
+                        self.bind_error_patterns(patterns);
+                        (t::Pattern::Error, Type::Error)
+                    }
+                }
+            }
+            r::Pattern::Literal(negated, ref literal) => {
+                let typ = self.infer_literal(literal);
+                let typ = self.unify(&typ, expected, Reason::Pattern(span));
+                let value = match *literal {
+                    r::Literal::Number(num) => i128::from(num.value),
+                    r::Literal::Char(ch) => i128::from(ch),
+                    _ => panic!("invalid literal pattern"),
+                };
+                let value = if negated { -value } else { value };
+                if let Type::Error = typ {
+                    (t::Pattern::Error, Type::Error)
+                } else {
+                    (t::Pattern::Int(value), typ)
+                }
+            }
+            r::Pattern::Range(ref start, ref end) => {
+                let start = self.infer_pattern(start, expected);
+                let end = self.infer_pattern(end, expected);
+                match (start.pattern, end.pattern) {
+                    (t::Pattern::Int(a), t::Pattern::Int(b)) => {
+                        let a = Spanned::new(a, start.span);
+                        let b = Spanned::new(b, end.span);
+                        (t::Pattern::Range(a, b), start.typ)
+                    }
+                    _ => (t::Pattern::Error, Type::Error),
+                }
+            }
+            r::Pattern::Error => (t::Pattern::Error, Type::Error),
+        };
+        t::TypedPattern {
+            pattern: typed,
+            span,
+            typ,
+        }
+    }
+
+    fn bind_error_patterns(&mut self, patterns: &[Spanned<r::Pattern>]) {
+        // bindings still have to get a type, even if it is an error
+        for pattern in patterns {
+            self.infer_pattern(pattern, &Type::Error);
+        }
+    }
+
     fn infer_function(&mut self, function: &r::Function) -> t::Function {
         let mut params = Vec::new();
         for param in &function.params {
@@ -633,20 +1399,34 @@ impl<'a> Inferer<'a> {
                     self.normalize_statement(stmt);
                 }
             }
-            t::Statement::Break | t::Statement::Continue | t::Statement::Error => {}
-            t::Statement::Return(ref mut expr) | t::Statement::Expr(ref mut expr) => {
+            t::Statement::Break(_, None) | t::Statement::Continue(_) | t::Statement::Error => {}
+            t::Statement::Break(_, Some(ref mut expr)) |
+            t::Statement::Return(ref mut expr) |
+            t::Statement::Expr(ref mut expr) => {
                 self.normalize_expr(expr);
             }
             t::Statement::Block(ref mut stmts) => for stmt in stmts {
                 self.normalize_statement(stmt);
             },
-            t::Statement::Loop(ref mut stmt) => {
+            t::Statement::Loop(_, ref mut stmt) => {
                 self.normalize_statement(stmt);
             }
-            t::Statement::While(ref mut cond, ref mut stmt) => {
+            t::Statement::While(_, ref mut cond, ref mut stmt) => {
                 self.normalize_expr(cond);
                 self.normalize_statement(stmt);
             }
+            t::Statement::For(_, _, ref mut start, ref mut end, _, ref mut stmt) => {
+                self.normalize_expr(start);
+                self.normalize_expr(end);
+                self.normalize_statement(stmt);
+            }
+            t::Statement::Match(ref mut value, ref mut arms) => {
+                self.normalize_expr(value);
+                for arm in arms {
+                    self.normalize_pattern(&mut arm.pattern);
+                    self.normalize_statement(&mut arm.body);
+                }
+            }
             t::Statement::Let(_, ref mut typ, ref mut value) => {
                 if let Some(ref mut value) = *value {
                     self.normalize_expr(value);
@@ -667,12 +1447,42 @@ impl<'a> Inferer<'a> {
         }
     }
 
+    fn normalize_pattern(&mut self, pattern: &mut t::TypedPattern) {
+        match pattern.pattern {
+            t::Pattern::Variant(_, ref mut patterns) => for pattern in patterns {
+                self.normalize_pattern(pattern);
+            },
+            t::Pattern::Error => {
+                pattern.typ = Type::Error;
+                return;
+            }
+            t::Pattern::Wildcard |
+            t::Pattern::Binding(_) |
+            t::Pattern::Int(_) |
+            t::Pattern::Range(_, _) => {}
+        }
+        match self.unifier.normalize(&pattern.typ) {
+            Ok(typ) => pattern.typ = typ,
+            Err(()) => {
+                pattern.typ = t::Type::Error;
+                self.ctx
+                    .reporter
+                    .error("could not completely infer type", pattern.span)
+                    .span(pattern.span)
+                    .build();
+            }
+        }
+    }
+
     fn normalize_expr(&mut self, expr: &mut t::TypedExpr) {
         match *expr.expr.as_mut() {
-            t::Expr::Binary(ref mut lhs, _, ref mut rhs) => {
+            t::Expr::Binary(ref mut lhs, _, ref mut rhs) | t::Expr::Index(ref mut lhs, ref mut rhs) => {
                 self.normalize_expr(lhs);
                 self.normalize_expr(rhs);
             }
+            t::Expr::Array(ref mut values) | t::Expr::Tuple(ref mut values) => for value in values {
+                self.normalize_expr(value);
+            },
             t::Expr::Field(ref mut expr, _) | t::Expr::Unary(_, ref mut expr) => {
                 self.normalize_expr(expr);
             }
@@ -687,20 +1497,66 @@ impl<'a> Inferer<'a> {
                 return;
             }
             t::Expr::Literal(_) => {}
-            t::Expr::Name(_, ref mut params) => for param in params {
-                match self.unifier.normalize(param) {
-                    Ok(t) => **param = t,
-                    Err(()) => {
-                        let span = Spanned::span(param);
-                        self.ctx
-                            .reporter
-                            .error("could not completely infer type", span)
-                            .span(span)
-                            .build();
-                        **param = Type::Error;
+            t::Expr::Lambda(ref mut params, ref mut body, ref mut captures) => {
+                for var in params.iter_mut().chain(captures) {
+                    match self.unifier.normalize(&var.typ) {
+                        Ok(t) => var.typ = t,
+                        Err(()) => {
+                            let name = self.ctx.symbols.get_name(var.name);
+                            let msg = format!("could not infer type of `{}`", name);
+                            self.ctx
+                                .reporter
+                                .error(msg, expr.span)
+                                .span(expr.span)
+                                .build();
+                            var.typ = Type::Error;
+                        }
                     }
                 }
-            },
+                self.normalize_expr(body);
+            }
+            t::Expr::Closure(_, _) => panic!("closure before lowering lambdas"),
+            t::Expr::If(ref mut cond, ref mut then, ref mut else_) => {
+                self.normalize_expr(cond);
+                self.normalize_expr(then);
+                if let Some(ref mut else_) = *else_ {
+                    self.normalize_expr(else_);
+                }
+            }
+            t::Expr::Block(ref mut stmts, ref mut value) => {
+                for stmt in stmts.iter_mut() {
+                    self.normalize_statement(stmt);
+                }
+                match *value {
+                    Some(ref mut value) => self.normalize_expr(value),
+                    None => {
+                        let _ = self.unifier.unify(&expr.typ, &Type::Unit);
+                    }
+                }
+            }
+            t::Expr::Loop(_, ref mut body) => {
+                self.normalize_statement(body);
+                // loop without `break` never finishes, so it
+                // can be any type, default to `unit`
+                let _ = self.unifier.unify(&expr.typ, &Type::Unit);
+            }
+            t::Expr::Name(name, ref mut params) => {
+                for param in params.iter_mut() {
+                    match self.unifier.normalize(param) {
+                        Ok(t) => **param = t,
+                        Err(()) => {
+                            let span = Spanned::span(param);
+                            self.ctx
+                                .reporter
+                                .error("could not completely infer type", span)
+                                .span(span)
+                                .build();
+                            **param = Type::Error;
+                        }
+                    }
+                }
+                self.check_bounds(*name, params);
+            }
             t::Expr::Cast(ref mut expr, ref mut typ) => {
                 self.normalize_expr(expr);
                 match self.unifier.normalize(typ) {
@@ -736,13 +1592,196 @@ impl<'a> Inferer<'a> {
         for (&name, s) in &program.structs {
             structs.insert(name, self.convert_struct(s));
         }
+        let mut enums = HashMap::new();
+        for (&name, e) in &program.enums {
+            enums.insert(name, self.convert_enum(e));
+        }
         for f in &program.functions {
             self.add_function_to_env(f);
         }
+        self.methods = program.methods.clone();
+        self.traits = program.traits.clone();
+        self.bounds = program.bounds.clone();
+        self.impls = self.check_impls(&program.impls);
+        for s in &program.statics {
+            let typ = self.convert_resolved_type(&s.typ);
+            let scheme = Scheme {
+                vars: Vec::new(),
+                typ,
+            };
+            self.env.insert(Spanned::into_value(s.name), scheme);
+        }
+        for c in &program.consts {
+            let typ = self.convert_resolved_type(&c.typ);
+            let scheme = Scheme {
+                vars: Vec::new(),
+                typ,
+            };
+            self.env.insert(Spanned::into_value(c.name), scheme);
+        }
+        let statics = program
+            .statics
+            .iter()
+            .map(|s| self.infer_static(s))
+            .collect();
+        let consts = program
+            .consts
+            .iter()
+            .map(|c| self.infer_const(c))
+            .collect();
         for f in &program.functions {
             functions.push(self.infer_function(f));
         }
-        t::Program { structs, functions }
+        t::Program {
+            structs,
+            enums,
+            impls: self.impls.clone(),
+            functions,
+            statics,
+            consts,
+        }
+    }
+
+    /// Checks that implementations do not overlap, and that their
+    /// functions have same types as methods of the trait.
+    fn check_impls(&mut self, impls: &[r::Impl]) -> Vec<t::Impl> {
+        let mut checked: Vec<(t::Impl, Span)> = Vec::new();
+        for impl_ in impls {
+            let converted = t::Impl {
+                trait_: *impl_.trait_,
+                type_params: impl_.type_params.iter().map(|p| **p).collect(),
+                typ: self.convert_resolved_type(&impl_.typ),
+                functions: impl_.functions.iter().map(|(&m, f)| (m, **f)).collect(),
+            };
+            let span = Spanned::span(&impl_.typ);
+            let conflict = checked
+                .iter()
+                .find(|c| c.0.trait_ == converted.trait_ && c.0.matches(&converted.typ).is_some())
+                .map(|c| c.1);
+            if let Some(prev_span) = conflict {
+                let msg = format!(
+                    "conflicting implementations of trait `{}` for `{}`",
+                    self.ctx.symbols.get_name(converted.trait_),
+                    self.format_type(&converted.typ),
+                );
+                self.ctx
+                    .reporter
+                    .error(msg, span)
+                    .span_note("conflicting implementation", span)
+                    .span_note("first implementation is here", prev_span)
+                    .build();
+                continue;
+            }
+            for (&method, function) in &impl_.functions {
+                self.check_impl_function(&converted, method, function);
+            }
+            checked.push((converted, span));
+        }
+        checked.into_iter().map(|(i, _)| i).collect()
+    }
+
+    fn check_impl_function(&mut self, impl_: &t::Impl, method: Symbol, function: &Spanned<Symbol>) {
+        let expected = self.env[&method].clone();
+        let actual = self.env[&**function].clone();
+        let impl_params = impl_.type_params.len();
+        let span = Spanned::span(function);
+        let name = self.ctx.symbols.get_name(method);
+        // symbol name is prefixed with the trait
+        let name = name.rsplit("::").next().unwrap().to_string();
+        let trait_ = self.ctx.symbols.get_name(impl_.trait_).to_string();
+        // method's own type parameters go after `Self` in trait, and
+        // after type parameters of `impl` in implementing function
+        if expected.vars.len() - 1 != actual.vars.len() - impl_params {
+            let msg = format!(
+                "method `{}` has {} type parameters, but its declaration in trait `{}` has {}",
+                name,
+                actual.vars.len() - impl_params,
+                trait_,
+                expected.vars.len() - 1,
+            );
+            self.ctx.reporter.error(msg, span).span(span).build();
+            return;
+        }
+        let own_params = actual.vars[impl_params..]
+            .iter()
+            .map(|&p| Type::Concrete(p, Vec::new().into()))
+            .collect::<Vec<_>>();
+        let impl_type = Scheme {
+            vars: impl_.type_params.clone(),
+            typ: impl_.typ.clone(),
+        };
+        let self_type = impl_type.instantiate(&actual.vars[..impl_params]
+            .iter()
+            .map(|&p| Type::Concrete(p, Vec::new().into()))
+            .collect::<Vec<_>>());
+        let params = Some(self_type)
+            .into_iter()
+            .chain(own_params)
+            .collect::<Vec<_>>();
+        let expected_type = expected.instantiate(&params);
+        if self.unifier.unify(&actual.typ, &expected_type).is_err() {
+            let msg = format!(
+                "method `{}` has an incompatible type for trait `{}`",
+                name,
+                trait_,
+            );
+            let short_msg = format!("expected `{}`", self.format_type(&expected_type));
+            self.ctx
+                .reporter
+                .error(msg, span)
+                .span_note(short_msg, span)
+                .build();
+            return;
+        }
+        let own_vars = expected.vars[1..].iter().zip(&actual.vars[impl_params..]);
+        for (expected_var, actual_var) in own_vars {
+            let mut expected_bounds = self.bounds.get(expected_var).cloned().unwrap_or_default();
+            let mut actual_bounds = self.bounds.get(actual_var).cloned().unwrap_or_default();
+            expected_bounds.sort();
+            actual_bounds.sort();
+            if expected_bounds != actual_bounds {
+                let msg = format!(
+                    "type parameter `{}` of method `{}` must have same bounds as in trait `{}`",
+                    self.ctx.symbols.get_name(*actual_var),
+                    name,
+                    trait_,
+                );
+                self.ctx.reporter.error(msg, span).span(span).build();
+            }
+        }
+    }
+
+    fn infer_const(&mut self, c: &r::Const) -> t::Const {
+        let (typ, value) = self.infer_item_value(c.name, &c.value);
+        t::Const {
+            name: Spanned::into_value(c.name),
+            typ,
+            value,
+        }
+    }
+
+    fn infer_static(&mut self, s: &r::Static) -> t::Static {
+        let (typ, value) = self.infer_item_value(s.name, &s.value);
+        t::Static {
+            mutable: s.mutable,
+            name: Spanned::into_value(s.name),
+            typ,
+            value,
+        }
+    }
+
+    fn infer_item_value(
+        &mut self,
+        name: Spanned<Symbol>,
+        value: &Spanned<r::Expr>,
+    ) -> (Type, t::TypedExpr) {
+        let typ = self.env[&Spanned::into_value(name)].typ.clone();
+        let mut value = self.infer_expr(value);
+        self.unify(&value.typ, &typ, Reason::Assign(value.span));
+        self.normalize_expr(&mut value);
+        // wildcards in item types are already reported
+        let typ = self.unifier.normalize(&typ).unwrap_or(Type::Error);
+        (typ, value)
     }
 
     fn add_function_to_env(&mut self, f: &r::Function) {
@@ -757,7 +1796,49 @@ impl<'a> Inferer<'a> {
         self.env.insert(Spanned::into_value(f.name.name), scheme);
     }
 
+    fn convert_enum(&mut self, e: &r::Enum) -> t::Enum {
+        let name = Spanned::into_value(e.name.name);
+        let vars = e.name.type_params.iter().map(|p| **p).collect::<Vec<_>>();
+        let enum_type = Type::Concrete(
+            name,
+            vars.iter()
+                .map(|&p| Type::Concrete(p, Vec::new().into()))
+                .collect::<Vec<_>>()
+                .into(),
+        );
+        self.enums.insert(name);
+        let variants = e.variants
+            .iter()
+            .enumerate()
+            .map(|(index, v)| {
+                let variant_name = Spanned::into_value(v.name);
+                let fields = v.fields
+                    .iter()
+                    .map(|f| self.convert_resolved_type(f))
+                    .collect::<Vec<_>>();
+                let typ = Type::Function(fields.clone().into(), Rc::new(enum_type.clone()));
+                let scheme = Scheme {
+                    vars: vars.clone(),
+                    typ,
+                };
+                self.env.insert(variant_name, scheme);
+                self.variants.insert(variant_name, index);
+                t::Variant {
+                    name: variant_name,
+                    fields,
+                }
+            })
+            .collect();
+        t::Enum {
+            complete_span: e.complete_span,
+            name,
+            type_params: vars,
+            variants,
+        }
+    }
+
     fn convert_struct(&mut self, s: &r::Struct) -> t::Struct {
+        self.structs.insert(Spanned::into_value(s.name.name));
         let param_types = s.fields
             .iter()
             .map(|f| self.convert_resolved_type(&f.typ))
@@ -8,6 +8,9 @@ enum VarTarget {
     Type(Type),
     UnsizedInt(Signedness),
     Int,
+    /// Lambda that does not capture anything, it can
+    /// become either a function or a closure.
+    Lambda(Rc<[Type]>, Rc<Type>),
 }
 
 pub struct UnifyTable {
@@ -40,6 +43,23 @@ impl UnifyTable {
         var
     }
 
+    pub fn fresh_lambda_var(&mut self, params: Rc<[Type]>, out: Rc<Type>) -> TypeVar {
+        let var = TypeVar(self.next_var);
+        self.var_target.insert(var, VarTarget::Lambda(params, out));
+        self.var_target.commit();
+        self.next_var += 1;
+        var
+    }
+
+    /// Returns parameter and return types if given
+    /// var is a lambda that is not yet resolved.
+    pub fn lambda_signature(&self, var: TypeVar) -> Option<(Rc<[Type]>, Rc<Type>)> {
+        match self.var_target.get(&var) {
+            Some(VarTarget::Lambda(params, out)) => Some((params.clone(), out.clone())),
+            _ => None,
+        }
+    }
+
     pub fn unify(&mut self, a: &Type, b: &Type) -> Result<Type, ()> {
         match self.unify_raw(a, b) {
             Ok(()) => {
@@ -66,15 +86,9 @@ impl UnifyTable {
             } else {
                 Err(())
             },
-            (Type::Function(ref ap, ref a), Type::Function(ref bp, ref b)) => {
-                if ap.len() == bp.len() {
-                    for (a, b) in ap.iter().zip(bp.iter()) {
-                        self.unify_raw(a, b)?;
-                    }
-                    self.unify_raw(a, b)
-                } else {
-                    Err(())
-                }
+            (Type::Function(ref ap, ref a), Type::Function(ref bp, ref b)) |
+            (Type::Closure(ref ap, ref a), Type::Closure(ref bp, ref b)) => {
+                self.unify_signatures((ap, a), (bp, b))
             }
             (Type::Int(sign1, size1), Type::Int(sign2, size2)) => {
                 if sign1 == sign2 && size1 == size2 {
@@ -84,6 +98,19 @@ impl UnifyTable {
                 }
             }
             (Type::Pointer(ref a), Type::Pointer(ref b)) => self.unify_raw(a, b),
+            (Type::Array(ref a, alen), Type::Array(ref b, blen)) => if alen == blen {
+                self.unify_raw(a, b)
+            } else {
+                Err(())
+            },
+            (Type::Tuple(ref a), Type::Tuple(ref b)) => if a.len() == b.len() {
+                for (a, b) in a.iter().zip(b.iter()) {
+                    self.unify_raw(a, b)?;
+                }
+                Ok(())
+            } else {
+                Err(())
+            },
             (Type::Var(a), ty) | (ty, Type::Var(a)) => self.unify_var_type(a, ty),
             (Type::Bool, Type::Bool) |
             (Type::Unit, Type::Unit) |
@@ -92,6 +119,21 @@ impl UnifyTable {
         }
     }
 
+    fn unify_signatures(
+        &mut self,
+        (ap, a): (&[Type], &Type),
+        (bp, b): (&[Type], &Type),
+    ) -> Result<(), ()> {
+        if ap.len() == bp.len() {
+            for (a, b) in ap.iter().zip(bp.iter()) {
+                self.unify_raw(a, b)?;
+            }
+            self.unify_raw(a, b)
+        } else {
+            Err(())
+        }
+    }
+
     fn unify_var_var(&mut self, a: TypeVar, b: TypeVar) -> Result<(), ()> {
         let at = self.var_target.get(&a).cloned();
         let bt = self.var_target.get(&b).cloned();
@@ -99,6 +141,14 @@ impl UnifyTable {
             (Some(VarTarget::Type(_)), _) | (_, Some(VarTarget::Type(_))) => {
                 panic!("cannot unify non-normalized var")
             }
+            (Some(VarTarget::Lambda(ap, a_out)), Some(VarTarget::Lambda(bp, b_out))) => {
+                self.unify_signatures((&ap, &a_out), (&bp, &b_out))?;
+                self.var_target.insert(a, VarTarget::Type(Type::Var(b)));
+                Ok(())
+            }
+            (Some(VarTarget::Lambda(_, _)), Some(_)) | (Some(_), Some(VarTarget::Lambda(_, _))) => {
+                Err(())
+            }
             (None, _) | (Some(VarTarget::Int), Some(_)) => {
                 self.var_target.insert(a, VarTarget::Type(Type::Var(b)));
                 Ok(())
@@ -143,6 +193,17 @@ impl UnifyTable {
             } else {
                 Err(())
             },
+            (Some(VarTarget::Lambda(params, out)), ty @ Type::Function(_, _)) |
+            (Some(VarTarget::Lambda(params, out)), ty @ Type::Closure(_, _)) => {
+                match ty {
+                    Type::Function(ref bp, ref b) | Type::Closure(ref bp, ref b) => {
+                        self.unify_signatures((&params, &out), (bp, b))?
+                    }
+                    _ => unreachable!(),
+                }
+                self.var_target.insert(v, VarTarget::Type(ty));
+                Ok(())
+            }
             _ => Err(()),
         }
     }
@@ -151,7 +212,7 @@ impl UnifyTable {
         let typ = self.shallow_normalize(typ);
         match typ {
             Type::Bool | Type::Error | Type::Int(_, _) | Type::Unit => false,
-            Type::Concrete(_, ref params) => {
+            Type::Concrete(_, ref params) | Type::Tuple(ref params) => {
                 for param in params.iter() {
                     if self.occurs(var, param) {
                         return true;
@@ -159,7 +220,7 @@ impl UnifyTable {
                 }
                 false
             }
-            Type::Function(ref params, ref out) => {
+            Type::Function(ref params, ref out) | Type::Closure(ref params, ref out) => {
                 for param in params.iter() {
                     if self.occurs(var, param) {
                         return true;
@@ -167,15 +228,28 @@ impl UnifyTable {
                 }
                 self.occurs(var, out)
             }
-            Type::Pointer(ref to) => self.occurs(var, to),
-            Type::Var(v) => var == v,
+            Type::Pointer(ref to) | Type::Array(ref to, _) => self.occurs(var, to),
+            Type::Var(v) => {
+                if var == v {
+                    return true;
+                }
+                match self.lambda_signature(v) {
+                    Some((params, out)) => {
+                        params.iter().any(|p| self.occurs(var, p)) || self.occurs(var, &out)
+                    }
+                    None => false,
+                }
+            }
         }
     }
 
     fn get_var_type(&self, var: TypeVar) -> Type {
         match self.var_target.get(&var) {
             Some(&VarTarget::Type(ref ty)) => self.shallow_normalize(ty),
-            Some(&VarTarget::Int) | Some(&VarTarget::UnsizedInt(_)) | None => Type::Var(var),
+            Some(&VarTarget::Int) |
+            Some(&VarTarget::UnsizedInt(_)) |
+            Some(&VarTarget::Lambda(_, _)) |
+            None => Type::Var(var),
         }
     }
 
@@ -206,11 +280,30 @@ impl UnifyTable {
                 }
                 Ok(Type::Function(normalized.into(), Rc::new(out)))
             }
+            Type::Closure(ref params, ref out) => {
+                let out = self.normalize(out)?;
+                let mut normalized = Vec::new();
+                for param in &**params {
+                    normalized.push(self.normalize(param)?);
+                }
+                Ok(Type::Closure(normalized.into(), Rc::new(out)))
+            }
             ty @ Type::Int(_, _) => Ok(ty),
             Type::Pointer(ref ty) => {
                 let ty = self.normalize(ty)?;
                 Ok(Type::Pointer(Rc::new(ty)))
             }
+            Type::Array(ref ty, len) => {
+                let ty = self.normalize(ty)?;
+                Ok(Type::Array(Rc::new(ty), len))
+            }
+            Type::Tuple(ref types) => {
+                let mut normalized = Vec::new();
+                for typ in &**types {
+                    normalized.push(self.normalize(typ)?);
+                }
+                Ok(Type::Tuple(normalized.into()))
+            }
             Type::Var(var) => {
                 match self.var_target.get(&var) {
                     Some(&VarTarget::Type(_)) => panic!("var not normalized"),
@@ -220,6 +313,13 @@ impl UnifyTable {
                     Some(&VarTarget::UnsizedInt(sign)) => {
                         return Ok(Type::Int(sign, Size::Bit32));
                     }
+                    Some(VarTarget::Lambda(params, out)) => {
+                        // lambdas without captures default to functions
+                        let typ = Type::Function(params.clone(), out.clone());
+                        self.var_target.insert(var, VarTarget::Type(typ));
+                        self.var_target.commit();
+                        return self.normalize(&Type::Var(var));
+                    }
                     None => {
                         // there is no sensible default
                         // fall though because of borrowck
@@ -1,6 +1,7 @@
-use std::collections::HashMap;
-use plank_syntax::position::Spanned;
-use ast::resolved::{Expr, Function, FunctionType, Program, Statement, Struct, Symbol, Type};
+use std::collections::{HashMap, HashSet};
+use plank_syntax::position::{Span, Spanned};
+use ast::resolved::{Const, Enum, Expr, Function, FunctionType, Program, Statement, Static,
+                    Struct, Symbol, Type};
 use CompileCtx;
 
 
@@ -12,6 +13,9 @@ pub(crate) fn check_type_params(program: &mut Program, ctx: &mut CompileCtx) {
 struct Context<'a> {
     ctx: &'a mut CompileCtx,
     param_count: HashMap<Symbol, usize>,
+    /// Types from alias bodies are checked for every use of the
+    /// alias, so errors are reported only once for each span.
+    reported: HashSet<Span>,
 }
 
 impl<'a> Context<'a> {
@@ -19,6 +23,7 @@ impl<'a> Context<'a> {
         Context {
             ctx,
             param_count: HashMap::new(),
+            reported: HashSet::new(),
         }
     }
 
@@ -34,6 +39,24 @@ impl<'a> Context<'a> {
             );
         }
 
+        for enum_ in program.enums.values() {
+            let params = enum_.name.type_params.len();
+            self.param_count
+                .insert(Spanned::into_value(enum_.name.name), params);
+            // variant constructors take same type parameters as enum
+            for variant in &enum_.variants {
+                self.param_count
+                    .insert(Spanned::into_value(variant.name), params);
+            }
+        }
+
+        for alias in program.type_aliases.values() {
+            self.param_count.insert(
+                Spanned::into_value(alias.name.name),
+                alias.name.type_params.len(),
+            );
+        }
+
         for fn_ in &mut program.functions {
             self.param_count.insert(
                 Spanned::into_value(fn_.name.name),
@@ -54,9 +77,39 @@ impl<'a> Context<'a> {
             self.check_struct(struct_);
         }
 
+        for enum_ in program.enums.values_mut() {
+            self.check_enum(enum_);
+        }
+
+        for alias in program.type_aliases.values_mut() {
+            self.check_type(&mut alias.typ);
+        }
+
+        for impl_ in &mut program.impls {
+            self.check_type(&mut impl_.typ);
+        }
+
         for fn_ in &mut program.functions {
             self.check_function(fn_);
         }
+
+        for static_ in &mut program.statics {
+            self.check_static(static_);
+        }
+
+        for const_ in &mut program.consts {
+            self.check_const(const_);
+        }
+    }
+
+    fn check_const(&mut self, const_: &mut Const) {
+        self.check_type(&mut const_.typ);
+        self.check_expr(&mut const_.value);
+    }
+
+    fn check_static(&mut self, static_: &mut Static) {
+        self.check_type(&mut static_.typ);
+        self.check_expr(&mut static_.value);
     }
 
     fn check_struct(&mut self, struct_: &mut Struct) {
@@ -65,6 +118,14 @@ impl<'a> Context<'a> {
         }
     }
 
+    fn check_enum(&mut self, enum_: &mut Enum) {
+        for variant in &mut enum_.variants {
+            for field in &mut variant.fields {
+                self.check_type(field);
+            }
+        }
+    }
+
     fn check_function(&mut self, fn_: &mut Function) {
         for param in &mut fn_.params {
             self.check_type(&mut param.typ);
@@ -88,12 +149,21 @@ impl<'a> Context<'a> {
             Type::U8 |
             Type::U16 |
             Type::U32 |
+            Type::I64 |
+            Type::U64 |
             Type::Wildcard => return,
-            Type::Pointer(ref mut typ) => {
+            Type::Pointer(ref mut typ) | Type::Array(ref mut typ, _) => {
                 self.check_type(typ);
                 return;
             }
-            Type::Function(ref mut params, ref mut out) => {
+            Type::Tuple(ref mut types) => {
+                for typ in types {
+                    self.check_type(typ);
+                }
+                return;
+            }
+            Type::Function(ref mut params, ref mut out) |
+            Type::Closure(ref mut params, ref mut out) => {
                 for param in params {
                     self.check_type(param);
                 }
@@ -107,7 +177,9 @@ impl<'a> Context<'a> {
                     self.check_type(param);
                 }
                 let params_expected = self.params_taken(name);
-                if params.len() != params_expected {
+                if params.len() == params_expected {
+                    return;
+                } else if self.reported.insert(name_span) {
                     let name = self.ctx.symbols.get_name(name);
                     let msg = make_error_message("type", name, params_expected, params.len());
                     let short_msg = make_short_message(params_expected);
@@ -116,8 +188,6 @@ impl<'a> Context<'a> {
                         .error(msg, name_span)
                         .span_note(short_msg, name_span)
                         .build();
-                } else {
-                    return;
                 }
             }
         }
@@ -129,7 +199,8 @@ impl<'a> Context<'a> {
             Statement::Block(ref mut stmts) => for stmt in stmts {
                 self.check_statement(stmt);
             },
-            Statement::Break | Statement::Continue | Statement::Error => {}
+            Statement::Break(_, None) | Statement::Continue(_) | Statement::Error => {}
+            Statement::Break(_, Some(ref mut value)) => self.check_expr(value),
             Statement::Expr(ref mut expr) | Statement::Return(ref mut expr) => {
                 self.check_expr(expr)
             }
@@ -146,22 +217,40 @@ impl<'a> Context<'a> {
                     self.check_expr(value);
                 }
             }
-            Statement::Loop(ref mut body) => self.check_statement(body),
-            Statement::While(ref mut cond, ref mut body) => {
+            Statement::Loop(_, ref mut body) => self.check_statement(body),
+            Statement::While(_, ref mut cond, ref mut body) => {
                 self.check_expr(cond);
                 self.check_statement(body);
             }
+            Statement::For(_, _, ref mut start, ref mut end, _, ref mut body) => {
+                self.check_expr(start);
+                self.check_expr(end);
+                self.check_statement(body);
+            }
+            Statement::Match(ref mut value, ref mut arms) => {
+                self.check_expr(value);
+                for arm in arms {
+                    self.check_statement(&mut arm.body);
+                }
+            }
         }
     }
 
     fn check_expr(&mut self, expr: &mut Spanned<Expr>) {
         match **expr {
-            Expr::Binary(ref mut lhs, _, ref mut rhs) => {
+            Expr::Binary(ref mut lhs, _, ref mut rhs) | Expr::Index(ref mut lhs, ref mut rhs) => {
                 self.check_expr(lhs);
                 self.check_expr(rhs);
                 return;
             }
-            Expr::Call(ref mut expr, ref mut params) => {
+            Expr::Array(ref mut values) | Expr::Tuple(ref mut values) => {
+                for value in values {
+                    self.check_expr(value);
+                }
+                return;
+            }
+            Expr::Call(ref mut expr, ref mut params) |
+            Expr::MethodCall(ref mut expr, _, ref mut params) => {
                 self.check_expr(expr);
                 for param in params {
                     self.check_expr(param);
@@ -172,6 +261,34 @@ impl<'a> Context<'a> {
                 self.check_expr(expr);
                 return;
             }
+            Expr::Lambda(ref mut params, ref mut body, _) => {
+                for param in params {
+                    self.check_type(&mut param.typ);
+                }
+                self.check_expr(body);
+                return;
+            }
+            Expr::If(ref mut cond, ref mut then, ref mut else_) => {
+                self.check_expr(cond);
+                self.check_expr(then);
+                if let Some(ref mut else_) = *else_ {
+                    self.check_expr(else_);
+                }
+                return;
+            }
+            Expr::Loop(_, ref mut body) => {
+                self.check_statement(body);
+                return;
+            }
+            Expr::Block(ref mut stmts, ref mut value) => {
+                for stmt in stmts {
+                    self.check_statement(stmt);
+                }
+                if let Some(ref mut value) = *value {
+                    self.check_expr(value);
+                }
+                return;
+            }
             Expr::Error | Expr::Literal(_) => return,
             Expr::Name(name, ref mut params) => {
                 let name_span = Spanned::span(&name);
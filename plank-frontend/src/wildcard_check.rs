@@ -1,6 +1,6 @@
 use plank_errors::reporter::Builder;
 use plank_syntax::position::{Span, Spanned};
-use ast::resolved::{Function, Program, Struct, Type};
+use ast::resolved::{Const, Enum, Function, Program, Static, Struct, Type};
 use CompileCtx;
 
 
@@ -32,6 +32,30 @@ impl<'a> Context<'a> {
         for struct_ in program.structs.values() {
             self.check_struct(struct_);
         }
+
+        for enum_ in program.enums.values() {
+            self.check_enum(enum_);
+        }
+
+        for static_ in &program.statics {
+            self.check_static(static_);
+        }
+
+        for const_ in &program.consts {
+            self.check_const(const_);
+        }
+    }
+
+    fn check_const(&mut self, const_: &Const) {
+        self.item_kind = Some("constant type");
+        self.check_type(&const_.typ);
+        self.finish_item_check();
+    }
+
+    fn check_static(&mut self, static_: &Static) {
+        self.item_kind = Some("static type");
+        self.check_type(&static_.typ);
+        self.finish_item_check();
     }
 
     fn check_struct(&mut self, struct_: &Struct) {
@@ -42,6 +66,16 @@ impl<'a> Context<'a> {
         self.finish_item_check();
     }
 
+    fn check_enum(&mut self, enum_: &Enum) {
+        self.item_kind = Some("enum variant");
+        for variant in &enum_.variants {
+            for field in &variant.fields {
+                self.check_type(field);
+            }
+        }
+        self.finish_item_check();
+    }
+
     fn check_function(&mut self, fn_: &Function) {
         self.item_kind = Some("function header");
         for param in &fn_.params {
@@ -61,15 +95,17 @@ impl<'a> Context<'a> {
             Type::I32 |
             Type::U8 |
             Type::U16 |
-            Type::U32 => {}
-            Type::Pointer(ref typ) => self.check_type(typ),
-            Type::Function(ref params, ref out) => {
+            Type::U32 |
+            Type::I64 |
+            Type::U64 => {}
+            Type::Pointer(ref typ) | Type::Array(ref typ, _) => self.check_type(typ),
+            Type::Function(ref params, ref out) | Type::Closure(ref params, ref out) => {
                 for param in params {
                     self.check_type(param);
                 }
                 self.check_type(out);
             }
-            Type::Concrete(_, ref params) => for param in params {
+            Type::Concrete(_, ref params) | Type::Tuple(ref params) => for param in params {
                 self.check_type(param);
             },
             Type::Wildcard => self.report_error(Spanned::span(typ)),
@@ -0,0 +1,146 @@
+extern crate plank_errors;
+extern crate plank_frontend;
+extern crate plank_syntax;
+
+use plank_errors::Reporter;
+use plank_errors::reporter::Severity;
+use plank_syntax::position::FileId;
+
+
+/// Compiles a program and returns messages of reported errors.
+fn compile_errors(source: &str) -> Vec<String> {
+    let reporter = Reporter::new();
+    let tokens = plank_syntax::lex(source, FileId(0), reporter.clone());
+    let program = plank_syntax::parse(tokens, FileId(0), reporter.clone());
+    let result = plank_frontend::compile(&program, reporter.clone());
+    let errors = reporter
+        .get_diagnostics()
+        .into_iter()
+        .filter(|d| d.severity == Severity::Error)
+        .map(|d| d.message)
+        .collect::<Vec<_>>();
+    assert_eq!(result.is_err(), !errors.is_empty());
+    errors
+}
+
+fn assert_error(source: &str, message: &str) {
+    let errors = compile_errors(source);
+    assert!(
+        errors.iter().any(|e| e == message),
+        "expected error `{}`, got {:?}",
+        message,
+        errors
+    );
+}
+
+fn assert_compiles(source: &str) {
+    let errors = compile_errors(source);
+    assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+}
+
+const MAIN: &str = "fn main() -> i32 { return 0; }\n";
+
+#[test]
+fn static_array_with_wrong_element_type() {
+    let source = format!("static X: [u8; 1] = [1u32];\n{}", MAIN);
+    assert_error(&source, "cannot assign `[u32; 1]` to `[u8; 1]`");
+}
+
+#[test]
+fn static_array_with_wrong_length() {
+    let source = format!("static X: [u8; 3] = [1, 2];\n{}", MAIN);
+    assert_error(&source, "cannot assign `[{int}; 2]` to `[u8; 3]`");
+}
+
+#[test]
+fn static_struct_with_wrong_field_type() {
+    let source = format!("struct P {{ a: u8 }}\nstatic X: P = P(70000u32);\n{}", MAIN);
+    assert_error(&source, "1st argument should be `u8`, but is `u32`");
+}
+
+#[test]
+fn static_aggregates() {
+    let source = format!(
+        "struct P {{ a: u8, b: u32 }}\nstatic X: [u8; 3] = [1, 2, 3];\nstatic Y: P = P(1, 2);\n{}",
+        MAIN
+    );
+    assert_compiles(&source);
+}
+
+#[test]
+fn address_of_constant() {
+    let source = "const A: u32 = 5;\nfn main() -> i32 {\n    let p = &A;\n    return 0;\n}\n";
+    assert_error(source, "cannot take the address of constant `A`");
+}
+
+const POINT: &str = "struct P { x: u8 }
+impl P {
+    fn m(self: *P) -> u8 {
+        return self.x;
+    }
+}
+fn mk() -> P {
+    return P(1);
+}
+";
+
+#[test]
+fn pointer_method_on_temporary() {
+    for call in &["P(1).m()", "mk().m()"] {
+        let source = format!("{}fn main() -> i32 {{\n    return {} as i32;\n}}\n", POINT, call);
+        assert_error(&source, "cannot take the address of a temporary");
+    }
+}
+
+#[test]
+fn pointer_method_on_lvalue() {
+    let source = format!(
+        "{}fn main() -> i32 {{\n    let p = mk();\n    let ps = [p];\n    return (p.m() + ps[0].m() + (&p).m()) as i32;\n}}\n",
+        POINT
+    );
+    assert_compiles(&source);
+}
+
+#[test]
+fn method_on_unbounded_type_parameter() {
+    let source = "trait Get {\n    fn get(self: Self) -> u8;\n}\nfn f<T>(x: T) -> u8 {\n    return x.get();\n}\nfn main() -> i32 {\n    return 0;\n}\n";
+    assert_error(source, "type parameter `T` has no method `get`");
+}
+
+#[test]
+fn static_tuple() {
+    let source = "static T: (u8, u32) = (1, 2);\nstatic U: ((u8, u8), [u16; 2]) = ((1, 2), [3, 4]);\nfn main() -> i32 {\n    return (T.0 as u32 + T.1) as i32;\n}\n";
+    assert_compiles(source);
+}
+
+#[test]
+fn negated_minimum_int_literals() {
+    let source = "fn main() -> i32 {\n    let a = -128i8;\n    let b = -32768i16;\n    let c = -2147483648i32;\n    let d = -9223372036854775808i64;\n    return a as i32 + b as i32 + c + d as i32;\n}\n";
+    assert_compiles(source);
+}
+
+#[test]
+fn minimum_int_literals_without_negation() {
+    for &(literal, typ) in &[("128i8", "i8"), ("32768i16", "i16"), ("2147483648i32", "i32")] {
+        let source = format!("fn main() -> i32 {{\n    let x = {};\n    return x as i32;\n}}\n", literal);
+        assert_error(&source, &format!("literal out of range for `{}`", typ));
+    }
+    let source = "fn main() -> i32 {\n    let x = 1i8 - 128i8;\n    return x as i32;\n}\n";
+    assert_error(source, "literal out of range for `i8`");
+}
+
+#[test]
+fn int_literals_out_of_range_of_suffix() {
+    for &(literal, typ) in &[("0x100u8", "u8"), ("-129i8", "i8"), ("0x80i8", "i8")] {
+        let source = format!("fn main() -> i32 {{\n    let x = {};\n    return x as i32;\n}}\n", literal);
+        assert_error(&source, &format!("literal out of range for `{}`", typ));
+    }
+}
+
+#[test]
+fn static_string() {
+    let source = format!("static S: *u8 = \"hi\";\n{}", MAIN);
+    assert_error(&source, "string literals cannot be used in constant expressions");
+    let source = format!("static S: [u8; 3] = ['h', 'i', 0];\n{}", MAIN);
+    assert_compiles(&source);
+}
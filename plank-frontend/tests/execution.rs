@@ -0,0 +1,58 @@
+extern crate plank_errors;
+extern crate plank_frontend;
+extern crate plank_interpreter;
+extern crate plank_syntax;
+
+use std::io;
+use plank_errors::Reporter;
+use plank_syntax::position::FileId;
+
+
+/// Compiles and interprets a program, returns its exit code.
+fn run(source: &str) -> i32 {
+    let reporter = Reporter::new();
+    let tokens = plank_syntax::lex(source, FileId(0), reporter.clone());
+    let program = plank_syntax::parse(tokens, FileId(0), reporter.clone());
+    let program = plank_frontend::compile(&program, reporter.clone())
+        .unwrap_or_else(|()| panic!("compile errors: {:?}", reporter.get_diagnostics()));
+    plank_interpreter::run_program(&program, &[], 1 << 20, io::empty(), io::sink())
+        .expect("program failed")
+}
+
+#[test]
+fn compound_assign_evaluates_target_once() {
+    let source = "fn f(p: *i32) -> *i32 {\n    *p += 10;\n    return p;\n}\nfn main() -> i32 {\n    let x = 1;\n    *f(&x) += 1;\n    return x;\n}\n";
+    assert_eq!(run(source), 12);
+}
+
+#[test]
+fn compound_assign_reads_target_after_rhs() {
+    let local = "fn main() -> i32 {\n    let x = 1;\n    let p = &x;\n    x += { *p = 5; 1 };\n    return x;\n}\n";
+    let deref = "fn main() -> i32 {\n    let y = 1;\n    let q = &y;\n    *q += { *q = 5; 1 };\n    return y;\n}\n";
+    let field = "struct P { a: i32 }\nfn main() -> i32 {\n    let s = P(1);\n    let p = &s.a;\n    s.a += { *p = 5; 1 };\n    return s.a;\n}\n";
+    assert_eq!(run(local), 6);
+    assert_eq!(run(deref), 6);
+    assert_eq!(run(field), 6);
+}
+
+#[test]
+fn compound_assign_target_pointer_is_not_reassigned() {
+    let source = "fn main() -> i32 {\n    let x = 1;\n    let y = 1;\n    let p = &x;\n    *p += { p = &y; 1 };\n    return x * 10 + y;\n}\n";
+    assert_eq!(run(source), 21);
+}
+
+#[test]
+fn pointer_arithmetic_on_wide_elements() {
+    // `T` is 12 bytes, so offsets are multiples of 12
+    let program = |result: &str| {
+        format!(
+            "struct T {{ a: u32, b: u16, c: u32 }}\nfn main() -> i32 {{\n    let a = [T(1, 2, 3), T(4, 5, 6), T(7, 8, 9)];\n    let p = &a[0];\n    let q = p + 2u32;\n    let r = q - 1u32;\n    return {};\n}}\n",
+            result
+        )
+    };
+    assert_eq!(run(&program("(q as u32 - p as u32) as i32")), 24);
+    assert_eq!(run(&program("(r as u32 - p as u32) as i32")), 12);
+    assert_eq!(run(&program("q - p")), 2);
+    assert_eq!(run(&program("p - q")), -2);
+    assert_eq!(run(&program("(*q).c as i32 * 10 + (*r).a as i32")), 94);
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeSet, HashMap};
+use Error;
+
+
+/// Allocator for a fixed region of vm memory.
+pub struct Heap {
+    /// Free ranges as `(start, size)`, sorted by address. Adjacent
+    /// ranges are always merged.
+    free: Vec<(u32, u32)>,
+    /// Sizes of allocated blocks, by their addresses.
+    allocated: HashMap<u32, u32>,
+    /// Addresses of freed blocks that were not reused since.
+    freed: BTreeSet<u32>,
+}
+
+impl Heap {
+    pub fn new(start: u32, size: u32) -> Heap {
+        Heap {
+            free: vec![(start, size)],
+            allocated: HashMap::new(),
+            freed: BTreeSet::new(),
+        }
+    }
+
+    /// Allocates a block, returns `None` if there is no space
+    /// left or alignment is not a power of two.
+    pub fn alloc(&mut self, size: u32, align: u32) -> Option<u32> {
+        if !align.is_power_of_two() {
+            return None;
+        }
+        // every allocation gets a distinct address
+        let size = size.max(1);
+        let (index, address) = self.free.iter().enumerate().find_map(|(index, &(start, len))| {
+            let address = start.checked_add(align - 1)? & !(align - 1);
+            if u64::from(address) + u64::from(size) <= u64::from(start) + u64::from(len) {
+                Some((index, address))
+            } else {
+                None
+            }
+        })?;
+        let (start, len) = self.free.remove(index);
+        let mut leftover = Vec::new();
+        if address > start {
+            leftover.push((start, address - start));
+        }
+        if address + size < start + len {
+            leftover.push((address + size, start + len - address - size));
+        }
+        for (i, range) in leftover.into_iter().enumerate() {
+            self.free.insert(index + i, range);
+        }
+        self.allocated.insert(address, size);
+        let reused = self.freed.range(address..address + size).cloned().collect::<Vec<_>>();
+        for freed in reused {
+            self.freed.remove(&freed);
+        }
+        Some(address)
+    }
+
+    /// Frees a block allocated with `alloc`. Freeing null
+    /// pointer does nothing.
+    pub fn free(&mut self, address: u32) -> Result<(), Error> {
+        if address == 0 {
+            return Ok(());
+        }
+        let size = match self.allocated.remove(&address) {
+            Some(size) => size,
+            None if self.freed.contains(&address) => return Err(Error::DoubleFree(address)),
+            None => return Err(Error::InvalidFree(address)),
+        };
+        self.freed.insert(address);
+        let index = self.free
+            .iter()
+            .position(|&(start, _)| start > address)
+            .unwrap_or(self.free.len());
+        self.free.insert(index, (address, size));
+        // merge with the next range first, so that index stays valid
+        if index + 1 < self.free.len() && address + size == self.free[index + 1].0 {
+            let (_, len) = self.free.remove(index + 1);
+            self.free[index].1 += len;
+        }
+        if index > 0 && self.free[index - 1].0 + self.free[index - 1].1 == address {
+            let (_, len) = self.free.remove(index);
+            self.free[index - 1].1 += len;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Heap;
+    use Error;
+
+    #[test]
+    fn freed_block_is_reused() {
+        let mut heap = Heap::new(16, 64);
+        let a = heap.alloc(8, 4).unwrap();
+        let b = heap.alloc(8, 4).unwrap();
+        heap.free(a).unwrap();
+        assert_eq!(heap.alloc(8, 4), Some(a));
+        assert_ne!(a, b);
+        assert_eq!(heap.free, vec![(32, 48)]);
+    }
+
+    #[test]
+    fn free_merges_with_neighbours() {
+        let mut heap = Heap::new(16, 64);
+        let a = heap.alloc(8, 1).unwrap();
+        let b = heap.alloc(8, 1).unwrap();
+        let c = heap.alloc(8, 1).unwrap();
+        heap.free(a).unwrap();
+        heap.free(c).unwrap();
+        assert_eq!(heap.free, vec![(16, 8), (32, 48)]);
+        // `b` merges with `a` before it and `c` after it
+        heap.free(b).unwrap();
+        assert_eq!(heap.free, vec![(16, 64)]);
+        assert_eq!(heap.alloc(64, 1), Some(16));
+    }
+
+    #[test]
+    fn double_free_after_merge() {
+        let mut heap = Heap::new(16, 64);
+        let a = heap.alloc(8, 1).unwrap();
+        let b = heap.alloc(8, 1).unwrap();
+        heap.free(a).unwrap();
+        heap.free(b).unwrap();
+        match heap.free(a) {
+            Err(Error::DoubleFree(address)) => assert_eq!(address, a),
+            result => panic!("expected double free, got {:?}", result),
+        }
+        match heap.free(b) {
+            Err(Error::DoubleFree(address)) => assert_eq!(address, b),
+            result => panic!("expected double free, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn free_inside_block() {
+        let mut heap = Heap::new(16, 64);
+        let a = heap.alloc(8, 1).unwrap();
+        match heap.free(a + 4) {
+            Err(Error::InvalidFree(address)) => assert_eq!(address, a + 4),
+            result => panic!("expected invalid free, got {:?}", result),
+        }
+        // block is still allocated
+        heap.free(a).unwrap();
+    }
+}
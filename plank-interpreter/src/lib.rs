@@ -1,18 +1,50 @@
 extern crate plank_ir;
 
+mod heap;
+
 use std::collections::HashMap;
 use std::io::{self, Read, Write};
-use plank_ir::{ir, Program};
+use std::ops::Range;
+use std::rc::Rc;
+use plank_ir::{ir, Program, ValidationError};
+use heap::Heap;
+
 
+/// Size of memory region used by `alloc` and `free`.
+const HEAP_SIZE: u32 = 1 << 20;
+
+/// Stack size limit used by `plank` unless another one is given.
+pub const DEFAULT_MAX_STACK: usize = 8 << 20;
 
 #[derive(Debug)]
 pub enum Error {
     BadDeref,
     DivisionByZero,
+    DoubleFree(u32),
+    InvalidFree(u32),
     MissingSymbol(ir::Symbol),
+    /// Call depth at which stack got too large.
+    StackOverflow(usize),
+    /// Program was rejected by IR validation.
+    InvalidIr(ValidationError),
+    /// Instruction cannot be executed, with explanation.
+    BadInstruction(&'static str),
+    /// `main` has signature that interpreter cannot call.
+    InvalidMain,
+    /// Error that happened while executing given instruction.
+    At(Location, Box<Error>),
     Io(io::Error),
 }
 
+/// Position of an instruction in the program. Block terminator
+/// has index equal to the number of instructions in the block.
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub function: ir::Symbol,
+    pub block: ir::BlockId,
+    pub instruction: usize,
+}
+
 impl ::std::convert::From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
         Error::Io(err)
@@ -24,9 +56,36 @@ impl ::std::fmt::Display for Error {
         match *self {
             Error::BadDeref => write!(f, "dereferenced invalid pointer"),
             Error::DivisionByZero => write!(f, "division by zero"),
+            Error::DoubleFree(address) => {
+                write!(f, "pointer {:#x} was freed twice", address)
+            }
+            Error::InvalidFree(address) => {
+                write!(f, "freed pointer {:#x} that was not allocated", address)
+            }
             Error::MissingSymbol(ref sym) => {
                 write!(f, "missing definition for symbol `{}`", sym.0)
             }
+            Error::StackOverflow(depth) => {
+                write!(f, "stack overflow at call depth {}", depth)
+            }
+            Error::InvalidIr(ref err) => write!(f, "{}", err),
+            Error::BadInstruction(msg) => write!(f, "bad instruction: {}", msg),
+            Error::InvalidMain => {
+                write!(
+                    f,
+                    "`main` must return `i32` and take either no parameters or `argc` and `argv`"
+                )
+            }
+            Error::At(ref location, ref err) => {
+                write!(
+                    f,
+                    "{} (in `{}` at label_{}, instruction {})",
+                    err,
+                    location.function.0,
+                    location.block.0,
+                    location.instruction,
+                )
+            }
             Error::Io(ref err) => {
                 write!(f, "io error: {}", err)
             }
@@ -34,9 +93,18 @@ impl ::std::fmt::Display for Error {
     }
 }
 
-pub fn run_program<R: Read, W: Write>(program: &Program, input: R, output: W) -> Result<i32, Error> {
-    plank_ir::validate_ir(program);
-    Vm::new(program, input, output)?.run()
+/// Runs the program. If `main` takes parameters, it gets the
+/// number of `args` and a pointer to them as C strings. Stack
+/// frames can use at most `max_stack` bytes in total.
+pub fn run_program<R: Read, W: Write>(
+    program: &Program,
+    args: &[String],
+    max_stack: usize,
+    input: R,
+    output: W,
+) -> Result<i32, Error> {
+    plank_ir::validate_ir(program).map_err(Error::InvalidIr)?;
+    Vm::new(program, args, max_stack, input, output)?.run()
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -46,11 +114,32 @@ enum Value {
     Byte(u8),
     Word(u16),
     DoubleWord(u32),
+    QuadWord(u64),
+}
+
+impl Value {
+    /// Size of the value, `None` if it is only known from
+    /// the place it is written to.
+    fn len(&self) -> Option<u32> {
+        match *self {
+            Value::AddressRange(_, len) => Some(len),
+            Value::FromAddress(_) => None,
+            Value::Byte(_) => Some(1),
+            Value::Word(_) => Some(2),
+            Value::DoubleWord(_) => Some(4),
+            Value::QuadWord(_) => Some(8),
+        }
+    }
 }
 
+/// Cases of switches in a function sorted by value, by block.
+type SwitchTable = HashMap<ir::BlockId, Vec<(u64, ir::BlockId)>>;
+
 struct StackFrame<'a> {
     stack_start: usize,
+    symbol: ir::Symbol,
     function: &'a ir::Function,
+    switches: Rc<SwitchTable>,
     registers: HashMap<ir::Reg, u32>,
     current_block: ir::BlockId,
     current_op: usize,
@@ -60,17 +149,29 @@ struct StackFrame<'a> {
 struct Vm<'a, R, W> {
     input: R,
     output: W,
+    /// Whether something was written to `output` since it was last flushed.
+    output_dirty: bool,
     program: &'a Program,
     memory: Vec<u8>,
+    heap: Heap,
+    stack_base: usize,
+    max_stack: usize,
     frames: Vec<StackFrame<'a>>,
     current_frame: StackFrame<'a>,
-    strings: HashMap<Vec<u8>, u32>,
+    globals: HashMap<ir::Symbol, u32>,
     symbol_ids: HashMap<ir::Symbol, u32>,
     symbols_by_id: HashMap<u32, ir::Symbol>,
+    switches: HashMap<ir::Symbol, Rc<SwitchTable>>,
 }
 
 impl<'a, R: Read, W: Write> Vm<'a, R, W> {
-    fn new(program: &'a Program, input: R, output: W) -> Result<Self, Error> {
+    fn new(
+        program: &'a Program,
+        args: &[String],
+        max_stack: usize,
+        input: R,
+        output: W,
+    ) -> Result<Self, Error> {
         let main_symbol = ir::Symbol("main".into());
         let main = match program.functions.get(&main_symbol) {
             Some(f) => f,
@@ -80,9 +181,20 @@ impl<'a, R: Read, W: Write> Vm<'a, R, W> {
             Some(block) => block,
             None => return Err(Error::MissingSymbol(main_symbol)),
         };
-        let main_frame = StackFrame {
+        let returns_i32 = main.output_layout.is_some_and(|layout| layout.size == 4);
+        if !returns_i32 || (!main.parameters.is_empty() && main.parameters.len() != 2) {
+            return Err(Error::InvalidMain);
+        }
+        let switches = program
+            .functions
+            .iter()
+            .map(|(symbol, f)| (symbol.clone(), Rc::new(switch_table(f))))
+            .collect::<HashMap<_, _>>();
+        let mut main_frame = StackFrame {
             stack_start: 4,
             function: main,
+            switches: switches[&main_symbol].clone(),
+            symbol: main_symbol,
             registers: HashMap::new(),
             current_block: block,
             current_op: 0,
@@ -93,18 +205,36 @@ impl<'a, R: Read, W: Write> Vm<'a, R, W> {
         for (index, (symbol, f)) in program.functions.iter().enumerate() {
             if f.start_block.is_some() ||
                 &*symbol.0 == "@plank_getc" ||
-                &*symbol.0 == "@plank_putc"
+                &*symbol.0 == "@plank_putc" ||
+                &*symbol.0 == "@plank_alloc" ||
+                &*symbol.0 == "@plank_free"
             {
                 symbol_ids.insert(symbol.clone(), index as u32);
                 symbols_by_id.insert(index as u32, symbol.clone());
             }
         }
-        let mut strings = HashMap::new();
+        let mut globals = HashMap::new();
         let mut memory = vec![0, 0, 0, 0];
+        // place globals in a fixed order, so that every run
+        // gives them (and the heap after them) the same addresses
+        let mut sorted_globals = program.globals.iter().collect::<Vec<_>>();
+        sorted_globals.sort_by(|&(a, _), &(b, _)| a.0.cmp(&b.0));
+        for (symbol, global) in sorted_globals {
+            let align = global.layout.align as usize;
+            while memory.len() % align != 0 {
+                memory.push(0);
+            }
+            globals.insert(symbol.clone(), memory.len() as u32);
+            memory.extend(global.value.iter().cloned());
+        }
+        // heap is placed between globals and the stack
+        let heap = Heap::new(memory.len() as u32, HEAP_SIZE);
+        memory.resize(memory.len() + HEAP_SIZE as usize, 0);
+        let argv = layout_args(&mut memory, args);
+        main_frame.stack_start = memory.len();
         for f in program.functions.values() {
             for block in f.blocks.values() {
                 for op in &block.ops {
-                    collect_strings(op, &mut strings, &mut memory);
                     validate_symbol_refs(op, &symbol_ids)?;
                 }
             }
@@ -112,24 +242,50 @@ impl<'a, R: Read, W: Write> Vm<'a, R, W> {
         let mut vm = Vm {
             input,
             output,
+            output_dirty: false,
             program,
+            stack_base: memory.len(),
+            max_stack,
             memory,
+            heap,
             frames: Vec::new(),
             current_frame: main_frame,
-            strings,
+            globals,
             symbol_ids,
             symbols_by_id,
+            switches,
         };
-        let regs = vm.allocate_registers(&vm.current_frame.function.registers);
+        let regs = vm.allocate_registers(&vm.current_frame.function.registers, 1)?;
         vm.current_frame.registers = regs;
+        let main_args = [
+            Value::DoubleWord(args.len() as u32),
+            Value::DoubleWord(argv),
+        ];
+        for (param, &value) in main.parameters.iter().zip(main_args.iter()) {
+            let (at, len) = vm.register_address(*param)?;
+            vm.write_value(at, Some(len), value)?;
+        }
         Ok(vm)
     }
 
-    fn current_block(&self) -> &'a ir::Block {
-        &self.current_frame.function.blocks[&self.current_frame.current_block]
+    fn current_block(&self) -> Result<&'a ir::Block, Error> {
+        let frame = &self.current_frame;
+        frame.function.blocks.get(&frame.current_block).ok_or(
+            Error::BadInstruction("jump to missing block"),
+        )
     }
 
-    fn allocate_registers(&mut self, registers: &HashMap<ir::Reg, ir::Layout>) -> HashMap<ir::Reg, u32> {
+    /// Allocates a frame at the top of the stack, `depth` is the
+    /// number of frames including the new one.
+    fn allocate_registers(
+        &mut self,
+        registers: &HashMap<ir::Reg, ir::Layout>,
+        depth: usize,
+    ) -> Result<HashMap<ir::Reg, u32>, Error> {
+        let frame_size = registers.values().map(|l| l.size as usize).sum::<usize>();
+        if self.memory.len() - self.stack_base + frame_size > self.max_stack {
+            return Err(Error::StackOverflow(depth));
+        }
         let mut result = HashMap::new();
         for (&reg, &layout) in registers {
             let at = self.memory.len() as u32;
@@ -138,188 +294,292 @@ impl<'a, R: Read, W: Write> Vm<'a, R, W> {
             }
             result.insert(reg, at);
         }
-        result
+        Ok(result)
+    }
+
+    fn memory_range(&self, at: u32, len: u32) -> Result<Range<usize>, Error> {
+        let start = at as usize;
+        let end = start + len as usize;
+        if end <= self.memory.len() {
+            Ok(start..end)
+        } else {
+            Err(Error::BadDeref)
+        }
+    }
+
+    fn read_bytes(&self, at: u32, len: u32) -> Result<&[u8], Error> {
+        let range = self.memory_range(at, len)?;
+        Ok(&self.memory[range])
+    }
+
+    fn write_bytes(&mut self, at: u32, bytes: &[u8]) -> Result<(), Error> {
+        let range = self.memory_range(at, bytes.len() as u32)?;
+        self.memory[range].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Reads a little endian integer of `len` bytes from a register.
+    fn load_register(&self, reg: ir::Reg, len: u32) -> Result<u64, Error> {
+        let (at, size) = self.register_address(reg)?;
+        if size != len {
+            return Err(Error::BadInstruction("register has wrong size"));
+        }
+        let bytes = self.read_bytes(at, len)?;
+        Ok(bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | u64::from(b)))
     }
 
-    fn load_8bit(&self, val: &ir::Value) -> u8 {
+    fn load_8bit(&self, val: &ir::Value) -> Result<u8, Error> {
         match *val {
-            ir::Value::Int(i, ir::Size::Bit8) => i as u8,
-            ir::Value::Reg(reg) => {
-                let reg_at = self.current_frame.registers[&reg] as usize;
-                if reg_at < self.memory.len() {
-                    self.memory[reg_at]
-                } else {
-                    panic!("register out of bounds")
-                }
-            }
-            _ => panic!("bad 8 bit value"),
+            ir::Value::Int(i, ir::Size::Bit8) => Ok(i as u8),
+            ir::Value::Reg(reg) => Ok(self.load_register(reg, 1)? as u8),
+            _ => Err(Error::BadInstruction("bad 8 bit value")),
         }
     }
 
-    fn load_16bit(&self, val: &ir::Value) -> u16 {
+    fn load_16bit(&self, val: &ir::Value) -> Result<u16, Error> {
         match *val {
-            ir::Value::Int(i, ir::Size::Bit16) => i as u16,
-            ir::Value::Reg(reg) => {
-                let reg_at = self.current_frame.registers[&reg] as usize;
-                if reg_at + 4 <= self.memory.len() {
-                    let b1 = self.memory[reg_at + 0] as u16;
-                    let b2 = self.memory[reg_at + 1] as u16;
-                    (b2 << 8) | (b1 << 0)
-                } else {
-                    panic!("register out of bounds")
-                }
-            }
-            _ => panic!("bad 16 bit value"),
+            ir::Value::Int(i, ir::Size::Bit16) => Ok(i as u16),
+            ir::Value::Reg(reg) => Ok(self.load_register(reg, 2)? as u16),
+            _ => Err(Error::BadInstruction("bad 16 bit value")),
         }
     }
 
-    fn load_32bit(&self, val: &ir::Value) -> u32 {
+    fn load_32bit(&self, val: &ir::Value) -> Result<u32, Error> {
         match *val {
-            ir::Value::Int(i, ir::Size::Bit32) => i as u32,
-            ir::Value::Reg(reg) => {
-                let reg_at = self.current_frame.registers[&reg] as usize;
-                if reg_at + 4 <= self.memory.len() {
-                    let b1 = self.memory[reg_at + 0] as u32;
-                    let b2 = self.memory[reg_at + 1] as u32;
-                    let b3 = self.memory[reg_at + 2] as u32;
-                    let b4 = self.memory[reg_at + 3] as u32;
-                    (b4 << 24) | (b3 << 16) | (b2 << 8) | (b1 << 0)
-                } else {
-                    panic!("register out of bounds")
-                }
-            }
-            ir::Value::Bytes(ref s) => self.strings[s],
-            ir::Value::Symbol(ref sym) => self.symbol_ids[sym],
-            _ => panic!("bad 32 bit value"),
+            ir::Value::Int(i, ir::Size::Bit32) => Ok(i as u32),
+            ir::Value::Reg(reg) => Ok(self.load_register(reg, 4)? as u32),
+            ir::Value::Global(ref sym) => self.global_address(sym),
+            ir::Value::Symbol(ref sym) => self.symbol_id(sym),
+            _ => Err(Error::BadInstruction("bad 32 bit value")),
         }
     }
 
-    fn read_value(&self, val: &ir::Value) -> Value {
+    fn load_64bit(&self, val: &ir::Value) -> Result<u64, Error> {
         match *val {
-            ir::Value::Bytes(ref s) => Value::DoubleWord(self.strings[s]),
-            ir::Value::Int(i, ir::Size::Bit8) => Value::Byte(i as u8),
-            ir::Value::Int(i, ir::Size::Bit16) => Value::Word(i as u16),
-            ir::Value::Int(i, ir::Size::Bit32) => Value::DoubleWord(i as u32),
-            ir::Value::Reg(reg) => {
-                let (at, size) = self.register_address(reg);
-                Value::AddressRange(at, size)
-            }
-            ir::Value::Symbol(ref sym) => Value::DoubleWord(self.symbol_ids[sym]),
+            ir::Value::Int(i, ir::Size::Bit64) => Ok(i),
+            ir::Value::Reg(reg) => self.load_register(reg, 8),
+            _ => Err(Error::BadInstruction("bad 64 bit value")),
         }
     }
 
-    fn register_address(&self, reg: ir::Reg) -> (u32, u32) {
-        let at = self.current_frame.registers[&reg];
-        let size = self.current_frame.function.registers[&reg].size;
-        (at, size)
+    fn int_size(&self, val: &ir::Value) -> Result<u32, Error> {
+        match *val {
+            ir::Value::Int(_, ir::Size::Bit8) => Ok(1),
+            ir::Value::Int(_, ir::Size::Bit16) => Ok(2),
+            ir::Value::Int(_, ir::Size::Bit32) => Ok(4),
+            ir::Value::Int(_, ir::Size::Bit64) => Ok(8),
+            ir::Value::Reg(reg) => Ok(self.register_address(reg)?.1),
+            ir::Value::Global(_) => Ok(ir::POINTER_SIZE),
+            ir::Value::Symbol(_) => Ok(ir::FUNCTION_SIZE),
+        }
+    }
+
+    fn load_int(&self, val: &ir::Value) -> Result<u64, Error> {
+        match self.int_size(val)? {
+            1 => Ok(u64::from(self.load_8bit(val)?)),
+            2 => Ok(u64::from(self.load_16bit(val)?)),
+            4 => Ok(u64::from(self.load_32bit(val)?)),
+            8 => self.load_64bit(val),
+            _ => Err(Error::BadInstruction("bad int size")),
+        }
     }
 
-    fn mem_copy(&mut self, from: u32, to: u32, len: u32) {
-        if from == to {
-            return;
+    fn read_value(&self, val: &ir::Value) -> Result<Value, Error> {
+        match *val {
+            ir::Value::Global(ref sym) => Ok(Value::DoubleWord(self.global_address(sym)?)),
+            ir::Value::Int(i, ir::Size::Bit8) => Ok(Value::Byte(i as u8)),
+            ir::Value::Int(i, ir::Size::Bit16) => Ok(Value::Word(i as u16)),
+            ir::Value::Int(i, ir::Size::Bit32) => Ok(Value::DoubleWord(i as u32)),
+            ir::Value::Int(i, ir::Size::Bit64) => Ok(Value::QuadWord(i)),
+            ir::Value::Reg(reg) => {
+                let (at, size) = self.register_address(reg)?;
+                Ok(Value::AddressRange(at, size))
+            }
+            ir::Value::Symbol(ref sym) => Ok(Value::DoubleWord(self.symbol_id(sym)?)),
         }
-        if from < to && from + len > to {
-            panic!("copy overlap");
+    }
+
+    fn global_address(&self, sym: &ir::Symbol) -> Result<u32, Error> {
+        match self.globals.get(sym) {
+            Some(&address) => Ok(address),
+            None => Err(Error::MissingSymbol(sym.clone())),
         }
-        if to < from && to + len > from {
-            panic!("copy overlap");
+    }
+
+    fn symbol_id(&self, sym: &ir::Symbol) -> Result<u32, Error> {
+        match self.symbol_ids.get(sym) {
+            Some(&id) => Ok(id),
+            None => Err(Error::MissingSymbol(sym.clone())),
         }
-        for i in 0..len {
-            self.memory[(to + i) as usize] = self.memory[(from + i) as usize];
+    }
+
+    fn register_address(&self, reg: ir::Reg) -> Result<(u32, u32), Error> {
+        let frame = &self.current_frame;
+        match (frame.registers.get(&reg), frame.function.registers.get(&reg)) {
+            (Some(&at), Some(layout)) => Ok((at, layout.size)),
+            _ => Err(Error::BadInstruction("use of undeclared register")),
         }
     }
 
-    fn mem_cmp(&self, a: u32, b: u32, len: u32) -> bool {
-        for i in 0..len {
-            if self.memory[(a + i) as usize] != self.memory[(b + i) as usize] {
-                return false;
-            }
+    fn mem_copy(&mut self, from: u32, to: u32, len: u32) -> Result<(), Error> {
+        let source = self.memory_range(from, len)?;
+        self.memory_range(to, len)?;
+        self.memory.copy_within(source, to as usize);
+        Ok(())
+    }
+
+    /// Bytes of a value that is `len` bytes long.
+    fn value_bytes(&self, value: Value, len: u32) -> Result<Vec<u8>, Error> {
+        match value {
+            Value::AddressRange(at, _) |
+            Value::FromAddress(at) => Ok(self.read_bytes(at, len)?.to_vec()),
+            Value::Byte(b) => Ok(vec![b]),
+            Value::Word(w) => Ok(w.to_le_bytes().to_vec()),
+            Value::DoubleWord(dw) => Ok(dw.to_le_bytes().to_vec()),
+            Value::QuadWord(qw) => Ok(qw.to_le_bytes().to_vec()),
         }
-        true
     }
 
-    fn compare_values(&self, a: Value, b: Value) -> bool {
-        match (a, b) {
-            (Value::AddressRange(a, al), Value::AddressRange(b, bl)) => {
-                assert_eq!(al, bl);
-                self.mem_cmp(a, b, al)
+    fn compare_values(&self, a: Value, b: Value) -> Result<bool, Error> {
+        let len = match (a.len(), b.len()) {
+            (Some(al), Some(bl)) if al != bl => {
+                return Err(Error::BadInstruction("compared values have different sizes"));
             }
-            (Value::AddressRange(a, al), Value::Byte(b)) |
-            (Value::Byte(b), Value::AddressRange(a, al)) => {
-                assert_eq!(al, 1);
-                self.memory[a as usize] == b
+            (Some(len), _) | (None, Some(len)) => len,
+            (None, None) => return Err(Error::BadInstruction("compared values have no size")),
+        };
+        Ok(self.value_bytes(a, len)? == self.value_bytes(b, len)?)
+    }
+
+    fn write_value(&mut self, to: u32, len: Option<u32>, value: Value) -> Result<(), Error> {
+        let len = match (value.len(), len) {
+            (Some(value_len), Some(len)) if value_len != len => {
+                return Err(Error::BadInstruction("written value has wrong size"));
             }
-            (Value::AddressRange(a, al), Value::Word(b)) |
-            (Value::Word(b), Value::AddressRange(a, al)) => {
-                assert_eq!(al, 2);
-                self.memory[a as usize + 0] == (b & 0xFF) as u8 && 
-                self.memory[a as usize + 1] == ((b >> 8) & 0xFF) as u8
+            (Some(len), _) | (None, Some(len)) => len,
+            (None, None) => return Err(Error::BadInstruction("written value has no size")),
+        };
+        match value {
+            Value::AddressRange(from, _) |
+            Value::FromAddress(from) => self.mem_copy(from, to, len),
+            _ => {
+                let bytes = self.value_bytes(value, len)?;
+                self.write_bytes(to, &bytes)
             }
-            (Value::AddressRange(a, al), Value::DoubleWord(b)) |
-            (Value::DoubleWord(b), Value::AddressRange(a, al)) => {
-                assert_eq!(al, 4);
-                self.memory[a as usize + 0] == (b & 0xFF) as u8 && 
-                self.memory[a as usize + 1] == ((b >> 8) & 0xFF) as u8 &&
-                self.memory[a as usize + 2] == ((b >> 16) & 0xFF) as u8 &&
-                self.memory[a as usize + 3] == ((b >> 24) & 0xFF) as u8
+        }
+    }
+
+    fn call_getc(&mut self, dest: ir::Reg) -> Result<(), Error> {
+        // let interactive programs show a prompt before reading
+        if self.output_dirty {
+            self.output.flush()?;
+            self.output_dirty = false;
+        }
+        let mut buf = [0];
+        let result = match self.input.read(&mut buf)? {
+            0 => !0u32,
+            _ => buf[0] as u32,
+        };
+        let (ret, len) = self.register_address(dest)?;
+        self.write_value(ret, Some(len), Value::DoubleWord(result))
+    }
+
+    fn call_alloc(&mut self, dest: ir::Reg, params: &[ir::Value]) -> Result<(), Error> {
+        let size = self.load_32bit(&params[0])?;
+        let align = self.load_32bit(&params[1])?;
+        // null pointer if the block cannot be allocated
+        let address = self.heap.alloc(size, align).unwrap_or(0);
+        let (ret, len) = self.register_address(dest)?;
+        self.write_value(ret, Some(len), Value::DoubleWord(address))
+    }
+
+    /// Calls a function, its result is written to `dest`.
+    fn call(
+        &mut self,
+        sym: &ir::Symbol,
+        params: &[ir::Value],
+        dest: Option<ir::Reg>,
+    ) -> Result<(), Error> {
+        match (&*sym.0, dest) {
+            ("@plank_getc", Some(dest)) if params.is_empty() => return self.call_getc(dest),
+            ("@plank_putc", None) if params.len() == 1 => {
+                let val = self.load_8bit(&params[0])?;
+                self.output.write_all(&[val])?;
+                self.output_dirty = true;
+                return Ok(());
             }
-            (Value::FromAddress(a), Value::Byte(b)) |
-            (Value::Byte(b), Value::FromAddress(a)) => {
-                self.memory[a as usize] == b
+            ("@plank_alloc", Some(dest)) if params.len() == 2 => {
+                return self.call_alloc(dest, params);
             }
-            (Value::FromAddress(a), Value::Word(b)) |
-            (Value::Word(b), Value::FromAddress(a)) => {
-                self.memory[a as usize + 0] == (b & 0xFF) as u8 && 
-                self.memory[a as usize + 1] == ((b >> 8) & 0xFF) as u8
+            ("@plank_free", None) if params.len() == 1 => {
+                let address = self.load_32bit(&params[0])?;
+                return self.heap.free(address);
             }
-            (Value::FromAddress(a), Value::DoubleWord(b)) |
-            (Value::DoubleWord(b), Value::FromAddress(a)) => {
-                self.memory[a as usize + 0] == (b & 0xFF) as u8 && 
-                self.memory[a as usize + 1] == ((b >> 8) & 0xFF) as u8 &&
-                self.memory[a as usize + 2] == ((b >> 16) & 0xFF) as u8 &&
-                self.memory[a as usize + 3] == ((b >> 24) & 0xFF) as u8
+            ("@plank_getc", _) |
+            ("@plank_putc", _) |
+            ("@plank_alloc", _) |
+            ("@plank_free", _) => {
+                return Err(Error::BadInstruction("bad call to builtin function"));
             }
-            (Value::Byte(a), Value::Byte(b)) => a == b,
-            (Value::Word(a), Value::Word(b)) => a == b,
-            (Value::DoubleWord(a), Value::DoubleWord(b)) => a == b,
-            _ => panic!("invalid cmp"),
+            _ => {}
         }
-    }
-
-    fn write_value(&mut self, to: u32, len: Option<u32>, value: Value) {
-        match value {
-            Value::Byte(b) => {
-                if len.is_some() {
-                    assert_eq!(len, Some(1));
+        let f = match self.program.functions.get(sym) {
+            Some(f) => f,
+            None => return Err(Error::MissingSymbol(sym.clone())),
+        };
+        let block = match f.start_block {
+            Some(block) => block,
+            None => return Err(Error::MissingSymbol(sym.clone())),
+        };
+        if f.parameters.len() != params.len() {
+            return Err(Error::BadInstruction("wrong number of parameters"));
+        }
+        let return_address = match (dest, f.output_layout) {
+            (Some(dest), Some(layout)) => {
+                let (ret, len) = self.register_address(dest)?;
+                if len != layout.size {
+                    return Err(Error::BadInstruction("result register has wrong size"));
                 }
-                self.memory[to as usize] = b;
+                Some(ret)
             }
-            Value::Word(w) => {
-                if len.is_some() {
-                    assert_eq!(len, Some(2));
-                }
-                self.memory[to as usize] = (w & 0xFF) as u8;
-                self.memory[(to + 1) as usize] = ((w >> 8) & 0xFF) as u8;
+            (None, None) => None,
+            (Some(_), None) => {
+                return Err(Error::BadInstruction("called function does not return a value"));
             }
-            Value::DoubleWord(dw) => {
-                if len.is_some() {
-                    assert_eq!(len, Some(4));
-                }
-                self.memory[to as usize] = (dw & 0xFF) as u8;
-                self.memory[(to + 1) as usize] = ((dw >> 8) & 0xFF) as u8;
-                self.memory[(to + 2) as usize] = ((dw >> 16) & 0xFF) as u8;
-                self.memory[(to + 3) as usize] = ((dw >> 24) & 0xFF) as u8;
-            }
-            Value::FromAddress(a) => {
-                let len = len.unwrap();
-                self.mem_copy(a, to, len);
-            }
-            Value::AddressRange(a, l) => {
-                if len.is_some() {
-                    assert_eq!(len, Some(l));
-                }
-                self.mem_copy(a, to, l);
+            (None, Some(_)) => {
+                return Err(Error::BadInstruction("result of called function is not used"));
             }
+        };
+        let stack_start = self.memory.len();
+        let depth = self.frames.len() + 2;
+        let registers = self.allocate_registers(&f.registers, depth)?;
+        for (param, val) in f.parameters.iter().zip(params.iter()) {
+            let (at, len) = match (registers.get(param), f.registers.get(param)) {
+                (Some(&at), Some(layout)) => (at, layout.size),
+                _ => return Err(Error::BadInstruction("parameter is not a register")),
+            };
+            let val = self.read_value(val)?;
+            self.write_value(at, Some(len), val)?;
+        }
+        let frame = StackFrame {
+            stack_start,
+            symbol: sym.clone(),
+            function: f,
+            switches: self.switches.get(sym).cloned().unwrap_or_default(),
+            registers,
+            current_block: block,
+            current_op: 0,
+            return_address,
+        };
+        self.frames.push(::std::mem::replace(&mut self.current_frame, frame));
+        Ok(())
+    }
+
+    /// Finds the function that a function pointer refers to.
+    fn function_by_id(&self, val: &ir::Value) -> Result<ir::Symbol, Error> {
+        match self.symbols_by_id.get(&self.load_32bit(val)?) {
+            Some(sym) => Ok(sym.clone()),
+            None => Err(Error::BadDeref),
         }
     }
 
@@ -327,310 +587,322 @@ impl<'a, R: Read, W: Write> Vm<'a, R, W> {
         match *i {
             ir::Instruction::Assign(reg, ref val) |
             ir::Instruction::CastAssign(reg, ref val) => {
-                let (to, len) = self.register_address(reg);
-                let val = self.read_value(val);
-                self.write_value(to, Some(len), val);
-                Ok(())
+                let (to, len) = self.register_address(reg)?;
+                let val = self.read_value(val)?;
+                self.write_value(to, Some(len), val)
+            }
+            ir::Instruction::ZeroExtend(reg, ref val) |
+            ir::Instruction::Truncate(reg, ref val) => {
+                let (to, len) = self.register_address(reg)?;
+                let val = self.load_int(val)?;
+                self.write_value(to, Some(len), int_value(val, len)?)
+            }
+            ir::Instruction::SignExtend(reg, ref val) => {
+                let (to, len) = self.register_address(reg)?;
+                let shift = 64 - 8 * self.int_size(val)?;
+                let val = ((self.load_int(val)? << shift) as i64 >> shift) as u64;
+                self.write_value(to, Some(len), int_value(val, len)?)
             }
             ir::Instruction::BinaryOp(dest, op, ref a, ref b) => {
-                let (to, len) = self.register_address(dest);
-                match op {
+                let (to, len) = self.register_address(dest)?;
+                let res = match op {
                     ir::BinaryOp::IntOp(op, sign, ir::Size::Bit8) => {
-                        let a = self.load_8bit(a);
-                        let b = self.load_8bit(b);
-                        let res = int_op_8(op, sign, a, b)?;
-                        self.write_value(to, Some(len), res);
-                        Ok(())
+                        int_op_8(op, sign, self.load_8bit(a)?, self.load_8bit(b)?)?
                     }
                     ir::BinaryOp::IntOp(op, sign, ir::Size::Bit16) => {
-                        let a = self.load_16bit(a);
-                        let b = self.load_16bit(b);
-                        let res = int_op_16(op, sign, a, b)?;
-                        self.write_value(to, Some(len), res);
-                        Ok(())
+                        int_op_16(op, sign, self.load_16bit(a)?, self.load_16bit(b)?)?
                     }
                     ir::BinaryOp::IntOp(op, sign, ir::Size::Bit32) => {
-                        let a = self.load_32bit(a);
-                        let b = self.load_32bit(b);
-                        let res = int_op_32(op, sign, a, b)?;
-                        self.write_value(to, Some(len), res);
-                        Ok(())
+                        int_op_32(op, sign, self.load_32bit(a)?, self.load_32bit(b)?)?
+                    }
+                    ir::BinaryOp::IntOp(op, sign, ir::Size::Bit64) => {
+                        int_op_64(op, sign, self.load_64bit(a)?, self.load_64bit(b)?)?
                     }
                     ir::BinaryOp::Eq => {
-                        assert_eq!(len, 1);
-                        let a = self.read_value(a);
-                        let b = self.read_value(b);
-                        let res = self.compare_values(a, b) as u8;
-                        self.memory[to as usize] = res;
-                        Ok(())
+                        let a = self.read_value(a)?;
+                        let b = self.read_value(b)?;
+                        Value::Byte(self.compare_values(a, b)? as u8)
                     }
                     ir::BinaryOp::Neq => {
-                        assert_eq!(len, 1);
-                        let a = self.read_value(a);
-                        let b = self.read_value(b);
-                        let res = !self.compare_values(a, b) as u8;
-                        self.memory[to as usize] = res;
-                        Ok(())
+                        let a = self.read_value(a)?;
+                        let b = self.read_value(b)?;
+                        Value::Byte(!self.compare_values(a, b)? as u8)
                     }
                     ir::BinaryOp::BitOp(op, ir::Size::Bit8) => {
-                        let a = self.load_8bit(a);
-                        let b = self.load_8bit(b);
-                        let res = bit_op_8(op, a, b);
-                        self.write_value(to, Some(len), res);
-                        Ok(())
+                        bit_op_8(op, self.load_8bit(a)?, self.load_8bit(b)?)
                     }
                     ir::BinaryOp::BitOp(op, ir::Size::Bit16) => {
-                        let a = self.load_16bit(a);
-                        let b = self.load_16bit(b);
-                        let res = bit_op_16(op, a, b);
-                        self.write_value(to, Some(len), res);
-                        Ok(())
+                        bit_op_16(op, self.load_16bit(a)?, self.load_16bit(b)?)
                     }
                     ir::BinaryOp::BitOp(op, ir::Size::Bit32) => {
-                        let a = self.load_32bit(a);
-                        let b = self.load_32bit(b);
-                        let res = bit_op_32(op, a, b);
-                        self.write_value(to, Some(len), res);
-                        Ok(())
+                        bit_op_32(op, self.load_32bit(a)?, self.load_32bit(b)?)
                     }
-                }
+                    ir::BinaryOp::BitOp(op, ir::Size::Bit64) => {
+                        bit_op_64(op, self.load_64bit(a)?, self.load_64bit(b)?)
+                    }
+                    ir::BinaryOp::ShiftOp(op, sign, ir::Size::Bit8) => {
+                        shift_op_8(op, sign, self.load_8bit(a)?, self.load_8bit(b)?)
+                    }
+                    ir::BinaryOp::ShiftOp(op, sign, ir::Size::Bit16) => {
+                        shift_op_16(op, sign, self.load_16bit(a)?, self.load_16bit(b)?)
+                    }
+                    ir::BinaryOp::ShiftOp(op, sign, ir::Size::Bit32) => {
+                        shift_op_32(op, sign, self.load_32bit(a)?, self.load_32bit(b)?)
+                    }
+                    ir::BinaryOp::ShiftOp(op, sign, ir::Size::Bit64) => {
+                        shift_op_64(op, sign, self.load_64bit(a)?, self.load_64bit(b)?)
+                    }
+                };
+                self.write_value(to, Some(len), res)
             }
             ir::Instruction::Call(dest, ref sym, ref params) => {
-                if "@plank_getc" == &*sym.0 {
-                    assert_eq!(params.len(), 0);
-                    let mut buf = [0];
-                    let result = match self.input.read(&mut buf)? {
-                        0 => !0u32,
-                        1 => buf[0] as u32,
-                        _ => panic!("wut"),
-                    };
-                    let (ret, _) = self.register_address(dest);
-                    self.write_value(ret, Some(4), Value::DoubleWord(result));
-                    return Ok(());
-                }
-                let f = &self.program.functions[sym];
-                let (ret, _) = self.register_address(dest);
-                let stack_start = self.memory.len();
-                let registers = self.allocate_registers(&f.registers);
-                assert_eq!(f.parameters.len(), params.len());
-                for (param, val) in f.parameters.iter().zip(params.iter()) {
-                    let at = registers[param];
-                    let len = f.registers[param].size;
-                    let val = self.read_value(val);
-                    self.write_value(at, Some(len), val);
-                }
-                let frame = StackFrame {
-                    stack_start,
-                    function: f,
-                    registers,
-                    current_block: f.start_block.unwrap(),
-                    current_op: 0,
-                    return_address: Some(ret),
-                };
-                self.frames.push(::std::mem::replace(&mut self.current_frame, frame));
-                Ok(())
-            }
-            ir::Instruction::CallProc(ref sym, ref params) => {
-                if "@plank_putc" == &*sym.0 {
-                    assert_eq!(params.len(), 1);
-                    let val = self.load_8bit(&params[0]);
-                    self.output.write_all(&[val])?;
-                    return Ok(());
-                }
-                let f = &self.program.functions[sym];
-                let stack_start = self.memory.len();
-                let registers = self.allocate_registers(&f.registers);
-                assert_eq!(f.parameters.len(), params.len());
-                for (param, val) in f.parameters.iter().zip(params.iter()) {
-                    let at = registers[param];
-                    let len = f.registers[param].size;
-                    let val = self.read_value(val);
-                    self.write_value(at, Some(len), val);
-                }
-                let frame = StackFrame {
-                    stack_start,
-                    function: f,
-                    registers,
-                    current_block: f.start_block.unwrap(),
-                    current_op: 0,
-                    return_address: None,
-                };
-                self.frames.push(::std::mem::replace(&mut self.current_frame, frame));
-                Ok(())
+                self.call(sym, params, Some(dest))
             }
+            ir::Instruction::CallProc(ref sym, ref params) => self.call(sym, params, None),
             ir::Instruction::CallVirt(dest, ref val, ref params) => {
-                let sym = &self.symbols_by_id[&self.load_32bit(val)].clone();
-                if "@plank_getc" == &*sym.0 {
-                    assert_eq!(params.len(), 0);
-                    let mut buf = [0];
-                    let result = match self.input.read(&mut buf)? {
-                        0 => !0u32,
-                        1 => buf[0] as u32,
-                        _ => panic!("wut"),
-                    };
-                    let (ret, _) = self.register_address(dest);
-                    self.write_value(ret, Some(4), Value::DoubleWord(result));
-                    return Ok(());
-                }
-                let f = &self.program.functions[sym];
-                let (ret, _) = self.register_address(dest);
-                let stack_start = self.memory.len();
-                let registers = self.allocate_registers(&f.registers);
-                assert_eq!(f.parameters.len(), params.len());
-                for (param, val) in f.parameters.iter().zip(params.iter()) {
-                    let at = registers[param];
-                    let len = f.registers[param].size;
-                    let val = self.read_value(val);
-                    self.write_value(at, Some(len), val);
-                }
-                let frame = StackFrame {
-                    stack_start,
-                    function: f,
-                    registers,
-                    current_block: f.start_block.ok_or(Error::BadDeref)?,
-                    current_op: 0,
-                    return_address: Some(ret),
-                };
-                self.frames.push(::std::mem::replace(&mut self.current_frame, frame));
-                Ok(())
+                let sym = self.function_by_id(val)?;
+                self.call(&sym, params, Some(dest))
             }
             ir::Instruction::CallProcVirt(ref val, ref params) => {
-                let sym = &self.symbols_by_id[&self.load_32bit(val)].clone();
-                if "@plank_putc" == &*sym.0 {
-                    assert_eq!(params.len(), 1);
-                    let val = self.load_8bit(&params[0]);
-                    self.output.write_all(&[val])?;
-                    return Ok(());
-                }
-                let f = &self.program.functions[sym];
-                let stack_start = self.memory.len();
-                let registers = self.allocate_registers(&f.registers);
-                assert_eq!(f.parameters.len(), params.len());
-                for (param, val) in f.parameters.iter().zip(params.iter()) {
-                    let at = registers[param];
-                    let len = f.registers[param].size;
-                    let val = self.read_value(val);
-                    self.write_value(at, Some(len), val);
-                }
-                let frame = StackFrame {
-                    stack_start,
-                    function: f,
-                    registers,
-                    current_block: f.start_block.ok_or(Error::BadDeref)?,
-                    current_op: 0,
-                    return_address: None,
-                };
-                self.frames.push(::std::mem::replace(&mut self.current_frame, frame));
-                Ok(())
+                let sym = self.function_by_id(val)?;
+                self.call(&sym, params, None)
             }
             ir::Instruction::DerefLoad(dest, ref address, offset) => {
-                let address = self.load_32bit(address) + offset;
-                let (to, len) = self.register_address(dest);
-                self.write_value(to, Some(len), Value::FromAddress(address));
-                Ok(())
+                let address = self.load_32bit(address)?.wrapping_add(offset);
+                let (to, len) = self.register_address(dest)?;
+                self.write_value(to, Some(len), Value::FromAddress(address))
             }
             ir::Instruction::DerefStore(ref address, offset, ref value) => {
-                let address = self.load_32bit(address) + offset;
-                let value = self.read_value(value);
-                self.write_value(address, None, value);
-                Ok(())
+                let address = self.load_32bit(address)?.wrapping_add(offset);
+                let value = self.read_value(value)?;
+                self.write_value(address, None, value)
             }
             ir::Instruction::Drop(_) |
             ir::Instruction::Init(_) => Ok(()),
             ir::Instruction::Load(dest, reg, offset) => {
-                let (to, len) = self.register_address(dest);
-                let (from, _) = self.register_address(reg);
-                self.mem_copy(from + offset, to, len);
-                Ok(())
+                let (to, len) = self.register_address(dest)?;
+                let (from, _) = self.register_address(reg)?;
+                self.mem_copy(from.wrapping_add(offset), to, len)
             }
             ir::Instruction::Store(dest, offset, ref value) => {
-                let (to, _) = self.register_address(dest);
-                let value = self.read_value(value);
-                self.write_value(to + offset, None, value);
-                Ok(())
+                let (to, _) = self.register_address(dest)?;
+                let value = self.read_value(value)?;
+                self.write_value(to.wrapping_add(offset), None, value)
             }
             ir::Instruction::TakeAddress(dest, reg, offset) => {
-                let (to, _) = self.register_address(dest);
-                let reg_at = self.current_frame.registers[&reg];
-                let value = Value::DoubleWord(reg_at + offset);
-                self.write_value(to, None, value);
-                Ok(())
+                let (to, len) = self.register_address(dest)?;
+                let (reg_at, _) = self.register_address(reg)?;
+                let value = Value::DoubleWord(reg_at.wrapping_add(offset));
+                self.write_value(to, Some(len), value)
             }
             ir::Instruction::UnaryOp(reg, ir::UnaryOp::Negate(_, size), ref value) => {
-                let (to, len) = self.register_address(reg);
-                match size {
-                    ir::Size::Bit8 => {
-                        let val = self.load_8bit(value);
-                        let res = (!val).wrapping_add(1);
-                        self.write_value(to, Some(len), Value::Byte(res));
+                let (to, len) = self.register_address(reg)?;
+                let res = match size {
+                    ir::Size::Bit8 => Value::Byte(self.load_8bit(value)?.wrapping_neg()),
+                    ir::Size::Bit16 => Value::Word(self.load_16bit(value)?.wrapping_neg()),
+                    ir::Size::Bit32 => Value::DoubleWord(self.load_32bit(value)?.wrapping_neg()),
+                    ir::Size::Bit64 => Value::QuadWord(self.load_64bit(value)?.wrapping_neg()),
+                };
+                self.write_value(to, Some(len), res)
+            }
+        }
+    }
+
+    /// Executes one instruction or block terminator. Returns exit
+    /// code once `main` returns.
+    fn step(&mut self) -> Result<Option<i32>, Error> {
+        let block = self.current_block()?;
+        if self.current_frame.current_op < block.ops.len() {
+            let op = &block.ops[self.current_frame.current_op];
+            self.current_frame.current_op += 1;
+            self.run_op(op)?;
+            return Ok(None);
+        }
+        match block.end {
+            ir::BlockEnd::Jump(block) => {
+                self.current_frame.current_block = block;
+                self.current_frame.current_op = 0;
+            }
+            ir::BlockEnd::Branch(ref val, a, b) => {
+                let cond = self.load_8bit(val)?;
+                self.current_frame.current_op = 0;
+                if cond != 0 {
+                    self.current_frame.current_block = a;
+                } else {
+                    self.current_frame.current_block = b;
+                }
+            }
+            ir::BlockEnd::Switch(ref val, _, default) => {
+                let value = self.load_int(val)?;
+                let target = {
+                    let frame = &self.current_frame;
+                    let cases = match frame.switches.get(&frame.current_block) {
+                        Some(cases) => cases,
+                        None => return Err(Error::BadInstruction("switch was not prepared")),
+                    };
+                    match cases.binary_search_by_key(&value, |&(case, _)| case) {
+                        Ok(index) => cases[index].1,
+                        Err(_) => default,
                     }
-                    ir::Size::Bit16 => {
-                        let val = self.load_16bit(value);
-                        let res = (!val).wrapping_add(1);
-                        self.write_value(to, Some(len), Value::Word(res));
+                };
+                self.current_frame.current_op = 0;
+                self.current_frame.current_block = target;
+            }
+            ir::BlockEnd::Return(ref val) => {
+                let val = self.read_value(val)?;
+                let len = match self.current_frame.function.output_layout {
+                    Some(layout) => layout.size,
+                    None => return Err(Error::BadInstruction("procedure returns a value")),
+                };
+                let to = match self.current_frame.return_address {
+                    Some(to) => to,
+                    None => {
+                        return Err(Error::BadInstruction("result of called function is not used"));
                     }
-                    ir::Size::Bit32 => {
-                        let val = self.load_32bit(value);
-                        let res = (!val).wrapping_add(1);
-                        self.write_value(to, Some(len), Value::DoubleWord(res));
+                };
+                self.write_value(to, Some(len), val)?;
+                self.memory.truncate(self.current_frame.stack_start);
+                match self.frames.pop() {
+                    Some(frame) => self.current_frame = frame,
+                    None => {
+                        let bytes = self.read_bytes(0, 4)?;
+                        let code = bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | u32::from(b));
+                        return Ok(Some(code as i32));
                     }
                 }
-                Ok(())
+            }
+            ir::BlockEnd::ReturnProc => {
+                if self.current_frame.return_address.is_some() {
+                    return Err(Error::BadInstruction("function did not return a value"));
+                }
+                self.memory.truncate(self.current_frame.stack_start);
+                match self.frames.pop() {
+                    Some(frame) => self.current_frame = frame,
+                    None => return Err(Error::InvalidMain),
+                }
             }
         }
+        Ok(None)
     }
 
     fn run(&mut self) -> Result<i32, Error> {
         loop {
-            let block = self.current_block();
-            if self.current_frame.current_op == block.ops.len() {
-                match block.end {
-                    ir::BlockEnd::Jump(block) => {
-                        self.current_frame.current_block = block;
-                        self.current_frame.current_op = 0;
-                    }
-                    ir::BlockEnd::Branch(ref val, a, b) => {
-                        self.current_frame.current_op = 0;
-                        if self.load_8bit(val) != 0 {
-                            self.current_frame.current_block = a;
-                        } else {
-                            self.current_frame.current_block = b;
-                        }
-                    }
-                    ir::BlockEnd::Return(ref val) => {
-                        let val = self.read_value(val);
-                        let len = self.current_frame.function.output_layout.unwrap().size;
-                        let to = self.current_frame.return_address.unwrap();
-                        self.write_value(to, Some(len), val);
-                        match self.frames.pop() {
-                            Some(frame) => self.current_frame = frame,
-                            None => return Ok({
-                                let b1 = (self.memory[0] as u32) << 0;
-                                let b2 = (self.memory[1] as u32) << 8;
-                                let b3 = (self.memory[2] as u32) << 16;
-                                let b4 = (self.memory[3] as u32) << 24;
-                                (b1 | b2 | b3 | b4) as i32
-                            }),
-                        }
-                    }
-                    ir::BlockEnd::ReturnProc => {
-                        assert!(self.current_frame.return_address.is_none());
-                        self.memory.truncate(self.current_frame.stack_start);
-                        match self.frames.pop() {
-                            Some(frame) => self.current_frame = frame,
-                            None => panic!("main did not return a value"),
-                        }
-                    }
+            let block = self.current_frame.current_block;
+            let instruction = self.current_frame.current_op;
+            match self.step() {
+                Ok(Some(code)) => return Ok(code),
+                Ok(None) => {}
+                Err(err) => {
+                    // failed steps never leave the frame they started in
+                    let location = Location {
+                        function: self.current_frame.symbol.clone(),
+                        block,
+                        instruction,
+                    };
+                    return Err(Error::At(location, Box::new(err)));
                 }
-            } else {
-                let op = &block.ops[self.current_frame.current_op];
-                self.current_frame.current_op += 1;
-                self.run_op(op)?;
             }
         }
     }
 }
 
+/// Sorts cases of every switch in a function, so that they
+/// can be binary searched.
+fn switch_table(f: &ir::Function) -> SwitchTable {
+    f.blocks
+        .iter()
+        .filter_map(|(&id, block)| match block.end {
+            ir::BlockEnd::Switch(_, ref cases, _) => {
+                let mut cases = cases.clone();
+                cases.sort_by_key(|&(case, _)| case);
+                Some((id, cases))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Writes NUL-terminated arguments and a null-terminated array
+/// of pointers to them at the end of memory. Returns address of
+/// the pointer array.
+fn layout_args(memory: &mut Vec<u8>, args: &[String]) -> u32 {
+    let mut pointers = Vec::new();
+    for arg in args {
+        pointers.push(memory.len() as u32);
+        memory.extend(arg.bytes());
+        memory.push(0);
+    }
+    while !memory.len().is_multiple_of(4) {
+        memory.push(0);
+    }
+    let argv = memory.len() as u32;
+    for pointer in pointers.into_iter().chain(Some(0)) {
+        memory.extend(&pointer.to_le_bytes());
+    }
+    argv
+}
+
+fn int_op_64(op: ir::IntOp, sign: ir::Signedness, a: u64, b: u64) -> Result<Value, Error> {
+    match (op, sign) {
+        (ir::IntOp::Add, _) => Ok(Value::QuadWord(a.wrapping_add(b))),
+        (ir::IntOp::Sub, _) => Ok(Value::QuadWord(a.wrapping_sub(b))),
+        (ir::IntOp::Greater, ir::Signedness::Unsigned) => {
+            Ok(Value::Byte((a > b) as u8))
+        }
+        (ir::IntOp::Greater, ir::Signedness::Signed) => {
+            Ok(Value::Byte((a as i64 > b as i64) as u8))
+        }
+        (ir::IntOp::GreaterEq, ir::Signedness::Unsigned) => {
+            Ok(Value::Byte((a >= b) as u8))
+        }
+        (ir::IntOp::GreaterEq, ir::Signedness::Signed) => {
+            Ok(Value::Byte((a as i64 >= b as i64) as u8))
+        }
+        (ir::IntOp::Less, ir::Signedness::Unsigned) => {
+            Ok(Value::Byte((a < b) as u8))
+        }
+        (ir::IntOp::Less, ir::Signedness::Signed) => {
+            Ok(Value::Byte(((a as i64) < b as i64) as u8))
+        }
+        (ir::IntOp::LessEq, ir::Signedness::Unsigned) => {
+            Ok(Value::Byte((a <= b) as u8))
+        }
+        (ir::IntOp::LessEq, ir::Signedness::Signed) => {
+            Ok(Value::Byte((a as i64 <= b as i64) as u8))
+        }
+        (ir::IntOp::Mul, ir::Signedness::Unsigned) => {
+            Ok(Value::QuadWord(a.wrapping_mul(b)))
+        }
+        (ir::IntOp::Mul, ir::Signedness::Signed) => {
+            Ok(Value::QuadWord((a as i64).wrapping_mul(b as i64) as u64))
+        }
+        (ir::IntOp::Div, ir::Signedness::Unsigned) if b == 0 => {
+            Err(Error::DivisionByZero)
+        }
+        (ir::IntOp::Div, ir::Signedness::Unsigned) => {
+            Ok(Value::QuadWord(a / b))
+        }
+        (ir::IntOp::Div, ir::Signedness::Signed) if b as i64 == 0 => {
+            Err(Error::DivisionByZero)
+        }
+        (ir::IntOp::Div, ir::Signedness::Signed) => {
+            Ok(Value::QuadWord((a as i64).wrapping_div(b as i64) as u64))
+        }
+        (ir::IntOp::Mod, ir::Signedness::Unsigned) if b == 0 => {
+            Err(Error::DivisionByZero)
+        }
+        (ir::IntOp::Mod, ir::Signedness::Unsigned) => {
+            Ok(Value::QuadWord(a % b))
+        }
+        (ir::IntOp::Mod, ir::Signedness::Signed) if b as i64 == 0 => {
+            Err(Error::DivisionByZero)
+        }
+        (ir::IntOp::Mod, ir::Signedness::Signed) => {
+            Ok(Value::QuadWord((a as i64).wrapping_rem(b as i64) as u64))
+        }
+    }
+}
+
 fn int_op_32(op: ir::IntOp, sign: ir::Signedness, a: u32, b: u32) -> Result<Value, Error> {
     match (op, sign) {
         (ir::IntOp::Add, _) => Ok(Value::DoubleWord(a.wrapping_add(b))),
@@ -814,6 +1086,14 @@ fn int_op_8(op: ir::IntOp, sign: ir::Signedness, a: u8, b: u8) -> Result<Value,
     }
 }
 
+fn bit_op_64(op: ir::BitOp, a: u64, b: u64) -> Value {
+    match op {
+        ir::BitOp::And => Value::QuadWord(a & b),
+        ir::BitOp::Or => Value::QuadWord(a | b),
+        ir::BitOp::Xor => Value::QuadWord(a ^ b),
+    }
+}
+
 fn bit_op_32(op: ir::BitOp, a: u32, b: u32) -> Value {
     match op {
         ir::BitOp::And => Value::DoubleWord(a & b),
@@ -838,68 +1118,68 @@ fn bit_op_8(op: ir::BitOp, a: u8, b: u8) -> Value {
     }
 }
 
-fn collect_strings(i: &ir::Instruction, strings: &mut HashMap<Vec<u8>, u32>, mem: &mut Vec<u8>) {
-    match *i {
-        ir::Instruction::Assign(_, ir::Value::Bytes(ref s)) |
-        ir::Instruction::CastAssign(_, ir::Value::Bytes(ref s)) |
-        ir::Instruction::DerefLoad(_, ir::Value::Bytes(ref s), _) |
-        ir::Instruction::Store(_, _, ir::Value::Bytes(ref s)) |
-        ir::Instruction::UnaryOp(_, _, ir::Value::Bytes(ref s)) => {
-            if !strings.contains_key(s) {
-                let at = mem.len() as u32;
-                mem.extend(s.iter().cloned());
-                strings.insert(s.clone(), at);
-            }
-        }
-        ir::Instruction::Call(_, _, ref params) |
-        ir::Instruction::CallProc(_, ref params) => {
-            for param in params {
-                if let ir::Value::Bytes(ref s) = *param {
-                    if !strings.contains_key(s) {
-                        let at = mem.len() as u32;
-                        mem.extend(s.iter().cloned());
-                        strings.insert(s.clone(), at);
-                    }
-                }
-            }
+// Shift amount is treated as unsigned, shifting by bit width
+// or more gives 0 (or all sign bits for signed right shift).
+fn shift_op_64(op: ir::ShiftOp, sign: ir::Signedness, a: u64, b: u64) -> Value {
+    // amounts that don't fit in u32 are larger than bit width anyway
+    let b = if b > 64 { 64 } else { b as u32 };
+    match (op, sign) {
+        (ir::ShiftOp::Left, _) => Value::QuadWord(a.checked_shl(b).unwrap_or(0)),
+        (ir::ShiftOp::Right, ir::Signedness::Unsigned) => {
+            Value::QuadWord(a.checked_shr(b).unwrap_or(0))
         }
-        ir::Instruction::CallVirt(_, ref val, ref params) |
-        ir::Instruction::CallProcVirt(ref val, ref params) => {
-            if let ir::Value::Bytes(ref s) = *val {
-                if !strings.contains_key(s) {
-                    let at = mem.len() as u32;
-                    mem.extend(s.iter().cloned());
-                    strings.insert(s.clone(), at);
-                }
-            }
-            for param in params {
-                if let ir::Value::Bytes(ref s) = *param {
-                    if !strings.contains_key(s) {
-                        let at = mem.len() as u32;
-                        mem.extend(s.iter().cloned());
-                        strings.insert(s.clone(), at);
-                    }
-                }
-            }
+        (ir::ShiftOp::Right, ir::Signedness::Signed) => {
+            Value::QuadWord(((a as i64) >> b.min(63)) as u64)
         }
-        ir::Instruction::BinaryOp(_, _, ref a, ref b) |
-        ir::Instruction::DerefStore(ref a, _, ref b) => {
-            if let ir::Value::Bytes(ref s) = *a {
-                if !strings.contains_key(s) {
-                    let at = mem.len() as u32;
-                    mem.extend(s.iter().cloned());
-                    strings.insert(s.clone(), at);
-                }
-            }
-            if let ir::Value::Bytes(ref s) = *b {
-                if !strings.contains_key(s) {
-                    let at = mem.len() as u32;
-                    mem.extend(s.iter().cloned());
-                    strings.insert(s.clone(), at);
-                }
-            }
+    }
+}
+
+fn shift_op_32(op: ir::ShiftOp, sign: ir::Signedness, a: u32, b: u32) -> Value {
+    match (op, sign) {
+        (ir::ShiftOp::Left, _) => Value::DoubleWord(a.checked_shl(b).unwrap_or(0)),
+        (ir::ShiftOp::Right, ir::Signedness::Unsigned) => {
+            Value::DoubleWord(a.checked_shr(b).unwrap_or(0))
         }
-        _ => {}
+        (ir::ShiftOp::Right, ir::Signedness::Signed) => {
+            Value::DoubleWord(((a as i32) >> b.min(31)) as u32)
+        }
+    }
+}
+
+fn shift_op_16(op: ir::ShiftOp, sign: ir::Signedness, a: u16, b: u16) -> Value {
+    let b = u32::from(b);
+    match (op, sign) {
+        (ir::ShiftOp::Left, _) => Value::Word(a.checked_shl(b).unwrap_or(0)),
+        (ir::ShiftOp::Right, ir::Signedness::Unsigned) => {
+            Value::Word(a.checked_shr(b).unwrap_or(0))
+        }
+        (ir::ShiftOp::Right, ir::Signedness::Signed) => {
+            Value::Word(((a as i16) >> b.min(15)) as u16)
+        }
+    }
+}
+
+fn shift_op_8(op: ir::ShiftOp, sign: ir::Signedness, a: u8, b: u8) -> Value {
+    let b = u32::from(b);
+    match (op, sign) {
+        (ir::ShiftOp::Left, _) => Value::Byte(a.checked_shl(b).unwrap_or(0)),
+        (ir::ShiftOp::Right, ir::Signedness::Unsigned) => {
+            Value::Byte(a.checked_shr(b).unwrap_or(0))
+        }
+        (ir::ShiftOp::Right, ir::Signedness::Signed) => {
+            Value::Byte(((a as i8) >> b.min(7)) as u8)
+        }
+    }
+}
+
+/// Makes a value of given size from lowest bytes of `val`.
+fn int_value(val: u64, size: u32) -> Result<Value, Error> {
+    match size {
+        1 => Ok(Value::Byte(val as u8)),
+        2 => Ok(Value::Word(val as u16)),
+        4 => Ok(Value::DoubleWord(val as u32)),
+        8 => Ok(Value::QuadWord(val)),
+        _ => Err(Error::BadInstruction("bad int size")),
     }
 }
 
@@ -11,6 +11,14 @@ pub struct Symbol(pub Rc<str>);
 #[derive(Debug, Clone)]
 pub struct Program {
     pub functions: HashMap<Symbol, Function>,
+    pub globals: HashMap<Symbol, Global>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub layout: Layout,
+    /// Initial contents, `layout.size` bytes long.
+    pub value: Vec<u8>,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -72,6 +80,12 @@ pub enum Instruction {
     Assign(Reg, Value),
     /// `reg = cast value`
     CastAssign(Reg, Value),
+    /// `reg = zext value`, register is bigger than value
+    ZeroExtend(Reg, Value),
+    /// `reg = sext value`, register is bigger than value
+    SignExtend(Reg, Value),
+    /// `reg = trunc value`, register is smaller than value
+    Truncate(Reg, Value),
 }
 
 #[derive(Debug, Clone)]
@@ -79,7 +93,8 @@ pub enum Value {
     Int(u64, Size),
     Reg(Reg),
     Symbol(Symbol),
-    Bytes(Vec<u8>),
+    /// Address of a global
+    Global(Symbol),
 }
 
 #[derive(Debug, Clone)]
@@ -88,12 +103,16 @@ pub enum BlockEnd {
     ReturnProc,
     Jump(BlockId),
     Branch(Value, BlockId, BlockId),
+    /// Jumps to the block of the case equal to value, or to
+    /// the default block if there is no such case.
+    Switch(Value, Vec<(u64, BlockId)>, BlockId),
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum BinaryOp {
     IntOp(IntOp, Signedness, Size),
     BitOp(BitOp, Size),
+    ShiftOp(ShiftOp, Signedness, Size),
     Eq,
     Neq,
 }
@@ -109,6 +128,7 @@ pub enum Size {
     Bit8,
     Bit16,
     Bit32,
+    Bit64,
 }
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
@@ -131,6 +151,13 @@ pub enum BitOp {
     Xor,
 }
 
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum ShiftOp {
+    Left,
+    /// Arithmetic shift for signed operands, logical for unsigned.
+    Right,
+}
+
 #[derive(Debug, Clone)]
 pub enum UnaryOp {
     Negate(Signedness, Size),
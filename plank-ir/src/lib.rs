@@ -4,4 +4,4 @@ mod validation;
 
 pub use ir::Program;
 pub use printer::emit_program;
-pub use validation::validate_ir;
+pub use validation::{validate_ir, ValidationError};
@@ -2,6 +2,20 @@ use std::io::{self, Write};
 use ir;
 
 pub fn emit_program<W: Write>(program: &ir::Program, mut out: W) -> io::Result<()> {
+    for (name, global) in &program.globals {
+        write!(
+            out,
+            "global {}: size {}, align {} = ",
+            name.0,
+            global.layout.size,
+            global.layout.align
+        )?;
+        emit_bytes(&global.value, &mut out)?;
+        writeln!(out)?;
+    }
+    if !program.globals.is_empty() {
+        writeln!(out)?;
+    }
     for (name, func) in &program.functions {
         write!(out, "function {}", name.0)?;
         emit_function(func, &mut out)?;
@@ -51,6 +65,14 @@ fn emit_function<W: Write>(func: &ir::Function, out: &mut W) -> io::Result<()> {
                 emit_value(val, out)?;
                 writeln!(out, " label_{} label_{}", a.0, b.0)?;
             }
+            ir::BlockEnd::Switch(ref val, ref cases, default) => {
+                write!(out, "    switch ")?;
+                emit_value(val, out)?;
+                for &(value, id) in cases {
+                    write!(out, " {} => label_{},", value, id.0)?;
+                }
+                writeln!(out, " _ => label_{}", default.0)?;
+            }
             ir::BlockEnd::Jump(id) => {
                 writeln!(out, "    goto label_{}", id.0)?;
             }
@@ -79,6 +101,21 @@ fn emit_instruction<W: Write>(i: &ir::Instruction, out: &mut W) -> io::Result<()
             emit_value(val, out)?;
             writeln!(out)
         }
+        ir::Instruction::ZeroExtend(reg, ref val) => {
+            write!(out, "    %{} = zext ", reg.0)?;
+            emit_value(val, out)?;
+            writeln!(out)
+        }
+        ir::Instruction::SignExtend(reg, ref val) => {
+            write!(out, "    %{} = sext ", reg.0)?;
+            emit_value(val, out)?;
+            writeln!(out)
+        }
+        ir::Instruction::Truncate(reg, ref val) => {
+            write!(out, "    %{} = trunc ", reg.0)?;
+            emit_value(val, out)?;
+            writeln!(out)
+        }
         ir::Instruction::BinaryOp(dest, op, ref a, ref b) => {
             write!(out, "    %{} = ", dest.0)?;
             emit_binop(op, out)?;
@@ -148,25 +185,28 @@ fn emit_instruction<W: Write>(i: &ir::Instruction, out: &mut W) -> io::Result<()
     }
 }
 
+fn emit_bytes<W: Write>(bytes: &[u8], out: &mut W) -> io::Result<()> {
+    write!(out, "\"")?;
+    for &byte in bytes {
+        if byte < 32 || byte > 126 {
+            write!(out, "\\x{:0>2x}", byte)?;
+        } else if byte == b'"' {
+            write!(out, "\\\"")?;
+        } else {
+            write!(out, "{}", byte as char)?;
+        }
+    }
+    write!(out, "\"")
+}
+
 fn emit_value<W: Write>(val: &ir::Value, out: &mut W) -> io::Result<()> {
     match *val {
-        ir::Value::Bytes(ref bytes) => {
-            write!(out, "\"")?;
-            for &byte in bytes {
-                if byte < 32 || byte > 126 {
-                    write!(out, "\\x{:0>2x}", byte)?;
-                } else if byte == b'"' {
-                    write!(out, "\\\"")?;
-                } else {
-                    write!(out, "{}", byte as char)?;
-                }
-            }
-            write!(out, "\"")
-        }
+        ir::Value::Global(ref sym) => write!(out, "&{}", sym.0),
         ir::Value::Int(value, size) => match size {
             ir::Size::Bit8 => write!(out, "{}_b8", value),
             ir::Size::Bit16 => write!(out, "{}_b16", value),
             ir::Size::Bit32 => write!(out, "{}_b32", value),
+            ir::Size::Bit64 => write!(out, "{}_b64", value),
         },
         ir::Value::Reg(reg) => write!(out, "%{}", reg.0),
         ir::Value::Symbol(ref sym) => write!(out, "{}", sym.0),
@@ -187,6 +227,16 @@ fn emit_binop<W: Write>(op: ir::BinaryOp, out: &mut W) -> io::Result<()> {
             write!(out, "xor_")?;
             emit_size(s, out)
         }
+        ir::BinaryOp::ShiftOp(ir::ShiftOp::Left, sign, size) => {
+            write!(out, "shl_")?;
+            emit_sign(sign, out)?;
+            emit_size(size, out)
+        }
+        ir::BinaryOp::ShiftOp(ir::ShiftOp::Right, sign, size) => {
+            write!(out, "shr_")?;
+            emit_sign(sign, out)?;
+            emit_size(size, out)
+        }
         ir::BinaryOp::Eq => write!(out, "eq"),
         ir::BinaryOp::Neq => write!(out, "neq"),
         ir::BinaryOp::IntOp(ir::IntOp::Add, sign, size) => {
@@ -249,6 +299,7 @@ fn emit_size<W: Write>(size: ir::Size, out: &mut W) -> io::Result<()> {
         ir::Size::Bit8 => write!(out, "8"),
         ir::Size::Bit16 => write!(out, "16"),
         ir::Size::Bit32 => write!(out, "32"),
+        ir::Size::Bit64 => write!(out, "64"),
     }
 }
 
@@ -1,168 +1,327 @@
-use std::collections::HashMap;
-use ir::{BinaryOp, Block, BlockEnd, Function, Instruction, IntOp, Program, Reg, Size, Symbol,
-         UnaryOp, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use ir::{BinaryOp, BlockEnd, BlockId, Function, Global, Instruction, IntOp, Program, Reg, Size,
+         Symbol, UnaryOp, Value};
 
 
+/// Describes why a program is not valid IR.
+#[derive(Debug, Clone)]
+pub struct ValidationError {
+    /// Function or global that is invalid.
+    pub item: Symbol,
+    /// Block and index of the invalid instruction. Block terminator
+    /// has index equal to the number of instructions in the block.
+    pub location: Option<(BlockId, usize)>,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location {
+            Some((block, index)) => write!(
+                f,
+                "invalid IR in `{}` at label_{}, instruction {}: {}",
+                self.item.0,
+                block.0,
+                index,
+                self.message,
+            ),
+            None => write!(f, "invalid IR in `{}`: {}", self.item.0, self.message),
+        }
+    }
+}
+
+type Check<T> = Result<T, &'static str>;
+
+macro_rules! check {
+    ($cond:expr, $msg:expr) => {
+        if !$cond {
+            return Err($msg);
+        }
+    };
+}
+
 struct Context<'a> {
     functions: &'a HashMap<Symbol, Function>,
+    globals: &'a HashMap<Symbol, Global>,
+    name: &'a Symbol,
     function: &'a Function,
 }
 
 impl<'a> Context<'a> {
-    fn new(program: &'a Program, function: &'a Function) -> Self {
+    fn new(program: &'a Program, name: &'a Symbol, function: &'a Function) -> Self {
         Context {
             functions: &program.functions,
+            globals: &program.globals,
+            name,
             function,
         }
     }
 
-    fn validate(&self) {
+    fn error(&self, location: Option<(BlockId, usize)>, message: &'static str) -> ValidationError {
+        ValidationError {
+            item: self.name.clone(),
+            location,
+            message,
+        }
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        self.validate_signature().map_err(|msg| self.error(None, msg))?;
+        // check blocks in order, so that the same error is always reported
+        let mut blocks = self.function.blocks.iter().collect::<Vec<_>>();
+        blocks.sort_by_key(|&(&id, _)| id);
+        for (&id, block) in blocks {
+            for (index, op) in block.ops.iter().enumerate() {
+                self.validate_instruction(op)
+                    .map_err(|msg| self.error(Some((id, index)), msg))?;
+            }
+            self.validate_block_end(&block.end)
+                .map_err(|msg| self.error(Some((id, block.ops.len())), msg))?;
+        }
+        Ok(())
+    }
+
+    fn validate_signature(&self) -> Check<()> {
         if let Some(layout) = self.function.output_layout {
-            assert!(layout.size > 0);
+            check!(layout.size > 0, "output has zero size");
         }
         for &layout in self.function.registers.values() {
-            assert!(layout.size > 0);
+            check!(layout.size > 0, "register has zero size");
         }
         for &reg in &self.function.parameters {
-            assert!(self.function.registers.contains_key(&reg));
+            check!(self.function.registers.contains_key(&reg), "parameter is not a register");
         }
         if let Some(block) = self.function.start_block {
-            assert!(self.function.blocks.contains_key(&block));
-        }
-        for block in self.function.blocks.values() {
-            self.validate_block(block);
+            check!(self.function.blocks.contains_key(&block), "start block does not exist");
         }
+        Ok(())
     }
 
-    fn validate_block(&self, block: &Block) {
-        for op in &block.ops {
-            self.validate_instruction(op);
-        }
-        match block.end {
+    fn validate_block_end(&self, end: &BlockEnd) -> Check<()> {
+        match *end {
             BlockEnd::Branch(ref val, a, b) => {
-                assert_eq!(self.value_size(val), 1);
-                assert!(self.function.blocks.contains_key(&a));
-                assert!(self.function.blocks.contains_key(&b));
+                check!(self.value_size(val)? == 1, "branch condition must be 1 byte");
+                self.check_block(a)?;
+                self.check_block(b)?;
+            }
+            BlockEnd::Switch(ref val, ref cases, default) => {
+                let size = self.value_size(val)?;
+                check!(is_int_size(size), "switch value must be an integer");
+                let mut values = HashSet::new();
+                for &(value, block) in cases {
+                    check!(size == 8 || value >> (size * 8) == 0, "switch case does not fit value");
+                    check!(values.insert(value), "duplicate switch case");
+                    self.check_block(block)?;
+                }
+                self.check_block(default)?;
             }
             BlockEnd::Jump(block) => {
-                assert!(self.function.blocks.contains_key(&block));
+                self.check_block(block)?;
             }
             BlockEnd::Return(ref val) => {
-                let return_size = self.function.output_layout.unwrap().size;
-                assert_eq!(self.value_size(val), return_size);
+                let return_size = match self.function.output_layout {
+                    Some(layout) => layout.size,
+                    None => return Err("procedure returns a value"),
+                };
+                check!(self.value_size(val)? == return_size, "returned value has wrong size");
             }
             BlockEnd::ReturnProc => {
-                assert!(self.function.output_layout.is_none());
+                check!(self.function.output_layout.is_none(), "function does not return a value");
             }
         }
+        Ok(())
     }
 
-    fn validate_instruction(&self, i: &Instruction) {
+    fn validate_instruction(&self, i: &Instruction) -> Check<()> {
         match *i {
             Instruction::Assign(reg, ref val) |
             Instruction::CastAssign(reg, ref val) => {
-                assert_eq!(self.register_size(reg), self.value_size(val));
+                check!(
+                    self.register_size(reg)? == self.value_size(val)?,
+                    "assigned value has wrong size"
+                );
+            }
+            Instruction::ZeroExtend(reg, ref val) |
+            Instruction::SignExtend(reg, ref val) => {
+                let reg_size = self.register_size(reg)?;
+                let val_size = self.value_size(val)?;
+                check!(is_int_size(reg_size), "register is not an integer");
+                check!(is_int_size(val_size), "value is not an integer");
+                check!(reg_size > val_size, "extended value is not smaller than register");
+            }
+            Instruction::Truncate(reg, ref val) => {
+                let reg_size = self.register_size(reg)?;
+                let val_size = self.value_size(val)?;
+                check!(is_int_size(reg_size), "register is not an integer");
+                check!(is_int_size(val_size), "value is not an integer");
+                check!(reg_size < val_size, "truncated value is not bigger than register");
             }
             Instruction::BinaryOp(dest, BinaryOp::IntOp(IntOp::Greater, _, size), ref a, ref b) |
             Instruction::BinaryOp(dest, BinaryOp::IntOp(IntOp::GreaterEq, _, size), ref a, ref b) |
             Instruction::BinaryOp(dest, BinaryOp::IntOp(IntOp::Less, _, size), ref a, ref b) |
             Instruction::BinaryOp(dest, BinaryOp::IntOp(IntOp::LessEq, _, size), ref a, ref b) => {
-                assert_eq!(self.register_size(dest), 1);
-                assert_eq!(self.value_size(a), in_bytes(size));
-                assert_eq!(self.value_size(b), in_bytes(size));
+                check!(self.register_size(dest)? == 1, "comparison result must be 1 byte");
+                check!(self.value_size(a)? == in_bytes(size), "operand has wrong size");
+                check!(self.value_size(b)? == in_bytes(size), "operand has wrong size");
             }
             Instruction::BinaryOp(dest, BinaryOp::BitOp(_, size), ref a, ref b) |
+            Instruction::BinaryOp(dest, BinaryOp::ShiftOp(_, _, size), ref a, ref b) |
             Instruction::BinaryOp(dest, BinaryOp::IntOp(_, _, size), ref a, ref b) => {
-                assert_eq!(self.register_size(dest), in_bytes(size));
-                assert_eq!(self.value_size(a), in_bytes(size));
-                assert_eq!(self.value_size(b), in_bytes(size));
+                check!(self.register_size(dest)? == in_bytes(size), "result has wrong size");
+                check!(self.value_size(a)? == in_bytes(size), "operand has wrong size");
+                check!(self.value_size(b)? == in_bytes(size), "operand has wrong size");
             }
             Instruction::BinaryOp(dest, BinaryOp::Eq, ref a, ref b) |
             Instruction::BinaryOp(dest, BinaryOp::Neq, ref a, ref b) => {
-                assert_eq!(self.register_size(dest), 1);
-                assert_eq!(self.value_size(a), self.value_size(b));
+                check!(self.register_size(dest)? == 1, "comparison result must be 1 byte");
+                check!(
+                    self.value_size(a)? == self.value_size(b)?,
+                    "compared values have different sizes"
+                );
             }
             Instruction::Call(dest, ref sym, ref params) => {
-                let callee = &self.functions[sym];
-                let out_size = callee.output_layout.unwrap().size;
-                assert_eq!(self.register_size(dest), out_size);
-                assert_eq!(params.len(), callee.parameters.len());
-                for (val, &reg) in params.iter().zip(callee.parameters.iter()) {
-                    let reg_size = callee.registers[&reg].size;
-                    assert_eq!(self.value_size(val), reg_size);
-                }
+                let callee = self.function(sym)?;
+                let out_size = match callee.output_layout {
+                    Some(layout) => layout.size,
+                    None => return Err("called function does not return a value"),
+                };
+                check!(self.register_size(dest)? == out_size, "result register has wrong size");
+                self.check_params(callee, params)?;
             }
             Instruction::CallProc(ref sym, ref params) => {
-                let callee = &self.functions[sym];
-                assert!(callee.output_layout.is_none());
-                assert_eq!(params.len(), callee.parameters.len());
-                for (val, &reg) in params.iter().zip(callee.parameters.iter()) {
-                    let reg_size = callee.registers[&reg].size;
-                    assert_eq!(self.value_size(val), reg_size);
+                let callee = self.function(sym)?;
+                check!(callee.output_layout.is_none(), "result of called function is not used");
+                self.check_params(callee, params)?;
+            }
+            Instruction::CallVirt(dest, ref address, ref params) => {
+                self.register_size(dest)?;
+                check!(self.value_size(address)? == ::ir::FUNCTION_SIZE, "bad function pointer size");
+                for param in params {
+                    self.value_size(param)?;
                 }
             }
-            Instruction::CallVirt(_, ref address, _) |
-            Instruction::CallProcVirt(ref address, _) => {
-                assert_eq!(self.value_size(address), ::ir::POINTER_SIZE);
+            Instruction::CallProcVirt(ref address, ref params) => {
+                check!(self.value_size(address)? == ::ir::FUNCTION_SIZE, "bad function pointer size");
+                for param in params {
+                    self.value_size(param)?;
+                }
             }
-            Instruction::DerefLoad(_, ref val, _) => {
-                assert_eq!(self.value_size(val), ::ir::POINTER_SIZE);
+            Instruction::DerefLoad(dest, ref val, _) => {
+                self.register_size(dest)?;
+                check!(self.value_size(val)? == ::ir::POINTER_SIZE, "bad pointer size");
             }
             Instruction::DerefStore(ref address, _, ref value) => {
-                assert_eq!(self.value_size(address), ::ir::POINTER_SIZE);
-                assert!(self.value_size(value) > 0);
+                check!(self.value_size(address)? == ::ir::POINTER_SIZE, "bad pointer size");
+                self.value_size(value)?;
             }
             Instruction::Drop(reg) |
             Instruction::Init(reg) => {
-                assert!(self.function.registers.contains_key(&reg));
+                self.register_size(reg)?;
             }
             Instruction::Load(dest, reg, offset) => {
-                let dest_size = self.register_size(dest);
-                let reg_size = self.register_size(reg);
-                assert!(dest_size + offset <= reg_size);
+                let dest_size = self.register_size(dest)?;
+                let reg_size = self.register_size(reg)?;
+                check!(
+                    u64::from(dest_size) + u64::from(offset) <= u64::from(reg_size),
+                    "loaded value is out of register bounds"
+                );
             }
             Instruction::Store(reg, offset, ref value) => {
-                let reg_size = self.register_size(reg);
-                let val_size = self.value_size(value);
-                assert!(reg_size >= offset + val_size);
+                let reg_size = self.register_size(reg)?;
+                let val_size = self.value_size(value)?;
+                check!(
+                    u64::from(offset) + u64::from(val_size) <= u64::from(reg_size),
+                    "stored value is out of register bounds"
+                );
             }
             Instruction::TakeAddress(dest, reg, offset) => {
-                assert_eq!(self.register_size(dest), ::ir::POINTER_SIZE);
-                let reg_size = self.register_size(reg);
-                assert!(offset < reg_size);
+                check!(self.register_size(dest)? == ::ir::POINTER_SIZE, "bad pointer size");
+                let reg_size = self.register_size(reg)?;
+                check!(offset < reg_size, "address is out of register bounds");
             }
             Instruction::UnaryOp(dest, UnaryOp::Negate(_, size), ref value) => {
-                assert_eq!(self.register_size(dest), in_bytes(size));
-                assert_eq!(self.value_size(value), in_bytes(size));
+                check!(self.register_size(dest)? == in_bytes(size), "result has wrong size");
+                check!(self.value_size(value)? == in_bytes(size), "operand has wrong size");
             }
         }
+        Ok(())
+    }
+
+    fn check_block(&self, block: BlockId) -> Check<()> {
+        check!(self.function.blocks.contains_key(&block), "jump to missing block");
+        Ok(())
+    }
+
+    fn check_params(&self, callee: &Function, params: &[Value]) -> Check<()> {
+        check!(params.len() == callee.parameters.len(), "wrong number of parameters");
+        for (val, reg) in params.iter().zip(callee.parameters.iter()) {
+            let reg_size = match callee.registers.get(reg) {
+                Some(layout) => layout.size,
+                None => return Err("parameter of called function is not a register"),
+            };
+            check!(self.value_size(val)? == reg_size, "parameter has wrong size");
+        }
+        Ok(())
     }
 
-    fn register_size(&self, reg: Reg) -> u32 {
-        self.function.registers[&reg].size
+    fn function(&self, sym: &Symbol) -> Check<&'a Function> {
+        self.functions.get(sym).ok_or("called function does not exist")
     }
 
-    fn value_size(&self, value: &Value) -> u32 {
+    fn register_size(&self, reg: Reg) -> Check<u32> {
+        match self.function.registers.get(&reg) {
+            Some(layout) => Ok(layout.size),
+            None => Err("use of undeclared register"),
+        }
+    }
+
+    fn value_size(&self, value: &Value) -> Check<u32> {
         match *value {
-            Value::Bytes(_) => ::ir::POINTER_SIZE,
-            Value::Int(_, size) => in_bytes(size),
+            Value::Global(ref sym) => {
+                check!(self.globals.contains_key(sym), "global does not exist");
+                Ok(::ir::POINTER_SIZE)
+            }
+            Value::Int(_, size) => Ok(in_bytes(size)),
             Value::Reg(reg) => self.register_size(reg),
-            Value::Symbol(_) => ::ir::FUNCTION_SIZE,
+            Value::Symbol(ref sym) => {
+                check!(self.functions.contains_key(sym), "function does not exist");
+                Ok(::ir::FUNCTION_SIZE)
+            }
         }
     }
 }
 
+fn is_int_size(size: u32) -> bool {
+    size == 1 || size == 2 || size == 4 || size == 8
+}
+
 fn in_bytes(size: Size) -> u32 {
     match size {
         Size::Bit8 => 1,
         Size::Bit16 => 2,
         Size::Bit32 => 4,
+        Size::Bit64 => 8,
     }
 }
 
-pub fn validate_ir(program: &Program) {
-    for f in program.functions.values() {
-        let ctx = Context::new(program, f);
-        ctx.validate();
+pub fn validate_ir(program: &Program) -> Result<(), ValidationError> {
+    for (name, global) in &program.globals {
+        let error = |message| ValidationError {
+            item: name.clone(),
+            location: None,
+            message,
+        };
+        if global.layout.align == 0 {
+            return Err(error("global has zero alignment"));
+        }
+        if global.value.len() as u64 != u64::from(global.layout.size) {
+            return Err(error("global value does not match its size"));
+        }
+    }
+    for (name, f) in &program.functions {
+        let ctx = Context::new(program, name, f);
+        ctx.validate()?;
     }
+    Ok(())
 }
@@ -121,8 +121,9 @@ fn publish_diagnostics<R, W>(source: &str, doc: Url, transport: &mut Transport<R
 
 fn make_diagnostics(source: &str) -> Vec<lst::Diagnostic> {
     let reporter = plank_errors::Reporter::new();
-    let tokens = plank_syntax::lex(source, reporter.clone());
-    let ast = plank_syntax::parse(tokens, reporter.clone());
+    let file = plank_errors::position::FileId(0);
+    let tokens = plank_syntax::lex(source, file, reporter.clone());
+    let ast = plank_syntax::parse(tokens, file, reporter.clone());
     let _ = plank_frontend::compile(&ast, reporter.clone());
     reporter
         .get_diagnostics()
@@ -32,6 +32,7 @@ impl ::std::fmt::Display for Number {
             Some(Size::Bit8) => write!(f, "8")?,
             Some(Size::Bit16) => write!(f, "16")?,
             Some(Size::Bit32) => write!(f, "32")?,
+            Some(Size::Bit64) => write!(f, "64")?,
             None => {}
         }
         Ok(())
@@ -49,6 +50,7 @@ pub enum Size {
     Bit8,
     Bit16,
     Bit32,
+    Bit64,
 }
 
 #[derive(Debug, Clone)]
@@ -57,9 +59,26 @@ pub enum Expr {
     Unary(Spanned<UnaryOp>, Box<Spanned<Expr>>),
     Call(Box<Spanned<Expr>>, Vec<CallParam>),
     Field(Box<Spanned<Expr>>, Spanned<Ident>),
+    Index(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Array(Vec<Spanned<Expr>>),
+    /// Tuple like `(a, b)`, or `(a,)` if it has one element.
+    Tuple(Vec<Spanned<Expr>>),
     Name(Spanned<Ident>, Vec<Spanned<Type>>),
+    /// Function from `impl` block of a type, like `Pair::new`.
+    Associated(Spanned<Ident>, Spanned<Ident>, Vec<Spanned<Type>>),
     Literal(Literal),
     Cast(Box<Spanned<Expr>>, Spanned<Type>),
+    /// Lambda expression like `|x: u8| x + 1`, parameters
+    /// without a type annotation have a wildcard type.
+    Lambda(Vec<Var>, Box<Spanned<Expr>>),
+    /// `if` used as a value. Both branches are blocks, except
+    /// that `else` branch can be another `if`.
+    If(Box<Spanned<Expr>>, Box<Spanned<Expr>>, Option<Box<Spanned<Expr>>>),
+    /// Block with an optional trailing expression without
+    /// semicolon, which gives the value of the block.
+    Block(Vec<Spanned<Statement>>, Option<Box<Spanned<Expr>>>),
+    /// `loop` used as a value, which is given by `break`.
+    Loop(Option<Spanned<Ident>>, Box<Spanned<Statement>>),
 }
 
 #[derive(Debug, Clone)]
@@ -75,6 +94,11 @@ pub enum BinaryOp {
     Multiply,
     Divide,
     Modulo,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
     Equal,
     NotEqual,
     Less,
@@ -84,6 +108,36 @@ pub enum BinaryOp {
     And,
     Or,
     Assign,
+    AddAssign,
+    SubtractAssign,
+    MultiplyAssign,
+    DivideAssign,
+    ModuloAssign,
+    BitAndAssign,
+    BitOrAssign,
+    BitXorAssign,
+    ShiftLeftAssign,
+    ShiftRightAssign,
+}
+
+impl BinaryOp {
+    /// Returns operator that compound assignment applies
+    /// to its operands, for example `Add` for `AddAssign`.
+    pub fn compound_assign_op(self) -> Option<BinaryOp> {
+        match self {
+            BinaryOp::AddAssign => Some(BinaryOp::Add),
+            BinaryOp::SubtractAssign => Some(BinaryOp::Subtract),
+            BinaryOp::MultiplyAssign => Some(BinaryOp::Multiply),
+            BinaryOp::DivideAssign => Some(BinaryOp::Divide),
+            BinaryOp::ModuloAssign => Some(BinaryOp::Modulo),
+            BinaryOp::BitAndAssign => Some(BinaryOp::BitAnd),
+            BinaryOp::BitOrAssign => Some(BinaryOp::BitOr),
+            BinaryOp::BitXorAssign => Some(BinaryOp::BitXor),
+            BinaryOp::ShiftLeftAssign => Some(BinaryOp::ShiftLeft),
+            BinaryOp::ShiftRightAssign => Some(BinaryOp::ShiftRight),
+            _ => None,
+        }
+    }
 }
 
 #[derive(PartialEq, PartialOrd, Eq, Ord, Hash, Debug, Copy, Clone)]
@@ -91,6 +145,7 @@ pub enum UnaryOp {
     Plus,
     Minus,
     Not,
+    BitNot,
     Deref,
     AddressOf,
 }
@@ -102,17 +157,50 @@ pub enum Statement {
         Box<Spanned<Statement>>,
         Option<Box<Spanned<Statement>>>,
     ),
-    Loop(Box<Spanned<Statement>>),
-    While(Spanned<Expr>, Box<Spanned<Statement>>),
-    Break,
-    Continue,
+    Loop(Option<Spanned<Ident>>, Box<Spanned<Statement>>),
+    While(Option<Spanned<Ident>>, Spanned<Expr>, Box<Spanned<Statement>>),
+    /// Loop over integers from start to end, flag
+    /// tells if the end is included (`..=`) or not.
+    For(
+        Option<Spanned<Ident>>,
+        Spanned<Ident>,
+        Spanned<Expr>,
+        Spanned<Expr>,
+        bool,
+        Box<Spanned<Statement>>,
+    ),
+    Match(Spanned<Expr>, Vec<MatchArm>),
+    /// Break with optional label of the loop and value,
+    /// which can be given only for `loop` expressions.
+    Break(Option<Spanned<Ident>>, Option<Spanned<Expr>>),
+    Continue(Option<Spanned<Ident>>),
     Return(Spanned<Expr>),
     Let(Spanned<Ident>, Option<Spanned<Type>>, Option<Spanned<Expr>>),
+    /// Let that destructures a tuple, like `let (a, _) = f();`,
+    /// wildcards are represented as `None`.
+    LetTuple(Vec<Spanned<Option<Ident>>>, Option<Spanned<Type>>, Spanned<Expr>),
     Block(Vec<Spanned<Statement>>),
     Expr(Spanned<Expr>),
     Error,
 }
 
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Spanned<Pattern>,
+    pub body: Spanned<Statement>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Name(Spanned<Ident>),
+    Variant(Spanned<Ident>, Vec<Spanned<Pattern>>),
+    /// Number or char literal, negated if the flag is set.
+    Literal(bool, Literal),
+    /// Inclusive range, both ends are literal patterns.
+    Range(Box<Spanned<Pattern>>, Box<Spanned<Pattern>>),
+}
+
 #[derive(Debug, Clone)]
 pub enum Type {
     Wildcard,
@@ -122,17 +210,30 @@ pub enum Type {
     U16,
     I32,
     U32,
+    I64,
+    U64,
     Bool,
     Unit,
     Concrete(Spanned<Ident>, Vec<Spanned<Type>>),
     Pointer(Box<Spanned<Type>>),
+    Array(Box<Spanned<Type>>, u32),
+    Tuple(Vec<Spanned<Type>>),
     Function(Vec<Spanned<Type>>, Box<Spanned<Type>>),
+    /// Type of a lambda, like `|u8| -> u8`.
+    Closure(Vec<Spanned<Type>>, Box<Spanned<Type>>),
 }
 
 #[derive(Debug, Clone)]
 pub struct ItemName {
     pub name: Spanned<Ident>,
-    pub type_params: Vec<Spanned<Ident>>,
+    pub type_params: Vec<TypeParam>,
+}
+
+/// Type parameter with traits it is bounded by, like `T: Ord`.
+#[derive(Debug, Clone)]
+pub struct TypeParam {
+    pub name: Spanned<Ident>,
+    pub bounds: Vec<Spanned<Ident>>,
 }
 
 #[derive(PartialEq, PartialOrd, Eq, Ord, Hash, Debug, Copy, Clone)]
@@ -144,6 +245,7 @@ pub enum FunctionType {
 #[derive(Debug, Clone)]
 pub struct Function {
     pub complete_span: Span,
+    pub public: bool,
     pub fn_type: FunctionType,
     pub name: ItemName,
     pub params: Vec<Var>,
@@ -160,12 +262,102 @@ pub struct Var {
 #[derive(Debug, Clone)]
 pub struct Struct {
     pub complete_span: Span,
+    pub public: bool,
     pub name: ItemName,
     pub fields: Vec<Var>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub complete_span: Span,
+    pub public: bool,
+    pub name: ItemName,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: Spanned<Ident>,
+    pub fields: Vec<Spanned<Type>>,
+}
+
+/// Alternative name for a type, which is substituted
+/// wherever the alias is used.
+#[derive(Debug, Clone)]
+pub struct TypeAlias {
+    pub complete_span: Span,
+    pub public: bool,
+    pub name: ItemName,
+    pub typ: Spanned<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Static {
+    pub complete_span: Span,
+    pub public: bool,
+    pub mutable: bool,
+    pub name: Spanned<Ident>,
+    pub typ: Spanned<Type>,
+    pub value: Spanned<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub complete_span: Span,
+    pub public: bool,
+    pub name: Spanned<Ident>,
+    pub typ: Spanned<Type>,
+    pub value: Spanned<Expr>,
+}
+
+/// Declaration of a trait. Methods have an implicit type
+/// parameter `Self`, which is the implementing type.
+#[derive(Debug, Clone)]
+pub struct Trait {
+    pub complete_span: Span,
+    pub public: bool,
+    pub name: Spanned<Ident>,
+    pub functions: Vec<Function>,
+}
+
+/// Functions associated with a struct or enum. Functions
+/// that take `self` as first parameter are methods. If `trait_`
+/// is present, functions implement methods of that trait.
+#[derive(Debug, Clone)]
+pub struct Impl {
+    pub complete_span: Span,
+    pub type_params: Vec<TypeParam>,
+    pub trait_: Option<Spanned<Ident>>,
+    pub typ: Spanned<Type>,
+    pub functions: Vec<Function>,
+}
+
+/// Module declaration, `program` is filled in by the driver after
+/// loading the module file.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub complete_span: Span,
+    pub name: Spanned<Ident>,
+    pub program: Option<Box<Program>>,
+}
+
+/// Import of an item, path is absolute from the root module.
+#[derive(Debug, Clone)]
+pub struct Use {
+    pub complete_span: Span,
+    pub path: Vec<Spanned<Ident>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Program {
+    pub modules: Vec<Module>,
+    pub uses: Vec<Use>,
     pub structs: Vec<Struct>,
+    pub enums: Vec<Enum>,
+    pub traits: Vec<Trait>,
+    pub impls: Vec<Impl>,
+    pub type_aliases: Vec<TypeAlias>,
     pub functions: Vec<Function>,
+    pub statics: Vec<Static>,
+    pub consts: Vec<Const>,
 }
@@ -1,12 +1,12 @@
 use std::str::Chars;
 use plank_errors::Reporter;
-use position::{Position, Span, Spanned};
+use position::{FileId, Position, Span, Spanned};
 use tokens::{Keyword, Number, Token};
 use ast::{Signedness, Size};
 
 
-pub fn lex(source: &str, reporter: Reporter) -> Vec<Spanned<Token>> {
-    let mut lexer = Lexer::new(source, reporter);
+pub fn lex(source: &str, file: FileId, reporter: Reporter) -> Vec<Spanned<Token>> {
+    let mut lexer = Lexer::new(source, file, reporter);
     let mut tokens = Vec::new();
     while let Some(tok) = lexer.next_token() {
         tokens.push(tok);
@@ -18,23 +18,29 @@ struct Lexer<'a> {
     chars: Chars<'a>,
     next_char: Option<char>,
     current_pos: Position,
+    file: FileId,
     reporter: Reporter,
     previous_error: bool,
 }
 
 impl<'a> Lexer<'a> {
-    fn new(source: &'a str, reporter: Reporter) -> Self {
+    fn new(source: &'a str, file: FileId, reporter: Reporter) -> Self {
         let mut chars = source.chars();
         let next_char = chars.next();
         Lexer {
             chars,
             next_char,
             current_pos: Position::new(0, 0),
+            file,
             reporter,
             previous_error: false,
         }
     }
 
+    fn span_from(&self, start: Position) -> Span {
+        Span::new(self.file, start, self.current_pos)
+    }
+
     fn peek(&self) -> Option<char> {
         self.next_char
     }
@@ -78,32 +84,73 @@ impl<'a> Lexer<'a> {
     fn single_char(&mut self, tok: Token) -> Spanned<Token> {
         let start = self.current_pos;
         self.advance();
-        let span = start.span_to(self.current_pos);
+        let span = self.span_from(start);
         Spanned::new(tok, span)
     }
 
-    fn two_char(&mut self, ch: char, tok: Token) -> Spanned<Token> {
+    fn test_second(&mut self, ch: char, if_one: Token, if_two: Token) -> Spanned<Token> {
         let start = self.current_pos;
-        let first = self.consume().expect("char disappeared");
-        let tok = if self.check(ch) {
+        self.advance();
+        let tok = if self.check(ch) { if_two } else { if_one };
+        let span = self.span_from(start);
+        Spanned::new(tok, span)
+    }
+
+    fn test_seconds(&mut self, if_one: Token, seconds: &[(char, Token)]) -> Spanned<Token> {
+        let start = self.current_pos;
+        self.advance();
+        let tok = seconds
+            .iter()
+            .find(|&&(ch, _)| self.peek() == Some(ch))
+            .map(|second| second.1.clone());
+        let tok = if let Some(tok) = tok {
+            self.advance();
             tok
         } else {
-            let span = start.span_to(self.current_pos.forward(1));
-            self.reporter
-                .error(format!("unknown token: `{}`", first), span)
-                .span_note(format!("maybe you wanted `{}{}`?", first, ch), span)
-                .build();
-            Token::Error
+            if_one
         };
-        let span = start.span_to(self.current_pos);
+        let span = self.span_from(start);
         Spanned::new(tok, span)
     }
 
-    fn test_second(&mut self, ch: char, if_one: Token, if_two: Token) -> Spanned<Token> {
+    fn angle_bracket(
+        &mut self,
+        ch: char,
+        single: Token,
+        equal: Token,
+        shift: Token,
+        shift_assign: Token,
+    ) -> Spanned<Token> {
         let start = self.current_pos;
         self.advance();
-        let tok = if self.check(ch) { if_two } else { if_one };
-        let span = start.span_to(self.current_pos);
+        let tok = if self.check('=') {
+            equal
+        } else if self.check(ch) {
+            if self.check('=') {
+                shift_assign
+            } else {
+                shift
+            }
+        } else {
+            single
+        };
+        let span = self.span_from(start);
+        Spanned::new(tok, span)
+    }
+
+    fn dot(&mut self) -> Spanned<Token> {
+        let start = self.current_pos;
+        self.advance();
+        let tok = if self.check('.') {
+            if self.check('=') {
+                Token::DotDotEqual
+            } else {
+                Token::DotDot
+            }
+        } else {
+            Token::Dot
+        };
+        let span = self.span_from(start);
         Spanned::new(tok, span)
     }
 
@@ -118,7 +165,7 @@ impl<'a> Lexer<'a> {
 
     fn skip_block_comment(&mut self) {
         // make span of starting `/*`
-        let opener_span = Span::new(self.current_pos.backwards(2), self.current_pos);
+        let opener_span = self.span_from(self.current_pos.backwards(2));
         let mut depth = 1;
         while depth > 0 {
             match self.consume() {
@@ -157,8 +204,13 @@ impl<'a> Lexer<'a> {
             self.skip_block_comment();
             None
         } else {
-            let span = start.span_to(self.current_pos);
-            Some(Spanned::new(Token::Slash, span))
+            let tok = if self.check('=') {
+                Token::SlashAssign
+            } else {
+                Token::Slash
+            };
+            let span = self.span_from(start);
+            Some(Spanned::new(tok, span))
         }
     }
 
@@ -167,14 +219,14 @@ impl<'a> Lexer<'a> {
         let start = self.current_pos;
         loop {
             match self.peek() {
-                Some(ch) if ch.is_alphanumeric() => {
+                Some(ch) if is_ident_char(ch) => {
                     string.push(ch);
                     self.advance();
                 }
                 _ => break,
             }
         }
-        let span = start.span_to(self.current_pos);
+        let span = self.span_from(start);
         let tok = match parse_number(&string) {
             Ok(num) => Token::Number(num),
             Err(err) => {
@@ -198,7 +250,7 @@ impl<'a> Lexer<'a> {
                 _ => break,
             }
         }
-        let span = start.span_to(self.current_pos);
+        let span = self.span_from(start);
         let tok = if let Some(tok) = keyword(&string) {
             tok
         } else {
@@ -215,7 +267,7 @@ impl<'a> Lexer<'a> {
             match self.peek() {
                 Some(ch) if ch == closing => {
                     self.advance();
-                    let span = start_pos.span_to(self.current_pos);
+                    let span = self.span_from(start_pos);
                     return Spanned::new(Some(string), span);
                 }
                 Some('\\') => {
@@ -234,7 +286,7 @@ impl<'a> Lexer<'a> {
                                 Some(byte) => string.push(byte),
                                 None => {
                                     let span =
-                                        self.current_pos.span_to(self.current_pos.forward(1));
+                                        Span::new(self.file, self.current_pos, self.current_pos.forward(1));
                                     self.reporter
                                         .error("`x` should be followed by two hex digits", span)
                                         .span(span)
@@ -243,7 +295,7 @@ impl<'a> Lexer<'a> {
                             }
                         }
                         _ => {
-                            let span = escape_start.span_to(self.current_pos);
+                            let span = self.span_from(escape_start);
                             self.reporter
                                 .error("invalid escape sequence", span)
                                 .span(span)
@@ -252,7 +304,7 @@ impl<'a> Lexer<'a> {
                     }
                 }
                 Some('\n') | Some('\r') | None => {
-                    let span = start_pos.span_to(self.current_pos);
+                    let span = self.span_from(start_pos);
                     self.reporter
                         .error("unterminated string", span)
                         .span(span)
@@ -267,7 +319,7 @@ impl<'a> Lexer<'a> {
                     } else {
                         let start = self.current_pos;
                         self.advance();
-                        let span = start.span_to(self.current_pos);
+                        let span = self.span_from(start);
                         self.reporter
                             .error(format!("unknown char in string (codepoint: {})", ch), span)
                             .span(span)
@@ -305,6 +357,30 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Checks if quote starts a label instead of a char
+    /// literal, which would have a closing quote.
+    fn is_label_start(&self) -> bool {
+        let mut rest = self.chars.clone();
+        match rest.next() {
+            Some(first) if is_ident_char(first) && !first.is_ascii_digit() => {
+                rest.find(|&ch| !is_ident_char(ch)) != Some('\'')
+            }
+            _ => false,
+        }
+    }
+
+    fn lex_label(&mut self) -> Spanned<Token> {
+        let start = self.current_pos;
+        self.advance();
+        let mut name = String::new();
+        while let Some(ch) = self.peek().filter(|&ch| is_ident_char(ch)) {
+            name.push(ch);
+            self.advance();
+        }
+        let span = self.span_from(start);
+        Spanned::new(Token::Label(name), span)
+    }
+
     fn next_raw_token(&mut self) -> Option<Spanned<Token>> {
         loop {
             return Some(match self.peek() {
@@ -314,31 +390,63 @@ impl<'a> Lexer<'a> {
                 }
                 Some(ch) if ch.is_digit(10) => self.lex_number(),
                 Some(ch) if is_ident_char(ch) => self.lex_name(),
-                Some('.') => self.single_char(Token::Dot),
+                Some('.') => self.dot(),
                 Some(',') => self.single_char(Token::Comma),
                 Some(';') => self.single_char(Token::Semicolon),
-                Some('*') => self.single_char(Token::Star),
-                Some('+') => self.single_char(Token::Plus),
-                Some('%') => self.single_char(Token::Percent),
+                Some('*') => self.test_second('=', Token::Star, Token::StarAssign),
+                Some('+') => self.test_second('=', Token::Plus, Token::PlusAssign),
+                Some('%') => self.test_second('=', Token::Percent, Token::PercentAssign),
+                Some('^') => self.test_second('=', Token::Caret, Token::CaretAssign),
+                Some('~') => self.single_char(Token::Tilde),
                 Some('(') => self.single_char(Token::LeftParen),
                 Some(')') => self.single_char(Token::RightParen),
                 Some('{') => self.single_char(Token::LeftBrace),
                 Some('}') => self.single_char(Token::RightBrace),
-                Some('|') => self.two_char('|', Token::Or),
+                Some('[') => self.single_char(Token::LeftBracket),
+                Some(']') => self.single_char(Token::RightBracket),
+                Some('|') => self.test_seconds(
+                    Token::Pipe,
+                    &[('|', Token::Or), ('=', Token::PipeAssign)],
+                ),
                 Some('!') => self.test_second('=', Token::Not, Token::NotEqual),
-                Some('&') => self.test_second('&', Token::Ampersand, Token::And),
+                Some('&') => self.test_seconds(
+                    Token::Ampersand,
+                    &[('&', Token::And), ('=', Token::AmpersandAssign)],
+                ),
                 Some(':') => self.test_second(':', Token::Colon, Token::DoubleColon),
-                Some('<') => self.test_second('=', Token::Less, Token::LessEqual),
-                Some('>') => self.test_second('=', Token::Greater, Token::GreaterEqual),
-                Some('-') => self.test_second('>', Token::Minus, Token::Arrow),
-                Some('=') => self.test_second('=', Token::Assign, Token::Equal),
+                Some('<') => self.angle_bracket(
+                    '<',
+                    Token::Less,
+                    Token::LessEqual,
+                    Token::ShiftLeft,
+                    Token::ShiftLeftAssign,
+                ),
+                Some('>') => self.angle_bracket(
+                    '>',
+                    Token::Greater,
+                    Token::GreaterEqual,
+                    Token::ShiftRight,
+                    Token::ShiftRightAssign,
+                ),
+                Some('-') => self.test_seconds(
+                    Token::Minus,
+                    &[('>', Token::Arrow), ('=', Token::MinusAssign)],
+                ),
+                Some('=') => self.test_seconds(
+                    Token::Assign,
+                    &[('=', Token::Equal), ('>', Token::FatArrow)],
+                ),
                 Some('/') => if let Some(tok) = self.slash_or_comment() {
                     tok
                 } else {
                     continue;
                 },
                 Some('"') => self.lex_string(),
-                Some('\'') => self.lex_char(),
+                Some('\'') => if self.is_label_start() {
+                    self.lex_label()
+                } else {
+                    self.lex_char()
+                },
                 Some(ch) => {
                     let tok = self.single_char(Token::Error);
                     if !self.previous_error {
@@ -385,10 +493,23 @@ fn keyword(s: &str) -> Option<Token> {
         "extern" => Some(Token::Keyword(Keyword::Extern)),
         "fn" => Some(Token::Keyword(Keyword::Fn)),
         "struct" => Some(Token::Keyword(Keyword::Struct)),
+        "enum" => Some(Token::Keyword(Keyword::Enum)),
+        "static" => Some(Token::Keyword(Keyword::Static)),
+        "mut" => Some(Token::Keyword(Keyword::Mut)),
+        "const" => Some(Token::Keyword(Keyword::Const)),
+        "mod" => Some(Token::Keyword(Keyword::Mod)),
+        "use" => Some(Token::Keyword(Keyword::Use)),
+        "pub" => Some(Token::Keyword(Keyword::Pub)),
+        "impl" => Some(Token::Keyword(Keyword::Impl)),
+        "trait" => Some(Token::Keyword(Keyword::Trait)),
+        "type" => Some(Token::Keyword(Keyword::Type)),
+        "for" => Some(Token::Keyword(Keyword::For)),
+        "in" => Some(Token::Keyword(Keyword::In)),
         "if" => Some(Token::Keyword(Keyword::If)),
         "else" => Some(Token::Keyword(Keyword::Else)),
         "loop" => Some(Token::Keyword(Keyword::Loop)),
         "while" => Some(Token::Keyword(Keyword::While)),
+        "match" => Some(Token::Keyword(Keyword::Match)),
         "continue" => Some(Token::Keyword(Keyword::Continue)),
         "break" => Some(Token::Keyword(Keyword::Break)),
         "let" => Some(Token::Keyword(Keyword::Let)),
@@ -403,6 +524,8 @@ fn keyword(s: &str) -> Option<Token> {
         "u16" => Some(Token::Keyword(Keyword::U16)),
         "i32" => Some(Token::Keyword(Keyword::I32)),
         "u32" => Some(Token::Keyword(Keyword::U32)),
+        "i64" => Some(Token::Keyword(Keyword::I64)),
+        "u64" => Some(Token::Keyword(Keyword::U64)),
         "bool" => Some(Token::Keyword(Keyword::Bool)),
         "unit" => Some(Token::Keyword(Keyword::Unit)),
         _ => None,
@@ -410,81 +533,133 @@ fn keyword(s: &str) -> Option<Token> {
 }
 
 enum ParseNumberError {
-    BadInt,
+    NoDigits,
+    BadDigit(char, u32),
+    LeadingZero,
     TooLarge,
     BadBitCount,
+    OutOfRange(Signedness, Size),
 }
 
 impl ::std::fmt::Display for ParseNumberError {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-        let msg = match *self {
-            ParseNumberError::BadInt => "invalid int literal",
-            ParseNumberError::TooLarge => "int literal is too big",
-            ParseNumberError::BadBitCount => "invalid int size",
-        };
-        write!(f, "{}", msg)
+        match *self {
+            ParseNumberError::NoDigits => write!(f, "int literal has no digits"),
+            ParseNumberError::BadDigit(ch, radix) => {
+                let kind = match radix {
+                    2 => "binary",
+                    8 => "octal",
+                    16 => "hexadecimal",
+                    _ => "decimal",
+                };
+                write!(f, "invalid digit `{}` in {} int literal", ch, kind)
+            }
+            ParseNumberError::LeadingZero => {
+                write!(f, "leading zeros are not allowed, use `0o` for octal literals")
+            }
+            ParseNumberError::TooLarge => write!(f, "int literal is too big"),
+            ParseNumberError::BadBitCount => write!(f, "invalid int size"),
+            ParseNumberError::OutOfRange(sign, size) => {
+                let sign = match sign {
+                    Signedness::Signed => 'i',
+                    Signedness::Unsigned => 'u',
+                };
+                write!(f, "literal out of range for `{}{}`", sign, bit_count(size))
+            }
+        }
     }
 }
 
 fn parse_number(s: &str) -> Result<Number, ParseNumberError> {
-    match s.find(|c| c == 'i' || c == 'u') {
-        Some(index) => {
-            // we have a suffix
-            // parse prefix as number, parse rest as bit count
-            let prefix = s.get(..index).unwrap();
-            let suffix = s.get((index + 1)..).unwrap();
-            let value = parse_simple_number(prefix)?;
-            let signedness = if s.get(index..(index + 1)).unwrap() == "i" {
+    let (radix, digits) = if let Some(digits) = s.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = s.strip_prefix("0o") {
+        (8, digits)
+    } else if let Some(digits) = s.strip_prefix("0b") {
+        (2, digits)
+    } else {
+        (10, s)
+    };
+    // `i` and `u` are not hex digits, so suffix is never ambiguous
+    let (digits, suffix) = match digits.find(|c| c == 'i' || c == 'u') {
+        Some(index) => (&digits[..index], Some(&digits[index..])),
+        None => (digits, None),
+    };
+    let value = parse_digits(digits, radix)?;
+    let (signedness, size) = match suffix {
+        Some(suffix) => {
+            let signedness = if suffix.starts_with('i') {
                 Signedness::Signed
             } else {
                 Signedness::Unsigned
             };
-            let size = match suffix {
+            let size = match &suffix[1..] {
                 "8" => Some(Size::Bit8),
                 "16" => Some(Size::Bit16),
                 "32" => Some(Size::Bit32),
+                "64" => Some(Size::Bit64),
                 "" => None,
                 _ => return Err(ParseNumberError::BadBitCount),
             };
-            Ok(Number {
-                value,
-                signedness: Some(signedness),
-                size,
-            })
-        }
-        None => {
-            // no suffix, parse simple number
-            let value = parse_simple_number(s)?;
-            Ok(Number {
-                value,
-                signedness: None,
-                size: None,
-            })
+            (Some(signedness), size)
         }
+        None => (None, None),
+    };
+    if let (Some(signedness), Some(size)) = (signedness, size) {
+        check_range(value, signedness, size)?;
     }
+    Ok(Number {
+        value,
+        signedness,
+        size,
+    })
 }
 
-fn parse_simple_number(s: &str) -> Result<u64, ParseNumberError> {
-    if s.is_empty() {
-        return Err(ParseNumberError::BadInt);
-    }
-    if s.len() > 1 && s.starts_with('0') {
-        // we have a leading zero, and number is longer than one digit
-        return Err(ParseNumberError::BadInt);
-    }
+fn parse_digits(s: &str, radix: u32) -> Result<u64, ParseNumberError> {
     let mut result = 0u64;
+    let mut digit_count = 0;
     for ch in s.chars() {
-        match ch.to_digit(10) {
-            Some(digit) => {
-                result = result
-                    .checked_mul(10)
-                    .and_then(|n| n.checked_add(u64::from(digit)))
-                    .ok_or(ParseNumberError::TooLarge)?;
-            }
-            None => {
-                return Err(ParseNumberError::BadInt);
-            }
+        if ch == '_' {
+            continue;
+        }
+        let digit = ch.to_digit(radix).ok_or(ParseNumberError::BadDigit(ch, radix))?;
+        if radix == 10 && digit_count == 1 && result == 0 {
+            // we have a leading zero, and number is longer than one digit
+            return Err(ParseNumberError::LeadingZero);
         }
+        result = result
+            .checked_mul(u64::from(radix))
+            .and_then(|n| n.checked_add(u64::from(digit)))
+            .ok_or(ParseNumberError::TooLarge)?;
+        digit_count += 1;
+    }
+    if digit_count == 0 {
+        return Err(ParseNumberError::NoDigits);
     }
     Ok(result)
 }
+
+fn bit_count(size: Size) -> u32 {
+    match size {
+        Size::Bit8 => 8,
+        Size::Bit16 => 16,
+        Size::Bit32 => 32,
+        Size::Bit64 => 64,
+    }
+}
+
+fn check_range(value: u64, signedness: Signedness, size: Size) -> Result<(), ParseNumberError> {
+    let bits = bit_count(size);
+    // signed literals can be one bigger than the maximum value,
+    // because they might be negated, like in `-128i8`; whether
+    // they actually are is checked by the frontend
+    let max = match signedness {
+        Signedness::Unsigned => u64::MAX >> (64 - bits),
+        Signedness::Signed => 1 << (bits - 1),
+    };
+    if value <= max {
+        Ok(())
+    } else {
+        Err(ParseNumberError::OutOfRange(signedness, size))
+    }
+}
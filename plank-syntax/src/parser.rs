@@ -1,8 +1,9 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 use plank_errors::Reporter;
-use ast::{BinaryOp, CallParam, Expr, Function, FunctionType, Ident, ItemName, Literal, Program,
-          Statement, Struct, Type, UnaryOp, Var};
-use position::{Position, Span, Spanned};
+use ast::{BinaryOp, CallParam, Const, Enum, Expr, Function, FunctionType, Ident, Impl, ItemName,
+          Literal, MatchArm, Module, Pattern, Program, Statement, Static, Struct, Trait, Type,
+          TypeAlias, TypeParam, UnaryOp, Use, Var, Variant};
+use position::{FileId, Position, Span, Spanned};
 use tokens::{Keyword, Token, TokenKind};
 
 
@@ -16,8 +17,8 @@ macro_rules! parse_infix {
     }}
 }
 
-pub fn parse(tokens: Vec<Spanned<Token>>, reporter: Reporter) -> Program {
-    let mut parser = Parser::new(tokens, reporter);
+pub fn parse(tokens: Vec<Spanned<Token>>, file: FileId, reporter: Reporter) -> Program {
+    let mut parser = Parser::new(tokens, file, reporter);
 
     parser.prefix(TokenKind::Literal, &LiteralParser);
     parser.prefix(TokenKind::Token(Token::Keyword(Keyword::Unit)), &LiteralParser);
@@ -36,10 +37,22 @@ pub fn parse(tokens: Vec<Spanned<Token>>, reporter: Reporter) -> Program {
         &UnaryOpParser(UnaryOp::Deref),
     );
     parser.prefix(TokenKind::Token(Token::Not), &UnaryOpParser(UnaryOp::Not));
+    parser.prefix(
+        TokenKind::Token(Token::Tilde),
+        &UnaryOpParser(UnaryOp::BitNot),
+    );
     parser.prefix(TokenKind::Token(Token::LeftParen), &ParenthesisedParser);
+    parser.prefix(TokenKind::Token(Token::LeftBracket), &ArrayParser);
+    parser.prefix(TokenKind::Token(Token::Pipe), &LambdaParser);
+    parser.prefix(TokenKind::Token(Token::Or), &LambdaParser);
+    parser.prefix(TokenKind::Token(Token::Keyword(Keyword::If)), &IfParser);
+    parser.prefix(TokenKind::Token(Token::LeftBrace), &BlockParser);
+    parser.prefix(TokenKind::Token(Token::Keyword(Keyword::Loop)), &LoopParser);
+    parser.prefix(TokenKind::Label, &LoopParser);
 
     parser.infix(TokenKind::Token(Token::LeftParen), &CallParser);
     parser.infix(TokenKind::Token(Token::Dot), &FieldParser);
+    parser.infix(TokenKind::Token(Token::LeftBracket), &IndexParser);
     parser.infix(TokenKind::Token(Token::Keyword(Keyword::As)), &CastParser);
 
     parse_infix!(parser, And, And, And, true);
@@ -49,6 +62,11 @@ pub fn parse(tokens: Vec<Spanned<Token>>, reporter: Reporter) -> Program {
     parse_infix!(parser, Star, Multiply, Multiplication, true);
     parse_infix!(parser, Slash, Divide, Multiplication, true);
     parse_infix!(parser, Percent, Modulo, Multiplication, true);
+    parse_infix!(parser, ShiftLeft, ShiftLeft, Shift, true);
+    parse_infix!(parser, ShiftRight, ShiftRight, Shift, true);
+    parse_infix!(parser, Ampersand, BitAnd, BitAnd, true);
+    parse_infix!(parser, Caret, BitXor, BitXor, true);
+    parse_infix!(parser, Pipe, BitOr, BitOr, true);
     parse_infix!(parser, Less, Less, Comparision, true);
     parse_infix!(parser, LessEqual, LessEqual, Comparision, true);
     parse_infix!(parser, Greater, Greater, Comparision, true);
@@ -56,6 +74,16 @@ pub fn parse(tokens: Vec<Spanned<Token>>, reporter: Reporter) -> Program {
     parse_infix!(parser, Equal, Equal, Equation, true);
     parse_infix!(parser, NotEqual, NotEqual, Equation, true);
     parse_infix!(parser, Assign, Assign, Assignment, false);
+    parse_infix!(parser, PlusAssign, AddAssign, Assignment, false);
+    parse_infix!(parser, MinusAssign, SubtractAssign, Assignment, false);
+    parse_infix!(parser, StarAssign, MultiplyAssign, Assignment, false);
+    parse_infix!(parser, SlashAssign, DivideAssign, Assignment, false);
+    parse_infix!(parser, PercentAssign, ModuloAssign, Assignment, false);
+    parse_infix!(parser, AmpersandAssign, BitAndAssign, Assignment, false);
+    parse_infix!(parser, PipeAssign, BitOrAssign, Assignment, false);
+    parse_infix!(parser, CaretAssign, BitXorAssign, Assignment, false);
+    parse_infix!(parser, ShiftLeftAssign, ShiftLeftAssign, Assignment, false);
+    parse_infix!(parser, ShiftRightAssign, ShiftRightAssign, Assignment, false);
 
     parser.parse_program()
 }
@@ -89,10 +117,11 @@ struct Parser<'a> {
     prefix_parsers: HashMap<TokenKind, &'a PrefixParser>,
     infix_parsers: HashMap<TokenKind, &'a InfixParser>,
     last_line_completed: bool,
+    file: FileId,
 }
 
 impl<'a> Parser<'a> {
-    fn new<I>(tokens: I, reporter: Reporter) -> Self
+    fn new<I>(tokens: I, file: FileId, reporter: Reporter) -> Self
     where
         I: IntoIterator<Item = Spanned<Token>>,
     {
@@ -108,6 +137,7 @@ impl<'a> Parser<'a> {
             expected: HashSet::new(),
             expected2: HashSet::new(),
             last_line_completed: false,
+            file,
         }
     }
 
@@ -169,8 +199,9 @@ impl<'a> Parser<'a> {
         } else if !self.last_line_completed && self.prev_span.is_some()
             && self.prev_span.unwrap().end.line < self.peek_span().start.line
         {
-            let last_pos = self.prev_span.unwrap().end;
-            let help_span = last_pos.forward(1).span_to(last_pos.forward(2));
+            let prev_span = self.prev_span.unwrap();
+            let last_pos = prev_span.end;
+            let help_span = Span::new(prev_span.file, last_pos.forward(1), last_pos.forward(2));
             builder.span_note(expected, help_span).build();
         } else {
             builder.build();
@@ -189,7 +220,7 @@ impl<'a> Parser<'a> {
                 if next_line > prev_line {
                     // make specialized error about expected
                     // semicolon, and pretend that it exists
-                    let help_span = Span::new(span.end, span.end.forward(1));
+                    let help_span = Span::new(span.file, span.end, span.end.forward(1));
                     (Some((help_span, "maybe you missed a `;`?".into())), Ok(()))
                 } else {
                     // regular error
@@ -212,6 +243,26 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn expect_closing_angle(&mut self, opener: Span) -> ParseResult<()> {
+        // tokens like `>>` or `>=` are lexed as a single token, split
+        // them when closing (possibly nested) type parameter lists
+        let rest = match self.peek() {
+            Some(&Token::ShiftRight) => Some(Token::Greater),
+            Some(&Token::GreaterEqual) => Some(Token::Assign),
+            Some(&Token::ShiftRightAssign) => Some(Token::GreaterEqual),
+            _ => None,
+        };
+        if let Some(rest) = rest {
+            let tok = self.next_token.take().expect("token disappeared");
+            let span = Spanned::span(&tok);
+            let middle = span.start.forward(1);
+            self.tokens
+                .push_front(Spanned::new(rest, Span::new(span.file, middle, span.end)));
+            self.next_token = Some(Spanned::new(Token::Greater, Span::new(span.file, span.start, middle)));
+        }
+        self.expect_closing(Token::Greater, opener)
+    }
+
     fn previous_span(&self) -> Span {
         self.prev_span.expect("no previous token")
     }
@@ -222,9 +273,9 @@ impl<'a> Parser<'a> {
             (None, Some(span)) => {
                 let start = span.end.forward(1);
                 let end = start.forward(1);
-                start.span_to(end)
+                Span::new(span.file, start, end)
             }
-            (None, None) => Position::new(1, 1).span_to(Position::new(1, 2)),
+            (None, None) => Span::new(self.file, Position::new(1, 1), Position::new(1, 2)),
         }
     }
 
@@ -272,6 +323,24 @@ impl<'a> Parser<'a> {
         self.next_token.is_none()
     }
 
+    fn check_label(&mut self) -> Option<Spanned<Ident>> {
+        self.expected.insert(Expectation::Token(TokenKind::Label));
+        match self.peek() {
+            Some(&Token::Label(_)) => {}
+            _ => return None,
+        }
+        match self.consume() {
+            Ok(tok) => {
+                let span = Spanned::span(&tok);
+                match Spanned::into_value(tok) {
+                    Token::Label(label) => Some(Spanned::new(Ident(label), span)),
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
     fn check_ident(&mut self) -> Option<Spanned<Ident>> {
         self.expected.insert(Expectation::Token(TokenKind::Ident));
         match self.peek() {
@@ -303,7 +372,16 @@ impl<'a> Parser<'a> {
         loop {
             match self.peek() {
                 Some(&Token::Keyword(Keyword::Struct)) |
+                Some(&Token::Keyword(Keyword::Enum)) |
+                Some(&Token::Keyword(Keyword::Static)) |
+                Some(&Token::Keyword(Keyword::Const)) |
                 Some(&Token::Keyword(Keyword::Extern)) |
+                Some(&Token::Keyword(Keyword::Mod)) |
+                Some(&Token::Keyword(Keyword::Use)) |
+                Some(&Token::Keyword(Keyword::Pub)) |
+                Some(&Token::Keyword(Keyword::Impl)) |
+                Some(&Token::Keyword(Keyword::Trait)) |
+                Some(&Token::Keyword(Keyword::Type)) |
                 None => {
                     return;
                 }
@@ -325,10 +403,13 @@ impl<'a> Parser<'a> {
                 Some(&Token::Keyword(Keyword::If)) |
                 Some(&Token::Keyword(Keyword::Loop)) |
                 Some(&Token::Keyword(Keyword::While)) |
+                Some(&Token::Keyword(Keyword::For)) |
+                Some(&Token::Keyword(Keyword::Match)) |
                 Some(&Token::Keyword(Keyword::Break)) |
                 Some(&Token::Keyword(Keyword::Continue)) |
                 Some(&Token::Keyword(Keyword::Let)) |
                 Some(&Token::Keyword(Keyword::Return)) |
+                Some(&Token::Label(_)) |
                 Some(&Token::LeftBrace) |
                 Some(&Token::RightBrace) => {
                     return Ok(());
@@ -338,7 +419,17 @@ impl<'a> Parser<'a> {
                         return Err(());
                     }
                 }
-                Some(&Token::Keyword(Keyword::Struct)) | None => {
+                Some(&Token::Keyword(Keyword::Struct)) |
+                Some(&Token::Keyword(Keyword::Enum)) |
+                Some(&Token::Keyword(Keyword::Static)) |
+                Some(&Token::Keyword(Keyword::Const)) |
+                Some(&Token::Keyword(Keyword::Mod)) |
+                Some(&Token::Keyword(Keyword::Use)) |
+                Some(&Token::Keyword(Keyword::Pub)) |
+                Some(&Token::Keyword(Keyword::Impl)) |
+                Some(&Token::Keyword(Keyword::Trait)) |
+                Some(&Token::Keyword(Keyword::Type)) |
+                None => {
                     return Err(());
                 }
                 _ => {}
@@ -352,22 +443,83 @@ impl<'a> Parser<'a> {
 
     fn parse_program(&mut self) -> Program {
         let mut program = Program {
+            modules: Vec::new(),
+            uses: Vec::new(),
             structs: Vec::new(),
+            enums: Vec::new(),
+            traits: Vec::new(),
+            impls: Vec::new(),
+            type_aliases: Vec::new(),
             functions: Vec::new(),
+            statics: Vec::new(),
+            consts: Vec::new(),
         };
         loop {
             self.last_line_completed = true;
             if self.is_at_end() {
                 return program;
-            } else if self.check(Token::Keyword(Keyword::Struct)) {
-                if let Ok(s) = self.parse_struct() {
+            } else if self.check(Token::Keyword(Keyword::Mod)) {
+                if let Ok(m) = self.parse_module() {
+                    program.modules.push(m);
+                } else {
+                    self.synchronize_item();
+                }
+                continue;
+            } else if self.check(Token::Keyword(Keyword::Use)) {
+                if let Ok(u) = self.parse_use() {
+                    program.uses.push(u);
+                } else {
+                    self.synchronize_item();
+                }
+                continue;
+            } else if self.check(Token::Keyword(Keyword::Impl)) {
+                if let Ok(i) = self.parse_impl() {
+                    program.impls.push(i);
+                } else {
+                    self.synchronize_item();
+                }
+                continue;
+            }
+            let public = self.check(Token::Keyword(Keyword::Pub));
+            if self.check(Token::Keyword(Keyword::Struct)) {
+                if let Ok(s) = self.parse_struct(public) {
                     program.structs.push(s);
                 } else {
                     self.synchronize_item();
                 }
+            } else if self.check(Token::Keyword(Keyword::Enum)) {
+                if let Ok(e) = self.parse_enum(public) {
+                    program.enums.push(e);
+                } else {
+                    self.synchronize_item();
+                }
+            } else if self.check(Token::Keyword(Keyword::Trait)) {
+                if let Ok(t) = self.parse_trait(public) {
+                    program.traits.push(t);
+                } else {
+                    self.synchronize_item();
+                }
+            } else if self.check(Token::Keyword(Keyword::Type)) {
+                if let Ok(t) = self.parse_type_alias(public) {
+                    program.type_aliases.push(t);
+                } else {
+                    self.synchronize_item();
+                }
+            } else if self.check(Token::Keyword(Keyword::Static)) {
+                if let Ok(s) = self.parse_static(public) {
+                    program.statics.push(s);
+                } else {
+                    self.synchronize_item();
+                }
+            } else if self.check(Token::Keyword(Keyword::Const)) {
+                if let Ok(c) = self.parse_const(public) {
+                    program.consts.push(c);
+                } else {
+                    self.synchronize_item();
+                }
             } else if self.check(Token::Keyword(Keyword::Fn)) {
                 let start_span = self.previous_span();
-                if let Ok(f) = self.parse_function(start_span, FunctionType::Normal) {
+                if let Ok(f) = self.parse_function(start_span, FunctionType::Normal, public) {
                     program.functions.push(f);
                 } else {
                     self.synchronize_item();
@@ -377,7 +529,7 @@ impl<'a> Parser<'a> {
                 if self.expect(Token::Keyword(Keyword::Fn)).is_err() {
                     self.synchronize_item();
                 }
-                if let Ok(f) = self.parse_function(start_span, FunctionType::Extern) {
+                if let Ok(f) = self.parse_function(start_span, FunctionType::Extern, public) {
                     program.functions.push(f);
                 } else {
                     self.synchronize_item();
@@ -389,7 +541,87 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_struct(&mut self) -> ParseResult<Struct> {
+    fn parse_module(&mut self) -> ParseResult<Module> {
+        let start_span = self.previous_span();
+        let name = self.consume_ident()?;
+        self.expect_semicolon()?;
+        let complete_span = self.previous_span().merge(start_span);
+        Ok(Module {
+            complete_span,
+            name,
+            program: None,
+        })
+    }
+
+    fn parse_use(&mut self) -> ParseResult<Use> {
+        let start_span = self.previous_span();
+        let mut path = vec![self.consume_ident()?];
+        while self.check(Token::DoubleColon) {
+            path.push(self.consume_ident()?);
+        }
+        self.expect_semicolon()?;
+        let complete_span = self.previous_span().merge(start_span);
+        Ok(Use {
+            complete_span,
+            path,
+        })
+    }
+
+    fn parse_impl(&mut self) -> ParseResult<Impl> {
+        let start_span = self.previous_span();
+        let type_params = if self.check(Token::Less) {
+            self.parse_type_var_list()?
+        } else {
+            Vec::new()
+        };
+        let trait_ = match (self.peek(), self.peek2()) {
+            (Some(&Token::Ident(_)), Some(&Token::Keyword(Keyword::For))) => {
+                let name = self.consume_ident()?;
+                self.expect(Token::Keyword(Keyword::For))?;
+                Some(name)
+            }
+            _ => None,
+        };
+        let typ = self.parse_type()?;
+        self.expect(Token::LeftBrace)?;
+        let mut functions = Vec::new();
+        while !self.check(Token::RightBrace) {
+            self.last_line_completed = true;
+            self.expect(Token::Keyword(Keyword::Fn))?;
+            let start_span = self.previous_span();
+            functions.push(self.parse_function(start_span, FunctionType::Normal, false)?);
+        }
+        let complete_span = self.previous_span().merge(start_span);
+        Ok(Impl {
+            complete_span,
+            type_params,
+            trait_,
+            typ,
+            functions,
+        })
+    }
+
+    fn parse_trait(&mut self, public: bool) -> ParseResult<Trait> {
+        let start_span = self.previous_span();
+        let name = self.consume_ident()?;
+        self.expect(Token::LeftBrace)?;
+        let mut functions = Vec::new();
+        while !self.check(Token::RightBrace) {
+            self.last_line_completed = true;
+            self.expect(Token::Keyword(Keyword::Fn))?;
+            let start_span = self.previous_span();
+            functions.push(self.parse_function(start_span, FunctionType::Normal, false)?);
+        }
+        let complete_span = self.previous_span().merge(start_span);
+        Ok(Trait {
+            complete_span,
+            public,
+            name,
+            functions,
+        })
+    }
+
+    fn parse_struct(&mut self, public: bool) -> ParseResult<Struct> {
         let start_span = self.previous_span();
         let name = self.parse_item_name()?;
         self.expect(Token::LeftBrace)?;
@@ -406,10 +638,107 @@ impl<'a> Parser<'a> {
             self.expect(Token::Comma)?;
         }
         let complete_span = self.previous_span().merge(start_span);
-        Ok(Struct { name, fields, complete_span })
+        Ok(Struct {
+            complete_span,
+            public,
+            name,
+            fields,
+        })
     }
 
-    fn parse_function(&mut self, start_span: Span, fn_type: FunctionType) -> ParseResult<Function> {
+    fn parse_enum(&mut self, public: bool) -> ParseResult<Enum> {
+        let start_span = self.previous_span();
+        let name = self.parse_item_name()?;
+        self.expect(Token::LeftBrace)?;
+        let mut variants = Vec::new();
+        while !self.check(Token::RightBrace) {
+            self.last_line_completed = true;
+            let name = self.consume_ident()?;
+            let mut fields = Vec::new();
+            if self.check(Token::LeftParen) {
+                let open_span = self.previous_span();
+                while !self.check(Token::RightParen) {
+                    fields.push(self.parse_type()?);
+                    if self.check(Token::RightParen) {
+                        break;
+                    }
+                    self.expect_closing(Token::Comma, open_span)?;
+                }
+            }
+            variants.push(Variant { name, fields });
+            if self.check(Token::RightBrace) {
+                break;
+            }
+            self.expect(Token::Comma)?;
+        }
+        let complete_span = self.previous_span().merge(start_span);
+        Ok(Enum {
+            complete_span,
+            public,
+            name,
+            variants,
+        })
+    }
+
+    fn parse_static(&mut self, public: bool) -> ParseResult<Static> {
+        let start_span = self.previous_span();
+        let mutable = self.check(Token::Keyword(Keyword::Mut));
+        let name = self.consume_ident()?;
+        self.expect(Token::Colon)?;
+        let typ = self.parse_type()?;
+        self.expect(Token::Assign)?;
+        let value = self.parse_expr()?;
+        self.expect_semicolon()?;
+        let complete_span = self.previous_span().merge(start_span);
+        Ok(Static {
+            complete_span,
+            public,
+            mutable,
+            name,
+            typ,
+            value,
+        })
+    }
+
+    fn parse_type_alias(&mut self, public: bool) -> ParseResult<TypeAlias> {
+        let start_span = self.previous_span();
+        let name = self.parse_item_name()?;
+        self.expect(Token::Assign)?;
+        let typ = self.parse_type()?;
+        self.expect_semicolon()?;
+        let complete_span = self.previous_span().merge(start_span);
+        Ok(TypeAlias {
+            complete_span,
+            public,
+            name,
+            typ,
+        })
+    }
+
+    fn parse_const(&mut self, public: bool) -> ParseResult<Const> {
+        let start_span = self.previous_span();
+        let name = self.consume_ident()?;
+        self.expect(Token::Colon)?;
+        let typ = self.parse_type()?;
+        self.expect(Token::Assign)?;
+        let value = self.parse_expr()?;
+        self.expect_semicolon()?;
+        let complete_span = self.previous_span().merge(start_span);
+        Ok(Const {
+            complete_span,
+            public,
+            name,
+            typ,
+            value,
+        })
+    }
+
+    fn parse_function(
+        &mut self,
+        start_span: Span,
+        fn_type: FunctionType,
+        public: bool,
+    ) -> ParseResult<Function> {
         let name = self.parse_item_name()?;
         self.expect(Token::LeftParen)?;
         let open_span = self.previous_span();
@@ -441,6 +770,7 @@ impl<'a> Parser<'a> {
         };
         Ok(Function {
             complete_span,
+            public,
             fn_type,
             name,
             params,
@@ -452,20 +782,36 @@ impl<'a> Parser<'a> {
     fn parse_item_name(&mut self) -> ParseResult<ItemName> {
         let name = self.consume_ident()?;
         let type_params = if self.check(Token::Less) {
-            let open_span = self.previous_span();
-            let mut type_params = Vec::new();
-            type_params.push(self.consume_ident()?);
-            while self.check(Token::Comma) {
-                type_params.push(self.consume_ident()?);
-            }
-            self.expect_closing(Token::Greater, open_span)?;
-            type_params
+            self.parse_type_var_list()?
         } else {
             Vec::new()
         };
         Ok(ItemName { name, type_params })
     }
 
+    fn parse_type_var_list(&mut self) -> ParseResult<Vec<TypeParam>> {
+        let open_span = self.previous_span();
+        let mut type_params = Vec::new();
+        type_params.push(self.parse_type_var()?);
+        while self.check(Token::Comma) {
+            type_params.push(self.parse_type_var()?);
+        }
+        self.expect_closing(Token::Greater, open_span)?;
+        Ok(type_params)
+    }
+
+    fn parse_type_var(&mut self) -> ParseResult<TypeParam> {
+        let name = self.consume_ident()?;
+        let mut bounds = Vec::new();
+        if self.check(Token::Colon) {
+            bounds.push(self.consume_ident()?);
+            while self.check(Token::Plus) {
+                bounds.push(self.consume_ident()?);
+            }
+        }
+        Ok(TypeParam { name, bounds })
+    }
+
     fn parse_type(&mut self) -> ParseResult<Spanned<Type>> {
         self.expected
             .insert(Expectation::Token(TokenKind::BuiltinType));
@@ -479,6 +825,33 @@ impl<'a> Parser<'a> {
             let span = start.merge(Spanned::span(&typ));
             let typ = Type::Pointer(Box::new(typ));
             Ok(Spanned::new(typ, span))
+        } else if self.check(Token::LeftBracket) {
+            let start = self.previous_span();
+            let typ = self.parse_type()?;
+            self.expect(Token::Semicolon)?;
+            let len = self.parse_array_len()?;
+            self.expect_closing(Token::RightBracket, start)?;
+            let span = start.merge(self.previous_span());
+            let typ = Type::Array(Box::new(typ), len);
+            Ok(Spanned::new(typ, span))
+        } else if self.check(Token::LeftParen) {
+            let start = self.previous_span();
+            let typ = self.parse_type()?;
+            if self.check(Token::RightParen) {
+                // just a parenthesised type
+                return Ok(typ);
+            }
+            self.expect_closing(Token::Comma, start)?;
+            let mut types = vec![typ];
+            while !self.check(Token::RightParen) {
+                types.push(self.parse_type()?);
+                if self.check(Token::RightParen) {
+                    break;
+                }
+                self.expect_closing(Token::Comma, start)?;
+            }
+            let span = start.merge(self.previous_span());
+            Ok(Spanned::new(Type::Tuple(types), span))
         } else if self.check(Token::Keyword(Keyword::Fn)) {
             let start = self.previous_span();
             self.expect(Token::LeftParen)?;
@@ -499,6 +872,20 @@ impl<'a> Parser<'a> {
             let span = start.merge(Spanned::span(&return_type));
             let typ = Type::Function(param_types, Box::new(return_type));
             Ok(Spanned::new(typ, span))
+        } else if self.check(Token::Or) {
+            let start = self.previous_span();
+            self.parse_closure_type(start, Vec::new())
+        } else if self.check(Token::Pipe) {
+            let start = self.previous_span();
+            let mut param_types = Vec::new();
+            while !self.check(Token::Pipe) {
+                param_types.push(self.parse_type()?);
+                if self.check(Token::Pipe) {
+                    break;
+                }
+                self.expect_closing(Token::Comma, start)?;
+            }
+            self.parse_closure_type(start, param_types)
         } else if self.check(Token::Underscore) {
             let span = self.previous_span();
             Ok(Spanned::new(Type::Wildcard, span))
@@ -520,6 +907,12 @@ impl<'a> Parser<'a> {
         } else if self.check(Token::Keyword(Keyword::U32)) {
             let span = self.previous_span();
             Ok(Spanned::new(Type::U32, span))
+        } else if self.check(Token::Keyword(Keyword::I64)) {
+            let span = self.previous_span();
+            Ok(Spanned::new(Type::I64, span))
+        } else if self.check(Token::Keyword(Keyword::U64)) {
+            let span = self.previous_span();
+            Ok(Spanned::new(Type::U64, span))
         } else if self.check(Token::Keyword(Keyword::Bool)) {
             let span = self.previous_span();
             Ok(Spanned::new(Type::Bool, span))
@@ -528,7 +921,7 @@ impl<'a> Parser<'a> {
             let params = if self.check(Token::Less) {
                 let open_span = self.previous_span();
                 let types = self.parse_type_params()?;
-                self.expect_closing(Token::Greater, open_span)?;
+                self.expect_closing_angle(open_span)?;
                 types
             } else {
                 Vec::new()
@@ -539,6 +932,42 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn parse_closure_type(
+        &mut self,
+        start: Span,
+        param_types: Vec<Spanned<Type>>,
+    ) -> ParseResult<Spanned<Type>> {
+        let return_type = if self.check(Token::Arrow) {
+            self.parse_type()?
+        } else {
+            Spanned::new(Type::Unit, self.previous_span())
+        };
+        let span = start.merge(Spanned::span(&return_type));
+        let typ = Type::Closure(param_types, Box::new(return_type));
+        Ok(Spanned::new(typ, span))
+    }
+
+    fn parse_array_len(&mut self) -> ParseResult<u32> {
+        self.expected.insert(Expectation::Token(TokenKind::Literal));
+        let len = match self.peek() {
+            Some(&Token::Number(num)) => num.value,
+            _ => {
+                self.emit_error(None);
+                return Err(());
+            }
+        };
+        let tok = self.consume().expect("token disappeared");
+        if len > u64::from(u32::MAX) {
+            let span = Spanned::span(&tok);
+            self.reporter
+                .error("array length is too large", span)
+                .span(span)
+                .build();
+            return Err(());
+        }
+        Ok(len as u32)
+    }
+
     fn parse_type_params(&mut self) -> ParseResult<Vec<Spanned<Type>>> {
         let mut types = Vec::new();
         types.push(self.parse_type()?);
@@ -550,31 +979,41 @@ impl<'a> Parser<'a> {
 
     fn parse_statement(&mut self) -> ParseResult<Spanned<Statement>> {
         self.last_line_completed = true;
+        if let Some(label) = self.check_label() {
+            self.expect(Token::Colon)?;
+            return match self.parse_loop_statement(Some(label))? {
+                Some(stmt) => Ok(stmt),
+                None => {
+                    self.emit_error(None);
+                    Err(())
+                }
+            };
+        }
+        if let Some(stmt) = self.parse_loop_statement(None)? {
+            return Ok(stmt);
+        }
         if self.check(Token::Keyword(Keyword::If)) {
             self.parse_if()
-        } else if self.check(Token::Keyword(Keyword::Loop)) {
-            let start = self.previous_span();
-            self.expect(Token::LeftBrace)?;
-            let body = self.parse_block()?;
-            let span = start.merge(self.previous_span());
-            let stmt = Statement::Loop(Box::new(body));
-            Ok(Spanned::new(stmt, span))
-        } else if self.check(Token::Keyword(Keyword::While)) {
+        } else if self.check(Token::Keyword(Keyword::Match)) {
+            self.parse_match()
+        } else if self.check(Token::Keyword(Keyword::Break)) {
             let start = self.previous_span();
-            let cond = self.parse_expr()?;
-            self.expect(Token::LeftBrace)?;
-            let body = self.parse_block()?;
+            let label = self.check_label();
+            let value = if self.check(Token::Semicolon) {
+                None
+            } else {
+                let value = self.parse_expr()?;
+                self.expect_semicolon()?;
+                Some(value)
+            };
             let span = start.merge(self.previous_span());
-            let stmt = Statement::While(cond, Box::new(body));
-            Ok(Spanned::new(stmt, span))
-        } else if self.check(Token::Keyword(Keyword::Break)) {
-            let span = self.previous_span();
-            self.expect_semicolon()?;
-            Ok(Spanned::new(Statement::Break, span))
+            Ok(Spanned::new(Statement::Break(label, value), span))
         } else if self.check(Token::Keyword(Keyword::Continue)) {
-            let span = self.previous_span();
+            let start = self.previous_span();
+            let label = self.check_label();
             self.expect_semicolon()?;
-            Ok(Spanned::new(Statement::Continue, span))
+            let span = start.merge(self.previous_span());
+            Ok(Spanned::new(Statement::Continue(label), span))
         } else if self.check(Token::Keyword(Keyword::Return)) {
             let start = self.previous_span();
             let value = if self.check(Token::Semicolon) {
@@ -588,6 +1027,9 @@ impl<'a> Parser<'a> {
             Ok(Spanned::new(Statement::Return(value), span))
         } else if self.check(Token::Keyword(Keyword::Let)) {
             let start = self.previous_span();
+            if self.check(Token::LeftParen) {
+                return self.parse_let_tuple(start);
+            }
             let name = self.consume_ident()?;
             let typ = if self.check(Token::Colon) {
                 Some(self.parse_type()?)
@@ -616,6 +1058,87 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses `loop`, `while` or `for` with given label, returns
+    /// `None` if the next token doesn't start any of them.
+    fn parse_loop_statement(
+        &mut self,
+        label: Option<Spanned<Ident>>,
+    ) -> ParseResult<Option<Spanned<Statement>>> {
+        let keyword = if self.check(Token::Keyword(Keyword::Loop)) {
+            Keyword::Loop
+        } else if self.check(Token::Keyword(Keyword::While)) {
+            Keyword::While
+        } else if self.check(Token::Keyword(Keyword::For)) {
+            Keyword::For
+        } else {
+            return Ok(None);
+        };
+        let start = match label {
+            Some(ref label) => Spanned::span(label),
+            None => self.previous_span(),
+        };
+        let stmt = if keyword == Keyword::Loop {
+            self.expect(Token::LeftBrace)?;
+            let body = self.parse_block()?;
+            Statement::Loop(label, Box::new(body))
+        } else if keyword == Keyword::While {
+            let cond = self.parse_expr()?;
+            self.expect(Token::LeftBrace)?;
+            let body = self.parse_block()?;
+            Statement::While(label, cond, Box::new(body))
+        } else {
+            let name = self.consume_ident()?;
+            self.expect(Token::Keyword(Keyword::In))?;
+            let range_start = self.parse_expr()?;
+            let inclusive = if self.check(Token::DotDotEqual) {
+                true
+            } else {
+                self.expect(Token::DotDot)?;
+                false
+            };
+            let range_end = self.parse_expr()?;
+            self.expect(Token::LeftBrace)?;
+            let body = self.parse_block()?;
+            Statement::For(
+                label,
+                name,
+                range_start,
+                range_end,
+                inclusive,
+                Box::new(body),
+            )
+        };
+        Ok(Some(Spanned::new(stmt, start.merge(self.previous_span()))))
+    }
+
+    fn parse_let_tuple(&mut self, start: Span) -> ParseResult<Spanned<Statement>> {
+        let open_span = self.previous_span();
+        let mut names = Vec::new();
+        while !self.check(Token::RightParen) {
+            if self.check(Token::Underscore) {
+                names.push(Spanned::new(None, self.previous_span()));
+            } else {
+                let name = self.consume_ident()?;
+                names.push(Spanned::map(name, Some));
+            }
+            if self.check(Token::RightParen) {
+                break;
+            }
+            self.expect_closing(Token::Comma, open_span)?;
+        }
+        let typ = if self.check(Token::Colon) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        self.expect(Token::Assign)?;
+        let value = self.parse_expr()?;
+        self.expect_semicolon()?;
+        let span = start.merge(self.previous_span());
+        let stmt = Statement::LetTuple(names, typ, value);
+        Ok(Spanned::new(stmt, span))
+    }
+
     fn parse_if(&mut self) -> ParseResult<Spanned<Statement>> {
         let start = self.previous_span();
         let cond = self.parse_expr()?;
@@ -636,6 +1159,80 @@ impl<'a> Parser<'a> {
         Ok(Spanned::new(stmt, span))
     }
 
+    fn parse_match(&mut self) -> ParseResult<Spanned<Statement>> {
+        let start = self.previous_span();
+        let value = self.parse_expr()?;
+        self.expect(Token::LeftBrace)?;
+        let mut arms = Vec::new();
+        while !self.check(Token::RightBrace) {
+            self.last_line_completed = true;
+            let pattern = self.parse_pattern()?;
+            self.expect(Token::FatArrow)?;
+            self.expect(Token::LeftBrace)?;
+            let body = self.parse_block()?;
+            arms.push(MatchArm { pattern, body });
+        }
+        let span = start.merge(self.previous_span());
+        let stmt = Statement::Match(value, arms);
+        Ok(Spanned::new(stmt, span))
+    }
+
+    fn parse_pattern(&mut self) -> ParseResult<Spanned<Pattern>> {
+        if self.check(Token::Underscore) {
+            let span = self.previous_span();
+            return Ok(Spanned::new(Pattern::Wildcard, span));
+        }
+        self.expected.insert(Expectation::Token(Token::Minus.kind()));
+        self.expected.insert(Expectation::Token(TokenKind::Literal));
+        match self.peek() {
+            Some(&Token::Minus) | Some(&Token::Number(_)) | Some(&Token::Char(_)) => {
+                let start = self.parse_literal_pattern()?;
+                if self.check(Token::DotDotEqual) {
+                    let end = self.parse_literal_pattern()?;
+                    let span = Spanned::span(&start).merge(Spanned::span(&end));
+                    let pattern = Pattern::Range(Box::new(start), Box::new(end));
+                    return Ok(Spanned::new(pattern, span));
+                }
+                return Ok(start);
+            }
+            _ => {}
+        }
+        let name = self.consume_ident()?;
+        if self.check(Token::LeftParen) {
+            let open_span = self.previous_span();
+            let mut patterns = Vec::new();
+            while !self.check(Token::RightParen) {
+                patterns.push(self.parse_pattern()?);
+                if self.check(Token::RightParen) {
+                    break;
+                }
+                self.expect_closing(Token::Comma, open_span)?;
+            }
+            let span = Spanned::span(&name).merge(self.previous_span());
+            Ok(Spanned::new(Pattern::Variant(name, patterns), span))
+        } else {
+            let span = Spanned::span(&name);
+            Ok(Spanned::new(Pattern::Name(name), span))
+        }
+    }
+
+    fn parse_literal_pattern(&mut self) -> ParseResult<Spanned<Pattern>> {
+        let start = self.peek_span();
+        let negated = self.check(Token::Minus);
+        self.expected.insert(Expectation::Token(TokenKind::Literal));
+        let literal = match self.peek() {
+            Some(&Token::Number(num)) => Literal::Number(num),
+            Some(&Token::Char(ch)) if !negated => Literal::Char(ch),
+            _ => {
+                self.emit_error(None);
+                return Err(());
+            }
+        };
+        self.consume().expect("token disappeared");
+        let span = start.merge(self.previous_span());
+        Ok(Spanned::new(Pattern::Literal(negated, literal), span))
+    }
+
     fn parse_block(&mut self) -> ParseResult<Spanned<Statement>> {
         let start = self.previous_span();
         let mut statements = Vec::new();
@@ -654,6 +1251,67 @@ impl<'a> Parser<'a> {
         Ok(Spanned::new(Statement::Block(statements), span))
     }
 
+    /// Parses block expression after its opening brace.
+    fn parse_block_expr(&mut self) -> ParseResult<Spanned<Expr>> {
+        let start = self.previous_span();
+        let mut statements = Vec::new();
+        let mut value = None;
+        while !self.check(Token::RightBrace) {
+            let statement_start = self.previous_span();
+            match self.parse_block_item() {
+                Ok(Ok(stmt)) => statements.push(stmt),
+                Ok(Err(expr)) => {
+                    value = Some(Box::new(expr));
+                    break;
+                }
+                Err(()) => {
+                    let span = statement_start.merge(self.previous_span());
+                    statements.push(Spanned::new(Statement::Error, span));
+                    self.synchronize_statement()?;
+                }
+            }
+        }
+        let span = start.merge(self.previous_span());
+        Ok(Spanned::new(Expr::Block(statements, value), span))
+    }
+
+    /// Parses a statement in block expression, or the expression
+    /// that ends the block together with the closing brace.
+    fn parse_block_item(&mut self) -> ParseResult<Result<Spanned<Statement>, Spanned<Expr>>> {
+        self.last_line_completed = true;
+        match self.peek() {
+            Some(&Token::Keyword(Keyword::If)) | Some(&Token::LeftBrace) => {
+                // blocks and `if`s are values in block expressions,
+                // and don't need a semicolon when used as statements
+                let expr = self.parse_expr()?;
+                if self.check(Token::RightBrace) {
+                    return Ok(Err(expr));
+                }
+                self.check(Token::Semicolon);
+                let span = Spanned::span(&expr);
+                Ok(Ok(Spanned::new(Statement::Expr(expr), span)))
+            }
+            Some(&Token::Keyword(Keyword::Loop)) |
+            Some(&Token::Label(_)) |
+            Some(&Token::Keyword(Keyword::While)) |
+            Some(&Token::Keyword(Keyword::For)) |
+            Some(&Token::Keyword(Keyword::Match)) |
+            Some(&Token::Keyword(Keyword::Break)) |
+            Some(&Token::Keyword(Keyword::Continue)) |
+            Some(&Token::Keyword(Keyword::Return)) |
+            Some(&Token::Keyword(Keyword::Let)) => self.parse_statement().map(Ok),
+            _ => {
+                let expr = self.parse_expr()?;
+                if self.check(Token::RightBrace) {
+                    return Ok(Err(expr));
+                }
+                self.expect_semicolon()?;
+                let span = Spanned::span(&expr);
+                Ok(Ok(Spanned::new(Statement::Expr(expr), span)))
+            }
+        }
+    }
+
     fn parse_expr(&mut self) -> ParseResult<Spanned<Expr>> {
         self.pratt_parse(Precedence::Lowest)
     }
@@ -696,6 +1354,10 @@ enum Precedence {
     And,
     Equation,
     Comparision,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
     Addition,
     Multiplication,
     Cast,
@@ -712,7 +1374,11 @@ impl Precedence {
             And => Or,
             Equation => And,
             Comparision => Equation,
-            Addition => Comparision,
+            BitOr => Comparision,
+            BitXor => BitOr,
+            BitAnd => BitXor,
+            Shift => BitAnd,
+            Addition => Shift,
             Multiplication => Addition,
             Prefix => Multiplication,
             Cast => Prefix,
@@ -805,13 +1471,40 @@ impl InfixParser for FieldParser {
 
     fn parse(&self, parser: &mut Parser, value: Spanned<Expr>) -> ParseResult<Spanned<Expr>> {
         parser.expect(Token::Dot).expect("expected dot");
-        let field = parser.consume_ident()?;
+        let field = match parser.peek() {
+            // tuple fields are named by their index
+            Some(&Token::Number(num)) if num.signedness.is_none() && num.size.is_none() => {
+                let tok = parser.consume().expect("token disappeared");
+                Spanned::new(Ident(num.value.to_string()), Spanned::span(&tok))
+            }
+            _ => parser.consume_ident()?,
+        };
         let span = Spanned::span(&value).merge(Spanned::span(&field));
         let expr = Expr::Field(Box::new(value), field);
         Ok(Spanned::new(expr, span))
     }
 }
 
+struct IndexParser;
+
+impl InfixParser for IndexParser {
+    fn precedence(&self) -> Precedence {
+        Precedence::CallOrField
+    }
+
+    fn parse(&self, parser: &mut Parser, value: Spanned<Expr>) -> ParseResult<Spanned<Expr>> {
+        parser
+            .expect(Token::LeftBracket)
+            .expect("expected left bracket");
+        let open_span = parser.previous_span();
+        let index = parser.parse_expr()?;
+        parser.expect_closing(Token::RightBracket, open_span)?;
+        let span = Spanned::span(&value).merge(parser.previous_span());
+        let expr = Expr::Index(Box::new(value), Box::new(index));
+        Ok(Spanned::new(expr, span))
+    }
+}
+
 struct CastParser;
 
 impl InfixParser for CastParser {
@@ -833,17 +1526,28 @@ struct NameParser;
 impl PrefixParser for NameParser {
     fn parse(&self, parser: &mut Parser) -> ParseResult<Spanned<Expr>> {
         let ident = parser.consume_ident().expect("identifier disappeared");
+        let item = if parser.peek() == Some(&Token::DoubleColon)
+            && parser.peek2().map(Token::kind) == Some(TokenKind::Ident)
+        {
+            parser.consume().expect("token disappeared");
+            Some(parser.consume_ident().expect("identifier disappeared"))
+        } else {
+            None
+        };
         let type_params = if parser.check(Token::DoubleColon) {
             parser.expect(Token::Less)?;
             let open_span = parser.previous_span();
             let types = parser.parse_type_params()?;
-            parser.expect_closing(Token::Greater, open_span)?;
+            parser.expect_closing_angle(open_span)?;
             types
         } else {
             Vec::new()
         };
         let span = Spanned::span(&ident).merge(parser.previous_span());
-        let expr = Expr::Name(ident, type_params);
+        let expr = match item {
+            Some(item) => Expr::Associated(ident, item, type_params),
+            None => Expr::Name(ident, type_params),
+        };
         Ok(Spanned::new(expr, span))
     }
 }
@@ -888,7 +1592,125 @@ impl PrefixParser for ParenthesisedParser {
         let tok = parser.consume().expect("token disappeared");
         let open_span = Spanned::span(&tok);
         let expr = parser.parse_expr()?;
-        parser.expect_closing(Token::RightParen, open_span)?;
-        Ok(expr)
+        if parser.check(Token::RightParen) {
+            return Ok(expr);
+        }
+        parser.expect_closing(Token::Comma, open_span)?;
+        let mut values = vec![expr];
+        while !parser.check(Token::RightParen) {
+            values.push(parser.parse_expr()?);
+            if parser.check(Token::RightParen) {
+                break;
+            }
+            parser.expect_closing(Token::Comma, open_span)?;
+        }
+        let span = open_span.merge(parser.previous_span());
+        let expr = Expr::Tuple(values);
+        Ok(Spanned::new(expr, span))
+    }
+}
+
+struct IfParser;
+
+impl PrefixParser for IfParser {
+    fn parse(&self, parser: &mut Parser) -> ParseResult<Spanned<Expr>> {
+        let tok = parser.consume().expect("token disappeared");
+        let start = Spanned::span(&tok);
+        let cond = parser.parse_expr()?;
+        parser.expect(Token::LeftBrace)?;
+        let then = parser.parse_block_expr()?;
+        let else_ = if parser.check(Token::Keyword(Keyword::Else)) {
+            if parser.peek() == Some(&Token::Keyword(Keyword::If)) {
+                Some(Box::new(self.parse(parser)?))
+            } else {
+                parser.expect(Token::LeftBrace)?;
+                Some(Box::new(parser.parse_block_expr()?))
+            }
+        } else {
+            None
+        };
+        let span = start.merge(parser.previous_span());
+        let expr = Expr::If(Box::new(cond), Box::new(then), else_);
+        Ok(Spanned::new(expr, span))
+    }
+}
+
+struct BlockParser;
+
+impl PrefixParser for BlockParser {
+    fn parse(&self, parser: &mut Parser) -> ParseResult<Spanned<Expr>> {
+        parser.consume().expect("token disappeared");
+        parser.parse_block_expr()
+    }
+}
+
+struct LoopParser;
+
+impl PrefixParser for LoopParser {
+    fn parse(&self, parser: &mut Parser) -> ParseResult<Spanned<Expr>> {
+        let label = parser.check_label();
+        if label.is_some() {
+            parser.expect(Token::Colon)?;
+        }
+        // only `loop` can give a value, so `while`
+        // and `for` are not allowed here
+        parser.expect(Token::Keyword(Keyword::Loop))?;
+        let start = match label {
+            Some(ref label) => Spanned::span(label),
+            None => parser.previous_span(),
+        };
+        parser.expect(Token::LeftBrace)?;
+        let body = parser.parse_block()?;
+        let span = start.merge(parser.previous_span());
+        Ok(Spanned::new(Expr::Loop(label, Box::new(body)), span))
+    }
+}
+
+struct ArrayParser;
+
+impl PrefixParser for ArrayParser {
+    fn parse(&self, parser: &mut Parser) -> ParseResult<Spanned<Expr>> {
+        let tok = parser.consume().expect("token disappeared");
+        let open_span = Spanned::span(&tok);
+        let mut values = Vec::new();
+        while !parser.check(Token::RightBracket) {
+            values.push(parser.parse_expr()?);
+            if parser.check(Token::RightBracket) {
+                break;
+            }
+            parser.expect_closing(Token::Comma, open_span)?;
+        }
+        let span = open_span.merge(parser.previous_span());
+        let expr = Expr::Array(values);
+        Ok(Spanned::new(expr, span))
+    }
+}
+
+struct LambdaParser;
+
+impl PrefixParser for LambdaParser {
+    fn parse(&self, parser: &mut Parser) -> ParseResult<Spanned<Expr>> {
+        let tok = parser.consume().expect("token disappeared");
+        let open_span = Spanned::span(&tok);
+        let mut params = Vec::new();
+        if Spanned::into_value(tok) == Token::Pipe {
+            while !parser.check(Token::Pipe) {
+                let name = parser.consume_ident()?;
+                let typ = if parser.check(Token::Colon) {
+                    parser.parse_type()?
+                } else {
+                    Spanned::new(Type::Wildcard, Spanned::span(&name))
+                };
+                params.push(Var { name, typ });
+                if parser.check(Token::Pipe) {
+                    break;
+                }
+                parser.expect_closing(Token::Comma, open_span)?;
+            }
+        }
+        let body = parser.parse_expr()?;
+        let span = open_span.merge(Spanned::span(&body));
+        let expr = Expr::Lambda(params, Box::new(body));
+        Ok(Spanned::new(expr, span))
     }
 }
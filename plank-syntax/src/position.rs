@@ -1,5 +1,5 @@
 use std::{borrow, ops};
-pub use plank_errors::position::{Position, Span};
+pub use plank_errors::position::{FileId, Position, Span};
 
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
@@ -5,6 +5,8 @@ pub use ast::Number;
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub enum Token {
     Ident(String),
+    /// Loop label, like `'outer`, without the quote.
+    Label(String),
     Number(Number),
     Bool(bool),
     Char(u8),
@@ -22,12 +24,22 @@ pub enum Token {
     Slash,
     Percent,
     Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
     Dot,
+    DotDot,
+    DotDotEqual,
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Arrow,
+    FatArrow,
     And,
     Or,
     Comma,
@@ -37,6 +49,16 @@ pub enum Token {
     Semicolon,
     Not,
     Assign,
+    PlusAssign,
+    MinusAssign,
+    StarAssign,
+    SlashAssign,
+    PercentAssign,
+    AmpersandAssign,
+    PipeAssign,
+    CaretAssign,
+    ShiftLeftAssign,
+    ShiftRightAssign,
     Error,
 }
 
@@ -44,6 +66,7 @@ impl Token {
     pub fn kind(&self) -> TokenKind {
         match *self {
             Token::Ident(_) => TokenKind::Ident,
+            Token::Label(_) => TokenKind::Label,
             Token::Number(_) | Token::Bool(_) | Token::Char(_) | Token::Str(_) => {
                 TokenKind::Literal
             }
@@ -53,6 +76,8 @@ impl Token {
             Token::Keyword(Keyword::U16) |
             Token::Keyword(Keyword::I32) |
             Token::Keyword(Keyword::U32) |
+            Token::Keyword(Keyword::I64) |
+            Token::Keyword(Keyword::U64) |
             Token::Keyword(Keyword::Bool) => TokenKind::BuiltinType,
             ref tok => TokenKind::Token(tok.clone()),
         }
@@ -63,6 +88,7 @@ impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Token::Ident(ref s) => write!(f, "{}", s),
+            Token::Label(ref s) => write!(f, "'{}", s),
             Token::Number(n) => write!(f, "{}", n),
             Token::Bool(b) => write!(f, "{}", b),
             Token::Char(_) => write!(f, "'...'"),
@@ -80,12 +106,22 @@ impl fmt::Display for Token {
             Token::Slash => write!(f, "/"),
             Token::Percent => write!(f, "%"),
             Token::Ampersand => write!(f, "&"),
+            Token::Pipe => write!(f, "|"),
+            Token::Caret => write!(f, "^"),
+            Token::Tilde => write!(f, "~"),
+            Token::ShiftLeft => write!(f, "<<"),
+            Token::ShiftRight => write!(f, ">>"),
             Token::Dot => write!(f, "."),
+            Token::DotDot => write!(f, ".."),
+            Token::DotDotEqual => write!(f, "..="),
             Token::LeftParen => write!(f, "("),
             Token::RightParen => write!(f, ")"),
             Token::LeftBrace => write!(f, "{{"),
             Token::RightBrace => write!(f, "}}"),
+            Token::LeftBracket => write!(f, "["),
+            Token::RightBracket => write!(f, "]"),
             Token::Arrow => write!(f, "->"),
+            Token::FatArrow => write!(f, "=>"),
             Token::And => write!(f, "&&"),
             Token::Or => write!(f, "||"),
             Token::Comma => write!(f, ","),
@@ -94,6 +130,16 @@ impl fmt::Display for Token {
             Token::DoubleColon => write!(f, "::"),
             Token::Semicolon => write!(f, ";"),
             Token::Assign => write!(f, "="),
+            Token::PlusAssign => write!(f, "+="),
+            Token::MinusAssign => write!(f, "-="),
+            Token::StarAssign => write!(f, "*="),
+            Token::SlashAssign => write!(f, "/="),
+            Token::PercentAssign => write!(f, "%="),
+            Token::AmpersandAssign => write!(f, "&="),
+            Token::PipeAssign => write!(f, "|="),
+            Token::CaretAssign => write!(f, "^="),
+            Token::ShiftLeftAssign => write!(f, "<<="),
+            Token::ShiftRightAssign => write!(f, ">>="),
             Token::Not => write!(f, "!"),
             Token::Error => write!(f, "?"),
         }
@@ -105,10 +151,23 @@ pub enum Keyword {
     Extern,
     Fn,
     Struct,
+    Enum,
+    Static,
+    Mut,
+    Const,
+    Mod,
+    Use,
+    Pub,
+    Impl,
+    Trait,
+    Type,
+    For,
+    In,
     If,
     Else,
     Loop,
     While,
+    Match,
     Continue,
     Break,
     Let,
@@ -121,6 +180,8 @@ pub enum Keyword {
     U16,
     I32,
     U32,
+    I64,
+    U64,
     Unit,
 }
 
@@ -130,10 +191,23 @@ impl fmt::Display for Keyword {
             Keyword::Extern => write!(f, "extern"),
             Keyword::Fn => write!(f, "fn"),
             Keyword::Struct => write!(f, "struct"),
+            Keyword::Enum => write!(f, "enum"),
+            Keyword::Static => write!(f, "static"),
+            Keyword::Mut => write!(f, "mut"),
+            Keyword::Const => write!(f, "const"),
+            Keyword::Mod => write!(f, "mod"),
+            Keyword::Use => write!(f, "use"),
+            Keyword::Pub => write!(f, "pub"),
+            Keyword::Impl => write!(f, "impl"),
+            Keyword::Trait => write!(f, "trait"),
+            Keyword::Type => write!(f, "type"),
+            Keyword::For => write!(f, "for"),
+            Keyword::In => write!(f, "in"),
             Keyword::If => write!(f, "if"),
             Keyword::Else => write!(f, "else"),
             Keyword::Loop => write!(f, "loop"),
             Keyword::While => write!(f, "while"),
+            Keyword::Match => write!(f, "match"),
             Keyword::Continue => write!(f, "continue"),
             Keyword::Break => write!(f, "break"),
             Keyword::Let => write!(f, "let"),
@@ -145,6 +219,8 @@ impl fmt::Display for Keyword {
             Keyword::U8 => write!(f, "u8"),
             Keyword::U16 => write!(f, "u16"),
             Keyword::U32 => write!(f, "u32"),
+            Keyword::I64 => write!(f, "i64"),
+            Keyword::U64 => write!(f, "u64"),
             Keyword::Bool => write!(f, "bool"),
             Keyword::Unit => write!(f, "unit"),
         }
@@ -155,6 +231,7 @@ impl fmt::Display for Keyword {
 pub enum TokenKind {
     Token(Token),
     Ident,
+    Label,
     Literal,
     BuiltinType,
 }
@@ -173,9 +250,24 @@ impl TokenKind {
             TokenKind::Token(Token::Star) |
             TokenKind::Token(Token::Slash) |
             TokenKind::Token(Token::Percent) |
+            TokenKind::Token(Token::Ampersand) |
+            TokenKind::Token(Token::Pipe) |
+            TokenKind::Token(Token::Caret) |
+            TokenKind::Token(Token::ShiftLeft) |
+            TokenKind::Token(Token::ShiftRight) |
             TokenKind::Token(Token::And) |
             TokenKind::Token(Token::Or) |
-            TokenKind::Token(Token::Assign) => true,
+            TokenKind::Token(Token::Assign) |
+            TokenKind::Token(Token::PlusAssign) |
+            TokenKind::Token(Token::MinusAssign) |
+            TokenKind::Token(Token::StarAssign) |
+            TokenKind::Token(Token::SlashAssign) |
+            TokenKind::Token(Token::PercentAssign) |
+            TokenKind::Token(Token::AmpersandAssign) |
+            TokenKind::Token(Token::PipeAssign) |
+            TokenKind::Token(Token::CaretAssign) |
+            TokenKind::Token(Token::ShiftLeftAssign) |
+            TokenKind::Token(Token::ShiftRightAssign) => true,
             _ => false,
         }
     }
@@ -189,7 +281,9 @@ impl TokenKind {
             TokenKind::Token(Token::Star) |
             TokenKind::Token(Token::Ampersand) |
             TokenKind::Token(Token::LeftParen) |
-            TokenKind::Token(Token::Not) => true,
+            TokenKind::Token(Token::LeftBracket) |
+            TokenKind::Token(Token::Not) |
+            TokenKind::Token(Token::Tilde) => true,
             _ => false,
         }
     }
@@ -199,6 +293,7 @@ impl fmt::Display for TokenKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             TokenKind::Ident => write!(f, "identifier"),
+            TokenKind::Label => write!(f, "label"),
             TokenKind::Literal => write!(f, "literal"),
             TokenKind::BuiltinType => write!(f, "builtin type"),
             TokenKind::Token(ref tok) => write!(f, "`{}`", tok),
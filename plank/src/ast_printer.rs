@@ -1,4 +1,5 @@
-use plank_syntax::ast::{Program, Statement, Expr, Function, Struct, Ident, Var, Type, FunctionType, BinaryOp, UnaryOp, CallParam, Literal, Signedness, Size};
+use plank_syntax::ast::{Program, Module, Use, Impl, Trait, TypeAlias, TypeParam, Statement, Expr, Function, Static, Const, Struct, Enum, Pattern, Ident, Var, Type, FunctionType, BinaryOp, UnaryOp, CallParam, Literal, Signedness, Size};
+use plank_syntax::position::Spanned;
 
 
 #[derive(Copy, Clone)]
@@ -102,41 +103,195 @@ impl Formatter {
     }
 
     fn format_program(&mut self, program: &Program) {
+        for m in &program.modules {
+            self.format_module(m);
+        }
+        for u in &program.uses {
+            self.format_use(u);
+        }
         for s in &program.structs {
             self.format_struct(s);
         }
+        for e in &program.enums {
+            self.format_enum(e);
+        }
+        for t in &program.type_aliases {
+            self.format_type_alias(t);
+        }
+        for c in &program.consts {
+            self.format_const(c);
+        }
+        for s in &program.statics {
+            self.format_static(s);
+        }
+        for t in &program.traits {
+            self.format_trait(t);
+        }
+        for i in &program.impls {
+            self.format_impl(i);
+        }
         for f in &program.functions {
             self.format_fn(f);
         }
     }
 
+    fn format_module(&mut self, m: &Module) {
+        self.fmt.start_list();
+        self.fmt.write_symbol("mod");
+        self.format_ident(&m.name);
+        if let Some(ref program) = m.program {
+            self.fmt.make_list_multiline();
+            self.format_program(program);
+        }
+        self.fmt.end_list();
+    }
+
+    fn format_use(&mut self, u: &Use) {
+        self.fmt.start_list();
+        self.fmt.write_symbol("use");
+        for segment in &u.path {
+            self.format_ident(segment);
+        }
+        self.fmt.end_list();
+    }
+
+    fn write_def(&mut self, kind: &str, public: bool) {
+        if public {
+            self.fmt.write_symbol(&format!("def-pub-{}", kind));
+        } else {
+            self.fmt.write_symbol(&format!("def-{}", kind));
+        }
+    }
+
+    fn format_type_alias(&mut self, t: &TypeAlias) {
+        self.fmt.start_list();
+        self.write_def("type", t.public);
+        self.format_ident(&t.name.name);
+        self.fmt.start_list();
+        for type_param in &t.name.type_params {
+            self.format_type_param(type_param);
+        }
+        self.fmt.end_list();
+        self.format_type(&t.typ);
+        self.fmt.end_list();
+    }
+
+    fn format_const(&mut self, c: &Const) {
+        self.fmt.start_list();
+        self.write_def("const", c.public);
+        self.format_ident(&c.name);
+        self.format_type(&c.typ);
+        self.format_expr(&c.value);
+        self.fmt.end_list();
+    }
+
+    fn format_static(&mut self, s: &Static) {
+        self.fmt.start_list();
+        if s.mutable {
+            self.write_def("static-mut", s.public);
+        } else {
+            self.write_def("static", s.public);
+        }
+        self.format_ident(&s.name);
+        self.format_type(&s.typ);
+        self.format_expr(&s.value);
+        self.fmt.end_list();
+    }
+
     fn format_struct(&mut self, s: &Struct) {
         self.fmt.start_list();
-        self.fmt.write_symbol("def-struct");
+        self.write_def("struct", s.public);
         self.format_ident(&s.name.name);
         self.fmt.start_list();
         for type_param in &s.name.type_params {
-            self.format_ident(type_param);
+            self.format_type_param(type_param);
         }
         self.fmt.end_list();
         self.format_var_list(&s.fields, true);
         self.fmt.end_list();
     }
 
+    fn format_enum(&mut self, e: &Enum) {
+        self.fmt.start_list();
+        self.write_def("enum", e.public);
+        self.format_ident(&e.name.name);
+        self.fmt.start_list();
+        for type_param in &e.name.type_params {
+            self.format_type_param(type_param);
+        }
+        self.fmt.end_list();
+        self.fmt.start_list();
+        self.fmt.make_list_multiline();
+        for v in &e.variants {
+            self.fmt.start_list();
+            self.format_ident(&v.name);
+            for typ in &v.fields {
+                self.format_type(typ);
+            }
+            self.fmt.end_list();
+        }
+        self.fmt.end_list();
+        self.fmt.end_list();
+    }
+
+    fn format_impl(&mut self, i: &Impl) {
+        self.fmt.start_list();
+        self.fmt.write_symbol("impl");
+        self.fmt.start_list();
+        for type_param in &i.type_params {
+            self.format_type_param(type_param);
+        }
+        self.fmt.end_list();
+        if let Some(ref trait_) = i.trait_ {
+            self.format_ident(trait_);
+            self.fmt.write_symbol("for");
+        }
+        self.format_type(&i.typ);
+        self.fmt.make_list_multiline();
+        for f in &i.functions {
+            self.format_fn(f);
+        }
+        self.fmt.end_list();
+    }
+
+    fn format_trait(&mut self, t: &Trait) {
+        self.fmt.start_list();
+        self.write_def("trait", t.public);
+        self.format_ident(&t.name);
+        self.fmt.make_list_multiline();
+        for f in &t.functions {
+            self.format_fn(f);
+        }
+        self.fmt.end_list();
+    }
+
+    fn format_type_param(&mut self, param: &TypeParam) {
+        if param.bounds.is_empty() {
+            self.format_ident(&param.name);
+        } else {
+            self.fmt.start_list();
+            self.format_ident(&param.name);
+            for bound in &param.bounds {
+                self.format_ident(bound);
+            }
+            self.fmt.end_list();
+        }
+    }
+
     fn format_fn(&mut self, f: &Function) {
         self.fmt.start_list();
         match f.fn_type {
             FunctionType::Extern => {
-                self.fmt.write_symbol("def-extern-fn");
+                self.write_def("extern-fn", f.public);
             }
             FunctionType::Normal => {
-                self.fmt.write_symbol("def-fn");
+                self.write_def("fn", f.public);
             }
         }
         self.format_ident(&f.name.name);
         self.fmt.start_list();
         for type_param in &f.name.type_params {
-            self.format_ident(type_param);
+            self.format_type_param(type_param);
         }
         self.fmt.end_list();
         self.format_var_list(&f.params, false);
@@ -151,6 +306,12 @@ impl Formatter {
         self.fmt.write_symbol(&i.0);
     }
 
+    fn format_label(&mut self, label: &Option<Spanned<Ident>>) {
+        if let Some(ref label) = *label {
+            self.fmt.write_symbol(&format!("'{}", label.0));
+        }
+    }
+
     fn format_var_list(&mut self, vars: &[Var], multiline: bool) {
         self.fmt.start_list();
         if multiline {
@@ -175,6 +336,8 @@ impl Formatter {
             Type::U16 => self.fmt.write_symbol("u16"),
             Type::I32 => self.fmt.write_symbol("i32"),
             Type::U32 => self.fmt.write_symbol("u32"),
+            Type::I64 => self.fmt.write_symbol("i64"),
+            Type::U64 => self.fmt.write_symbol("u64"),
             Type::Wildcard => self.fmt.write_symbol("_"),
             Type::Pointer(ref typ) => {
                 self.fmt.start_list();
@@ -182,6 +345,21 @@ impl Formatter {
                 self.format_type(typ);
                 self.fmt.end_list();
             }
+            Type::Array(ref typ, len) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("array");
+                self.format_type(typ);
+                self.fmt.write_symbol(&format!("{}", len));
+                self.fmt.end_list();
+            }
+            Type::Tuple(ref types) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("tuple");
+                for typ in types {
+                    self.format_type(typ);
+                }
+                self.fmt.end_list();
+            }
             Type::Function(ref params, ref output) => {
                 self.fmt.start_list();
                 self.fmt.write_symbol("fn");
@@ -193,6 +371,17 @@ impl Formatter {
                 self.format_type(output);
                 self.fmt.end_list();
             }
+            Type::Closure(ref params, ref output) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("closure");
+                self.fmt.start_list();
+                for typ in params {
+                    self.format_type(typ);
+                }
+                self.fmt.end_list();
+                self.format_type(output);
+                self.fmt.end_list();
+            }
             Type::Concrete(ref name, ref params) => {
                 self.fmt.start_list();
                 self.fmt.write_symbol("named");
@@ -218,11 +407,26 @@ impl Formatter {
                 }
                 self.fmt.end_list();
             }
-            Statement::Break => {
+            Statement::Break(None, None) => {
                 self.fmt.write_symbol("break");
             }
-            Statement::Continue => {
+            Statement::Break(ref label, ref value) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("break");
+                self.format_label(label);
+                if let Some(ref value) = *value {
+                    self.format_expr(value);
+                }
+                self.fmt.end_list();
+            }
+            Statement::Continue(None) => {
+                self.fmt.write_symbol("continue");
+            }
+            Statement::Continue(ref label) => {
+                self.fmt.start_list();
                 self.fmt.write_symbol("continue");
+                self.format_label(label);
+                self.fmt.end_list();
             }
             Statement::Expr(ref expr) => {
                 self.fmt.start_list();
@@ -264,9 +468,27 @@ impl Formatter {
                 }
                 self.fmt.end_list();
             }
-            Statement::Loop(ref body) => {
+            Statement::LetTuple(ref names, ref typ, ref value) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("let-tuple");
+                self.fmt.start_list();
+                for name in names {
+                    match **name {
+                        Some(ref name) => self.format_ident(name),
+                        None => self.fmt.write_symbol("_"),
+                    }
+                }
+                self.fmt.end_list();
+                if let Some(ref typ) = *typ {
+                    self.format_type(typ);
+                }
+                self.format_expr(value);
+                self.fmt.end_list();
+            }
+            Statement::Loop(ref label, ref body) => {
                 self.fmt.start_list();
                 self.fmt.write_symbol("loop");
+                self.format_label(label);
                 self.format_statement(body);
                 self.fmt.end_list();
             }
@@ -276,20 +498,80 @@ impl Formatter {
                 self.format_expr(value);
                 self.fmt.end_list();
             }
-            Statement::While(ref cond, ref body) => {
+            Statement::While(ref label, ref cond, ref body) => {
                 self.fmt.start_list();
                 self.fmt.write_symbol("while");
                 self.fmt.make_list_multiline();
+                self.format_label(label);
                 self.format_expr(cond);
                 self.format_statement(body);
                 self.fmt.end_list();
             }
+            Statement::For(ref label, ref name, ref start, ref end, inclusive, ref body) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("for");
+                self.fmt.make_list_multiline();
+                self.format_label(label);
+                self.fmt.write_symbol(&name.0);
+                self.fmt.start_list();
+                self.fmt.write_symbol(if inclusive { "range-inclusive" } else { "range" });
+                self.format_expr(start);
+                self.format_expr(end);
+                self.fmt.end_list();
+                self.format_statement(body);
+                self.fmt.end_list();
+            }
+            Statement::Match(ref value, ref arms) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("match");
+                self.fmt.make_list_multiline();
+                self.format_expr(value);
+                for arm in arms {
+                    self.fmt.start_list();
+                    self.fmt.write_symbol("arm");
+                    self.fmt.make_list_multiline();
+                    self.format_pattern(&arm.pattern);
+                    self.format_statement(&arm.body);
+                    self.fmt.end_list();
+                }
+                self.fmt.end_list();
+            }
             Statement::Error => {
                 self.fmt.write_symbol("error");
             }
         }
     }
 
+    fn format_pattern(&mut self, p: &Pattern) {
+        match *p {
+            Pattern::Wildcard => self.fmt.write_symbol("_"),
+            Pattern::Name(ref name) => self.format_ident(name),
+            Pattern::Variant(ref name, ref patterns) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("variant");
+                self.format_ident(name);
+                for pattern in patterns {
+                    self.format_pattern(pattern);
+                }
+                self.fmt.end_list();
+            }
+            Pattern::Literal(false, ref literal) => self.format_literal(literal),
+            Pattern::Literal(true, ref literal) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("-");
+                self.format_literal(literal);
+                self.fmt.end_list();
+            }
+            Pattern::Range(ref start, ref end) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("range");
+                self.format_pattern(start);
+                self.format_pattern(end);
+                self.fmt.end_list();
+            }
+        }
+    }
+
     fn format_expr(&mut self, e: &Expr) {
         match *e {
             Expr::Binary(ref lhs, ref op, ref rhs) => {
@@ -325,6 +607,29 @@ impl Formatter {
                 self.format_ident(field);
                 self.fmt.end_list();
             }
+            Expr::Index(ref value, ref index) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("index");
+                self.format_expr(value);
+                self.format_expr(index);
+                self.fmt.end_list();
+            }
+            Expr::Array(ref values) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("array");
+                for value in values {
+                    self.format_expr(value);
+                }
+                self.fmt.end_list();
+            }
+            Expr::Tuple(ref values) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("tuple");
+                for value in values {
+                    self.format_expr(value);
+                }
+                self.fmt.end_list();
+            }
             Expr::Literal(ref lit) => {
                 self.format_literal(lit);
             }
@@ -339,6 +644,18 @@ impl Formatter {
                 self.fmt.end_list();
                 self.fmt.end_list();
             }
+            Expr::Associated(ref typ, ref name, ref type_params) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("assoc");
+                self.format_ident(typ);
+                self.format_ident(name);
+                self.fmt.start_list();
+                for param in type_params {
+                    self.format_type(param);
+                }
+                self.fmt.end_list();
+                self.fmt.end_list();
+            }
             Expr::Cast(ref value, ref typ) => {
                 self.fmt.start_list();
                 self.fmt.write_symbol("cast");
@@ -346,6 +663,48 @@ impl Formatter {
                 self.format_type(typ);
                 self.fmt.end_list();
             }
+            Expr::Lambda(ref params, ref body) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("lambda");
+                self.format_var_list(params, false);
+                self.format_expr(body);
+                self.fmt.end_list();
+            }
+            Expr::If(ref cond, ref then, ref else_) => {
+                self.fmt.start_list();
+                if let Some(ref else_) = *else_ {
+                    self.fmt.write_symbol("if-expr-then-else");
+                    self.fmt.make_list_multiline();
+                    self.format_expr(cond);
+                    self.format_expr(then);
+                    self.format_expr(else_);
+                } else {
+                    self.fmt.write_symbol("if-expr-then");
+                    self.fmt.make_list_multiline();
+                    self.format_expr(cond);
+                    self.format_expr(then);
+                }
+                self.fmt.end_list();
+            }
+            Expr::Loop(ref label, ref body) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("loop-expr");
+                self.format_label(label);
+                self.format_statement(body);
+                self.fmt.end_list();
+            }
+            Expr::Block(ref statements, ref value) => {
+                self.fmt.start_list();
+                self.fmt.write_symbol("block-expr");
+                self.fmt.make_list_multiline();
+                for s in statements {
+                    self.format_statement(s);
+                }
+                if let Some(ref value) = *value {
+                    self.format_expr(value);
+                }
+                self.fmt.end_list();
+            }
         }
     }
 
@@ -371,9 +730,11 @@ impl Formatter {
                     (Some(Signedness::Signed), Some(Size::Bit8)) => "i8",
                     (Some(Signedness::Signed), Some(Size::Bit16)) => "i16",
                     (Some(Signedness::Signed), Some(Size::Bit32)) => "i32",
+                    (Some(Signedness::Signed), Some(Size::Bit64)) => "i64",
                     (Some(Signedness::Unsigned), Some(Size::Bit8)) => "u8",
                     (Some(Signedness::Unsigned), Some(Size::Bit16)) => "u16",
                     (Some(Signedness::Unsigned), Some(Size::Bit32)) => "u32",
+                    (Some(Signedness::Unsigned), Some(Size::Bit64)) => "u64",
                     _ => panic!("bad number literal"),
                 };
                 self.fmt.write_symbol(&format!("{}{}", num.value, suffix));
@@ -412,6 +773,9 @@ impl Formatter {
             BinaryOp::Add => "add",
             BinaryOp::And => "and",
             BinaryOp::Assign => "assign",
+            BinaryOp::BitAnd => "bit-and",
+            BinaryOp::BitOr => "bit-or",
+            BinaryOp::BitXor => "bit-xor",
             BinaryOp::Divide => "divide",
             BinaryOp::Equal => "equal?",
             BinaryOp::Greater => "greater?",
@@ -422,7 +786,19 @@ impl Formatter {
             BinaryOp::Multiply => "multiply",
             BinaryOp::NotEqual => "not-equal?",
             BinaryOp::Or => "or",
+            BinaryOp::ShiftLeft => "shift-left",
+            BinaryOp::ShiftRight => "shift-right",
             BinaryOp::Subtract => "subtract",
+            BinaryOp::AddAssign => "add-assign",
+            BinaryOp::SubtractAssign => "subtract-assign",
+            BinaryOp::MultiplyAssign => "multiply-assign",
+            BinaryOp::DivideAssign => "divide-assign",
+            BinaryOp::ModuloAssign => "modulo-assign",
+            BinaryOp::BitAndAssign => "bit-and-assign",
+            BinaryOp::BitOrAssign => "bit-or-assign",
+            BinaryOp::BitXorAssign => "bit-xor-assign",
+            BinaryOp::ShiftLeftAssign => "shift-left-assign",
+            BinaryOp::ShiftRightAssign => "shift-right-assign",
         };
         self.fmt.write_symbol(sym);
     }
@@ -430,6 +806,7 @@ impl Formatter {
     fn format_unary_op(&mut self, op: &UnaryOp) {
         let sym = match *op {
             UnaryOp::AddressOf => "take-address",
+            UnaryOp::BitNot => "bit-not",
             UnaryOp::Deref => "deref",
             UnaryOp::Minus => "minus",
             UnaryOp::Not => "not",
@@ -7,11 +7,14 @@ extern crate plank_interpreter;
 
 mod ast_printer;
 
+use std::collections::HashSet;
 use std::convert::From;
 use std::io;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
-use plank_errors::Reporter;
+use plank_errors::{Reporter, SourceFile};
+use plank_syntax::ast::Program;
+use plank_syntax::position::{FileId, Spanned};
 
 
 #[derive(Debug)]
@@ -39,7 +42,17 @@ enum Command {
     Lex,
     Parse,
     EmitIr,
-    Interpret,
+    Interpret(RunParams),
+}
+
+/// Settings for the interpreted program.
+#[derive(Debug)]
+struct RunParams {
+    /// Stream read by `getc`, empty input is used if there is none.
+    input: Option<Stream>,
+    /// Command line arguments passed to `main`.
+    args: Vec<String>,
+    max_stack: usize,
 }
 
 #[derive(Debug)]
@@ -55,6 +68,9 @@ struct Params {
     output: Stream,
 }
 
+/// Names and contents of loaded source files, indexed by `FileId`.
+type Sources = Vec<(String, String)>;
+
 type Result<T> = ::std::result::Result<T, Error>;
 
 fn main() {
@@ -82,25 +98,39 @@ fn main() {
 fn run() -> Result<()> {
     let params = parse_params()?;
     let input = read_input(&params.input)?;
+    // modules are loaded relative to the root file
+    let (name, dir) = match params.input {
+        Stream::File(ref path) => {
+            let dir = path.parent().map(Path::to_owned).unwrap_or_default();
+            (path.display().to_string(), dir)
+        }
+        Stream::Std => ("<stdin>".to_string(), PathBuf::new()),
+    };
+    let mut sources = vec![(name, input)];
     match params.output {
         Stream::Std => {
             let stdout = io::stdout();
             let stdout = stdout.lock();
-            run_command(&input, &params.command, stdout)
+            run_command(&mut sources, &dir, &params.command, stdout)
         }
         Stream::File(ref name) => {
             let file = ::std::fs::File::create(name)?;
-            run_command(&input, &params.command, file)
+            run_command(&mut sources, &dir, &params.command, file)
         }
     }
 }
 
-fn run_command<W: Write>(input: &str, command: &Command, output: W) -> Result<()> {
+fn run_command<W: Write>(
+    sources: &mut Sources,
+    dir: &Path,
+    command: &Command,
+    output: W,
+) -> Result<()> {
     match *command {
-        Command::Lex => lex(input, output),
-        Command::Parse => parse(input, output),
-        Command::EmitIr => emit_ir(input, output),
-        Command::Interpret => interpret(input, output),
+        Command::Lex => lex(sources, output),
+        Command::Parse => parse(sources, output),
+        Command::EmitIr => emit_ir(sources, dir, output),
+        Command::Interpret(ref params) => interpret(sources, dir, params, output),
     }
 }
 
@@ -127,23 +157,60 @@ fn parse_params() -> Result<Params> {
         .arg(Arg::with_name("input")
             .index(1)
             .help("Set input file, uses stdin if none provided"))
+        .arg(Arg::with_name("stdin")
+            .long("stdin")
+            .requires("input")
+            .help("Pass stdin to the interpreted program, requires input file")
+            .conflicts_with_all(&["lex", "parse", "emit-ir", "stdin-file"]))
+        .arg(Arg::with_name("stdin-file")
+            .long("stdin-file")
+            .takes_value(true)
+            .help("Set file to be read as the interpreted program's stdin")
+            .conflicts_with_all(&["lex", "parse", "emit-ir", "stdin"]))
+        .arg(Arg::with_name("max-stack")
+            .long("max-stack")
+            .takes_value(true)
+            .validator(|size| size.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+            .help("Set stack size limit in bytes for the interpreted program")
+            .conflicts_with_all(&["lex", "parse", "emit-ir"]))
+        .arg(Arg::with_name("args")
+            .multiple(true)
+            .last(true)
+            .help("Arguments for the interpreted program, given after `--`")
+            .conflicts_with_all(&["lex", "parse", "emit-ir"]))
         .arg(Arg::with_name("output")
             .short("o")
             .long("output")
             .takes_value(true)
             .help("Set output file, uses stdout if none provided"))
         .get_matches();
-    let default_command = Command::Interpret;
+    let program_input = if matches.is_present("stdin") {
+        Some(Stream::Std)
+    } else {
+        matches
+            .value_of_os("stdin-file")
+            .map(|path| Stream::File(Path::new(path).to_owned()))
+    };
     let command = if matches.is_present("lex") {
         Command::Lex
     } else if matches.is_present("parse") {
         Command::Parse
     } else if matches.is_present("emit-ir") {
         Command::EmitIr
-    } else if matches.is_present("interpret") {
-        Command::Interpret
     } else {
-        default_command
+        // like in C, first argument is the program name
+        let mut args = vec![
+            matches.value_of_lossy("input").map_or("<stdin>".into(), |s| s.into_owned()),
+        ];
+        args.extend(matches.values_of_lossy("args").unwrap_or_default());
+        let max_stack = matches
+            .value_of("max-stack")
+            .map_or(plank_interpreter::DEFAULT_MAX_STACK, |size| size.parse().unwrap());
+        Command::Interpret(RunParams {
+            input: program_input,
+            args,
+            max_stack,
+        })
     };
 
     let input = match matches.value_of_os("input") {
@@ -187,10 +254,17 @@ fn read_input(stream: &Stream) -> Result<String> {
     }
 }
 
-fn emit_diagnostics(input: &str, reporter: Reporter) -> Result<()> {
+fn emit_diagnostics(sources: &Sources, reporter: Reporter) -> Result<()> {
     let mut diagnostics = reporter.get_diagnostics();
-    diagnostics.sort_by_key(|d| d.primary_span.map(|s| s.start));
-    plank_errors::print_diagnostics(&input, &diagnostics);
+    diagnostics.sort_by_key(|d| d.primary_span.map(|s| (s.file, s.start)));
+    let files = sources
+        .iter()
+        .map(|file| SourceFile {
+            name: &file.0,
+            source: &file.1,
+        })
+        .collect::<Vec<_>>();
+    plank_errors::print_diagnostics(&files, &diagnostics);
     if reporter.has_errors() {
         Err(Error::BuildFail)
     } else {
@@ -198,48 +272,105 @@ fn emit_diagnostics(input: &str, reporter: Reporter) -> Result<()> {
     }
 }
 
-fn lex<W: Write>(source: &str, mut output: W) -> Result<()> {
+fn parse_source(source: &str, file: FileId, reporter: &Reporter) -> Program {
+    let tokens = plank_syntax::lex(source, file, reporter.clone());
+    plank_syntax::parse(tokens, file, reporter.clone())
+}
+
+/// Parse the root file and all modules it declares.
+fn load_program(sources: &mut Sources, dir: &Path, reporter: &Reporter) -> Program {
+    let mut program = parse_source(&sources[0].1, FileId(0), reporter);
+    load_modules(&mut program, dir, sources, reporter);
+    program
+}
+
+/// Load modules declared in a file from directory `dir`. Module `foo` is
+/// read from `dir/foo.plk`, and its own modules are looked up in `dir/foo/`.
+fn load_modules(program: &mut Program, dir: &Path, sources: &mut Sources, reporter: &Reporter) {
+    let mut loaded = HashSet::new();
+    for module in &mut program.modules {
+        let name = &module.name.0;
+        // duplicate module declarations are reported by the frontend
+        if !loaded.insert(name.clone()) {
+            continue;
+        }
+        let path = dir.join(format!("{}.plk", name));
+        match read_file(&path) {
+            Ok(source) => {
+                let file = FileId(sources.len() as u32);
+                let mut module_program = parse_source(&source, file, reporter);
+                sources.push((path.display().to_string(), source));
+                load_modules(&mut module_program, &dir.join(name), sources, reporter);
+                module.program = Some(Box::new(module_program));
+            }
+            Err(_) => {
+                let span = Spanned::span(&module.name);
+                let msg = format!("file not found for module `{}`", name);
+                let note = format!("cannot read `{}`", path.display());
+                reporter.error(msg, span).span_note(note, span).build();
+            }
+        }
+    }
+}
+
+fn lex<W: Write>(sources: &Sources, mut output: W) -> Result<()> {
     let reporter = Reporter::new();
-    let tokens = plank_syntax::lex(source, reporter.clone());
-    emit_diagnostics(source, reporter)?;
+    let tokens = plank_syntax::lex(&sources[0].1, FileId(0), reporter.clone());
+    emit_diagnostics(sources, reporter)?;
     for tok in tokens {
         output.write_fmt(format_args!("{:?}\n", *tok))?;
     }
     Ok(())
 }
 
-fn parse<W: Write>(source: &str, mut output: W) -> Result<()> {
+fn parse<W: Write>(sources: &Sources, mut output: W) -> Result<()> {
     let reporter = Reporter::new();
-    let tokens = plank_syntax::lex(source, reporter.clone());
-    let program = plank_syntax::parse(tokens, reporter.clone());
-    emit_diagnostics(source, reporter)?;
+    let program = parse_source(&sources[0].1, FileId(0), &reporter);
+    emit_diagnostics(sources, reporter)?;
     let formatted = ast_printer::format_program(&program);
     output.write_all(formatted.as_bytes())?;
     output.write_all(b"\n")?;
     Ok(())
 }
 
-fn emit_ir<W: Write>(source: &str, mut output: W) -> Result<()> {
+fn emit_ir<W: Write>(sources: &mut Sources, dir: &Path, mut output: W) -> Result<()> {
     let reporter = Reporter::new();
-    let tokens = plank_syntax::lex(source, reporter.clone());
-    let program = plank_syntax::parse(tokens, reporter.clone());
+    let program = load_program(sources, dir, &reporter);
     let ir = plank_frontend::compile(&program, reporter.clone());
-    emit_diagnostics(source, reporter)?;
+    emit_diagnostics(sources, reporter)?;
     let ir = ir.expect("no errors but failed to produce IR");
     plank_ir::emit_program(&ir, &mut output)?;
-    plank_ir::validate_ir(&ir);
+    if let Err(err) = plank_ir::validate_ir(&ir) {
+        panic!("frontend produced invalid IR: {}", err);
+    }
     Ok(())
 }
 
-fn interpret<W: Write>(source: &str, output: W) -> Result<()> {
+fn interpret<W: Write>(
+    sources: &mut Sources,
+    dir: &Path,
+    params: &RunParams,
+    output: W,
+) -> Result<()> {
     let reporter = Reporter::new();
-    let tokens = plank_syntax::lex(source, reporter.clone());
-    let program = plank_syntax::parse(tokens, reporter.clone());
+    let program = load_program(sources, dir, &reporter);
     let ir = plank_frontend::compile(&program, reporter.clone());
-    emit_diagnostics(source, reporter)?;
+    emit_diagnostics(sources, reporter)?;
     let ir = ir.expect("build succeeded but failed to produce IR");
-    let input = io::empty();
-    let exit_code = plank_interpreter::run_program(&ir, input, output)?;
+    let args = &params.args;
+    let max_stack = params.max_stack;
+    let exit_code = match params.input {
+        Some(Stream::File(ref path)) => {
+            let file = ::std::fs::File::open(path)?;
+            let input = io::BufReader::new(file);
+            plank_interpreter::run_program(&ir, args, max_stack, input, output)?
+        }
+        Some(Stream::Std) => {
+            let stdin = io::stdin();
+            plank_interpreter::run_program(&ir, args, max_stack, stdin.lock(), output)?
+        }
+        None => plank_interpreter::run_program(&ir, args, max_stack, io::empty(), output)?,
+    };
     if exit_code == 0 {
         Ok(())
     } else {